@@ -3,7 +3,7 @@
 #![deny(warnings)]
 
 /// Crates included which are to be used for the Blinking LED example.
-use rustduino::atmega2560p::hal::pin::Pins;
+use rustduino::atmega2560p::hal::peripherals::Peripherals;
 use rustduino::atmega2560p::hal::watchdog::WatchDog;
 
 #[no_mangle]
@@ -13,7 +13,7 @@ pub fn main() {
     watchdog.disable();
 
     // This pins represents pin 7 of port B ( pin 13).
-    let mut pins = Pins::new();
+    let mut pins = Peripherals::take().unwrap().pins;
 
     //This sets pin 7 of port B (pin 13) as output.
     pins.digital[13].set_output();
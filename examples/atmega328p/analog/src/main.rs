@@ -3,7 +3,7 @@
 #![deny(warnings)]
 
 /// Crates to be used.
-use rustduino::hal::pin::Pins;
+use rustduino::hal::peripherals::Peripherals;
 use rustduino::hal::watchdog::WatchDog;
 use rustduino::math::map;
 
@@ -14,7 +14,7 @@ pub fn main() {
     watchdog.disable();
 
     // Creates a array object consisting of all the pins.
-    let mut pins = Pins::new();
+    let mut pins = Peripherals::take().unwrap().pins;
 
     // Infinite loop for read and write continuously through the I/O pins.
     loop {
@@ -22,7 +22,7 @@ pub fn main() {
         let a: u32 = pins.analog[0].read();
 
         // Make the input value ready to be sent through a digital pin.
-        let b: u8 = map(a as u64, 0, 255, 0, 1023) as u8;
+        let b: u8 = map(a as i32, 0, 1023, 0, 255) as u8;
 
         rustduino::delay::delay_ms(1000);
 
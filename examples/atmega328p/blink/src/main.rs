@@ -2,7 +2,7 @@
 #![no_main]
 #![deny(warnings)]
 
-use rustduino::hal::pin::Pins;
+use rustduino::hal::peripherals::Peripherals;
 use rustduino::hal::watchdog::WatchDog;
 
 #[no_mangle]
@@ -12,7 +12,7 @@ pub extern "C" fn main() {
     wdog.disable();
 
     // Get all pins at once
-    let mut pins = Pins::new();
+    let mut pins = Peripherals::take().unwrap().pins;
 
     //Set the digital pin 13 as an output pin.
     pins.digital[13].set_output();
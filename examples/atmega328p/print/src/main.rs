@@ -15,7 +15,7 @@ pub fn main() {
     // Send's the required message to the peripheral device at interval of 2 seconds.
     loop {
         // Print function to transmit string through USART.
-        println_string("Hello World !!!");
+        println_string("Hello World !!!").unwrap();
 
         rustduino::delay::delay_ms(2000);
     }
@@ -16,7 +16,7 @@ pub fn main() {
     let serial = unsafe { Serial::new() };
 
     // This initializes USART0 and makes it ready to transmit and recieve.
-    unsafe { serial.usart[0].begin() };
+    unsafe { serial.usart[0].begin() }.unwrap();
 
     // Loop to send a string using the USART multiple times.
     let mut i: u8 = 100;
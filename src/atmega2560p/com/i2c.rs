@@ -157,10 +157,11 @@ pub fn read_sda() {
 
 impl Twi {
     /// Creates a pointer to TWI structure objects.
+    /// Use `peripherals::Peripherals::take()` to get the only owner without unsafe code.
     /// # Returns
     /// * `a reference to Twi struct object` - Which would be used to control the implementation.
-    pub fn new() -> &'static mut Self {
-        unsafe { &mut *(0xB8 as *mut Self) }
+    pub unsafe fn new() -> &'static mut Self {
+        &mut *(0xB8 as *mut Self)
     }
 
     /// Waits for the TWI bus to be ready.
@@ -417,3 +418,156 @@ impl Twi {
         return true;
     }
 }
+
+/// Errors of the I2C transfers.
+/// * `Start` - The START condition could not be sent, the bus is busy.
+/// * `Address` - No device acknowledged the address.
+/// * `Data` - A data byte was not acknowledged or not received.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TwiError {
+    Start,
+    Address,
+    Data,
+}
+
+impl Twi {
+    /// Writes bytes to a slave and sends a STOP, also when the transfer fails.
+    /// # Arguments
+    /// * `address` - a u8, the 7 bit address of the slave.
+    /// * `bytes` - a u8 slice, the bytes to be written.
+    /// # Returns
+    /// * `a Result` - Ok if every byte was acknowledged, otherwise the step which failed.
+    pub fn write_bytes(&mut self, address: u8, bytes: &[u8]) -> Result<(), TwiError> {
+        let result = self.send_bytes(address, bytes, false);
+        self.stop();
+        result
+    }
+
+    /// Reads bytes from a slave and sends a STOP, also when the transfer fails.
+    /// # Arguments
+    /// * `address` - a u8, the 7 bit address of the slave.
+    /// * `buffer` - a mutable u8 slice, which is filled with the bytes read.
+    /// # Returns
+    /// * `a Result` - Ok if every byte was received, otherwise the step which failed.
+    pub fn read_bytes(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), TwiError> {
+        let result = self.receive_bytes(address, buffer, false);
+        self.stop();
+        result
+    }
+
+    /// Writes bytes to a slave and then reads from it after a repeated START, as register
+    /// reads need, and sends a STOP, also when the transfer fails.
+    /// # Arguments
+    /// * `address` - a u8, the 7 bit address of the slave.
+    /// * `bytes` - a u8 slice, the bytes to be written, usually the register address.
+    /// * `buffer` - a mutable u8 slice, which is filled with the bytes read.
+    /// # Returns
+    /// * `a Result` - Ok if the transfer was complete, otherwise the step which failed.
+    pub fn write_read_bytes(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), TwiError> {
+        let result = self
+            .send_bytes(address, bytes, false)
+            .and_then(|_| self.receive_bytes(address, buffer, true));
+        self.stop();
+        result
+    }
+
+    /// Sends a START, or a repeated START, the address with the write bit and the bytes.
+    fn send_bytes(&mut self, address: u8, bytes: &[u8], repeated: bool) -> Result<(), TwiError> {
+        let started = if repeated {
+            self.rep_start()
+        } else {
+            self.start()
+        };
+        if !started {
+            return Err(TwiError::Start);
+        }
+        if !self.address_write(address) {
+            return Err(TwiError::Address);
+        }
+        for &byte in bytes {
+            self.twdr.write(byte);
+            self.twcr.write(0x84); // TWCR = (1<<TWINT)|(1<<TWEN);
+            if !self.wait_to_complete(MT_DATA_ACK) {
+                return Err(TwiError::Data);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a START, or a repeated START, the address with the read bit and reads the bytes.
+    /// Every byte but the last is acknowledged, which tells the device to stop after the last.
+    fn receive_bytes(
+        &mut self,
+        address: u8,
+        buffer: &mut [u8],
+        repeated: bool,
+    ) -> Result<(), TwiError> {
+        let started = if repeated {
+            self.rep_start()
+        } else {
+            self.start()
+        };
+        if !started {
+            return Err(TwiError::Start);
+        }
+        if !self.address_read(address) {
+            return Err(TwiError::Address);
+        }
+        let last = buffer.len().saturating_sub(1);
+        for (i, place) in buffer.iter_mut().enumerate() {
+            let received = if i == last {
+                self.twcr.write(0x84); // TWCR = (1 << TWINT) | (1 << TWEN)
+                self.wait_to_complete(MR_DATA_NACK)
+            } else {
+                self.twcr.write(0xC4); // TWCR = (1 << TWINT) | (1 << TWEA) | (1 << TWEN)
+                self.wait_to_complete(MR_DATA_ACK)
+            };
+            if !received {
+                return Err(TwiError::Data);
+            }
+            *place = self.twdr.read();
+        }
+        Ok(())
+    }
+}
+
+/// The TWI for the drivers written against the `embedded-hal` I2C traits.
+/// They are on the reference given by `Twi::new()`, which is handed to the driver.
+/// The bus is stopped after each call, also when it fails.
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::i2c::Write for &mut Twi {
+    type Error = TwiError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_bytes(address, bytes)
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::i2c::Read for &mut Twi {
+    type Error = TwiError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.read_bytes(address, buffer)
+    }
+}
+
+/// Writes and then reads with a repeated START between, as register reads need.
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::i2c::WriteRead for &mut Twi {
+    type Error = TwiError;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.write_read_bytes(address, bytes, buffer)
+    }
+}
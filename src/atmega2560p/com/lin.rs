@@ -0,0 +1,297 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! LIN 2.x master on the USARTs of ATMEGA2560P.
+//! The master sends the header of every frame, that is the break, the sync byte 0x55
+//! and the protected identifier. The data and the checksum then come either from
+//! the master itself or from the slave which answers the identifier.
+//! A LIN transceiver such as the TJA1021 joins TXD and RXD to the single wire bus,
+//! so every byte sent is also received back and is checked against what was sent.
+//! `<https://www.lin-cia.org/standards/>`
+
+// Source code crates required
+use crate::atmega2560p::com::usart_initialize::{
+    Usart, UsartDataSize, UsartError, UsartModes, UsartNum, UsartObject, UsartParity, UsartStop,
+};
+
+// Crates to be used for the implementation.
+use crate::delay::delay_us;
+use bit_field::BitField;
+
+/// Byte sent after the break, so that slaves can measure the bit rate.
+pub const LIN_SYNC: u8 = 0x55;
+
+/// Largest number of data bytes in one frame.
+pub const LIN_MAX_DATA: usize = 8;
+
+/// Time given to each byte of a slave response before it is taken as missing.
+pub const LIN_BYTE_TIMEOUT_MS: u32 = 10;
+
+/// Kind of checksum used by a frame.
+/// * `Classic` - the sum of the data bytes only, used by LIN 1.x slaves.
+/// * `Enhanced` - the sum of the protected identifier and the data bytes, used by LIN 2.x.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LinChecksum {
+    Classic,
+    Enhanced,
+}
+
+/// Errors found while exchanging a frame.
+/// * `NoResponse` - the slave did not answer within the timeout.
+/// * `Checksum` - the received checksum does not match the data.
+/// * `BitError` - a byte read back from the bus differs from the byte sent.
+/// * `Usart` - the USART found an error in a received frame or could not be set up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LinError {
+    NoResponse,
+    Checksum,
+    BitError,
+    Usart(UsartError),
+}
+
+/// One frame as seen on the bus.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LinFrame {
+    pub id: u8,
+    pub len: u8,
+    pub data: [u8; LIN_MAX_DATA],
+}
+
+impl LinFrame {
+    /// Gives the data bytes of the frame.
+    /// # Returns
+    /// * `a u8 slice` - the `len` data bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.len as usize]
+    }
+}
+
+/// Adds the two parity bits to a frame identifier.
+/// # Arguments
+/// * `id` - a u8, the frame identifier from 0 to 0x3F.
+/// # Returns
+/// * `a u8` - the protected identifier, P0 in bit 6 and P1 in bit 7.
+pub fn protected_id(id: u8) -> u8 {
+    if id > 0x3F {
+        unreachable!()
+    }
+    let bit = |n: u8| id.get_bit(n);
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5));
+    id | ((p0 as u8) << 6) | ((p1 as u8) << 7)
+}
+
+/// Calculates the checksum of a frame, the inverted sum with carry of its bytes.
+/// The diagnostic frames 0x3C and 0x3D always use the classic checksum.
+/// # Arguments
+/// * `pid` - a u8, the protected identifier of the frame.
+/// * `data` - a u8 slice, the data bytes of the frame.
+/// * `kind` - a `LinChecksum` object, the checksum used by the frame.
+/// # Returns
+/// * `a u8` - the checksum byte.
+pub fn checksum(pid: u8, data: &[u8], kind: LinChecksum) -> u8 {
+    let id = pid & 0x3F;
+    let mut sum: u16 = match kind {
+        LinChecksum::Enhanced if id != 0x3C && id != 0x3D => pid as u16,
+        _ => 0,
+    };
+    for &byte in data.iter() {
+        sum += byte as u16;
+        if sum > 0xFF {
+            sum -= 0xFF;
+        }
+    }
+    !(sum as u8)
+}
+
+/// Checks the response of a slave and turns it into a frame.
+/// # Arguments
+/// * `pid` - a u8, the protected identifier sent in the header.
+/// * `response` - a u8 slice, the data bytes followed by the checksum byte.
+/// * `kind` - a `LinChecksum` object, the checksum used by the frame.
+/// # Returns
+/// * `a Result<LinFrame, LinError>` - the frame, or `LinError::Checksum` if it does not match.
+pub fn parse_response(pid: u8, response: &[u8], kind: LinChecksum) -> Result<LinFrame, LinError> {
+    if response.len() < 2 || response.len() > LIN_MAX_DATA + 1 {
+        unreachable!()
+    }
+    let len = response.len() - 1;
+    let data = &response[..len];
+    if checksum(pid, data, kind) != response[len] {
+        return Err(LinError::Checksum);
+    }
+
+    let mut frame = LinFrame {
+        id: pid & 0x3F,
+        len: len as u8,
+        data: [0; LIN_MAX_DATA],
+    };
+    frame.data[..len].copy_from_slice(data);
+    Ok(frame)
+}
+
+/// A LIN master on one USART.
+pub struct LinMaster {
+    usart: UsartObject,
+    checksum: LinChecksum,
+}
+
+impl LinMaster {
+    /// Initializes the USART at the bus bit rate and enables the transmitter and the receiver.
+    /// # Arguments
+    /// * `num` - a `UsartNum` object, the USART connected to the transceiver.
+    /// * `baud` - a i64, the bit rate of the bus, usually 19200 or 9600.
+    /// * `checksum` - a `LinChecksum` object, the checksum used by the slaves.
+    /// # Returns
+    /// * `a Result<LinMaster, LinError>` - the master, an error if the bit rate can not be made.
+    pub unsafe fn new(
+        num: UsartNum,
+        baud: i64,
+        checksum: LinChecksum,
+    ) -> Result<LinMaster, LinError> {
+        let mut usart = UsartObject::new(num);
+        usart.transmit_enable();
+        usart.recieve_enable();
+        usart
+            .initialize(
+                UsartModes::Normasync,
+                baud,
+                UsartStop::One,
+                UsartDataSize::Eight,
+                UsartParity::No,
+            )
+            .map_err(LinError::Usart)?;
+        Ok(LinMaster { usart, checksum })
+    }
+
+    /// Gives the registers of the USART.
+    fn regs(&mut self) -> &mut Usart {
+        unsafe { &mut *self.usart.usart }
+    }
+
+    /// Drops the bytes left in the receiver.
+    fn discard(&mut self) {
+        while self.regs().ucsra.read().get_bit(7) {
+            let _ = self.regs().udr.read();
+        }
+    }
+
+    /// Sends one byte and checks that the same byte is read back from the bus.
+    fn send_checked(&mut self, byte: u8) -> Result<(), LinError> {
+        self.usart.transmit_data(byte);
+        match self.usart.read_byte_timeout(LIN_BYTE_TIMEOUT_MS) {
+            Some(echo) if echo == byte => Ok(()),
+            _ => Err(LinError::BitError),
+        }
+    }
+
+    /// Sends the break field, the bus is held dominant for at least 13 bit times.
+    /// A zero byte is sent at half the bit rate, which keeps the line low for
+    /// 18 bit times and gives a 2 bit delimiter with the stop bit.
+    pub fn send_break(&mut self) {
+        let regs = self.regs();
+        let ubrrl = regs.ubrrl.read();
+        let ubrrh = regs.ubrrh.read();
+        let ubrr = ((ubrrh as u16) << 8) | ubrrl as u16;
+        let slow = (ubrr * 2 + 1).min(0x0FFF);
+
+        // TXC is cleared by writing one to it, so that the end of the break can be seen.
+        regs.ucsra.update(|sra| {
+            sra.set_bit(6, true);
+        });
+        regs.ubrrh.write((slow >> 8) as u8);
+        regs.ubrrl.write(slow as u8);
+        regs.udr.write(0x00);
+        while !regs.ucsra.read().get_bit(6) {}
+        regs.ubrrh.write(ubrrh);
+        regs.ubrrl.write(ubrrl);
+        self.discard();
+    }
+
+    /// Sends the header of a frame.
+    /// # Arguments
+    /// * `id` - a u8, the frame identifier from 0 to 0x3F.
+    /// # Returns
+    /// * `a Result<u8, LinError>` - the protected identifier sent, or an error if the bus did not follow.
+    pub fn send_header(&mut self, id: u8) -> Result<u8, LinError> {
+        let pid = protected_id(id);
+        self.discard();
+        self.send_break();
+        self.send_checked(LIN_SYNC)?;
+        self.send_checked(pid)?;
+        Ok(pid)
+    }
+
+    /// Sends a whole frame whose response is given by the master.
+    /// # Arguments
+    /// * `id` - a u8, the frame identifier from 0 to 0x3F.
+    /// * `data` - a u8 slice, from 1 to 8 data bytes.
+    /// # Returns
+    /// * `a Result<(), LinError>` - an error if a byte read back from the bus was different.
+    pub fn write_frame(&mut self, id: u8, data: &[u8]) -> Result<(), LinError> {
+        if data.is_empty() || data.len() > LIN_MAX_DATA {
+            unreachable!()
+        }
+        let pid = self.send_header(id)?;
+        for &byte in data.iter() {
+            self.send_checked(byte)?;
+        }
+        self.send_checked(checksum(pid, data, self.checksum))
+    }
+
+    /// Sends the header of a frame and reads the response of the slave.
+    /// # Arguments
+    /// * `id` - a u8, the frame identifier from 0 to 0x3F.
+    /// * `len` - a usize, the number of data bytes the slave sends, from 1 to 8.
+    /// # Returns
+    /// * `a Result<LinFrame, LinError>` - the frame sent by the slave.
+    pub fn read_frame(&mut self, id: u8, len: usize) -> Result<LinFrame, LinError> {
+        if len == 0 || len > LIN_MAX_DATA {
+            unreachable!()
+        }
+        let pid = self.send_header(id)?;
+
+        let mut response = [0u8; LIN_MAX_DATA + 1];
+        for byte in response[..len + 1].iter_mut() {
+            let mut polls = LIN_BYTE_TIMEOUT_MS * 100;
+            let mut reply = self.usart.try_read_checked();
+            while reply.is_none() && polls != 0 {
+                delay_us(10);
+                polls -= 1;
+                reply = self.usart.try_read_checked();
+            }
+            *byte = match reply {
+                Some(Ok(value)) => value,
+                Some(Err(error)) => return Err(LinError::Usart(error)),
+                None => return Err(LinError::NoResponse),
+            };
+        }
+        parse_response(pid, &response[..len + 1], self.checksum)
+    }
+
+    /// Sends the go-to-sleep command, a master request frame 0x3C starting with zero.
+    /// # Returns
+    /// * `a Result<(), LinError>` - an error if a byte read back from the bus was different.
+    pub fn sleep(&mut self) -> Result<(), LinError> {
+        self.write_frame(0x3C, &[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF])
+    }
+
+    /// Wakes the bus by holding it dominant with a break.
+    /// Slaves need up to 100 milliseconds after this before they answer headers.
+    pub fn wake_up(&mut self) {
+        self.send_break();
+    }
+}
@@ -0,0 +1,358 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! MIDI input and output over the USARTs of ATMEGA2560P.
+//! The USART runs at 31250 baud with 8 data bits, no parity and one stop bit.
+//! Channel voice and real-time messages are sent and parsed as `MidiMessage` values,
+//! with running status on both sides. System exclusive data is skipped by the parser.
+//! `<https://www.midi.org/specifications/midi1-specifications>`
+
+// Source code crates required
+use crate::atmega2560p::com::usart_initialize::{
+    UsartDataSize, UsartError, UsartModes, UsartNum, UsartObject, UsartParity, UsartStop,
+};
+
+/// Baud rate of MIDI.
+pub const MIDI_BAUD: i64 = 31250;
+
+/// One MIDI message.
+/// Channels are from 0 to 15 and data values from 0 to 127, larger values are
+/// cut to their lower 4 and 7 bits when sent. The pitch bend value is 14 bits
+/// with 8192 as the centre.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MidiMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u8,
+    },
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    ActiveSensing,
+    Reset,
+}
+
+impl MidiMessage {
+    /// Puts the message into its bytes, status byte first.
+    /// # Arguments
+    /// * `bytes` - a mutable u8 array, where the bytes are stored.
+    /// # Returns
+    /// * `a usize` - the number of bytes of the message.
+    pub fn to_bytes(&self, bytes: &mut [u8; 3]) -> usize {
+        let (status, channel, data, length) = match *self {
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => (0x80, channel, [note, velocity], 3),
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => (0x90, channel, [note, velocity], 3),
+            MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => (0xA0, channel, [note, pressure], 3),
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => (0xB0, channel, [controller, value], 3),
+            MidiMessage::ProgramChange { channel, program } => (0xC0, channel, [program, 0], 2),
+            MidiMessage::ChannelPressure { channel, pressure } => (0xD0, channel, [pressure, 0], 2),
+            MidiMessage::PitchBend { channel, value } => {
+                (0xE0, channel, [value as u8, (value >> 7) as u8], 3)
+            }
+            MidiMessage::Clock => (0xF8, 0, [0, 0], 1),
+            MidiMessage::Start => (0xFA, 0, [0, 0], 1),
+            MidiMessage::Continue => (0xFB, 0, [0, 0], 1),
+            MidiMessage::Stop => (0xFC, 0, [0, 0], 1),
+            MidiMessage::ActiveSensing => (0xFE, 0, [0, 0], 1),
+            MidiMessage::Reset => (0xFF, 0, [0, 0], 1),
+        };
+        bytes[0] = status | (channel & 0x0F);
+        bytes[1] = data[0] & 0x7F;
+        bytes[2] = data[1] & 0x7F;
+        length
+    }
+}
+
+/// Gives the number of data bytes which follow a channel status byte.
+fn data_length(status: u8) -> u8 {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
+
+/// Turns the received bytes into messages, one byte at a time.
+/// Running status is kept, so data bytes without a status byte belong to
+/// the last channel message.
+pub struct MidiParser {
+    status: u8,
+    data: [u8; 2],
+    count: u8,
+}
+
+impl MidiParser {
+    /// Creates a parser which waits for the first status byte.
+    /// # Returns
+    /// * `a MidiParser object` - which will be given the received bytes.
+    pub fn new() -> MidiParser {
+        MidiParser {
+            status: 0,
+            data: [0; 2],
+            count: 0,
+        }
+    }
+
+    /// Takes one received byte.
+    /// A Note On with velocity zero is given as a Note Off, as senders use it that way
+    /// to make use of running status.
+    /// # Arguments
+    /// * `byte` - a u8, the received byte.
+    /// # Returns
+    /// * `a Option<MidiMessage>` - the message completed by the byte, if any.
+    pub fn parse(&mut self, byte: u8) -> Option<MidiMessage> {
+        // Real-time messages may come between any two bytes and do not change running status.
+        if byte >= 0xF8 {
+            return match byte {
+                0xF8 => Some(MidiMessage::Clock),
+                0xFA => Some(MidiMessage::Start),
+                0xFB => Some(MidiMessage::Continue),
+                0xFC => Some(MidiMessage::Stop),
+                0xFE => Some(MidiMessage::ActiveSensing),
+                0xFF => Some(MidiMessage::Reset),
+                _ => None,
+            };
+        }
+        // System common messages and system exclusive data end running status.
+        if byte >= 0xF0 {
+            self.status = 0;
+            self.count = 0;
+            return None;
+        }
+        if byte >= 0x80 {
+            self.status = byte;
+            self.count = 0;
+            return None;
+        }
+        if self.status == 0 {
+            return None;
+        }
+
+        self.data[self.count as usize] = byte;
+        self.count += 1;
+        if self.count < data_length(self.status) {
+            return None;
+        }
+        self.count = 0;
+
+        let channel = self.status & 0x0F;
+        let (first, second) = (self.data[0], self.data[1]);
+        Some(match self.status & 0xF0 {
+            0x80 => MidiMessage::NoteOff {
+                channel,
+                note: first,
+                velocity: second,
+            },
+            0x90 if second == 0 => MidiMessage::NoteOff {
+                channel,
+                note: first,
+                velocity: 0,
+            },
+            0x90 => MidiMessage::NoteOn {
+                channel,
+                note: first,
+                velocity: second,
+            },
+            0xA0 => MidiMessage::PolyPressure {
+                channel,
+                note: first,
+                pressure: second,
+            },
+            0xB0 => MidiMessage::ControlChange {
+                channel,
+                controller: first,
+                value: second,
+            },
+            0xC0 => MidiMessage::ProgramChange {
+                channel,
+                program: first,
+            },
+            0xD0 => MidiMessage::ChannelPressure {
+                channel,
+                pressure: first,
+            },
+            _ => MidiMessage::PitchBend {
+                channel,
+                value: (first as u16) | ((second as u16) << 7),
+            },
+        })
+    }
+}
+
+impl Default for MidiParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A MIDI port on one USART.
+pub struct Midi {
+    usart: UsartObject,
+    parser: MidiParser,
+    running_status: bool,
+    last_status: u8,
+}
+
+impl Midi {
+    /// Initializes the USART for MIDI and enables the transmitter and the receiver.
+    /// # Arguments
+    /// * `num` - a `UsartNum` object, the USART to be used.
+    /// # Returns
+    /// * `a Result<Midi, UsartError>` - the port, an error if the clock can not make 31250 baud.
+    pub unsafe fn new(num: UsartNum) -> Result<Midi, UsartError> {
+        let mut usart = UsartObject::new(num);
+        usart.transmit_enable();
+        usart.recieve_enable();
+        usart.initialize(
+            UsartModes::Normasync,
+            MIDI_BAUD,
+            UsartStop::One,
+            UsartDataSize::Eight,
+            UsartParity::No,
+        )?;
+        Ok(Midi {
+            usart,
+            parser: MidiParser::new(),
+            running_status: false,
+            last_status: 0,
+        })
+    }
+
+    /// Chooses if the status byte is left out when it is the same as the last one sent.
+    /// # Arguments
+    /// * `enable` - a boolean, true to send with running status.
+    pub fn set_running_status(&mut self, enable: bool) {
+        self.running_status = enable;
+        self.last_status = 0;
+    }
+
+    /// Sends one message.
+    /// # Arguments
+    /// * `message` - a `MidiMessage` object, the message to be sent.
+    pub fn send(&mut self, message: &MidiMessage) {
+        let mut bytes = [0u8; 3];
+        let length = message.to_bytes(&mut bytes);
+        let status = bytes[0];
+
+        let mut start = 0;
+        if status < 0xF0 {
+            if self.running_status && status == self.last_status {
+                start = 1;
+            }
+            self.last_status = status;
+        } else if status < 0xF8 {
+            self.last_status = 0;
+        }
+        for byte in bytes[start..length].iter() {
+            self.usart.transmit_data(*byte);
+        }
+    }
+
+    /// Sends a Note On message.
+    /// # Arguments
+    /// * `channel` - a u8, the channel from 0 to 15.
+    /// * `note` - a u8, the note number, 60 is the middle C.
+    /// * `velocity` - a u8, the velocity from 1 to 127.
+    pub fn note_on(&mut self, channel: u8, note: u8, velocity: u8) {
+        self.send(&MidiMessage::NoteOn {
+            channel,
+            note,
+            velocity,
+        });
+    }
+
+    /// Sends a Note Off message.
+    /// # Arguments
+    /// * `channel` - a u8, the channel from 0 to 15.
+    /// * `note` - a u8, the note number.
+    /// * `velocity` - a u8, the release velocity.
+    pub fn note_off(&mut self, channel: u8, note: u8, velocity: u8) {
+        self.send(&MidiMessage::NoteOff {
+            channel,
+            note,
+            velocity,
+        });
+    }
+
+    /// Sends a Control Change message.
+    /// # Arguments
+    /// * `channel` - a u8, the channel from 0 to 15.
+    /// * `controller` - a u8, the controller number.
+    /// * `value` - a u8, the value of the controller.
+    pub fn control_change(&mut self, channel: u8, controller: u8, value: u8) {
+        self.send(&MidiMessage::ControlChange {
+            channel,
+            controller,
+            value,
+        });
+    }
+
+    /// Parses the bytes received so far without waiting.
+    /// # Returns
+    /// * `a Option<MidiMessage>` - the next complete message, None if there is none yet.
+    pub fn read(&mut self) -> Option<MidiMessage> {
+        while let Some(byte) = self.usart.try_read() {
+            if let Some(message) = self.parser.parse(byte) {
+                return Some(message);
+            }
+        }
+        None
+    }
+}
@@ -15,11 +15,14 @@
 //     along with this program.  If not, see <https://www.gnu.org/licenses/>
 
 //! This files contain the code for combining all serial ports into one structure for easier implementation.
+//! Each USART also has its own handle, `Serial0` to `Serial3`, for code which uses only one of them.
 //! See the section 22 of ATMEGA2560P datasheet.
 
 // Crates which would be used in the implementation.
 // We will be using standard volatile and bit_field crates now for a better read and write.
-use crate::atmega2560p::com::usart_initialize::{UsartNum, UsartObject};
+use crate::atmega2560p::com::usart_initialize::{UsartError, UsartNum, UsartObject};
+use core::fmt;
+use core::ops::{Deref, DerefMut};
 
 /// This struct contains all 4 USART in ARDUINO MEGA arranged in a array.
 /// First a new Serial is needed to be created to access all USARTs.
@@ -45,4 +48,96 @@ impl Serial {
             ],
         }
     }
+
+    /// Splits the serial struct into a separate handle for each USART,
+    /// so that they can be given to different parts of the program.
+    /// # Returns
+    /// * `a tuple` - the handles of USART0, USART1, USART2 and USART3 in order.
+    pub fn split(self) -> (Serial0, Serial1, Serial2, Serial3) {
+        let [usart0, usart1, usart2, usart3] = self.usart;
+        (
+            Serial0 { usart: usart0 },
+            Serial1 { usart: usart1 },
+            Serial2 { usart: usart2 },
+            Serial3 { usart: usart3 },
+        )
+    }
+}
+
+// Makes the handle type of one USART, all the functions of `UsartObject`
+// can be used on the handle as well.
+macro_rules! serial_port {
+    ($(#[$attr:meta])* $name:ident, $num:expr) => {
+        $(#[$attr])*
+        pub struct $name {
+            usart: UsartObject,
+        }
+
+        impl $name {
+            /// Creates the handle of the USART.
+            /// # Returns
+            /// * `a handle object` - which will be used to control the USART.
+            pub unsafe fn new() -> $name {
+                $name {
+                    usart: UsartObject::new($num),
+                }
+            }
+
+            /// Initializes the USART in normal asynchronous mode with 8 data bits,
+            /// no parity and one stop bit, and enables the transmitter and the receiver.
+            /// # Arguments
+            /// * `baud` - a i64, the baud rate of USART the user wants to set.
+            /// # Returns
+            /// * `a Result` - `UsartError::BaudRateError` if the baud rate can not be generated closely enough.
+            pub fn begin(&mut self, baud: i64) -> Result<(), UsartError> {
+                unsafe { self.usart.begin_set_baud(baud) }
+            }
+
+            /// Disables the transmitter and the receiver of the USART.
+            pub fn end(&mut self) {
+                unsafe { self.usart.end() }
+            }
+        }
+
+        impl Deref for $name {
+            type Target = UsartObject;
+
+            fn deref(&self) -> &UsartObject {
+                &self.usart
+            }
+        }
+
+        impl DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut UsartObject {
+                &mut self.usart
+            }
+        }
+
+        impl fmt::Write for $name {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                fmt::Write::write_str(&mut self.usart, s)
+            }
+        }
+    };
 }
+
+serial_port!(
+    /// Handle of USART0, connected to the USB serial converter on pins 0 (RX) and 1 (TX).
+    Serial0,
+    UsartNum::Usart0
+);
+serial_port!(
+    /// Handle of USART1, on pins 19 (RX1) and 18 (TX1).
+    Serial1,
+    UsartNum::Usart1
+);
+serial_port!(
+    /// Handle of USART2, on pins 17 (RX2) and 16 (TX2).
+    Serial2,
+    UsartNum::Usart2
+);
+serial_port!(
+    /// Handle of USART3, on pins 15 (RX3) and 14 (TX3).
+    Serial3,
+    UsartNum::Usart3
+);
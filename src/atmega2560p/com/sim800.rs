@@ -0,0 +1,602 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the SIM800 and SIM900 GSM modems on the USARTs of ATMEGA2560P.
+//! The modem is driven with AT commands, and its answers are read line by line from
+//! the receive buffer of `usart_buffer`. Unsolicited result codes, which the modem
+//! sends at any time, are picked out in the receive interrupt so that a new SMS or a
+//! closed connection is noticed even while no command is running.
+//! SMS are sent and read in text mode, TCP data is kept in the modem until it is
+//! fetched with `tcp_receive()`.
+//! `<https://www.simcom.com/product/SIM800.html>`
+
+// Source code crates required
+use crate::atmega2560p::com::usart_initialize::{
+    Usart, UsartDataSize, UsartError, UsartModes, UsartNum, UsartParity, UsartStop,
+};
+use crate::delay::{delay_ms, delay_us};
+use core::ptr::{read_volatile, write_volatile};
+
+/// Status register, its global interrupt bit guards the unsolicited result codes.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+/// Length of the longest answer line kept, longer lines are cut.
+pub const MODEM_LINE_LENGTH: usize = 64;
+
+/// Length of the longest unsolicited result code recognised by the receive interrupt.
+const URC_LINE_LENGTH: usize = 24;
+
+/// Time in milliseconds for commands which answer at once.
+const COMMAND_TIMEOUT_MS: u32 = 1000;
+
+/// Time in milliseconds for an SMS to be sent.
+const SMS_TIMEOUT_MS: u32 = 60_000;
+
+/// Time in milliseconds for the modem to bring up the data connection.
+const NETWORK_TIMEOUT_MS: u32 = 85_000;
+
+/// Time in milliseconds for a TCP connection to be opened or data to be sent.
+const CONNECT_TIMEOUT_MS: u32 = 75_000;
+
+/// Largest amount of data asked for at once with `AT+CIPRXGET`.
+const TCP_READ_MAX: usize = 1460;
+
+/// Errors of the modem.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModemError {
+    /// The modem did not answer in time.
+    Timeout,
+    /// The modem answered with `ERROR`, `+CME ERROR` or `+CMS ERROR`.
+    Error,
+    /// The TCP connection could not be opened.
+    ConnectFailed,
+    /// The answer of the modem could not be understood.
+    InvalidResponse,
+    /// The USART could not be set up.
+    Usart(UsartError),
+}
+
+/// Unsolicited result codes recognised by the receive interrupt.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModemUrc {
+    /// A new SMS was stored at the given index (`+CMTI`).
+    NewSms(u16),
+    /// A voice call is coming in (`RING`).
+    Ring,
+    /// The remote side closed the TCP connection (`CLOSED`).
+    ConnectionClosed,
+    /// TCP data has arrived and waits in the modem (`+CIPRXGET: 1`).
+    DataReceived,
+    /// The data connection of the network was lost (`+PDP: DEACT`).
+    NetworkLost,
+}
+
+/// Collects received lines in the receive interrupt and keeps the unsolicited result codes found.
+struct UrcParser {
+    line: [u8; URC_LINE_LENGTH],
+    len: usize,
+    new_sms: Option<u16>,
+    ring: bool,
+    closed: bool,
+    data: bool,
+    deact: bool,
+}
+
+static mut URC: UrcParser = UrcParser {
+    line: [0; URC_LINE_LENGTH],
+    len: 0,
+    new_sms: None,
+    ring: false,
+    closed: false,
+    data: false,
+    deact: false,
+};
+
+impl UrcParser {
+    /// Takes one received byte.
+    fn push(&mut self, byte: u8) {
+        if byte == b'\n' {
+            self.line_done();
+            self.len = 0;
+        } else if byte != b'\r' && self.len < URC_LINE_LENGTH {
+            self.line[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    /// Checks a complete line against the known unsolicited result codes.
+    fn line_done(&mut self) {
+        let line = &self.line[..self.len];
+        if line.starts_with(b"+CMTI:") {
+            self.new_sms = field(line, 1).and_then(parse_number).map(|n| n as u16);
+        } else if line == b"RING" {
+            self.ring = true;
+        } else if line.ends_with(b"CLOSED") {
+            self.closed = true;
+        } else if line.starts_with(b"+CIPRXGET: 1") {
+            self.data = true;
+        } else if line.starts_with(b"+PDP: DEACT") {
+            self.deact = true;
+        }
+    }
+
+    /// Gives and clears one of the unsolicited result codes found.
+    fn take(&mut self) -> Option<ModemUrc> {
+        if let Some(index) = self.new_sms.take() {
+            Some(ModemUrc::NewSms(index))
+        } else if self.ring {
+            self.ring = false;
+            Some(ModemUrc::Ring)
+        } else if self.closed {
+            self.closed = false;
+            Some(ModemUrc::ConnectionClosed)
+        } else if self.data {
+            self.data = false;
+            Some(ModemUrc::DataReceived)
+        } else if self.deact {
+            self.deact = false;
+            Some(ModemUrc::NetworkLost)
+        } else {
+            None
+        }
+    }
+}
+
+/// Receive hook of the USART, it runs in the receive interrupt.
+fn urc_byte(byte: u8) {
+    unsafe { URC.push(byte) }
+}
+
+/// Gives the field of an answer line after the colon, fields are split by commas.
+fn field(line: &[u8], n: usize) -> Option<&[u8]> {
+    let start = line.iter().position(|&b| b == b':').map_or(0, |p| p + 1);
+    line[start..].split(|&b| b == b',').nth(n)
+}
+
+/// Reads the decimal number of a field, spaces and quotes around it are skipped.
+fn parse_number(field: &[u8]) -> Option<u32> {
+    let mut value: u32 = 0;
+    let mut digits = 0;
+    for &b in field.iter().filter(|&&b| b != b' ' && b != b'"') {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+        value = value.checked_mul(10)?.checked_add((b - b'0') as u32)?;
+        digits += 1;
+    }
+    if digits == 0 {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// Checks if an answer line reports a failed command.
+fn is_error(line: &[u8]) -> bool {
+    line == b"ERROR"
+        || line == b"SEND FAIL"
+        || line.starts_with(b"+CME ERROR")
+        || line.starts_with(b"+CMS ERROR")
+}
+
+/// Waits for one byte from the receive buffer.
+/// # Arguments
+/// * `usart` - a `Usart` object, with buffered reception enabled.
+/// * `polls` - a u32, the number of 10 us polls left, counted down while waiting.
+/// # Returns
+/// * `a Option<u8>` - the byte, or None when the polls ran out.
+fn next_byte(usart: &mut Usart, polls: &mut u32) -> Option<u8> {
+    let mut byte = [0; 1];
+    loop {
+        if usart.read_available(&mut byte) == 1 {
+            return Some(byte[0]);
+        }
+        if *polls == 0 {
+            return None;
+        }
+        *polls -= 1;
+        delay_us(10);
+    }
+}
+
+/// Reads one non-empty line from the receive buffer.
+/// # Arguments
+/// * `usart` - a `Usart` object, with buffered reception enabled.
+/// * `out` - a u8 slice, where the line is stored. Bytes which do not fit are dropped.
+/// * `timeout_ms` - a u32, the time in milliseconds to wait for the whole line.
+/// # Returns
+/// * `a Result<usize, ModemError>` - the length of the line stored.
+fn receive_line(usart: &mut Usart, out: &mut [u8], timeout_ms: u32) -> Result<usize, ModemError> {
+    let mut polls = timeout_ms.saturating_mul(100);
+    let mut len: usize = 0;
+    loop {
+        match next_byte(usart, &mut polls) {
+            None => return Err(ModemError::Timeout),
+            Some(b'\r') => (),
+            Some(b'\n') => {
+                if len > 0 {
+                    return Ok(len.min(out.len()));
+                }
+            }
+            Some(byte) => {
+                if len < out.len() {
+                    out[len] = byte;
+                }
+                len += 1;
+            }
+        }
+    }
+}
+
+/// A SIM800 or SIM900 modem connected to a USART.
+/// Only one modem may be used at a time, as the unsolicited result codes are kept in one place.
+pub struct Sim800 {
+    usart: &'static mut Usart,
+    line: [u8; MODEM_LINE_LENGTH],
+    len: usize,
+}
+
+impl Sim800 {
+    /// Sets up the USART for the modem with 8 data bits, no parity and one stop bit
+    /// and starts buffered reception. Global interrupts have to be enabled.
+    /// # Arguments
+    /// * `num` - a `UsartNum` object, the USART the modem is connected to.
+    /// * `baud` - a i64, the baud rate of the modem, 9600 or 115200 are common.
+    /// # Returns
+    /// * `a Result<Sim800, ModemError>` - the modem, or the error of the USART.
+    pub unsafe fn new(num: UsartNum, baud: i64) -> Result<Sim800, ModemError> {
+        let usart = Usart::new(num);
+        let mut object = usart.create_object();
+        object.transmit_enable();
+        object.recieve_enable();
+        object
+            .initialize(
+                UsartModes::Normasync,
+                baud,
+                UsartStop::One,
+                UsartDataSize::Eight,
+                UsartParity::No,
+            )
+            .map_err(ModemError::Usart)?;
+        usart.enable_buffered();
+        usart.set_receive_hook(Some(urc_byte));
+        Ok(Sim800 {
+            usart,
+            line: [0; MODEM_LINE_LENGTH],
+            len: 0,
+        })
+    }
+
+    /// Checks that the modem answers, turns off the echo of commands and
+    /// selects SMS text mode with a `+CMTI` notice for each new SMS.
+    /// # Returns
+    /// * `a Result<(), ModemError>` - Timeout if the modem does not answer.
+    pub fn init(&mut self) -> Result<(), ModemError> {
+        let mut result = Err(ModemError::Timeout);
+        for _ in 0..10 {
+            self.send_str("AT\r");
+            result = self.wait_for(b"OK", 500);
+            if result.is_ok() {
+                break;
+            }
+        }
+        result?;
+        self.command("ATE0")?;
+        self.command("AT+CMGF=1")?;
+        self.command("AT+CNMI=2,1,0,0,0")
+    }
+
+    /// Stops the recognition of unsolicited result codes.
+    /// The USART stays initialized.
+    pub fn release(self) {
+        self.usart.set_receive_hook(None);
+    }
+
+    /// Sends bytes to the modem.
+    fn send(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.usart.create_object().transmit_data(byte);
+        }
+    }
+
+    /// Sends a string to the modem.
+    fn send_str(&mut self, data: &str) {
+        self.send(data.as_bytes());
+    }
+
+    /// Sends a number as decimal digits.
+    fn send_number(&mut self, value: u32) {
+        let mut digits = [0; 10];
+        let mut n = value;
+        let mut count = 0;
+        loop {
+            digits[count] = b'0' + (n % 10) as u8;
+            count += 1;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        for i in (0..count).rev() {
+            self.usart.create_object().transmit_data(digits[i]);
+        }
+    }
+
+    /// Reads the next answer line into the line buffer.
+    fn read_line(&mut self, timeout_ms: u32) -> Result<(), ModemError> {
+        self.len = receive_line(self.usart, &mut self.line, timeout_ms)?;
+        Ok(())
+    }
+
+    /// Gives the last answer line read.
+    fn line(&self) -> &[u8] {
+        &self.line[..self.len]
+    }
+
+    /// Reads answer lines until the expected one or an error.
+    fn wait_for(&mut self, expected: &[u8], timeout_ms: u32) -> Result<(), ModemError> {
+        loop {
+            self.read_line(timeout_ms)?;
+            if self.line() == expected {
+                return Ok(());
+            }
+            if is_error(self.line()) {
+                return Err(ModemError::Error);
+            }
+        }
+    }
+
+    /// Waits for the `>` prompt after which the modem takes data.
+    fn wait_prompt(&mut self, timeout_ms: u32) -> Result<(), ModemError> {
+        let mut polls = timeout_ms.saturating_mul(100);
+        loop {
+            match next_byte(self.usart, &mut polls) {
+                None => return Err(ModemError::Timeout),
+                Some(b'>') => return Ok(()),
+                Some(_) => (),
+            }
+        }
+    }
+
+    /// Reads answer lines until one starts with the prefix and reads the numbers in it.
+    fn query_numbers(&mut self, prefix: &[u8], timeout_ms: u32) -> Result<[u32; 3], ModemError> {
+        loop {
+            self.read_line(timeout_ms)?;
+            if is_error(self.line()) {
+                return Err(ModemError::Error);
+            }
+            if self.line().starts_with(prefix) {
+                let mut numbers = [0; 3];
+                for (i, number) in numbers.iter_mut().enumerate() {
+                    if let Some(value) = field(self.line(), i).and_then(parse_number) {
+                        *number = value;
+                    }
+                }
+                return Ok(numbers);
+            }
+        }
+    }
+
+    /// Sends an AT command and waits for `OK`.
+    /// # Arguments
+    /// * `command` - a string object, the command without the closing carriage return.
+    /// # Returns
+    /// * `a Result<(), ModemError>` - Error if the modem rejected the command.
+    pub fn command(&mut self, command: &str) -> Result<(), ModemError> {
+        self.send_str(command);
+        self.send_str("\r");
+        self.wait_for(b"OK", COMMAND_TIMEOUT_MS)
+    }
+
+    /// Gives the received signal strength.
+    /// # Returns
+    /// * `a Result<u8, ModemError>` - from 0 (-115 dBm or less) to 31 (-52 dBm or more), 99 if unknown.
+    pub fn signal_quality(&mut self) -> Result<u8, ModemError> {
+        self.send_str("AT+CSQ\r");
+        let numbers = self.query_numbers(b"+CSQ:", COMMAND_TIMEOUT_MS)?;
+        self.wait_for(b"OK", COMMAND_TIMEOUT_MS)?;
+        Ok(numbers[0] as u8)
+    }
+
+    /// Checks if the modem is registered to the network.
+    /// # Returns
+    /// * `a Result<bool, ModemError>` - true if registered at home or roaming.
+    pub fn is_registered(&mut self) -> Result<bool, ModemError> {
+        self.send_str("AT+CREG?\r");
+        let numbers = self.query_numbers(b"+CREG:", COMMAND_TIMEOUT_MS)?;
+        self.wait_for(b"OK", COMMAND_TIMEOUT_MS)?;
+        Ok(numbers[1] == 1 || numbers[1] == 5)
+    }
+
+    /// Sends an SMS in text mode.
+    /// # Arguments
+    /// * `number` - a string object, the phone number, for example "+911234567890".
+    /// * `text` - a string object, the message of at most 160 characters.
+    /// # Returns
+    /// * `a Result<(), ModemError>` - Error if the network rejected the message.
+    pub fn send_sms(&mut self, number: &str, text: &str) -> Result<(), ModemError> {
+        self.send_str("AT+CMGS=\"");
+        self.send_str(number);
+        self.send_str("\"\r");
+        self.wait_prompt(COMMAND_TIMEOUT_MS)?;
+        self.send_str(text);
+        // Ctrl-Z ends the message.
+        self.send(&[0x1A]);
+        self.wait_for(b"OK", SMS_TIMEOUT_MS)
+    }
+
+    /// Reads a stored SMS, as given by `ModemUrc::NewSms`.
+    /// # Arguments
+    /// * `index` - a u16, the index of the SMS in the modem.
+    /// * `sender` - a u8 slice, where the phone number of the sender is stored.
+    /// * `text` - a u8 slice, where the message up to its first line break is stored.
+    /// # Returns
+    /// * `a Result<(usize, usize), ModemError>` - the lengths of the sender and the text, InvalidResponse if there is no SMS at the index.
+    pub fn read_sms(
+        &mut self,
+        index: u16,
+        sender: &mut [u8],
+        text: &mut [u8],
+    ) -> Result<(usize, usize), ModemError> {
+        self.send_str("AT+CMGR=");
+        self.send_number(index as u32);
+        self.send_str("\r");
+        loop {
+            self.read_line(COMMAND_TIMEOUT_MS)?;
+            if is_error(self.line()) {
+                return Err(ModemError::Error);
+            }
+            if self.line() == b"OK" {
+                return Err(ModemError::InvalidResponse);
+            }
+            if self.line().starts_with(b"+CMGR:") {
+                break;
+            }
+        }
+        // +CMGR: "REC UNREAD","+911234567890","","21/01/01,12:00:00+22"
+        let number = field(self.line(), 1).unwrap_or(&[]);
+        let mut sender_len: usize = 0;
+        for (place, &b) in sender.iter_mut().zip(number.iter().filter(|&&b| b != b'"')) {
+            *place = b;
+            sender_len += 1;
+        }
+        let text_len = receive_line(self.usart, text, COMMAND_TIMEOUT_MS)?;
+        self.wait_for(b"OK", COMMAND_TIMEOUT_MS)?;
+        Ok((sender_len, text_len))
+    }
+
+    /// Deletes a stored SMS.
+    /// # Arguments
+    /// * `index` - a u16, the index of the SMS in the modem.
+    /// # Returns
+    /// * `a Result<(), ModemError>` - Error if the modem rejected the command.
+    pub fn delete_sms(&mut self, index: u16) -> Result<(), ModemError> {
+        self.send_str("AT+CMGD=");
+        self.send_number(index as u32);
+        self.send_str("\r");
+        self.wait_for(b"OK", COMMAND_TIMEOUT_MS)
+    }
+
+    /// Brings up the data connection and opens a TCP connection.
+    /// Received data is kept in the modem and announced with `ModemUrc::DataReceived`.
+    /// # Arguments
+    /// * `apn` - a string object, the access point name of the mobile operator.
+    /// * `host` - a string object, the name or address of the server.
+    /// * `port` - a u16, the port of the server.
+    /// # Returns
+    /// * `a Result<(), ModemError>` - ConnectFailed if the server could not be reached.
+    pub fn tcp_connect(&mut self, apn: &str, host: &str, port: u16) -> Result<(), ModemError> {
+        self.send_str("AT+CIPSHUT\r");
+        self.wait_for(b"SHUT OK", NETWORK_TIMEOUT_MS)?;
+        self.command("AT+CIPMUX=0")?;
+        self.command("AT+CIPRXGET=1")?;
+        self.send_str("AT+CSTT=\"");
+        self.send_str(apn);
+        self.send_str("\"\r");
+        self.wait_for(b"OK", COMMAND_TIMEOUT_MS)?;
+        self.send_str("AT+CIICR\r");
+        self.wait_for(b"OK", NETWORK_TIMEOUT_MS)?;
+        // The local address is the only answer, without OK.
+        self.send_str("AT+CIFSR\r");
+        self.read_line(COMMAND_TIMEOUT_MS)?;
+        if is_error(self.line()) {
+            return Err(ModemError::Error);
+        }
+        self.send_str("AT+CIPSTART=\"TCP\",\"");
+        self.send_str(host);
+        self.send_str("\",\"");
+        self.send_number(port as u32);
+        self.send_str("\"\r");
+        self.wait_for(b"OK", COMMAND_TIMEOUT_MS)?;
+        loop {
+            self.read_line(CONNECT_TIMEOUT_MS)?;
+            match self.line() {
+                b"CONNECT OK" | b"ALREADY CONNECT" => return Ok(()),
+                b"CONNECT FAIL" => return Err(ModemError::ConnectFailed),
+                line if is_error(line) => return Err(ModemError::Error),
+                _ => (),
+            }
+        }
+    }
+
+    /// Sends data over the open TCP connection.
+    /// # Arguments
+    /// * `data` - a u8 slice, the data of at most 1460 bytes.
+    /// # Returns
+    /// * `a Result<(), ModemError>` - Error if the data could not be sent.
+    pub fn tcp_send(&mut self, data: &[u8]) -> Result<(), ModemError> {
+        self.send_str("AT+CIPSEND=");
+        self.send_number(data.len() as u32);
+        self.send_str("\r");
+        self.wait_prompt(COMMAND_TIMEOUT_MS)?;
+        self.send(data);
+        self.wait_for(b"SEND OK", CONNECT_TIMEOUT_MS)
+    }
+
+    /// Fetches TCP data kept in the modem.
+    /// # Arguments
+    /// * `buffer` - a u8 slice, where the data is stored.
+    /// # Returns
+    /// * `a Result<usize, ModemError>` - the number of bytes stored, 0 if no data was waiting.
+    pub fn tcp_receive(&mut self, buffer: &mut [u8]) -> Result<usize, ModemError> {
+        self.send_str("AT+CIPRXGET=2,");
+        self.send_number(buffer.len().min(TCP_READ_MAX) as u32);
+        self.send_str("\r");
+        // +CIPRXGET: 2,<length>,<left>
+        let numbers = self.query_numbers(b"+CIPRXGET: 2", COMMAND_TIMEOUT_MS)?;
+        let length = numbers[1] as usize;
+        if length > buffer.len() {
+            return Err(ModemError::InvalidResponse);
+        }
+        let mut polls = COMMAND_TIMEOUT_MS * 100;
+        for place in buffer[..length].iter_mut() {
+            *place = next_byte(self.usart, &mut polls).ok_or(ModemError::Timeout)?;
+        }
+        self.wait_for(b"OK", COMMAND_TIMEOUT_MS)?;
+        Ok(length)
+    }
+
+    /// Closes the TCP connection and the data connection.
+    /// # Returns
+    /// * `a Result<(), ModemError>` - Error if the modem rejected the command.
+    pub fn tcp_close(&mut self) -> Result<(), ModemError> {
+        self.send_str("AT+CIPCLOSE\r");
+        // The connection may already be closed by the remote side.
+        let _ = self.wait_for(b"CLOSE OK", COMMAND_TIMEOUT_MS);
+        self.send_str("AT+CIPSHUT\r");
+        self.wait_for(b"SHUT OK", NETWORK_TIMEOUT_MS)
+    }
+
+    /// Gives the next unsolicited result code found by the receive interrupt.
+    /// Each code is given once.
+    /// # Returns
+    /// * `a Option<ModemUrc>` - the code, or None if nothing new was received.
+    pub fn poll_urc(&mut self) -> Option<ModemUrc> {
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            let urc = URC.take();
+            write_volatile(SREG, sreg);
+            urc
+        }
+    }
+
+    /// Switches the modem off with `AT+CPOWD=1`.
+    pub fn power_down(&mut self) {
+        self.send_str("AT+CPOWD=1\r");
+        delay_ms(100);
+    }
+}
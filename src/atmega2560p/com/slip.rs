@@ -0,0 +1,225 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! SLIP framing of binary packets over the USARTs of ATMEGA2560P.
+//! Every packet ends with the END byte, and END or ESC bytes inside the packet
+//! are sent as two byte escape sequences, so packets can be found in the byte
+//! stream without any length field. This is the framing of RFC 1055, which
+//! is understood by the `slip` codecs on the PC side.
+//! `<https://datatracker.ietf.org/doc/html/rfc1055>`
+
+// Source code crates required
+use crate::atmega2560p::com::usart_initialize::{UsartError, UsartObject};
+
+/// Marks the end of a packet.
+pub const SLIP_END: u8 = 0xC0;
+
+/// Starts an escape sequence.
+pub const SLIP_ESC: u8 = 0xDB;
+
+/// Sent after ESC in place of an END byte in the packet.
+pub const SLIP_ESC_END: u8 = 0xDC;
+
+/// Sent after ESC in place of an ESC byte in the packet.
+pub const SLIP_ESC_ESC: u8 = 0xDD;
+
+/// Errors found while receiving a packet.
+/// * `Overflow` - the packet is longer than the buffer, the rest of it is dropped.
+/// * `BadEscape` - ESC was followed by a byte other than ESC_END or ESC_ESC.
+/// * `Usart` - the USART found an error in a received frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SlipError {
+    Overflow,
+    BadEscape,
+    Usart(UsartError),
+}
+
+/// Encodes one packet with its END byte.
+/// # Arguments
+/// * `packet` - a u8 slice, the packet to be encoded.
+/// * `output` - a mutable u8 slice, where the encoded bytes are stored.
+///   Twice the packet length plus one is always enough.
+/// # Returns
+/// * `a Option<usize>` - the number of encoded bytes, None if the output is too small.
+pub fn slip_encode(packet: &[u8], output: &mut [u8]) -> Option<usize> {
+    let mut len = 0;
+    for &byte in packet.iter() {
+        let escaped = match byte {
+            SLIP_END => Some(SLIP_ESC_END),
+            SLIP_ESC => Some(SLIP_ESC_ESC),
+            _ => None,
+        };
+        match escaped {
+            Some(code) => {
+                if len + 2 > output.len() {
+                    return None;
+                }
+                output[len] = SLIP_ESC;
+                output[len + 1] = code;
+                len += 2;
+            }
+            None => {
+                if len + 1 > output.len() {
+                    return None;
+                }
+                output[len] = byte;
+                len += 1;
+            }
+        }
+    }
+    if len + 1 > output.len() {
+        return None;
+    }
+    output[len] = SLIP_END;
+    Some(len + 1)
+}
+
+/// Decodes packets from the received bytes, one byte at a time.
+/// The packet is kept in the buffer given to the decoder until the next byte is pushed.
+pub struct SlipDecoder<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+    escaped: bool,
+    error: Option<SlipError>,
+}
+
+impl<'a> SlipDecoder<'a> {
+    /// Creates a decoder which stores the packets in the given buffer.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, as long as the largest packet expected.
+    /// # Returns
+    /// * `a SlipDecoder object` - which will be given the received bytes.
+    pub fn new(buffer: &'a mut [u8]) -> SlipDecoder<'a> {
+        SlipDecoder {
+            buffer,
+            len: 0,
+            escaped: false,
+            error: None,
+        }
+    }
+
+    /// Drops the part of the packet received so far.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.escaped = false;
+        self.error = None;
+    }
+
+    /// Takes one received byte.
+    /// Empty packets, such as the END sent before a packet to flush line noise, are skipped.
+    /// # Arguments
+    /// * `byte` - a u8, the received byte.
+    /// # Returns
+    /// * `a Option<Result<usize, SlipError>>` - the length of the packet completed by the byte,
+    ///   or the error found in it. None while the packet is not complete.
+    pub fn push(&mut self, byte: u8) -> Option<Result<usize, SlipError>> {
+        if byte == SLIP_END {
+            let len = self.len;
+            let error = self.error;
+            self.reset();
+            return match error {
+                Some(error) => Some(Err(error)),
+                None if len == 0 => None,
+                None => Some(Ok(len)),
+            };
+        }
+        if self.error.is_some() {
+            return None;
+        }
+
+        let value = if self.escaped {
+            self.escaped = false;
+            match byte {
+                SLIP_ESC_END => SLIP_END,
+                SLIP_ESC_ESC => SLIP_ESC,
+                _ => {
+                    self.error = Some(SlipError::BadEscape);
+                    return None;
+                }
+            }
+        } else if byte == SLIP_ESC {
+            self.escaped = true;
+            return None;
+        } else {
+            byte
+        };
+
+        if self.len >= self.buffer.len() {
+            self.error = Some(SlipError::Overflow);
+            return None;
+        }
+        self.buffer[self.len] = value;
+        self.len += 1;
+        None
+    }
+
+    /// Gives the packet completed by the last call of `push()`.
+    /// # Arguments
+    /// * `len` - a usize, the length returned by `push()`.
+    /// # Returns
+    /// * `a u8 slice` - the decoded packet.
+    pub fn packet(&self, len: usize) -> &[u8] {
+        &self.buffer[..len]
+    }
+}
+
+impl UsartObject {
+    /// Sends one packet with SLIP framing.
+    /// An END byte is sent first as well, so that noise received by the PC
+    /// before the packet ends up in an empty packet which is dropped.
+    /// # Arguments
+    /// * `packet` - a u8 slice, the packet to be sent.
+    pub fn write_slip(&mut self, packet: &[u8]) {
+        self.transmit_data(SLIP_END);
+        for &byte in packet.iter() {
+            match byte {
+                SLIP_END => {
+                    self.transmit_data(SLIP_ESC);
+                    self.transmit_data(SLIP_ESC_END);
+                }
+                SLIP_ESC => {
+                    self.transmit_data(SLIP_ESC);
+                    self.transmit_data(SLIP_ESC_ESC);
+                }
+                _ => self.transmit_data(byte),
+            }
+        }
+        self.transmit_data(SLIP_END);
+    }
+
+    /// Waits for one whole SLIP packet.
+    /// The USART has to be initialized with the receiver enabled.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, where the packet is stored.
+    /// # Returns
+    /// * `a Result<usize, SlipError>` - the length of the packet, or the error found in it.
+    pub fn read_slip(&mut self, buffer: &mut [u8]) -> Result<usize, SlipError> {
+        let mut decoder = SlipDecoder::new(buffer);
+        loop {
+            let byte = match self.read_byte() {
+                Ok(byte) => byte,
+                Err(error) => {
+                    // The rest of the damaged packet is dropped up to its END byte.
+                    while self.read_byte().ok() != Some(SLIP_END) {}
+                    return Err(SlipError::Usart(error));
+                }
+            };
+            if let Some(result) = decoder.push(byte) {
+                return result;
+            }
+        }
+    }
+}
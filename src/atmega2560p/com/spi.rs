@@ -0,0 +1,358 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! SPI master for ATMEGA2560P with devices which manage their own chip select.
+//! `Spi` controls the bus and `SpiDevice` owns the CS pin of one device together with
+//! its clock and mode settings. The settings are applied at the start of every transfer,
+//! so devices with different settings can share the bus and no driver has to handle CS.
+//! Section 21 of ATMEGA2560P datasheet.
+
+// Crates to be used for the implementation.
+use bit_field::BitField;
+use core::ptr::read_volatile;
+use volatile::Volatile;
+
+// Source code crates required
+use crate::atmega2560p::hal::gpio::{Output, Pin};
+use crate::atmega2560p::hal::port::{Port, PortName};
+use crate::atmega2560p::hal::power;
+
+// Pins of the SPI bus on port B.
+const SS: u8 = 0;
+const SCK: u8 = 1;
+const MOSI: u8 = 2;
+const MISO: u8 = 3;
+
+// Bits of SPCR.
+const SPR0: u8 = 0;
+const SPR1: u8 = 1;
+const CPHA: u8 = 2;
+const CPOL: u8 = 3;
+const MSTR: u8 = 4;
+const DORD: u8 = 5;
+const SPE: u8 = 6;
+
+// Bits of SPSR.
+const SPI2X: u8 = 0;
+const SPIF: u8 = 7;
+
+/// Clock polarity and phase of the bus.
+/// * `Mode0` - Clock idles low, data is sampled on the rising edge.
+/// * `Mode1` - Clock idles low, data is sampled on the falling edge.
+/// * `Mode2` - Clock idles high, data is sampled on the falling edge.
+/// * `Mode3` - Clock idles high, data is sampled on the rising edge.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SpiMode {
+    Mode0,
+    Mode1,
+    Mode2,
+    Mode3,
+}
+
+/// Order in which the bits of a byte are sent.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+/// Bus settings of one device.
+/// * `clock_hz` - the highest SCK frequency the device allows, the nearest lower one is used.
+/// * `mode` - the clock polarity and phase of the device.
+/// * `bit_order` - the order of the bits of the device.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SpiSettings {
+    pub clock_hz: u32,
+    pub mode: SpiMode,
+    pub bit_order: BitOrder,
+}
+
+impl SpiSettings {
+    /// Creates the settings of a device.
+    /// # Arguments
+    /// * `clock_hz` - a u32, the highest SCK frequency the device allows.
+    /// * `mode` - a `SpiMode` object, the clock polarity and phase of the device.
+    /// * `bit_order` - a `BitOrder` object, the order of the bits of the device.
+    /// # Returns
+    /// * `a SpiSettings object` - which is given to `SpiDevice::new()`.
+    pub fn new(clock_hz: u32, mode: SpiMode, bit_order: BitOrder) -> SpiSettings {
+        SpiSettings {
+            clock_hz,
+            mode,
+            bit_order,
+        }
+    }
+}
+
+/// Contains the registers of the SPI.
+///
+/// * `SPCR`: *SPI Control Register*. Enables the SPI and selects master mode,
+/// bit order, clock polarity, clock phase and the clock rate.
+///
+/// * `SPSR`: *SPI Status Register*. SPIF is set when a transfer is complete and
+/// SPI2X doubles the clock rate.
+///
+/// * `SPDR`: *SPI Data Register*. Writing it starts a transfer and reading it
+/// gives the byte received in the last transfer.
+#[repr(C, packed)]
+pub struct Spi {
+    spcr: Volatile<u8>,
+    spsr: Volatile<u8>,
+    spdr: Volatile<u8>,
+}
+
+impl Spi {
+    /// Creates a new reference to the Spi structure at a specified location.
+    /// # Returns
+    /// * `a reference to Spi object` - which will be used for further implementations.
+    pub unsafe fn new() -> &'static mut Spi {
+        &mut *(0x4C as *mut Spi)
+    }
+
+    /// Powers the SPI and enables it in master mode.
+    /// SS is made an output driven high, as a low level on it as an input would
+    /// turn the SPI into a slave. It can still be used as the CS pin of a device.
+    pub fn begin(&mut self) {
+        unsafe { power::Power::new() }.enable(power::Peripheral::Spi);
+
+        let port = unsafe { Port::new(PortName::B) };
+        port.update_byte(1 << SS, 1 << SS);
+        let ddr = unsafe { read_volatile(&port.ddr) };
+        port.write_direction((ddr | (1 << SS) | (1 << MOSI) | (1 << SCK)) & !(1 << MISO));
+
+        self.spcr.update(|spcr| {
+            spcr.set_bit(MSTR, true);
+            spcr.set_bit(SPE, true);
+        });
+    }
+
+    /// Disables the SPI, the pins go back to general I/O.
+    pub fn end(&mut self) {
+        self.spcr.update(|spcr| {
+            spcr.set_bit(SPE, false);
+        });
+    }
+
+    /// Applies the clock rate, mode and bit order of a device.
+    /// The clock is the system clock divided by 2, 4, 8, 16, 32, 64 or 128, and the
+    /// smallest division which does not go above `clock_hz` is used.
+    /// # Arguments
+    /// * `settings` - a `SpiSettings` object, the settings to be applied.
+    pub fn configure(&mut self, settings: &SpiSettings) {
+        let f_cpu = crate::config::cpu_frequency_hz();
+        let mut division: u8 = 0; // The clock is f_cpu >> (division + 1).
+        while division < 6 && (f_cpu >> (division + 1)) > settings.clock_hz {
+            division += 1;
+        }
+        // SPR1:0 select 4, 16, 64 or 128 and SPI2X halves the first three.
+        let (spr, double) = match division {
+            0 => (0b00, true),
+            1 => (0b00, false),
+            2 => (0b01, true),
+            3 => (0b01, false),
+            4 => (0b10, true),
+            5 => (0b10, false),
+            _ => (0b11, false),
+        };
+        let (cpol, cpha) = match settings.mode {
+            SpiMode::Mode0 => (false, false),
+            SpiMode::Mode1 => (false, true),
+            SpiMode::Mode2 => (true, false),
+            SpiMode::Mode3 => (true, true),
+        };
+        self.spcr.update(|spcr| {
+            spcr.set_bit(SPR0, spr & 0b01 != 0);
+            spcr.set_bit(SPR1, spr & 0b10 != 0);
+            spcr.set_bit(CPHA, cpha);
+            spcr.set_bit(CPOL, cpol);
+            spcr.set_bit(DORD, settings.bit_order == BitOrder::LsbFirst);
+        });
+        self.spsr.update(|spsr| {
+            spsr.set_bit(SPI2X, double);
+        });
+    }
+
+    /// Sends one byte and receives one byte at the same time.
+    /// # Arguments
+    /// * `data` - a u8, the byte to be sent.
+    /// # Returns
+    /// * `a u8` - the byte received.
+    pub fn transfer_byte(&mut self, data: u8) -> u8 {
+        self.spdr.write(data);
+        while !self.spsr.read().get_bit(SPIF) {}
+        self.spdr.read()
+    }
+
+    /// Sends the bytes of the buffer and replaces each with the byte received.
+    /// # Arguments
+    /// * `data` - a mutable u8 slice, the bytes to be sent.
+    pub fn transfer(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            *byte = self.transfer_byte(*byte);
+        }
+    }
+
+    /// Sends the bytes and drops the bytes received.
+    /// # Arguments
+    /// * `data` - a u8 slice, the bytes to be sent.
+    pub fn write(&mut self, data: &[u8]) {
+        for byte in data.iter() {
+            self.transfer_byte(*byte);
+        }
+    }
+}
+
+/// One device on the SPI bus which owns its chip select pin.
+/// CS is driven low for the length of every transfer and high again afterwards.
+pub struct SpiDevice {
+    cs: Pin<Output>,
+    settings: SpiSettings,
+}
+
+impl SpiDevice {
+    /// Creates a device, CS is driven high so that the device is not selected.
+    /// `Spi::begin()` has to be called once before the first transfer.
+    /// # Arguments
+    /// * `cs` - a `Pin<Output>` object, the chip select pin of the device.
+    /// * `settings` - a `SpiSettings` object, the bus settings of the device.
+    /// # Returns
+    /// * `a SpiDevice object` - which will be used for the transfers.
+    pub fn new(mut cs: Pin<Output>, settings: SpiSettings) -> SpiDevice {
+        cs.high();
+        SpiDevice { cs, settings }
+    }
+
+    /// Changes the bus settings of the device, used from the next transfer.
+    /// # Arguments
+    /// * `settings` - a `SpiSettings` object, the new settings.
+    pub fn set_settings(&mut self, settings: SpiSettings) {
+        self.settings = settings;
+    }
+
+    /// Gives the bus settings of the device.
+    /// # Returns
+    /// * `a SpiSettings object` - the present settings.
+    pub fn settings(&self) -> SpiSettings {
+        self.settings
+    }
+
+    /// Applies the settings of the device, selects it, runs the given
+    /// function on the bus and deselects the device again.
+    /// Used for exchanges which need more than one transfer under a single CS.
+    /// # Arguments
+    /// * `f` - a function, which does the transfers on the bus.
+    /// # Returns
+    /// * `a R` - the value given by the function.
+    pub fn transaction<R, F: FnOnce(&mut Spi) -> R>(&mut self, f: F) -> R {
+        let spi = unsafe { Spi::new() };
+        spi.configure(&self.settings);
+        self.cs.low();
+        let result = f(spi);
+        // The last byte has been received, so the device can be deselected at once.
+        self.cs.high();
+        result
+    }
+
+    /// Sends the bytes of the buffer to the device and replaces each with the byte received.
+    /// # Arguments
+    /// * `data` - a mutable u8 slice, the bytes to be sent.
+    pub fn transfer(&mut self, data: &mut [u8]) {
+        self.transaction(|spi| spi.transfer(data));
+    }
+
+    /// Sends the bytes to the device and drops the bytes received.
+    /// # Arguments
+    /// * `data` - a u8 slice, the bytes to be sent.
+    pub fn write(&mut self, data: &[u8]) {
+        self.transaction(|spi| spi.write(data));
+    }
+
+    /// Reads bytes from the device while sending zeros.
+    /// # Arguments
+    /// * `data` - a mutable u8 slice, where the received bytes are stored.
+    pub fn read(&mut self, data: &mut [u8]) {
+        self.transaction(|spi| {
+            for byte in data.iter_mut() {
+                *byte = spi.transfer_byte(0x00);
+            }
+        });
+    }
+
+    /// Sends a command and then reads the answer under the same CS, as most
+    /// register based devices expect.
+    /// # Arguments
+    /// * `command` - a u8 slice, the bytes to be sent first.
+    /// * `data` - a mutable u8 slice, where the answer is stored.
+    pub fn write_read(&mut self, command: &[u8], data: &mut [u8]) {
+        self.transaction(|spi| {
+            spi.write(command);
+            for byte in data.iter_mut() {
+                *byte = spi.transfer_byte(0x00);
+            }
+        });
+    }
+
+    /// Gives back the chip select pin.
+    /// # Returns
+    /// * `a Pin<Output> object` - the chip select pin, left high.
+    pub fn release(self) -> Pin<Output> {
+        self.cs
+    }
+}
+
+/// The SPI for the drivers written against the `embedded-hal` SPI traits, which
+/// manage the chip select of their device themselves. They are on the reference
+/// given by `Spi::new()`, `begin()` and `configure()` have to be called before.
+/// The SPI cannot fail, so the error is `Infallible`.
+#[cfg(feature = "embedded")]
+impl embedded_hal::spi::FullDuplex<u8> for &mut Spi {
+    type Error = core::convert::Infallible;
+
+    /// Gives the byte received once the transfer started by `send()` is complete.
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        if self.spsr.read().get_bit(SPIF) {
+            Ok(self.spdr.read())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Starts a transfer, `read()` has to be called after each byte sent.
+    fn send(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.spdr.write(byte);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::spi::Transfer<u8> for &mut Spi {
+    type Error = core::convert::Infallible;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        Spi::transfer(self, words);
+        Ok(words)
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::spi::Write<u8> for &mut Spi {
+    type Error = core::convert::Infallible;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        Spi::write(self, words);
+        Ok(())
+    }
+}
@@ -24,10 +24,11 @@
 use crate::atmega2560p::com::serial::Serial;
 use crate::atmega2560p::com::usart_initialize::UsartObject;
 use crate::atmega2560p::com::usart_initialize::{
-    UsartDataSize, UsartModes, UsartNum, UsartParity, UsartPolarity, UsartStop,
+    UsartDataSize, UsartError, UsartModes, UsartNum, UsartParity, UsartPolarity, UsartStop,
 };
 
 // Standard datatypes to be used
+use core::fmt::{self, Write};
 use core::{f64, u32};
 
 // Default setting parameters for various modes of USART in case user want's to skip them.
@@ -55,21 +56,25 @@ impl Serial {
 
 impl UsartObject {
     /// Can be use to initialize a USART with default settings.
-    pub unsafe fn begin(&mut self) {
+    pub unsafe fn begin(&mut self) -> Result<(), UsartError> {
         self.disable();
         self.transmit_enable();
         self.recieve_enable();
-        self.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+        let result = self.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+        self.enable();
+        result
     }
 
     /// Can be use to initialize with given baud rate and remaining settings will be set to default.
     /// # Arguments
     /// * `baud1` - a i64, the baud rate of USART the user wants to set.
-    pub unsafe fn begin_set_baud(&mut self, baud1: i64) {
+    pub unsafe fn begin_set_baud(&mut self, baud1: i64) -> Result<(), UsartError> {
         self.disable();
         self.transmit_enable();
         self.recieve_enable();
-        self.initialize(MODE, baud1, STOP, SIZE, PARITY);
+        let result = self.initialize(MODE, baud1, STOP, SIZE, PARITY);
+        self.enable();
+        result
     }
 
     /// Can be used to stop the functioning of initialized USART.
@@ -88,16 +93,19 @@ impl UsartObject {
 /// This will be used to transmit string data.
 /// # Arguments
 /// * `data` - a string object, which is to be transmitted using USART.
-pub fn println_string(data: &'static str) {
+pub fn println_string(data: &'static str) -> Result<(), UsartError> {
     unsafe {
         let mut u: UsartObject = UsartObject::new(NUM);
         u.disable();
         u.transmit_enable();
-        u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
-        u.write_string(data);
+        let result = u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+        if result.is_ok() {
+            u.write_string(data);
+        }
         u.transmit_disable();
         u.reset();
         u.enable();
+        result
     }
 }
 
@@ -108,16 +116,19 @@ pub fn println_string(data: &'static str) {
 /// This will be used to transmit integer data.
 /// # Arguments
 /// * `data` - a u32, which is to be transmitted using USART.
-pub fn println_integer(data: u32) {
+pub fn println_integer(data: u32) -> Result<(), UsartError> {
     unsafe {
         let mut u: UsartObject = UsartObject::new(NUM);
         u.disable();
         u.transmit_enable();
-        u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
-        u.write_integer(data);
+        let result = u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+        if result.is_ok() {
+            u.write_integer(data);
+        }
         u.transmit_disable();
         u.reset();
         u.enable();
+        result
     }
 }
 
@@ -129,16 +140,19 @@ pub fn println_integer(data: u32) {
 /// # Arguments
 /// * `data` - a f32, which is to be transmitted using USART.
 /// * `precision` - a u32, the number of decimal precision required in the transmission.
-pub fn println_float(data: f64, precision: u32) {
+pub fn println_float(data: f64, precision: u32) -> Result<(), UsartError> {
     unsafe {
         let mut u: UsartObject = UsartObject::new(NUM);
         u.disable();
         u.transmit_enable();
-        u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
-        u.write_float(data, precision);
+        let result = u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+        if result.is_ok() {
+            u.write_float(data, precision);
+        }
         u.transmit_disable();
         u.reset();
         u.enable();
+        result
     }
 }
 
@@ -149,16 +163,19 @@ pub fn println_float(data: f64, precision: u32) {
 /// # Arguments
 /// * `data` - a string object, which is to be transmitted using USART.
 /// * `baud1` - a i64, the baud rate of USART the user wants to set.
-pub fn println_set_baud(data: &'static str, baud1: i64) {
+pub fn println_set_baud(data: &'static str, baud1: i64) -> Result<(), UsartError> {
     unsafe {
         let mut u: UsartObject = UsartObject::new(NUM);
         u.disable();
         u.transmit_enable();
-        u.initialize(MODE, baud1, STOP, SIZE, PARITY);
-        u.write_string(data);
+        let result = u.initialize(MODE, baud1, STOP, SIZE, PARITY);
+        if result.is_ok() {
+            u.write_string(data);
+        }
         u.transmit_disable();
         u.reset();
         u.enable();
+        result
     }
 }
 
@@ -176,16 +193,19 @@ pub fn println_set_frame(
     size1: UsartDataSize,
     parity1: UsartParity,
     stop1: UsartStop,
-) {
+) -> Result<(), UsartError> {
     unsafe {
         let mut u: UsartObject = UsartObject::new(NUM);
         u.disable();
         u.transmit_enable();
-        u.initialize(MODE, BAUD, stop1, size1, parity1);
-        u.write_string(data);
+        let result = u.initialize(MODE, BAUD, stop1, size1, parity1);
+        if result.is_ok() {
+            u.write_string(data);
+        }
         u.transmit_disable();
         u.reset();
         u.enable();
+        result
     }
 }
 
@@ -209,15 +229,56 @@ pub fn println_detail(
     size1: UsartDataSize,
     parity1: UsartParity,
     stop1: UsartStop,
-) {
+) -> Result<(), UsartError> {
     unsafe {
         let mut u: UsartObject = UsartObject::new(num1);
         u.disable();
         u.transmit_enable();
-        u.initialize(mode1, baud1, stop1, size1, parity1);
-        u.write_string(data);
+        let result = u.initialize(mode1, baud1, stop1, size1, parity1);
+        if result.is_ok() {
+            u.write_string(data);
+        }
         u.transmit_disable();
         u.reset();
         u.enable();
+        result
+    }
+}
+
+// USART used by the `serial_print!` and `serial_println!` macros.
+static mut PRINT_USART: UsartNum = NUM;
+static mut PRINT_BAUD: i64 = BAUD;
+static mut PRINT_READY: bool = false;
+
+/// Chooses the USART used by the `serial_print!` and `serial_println!` macros.
+/// It is initialized again with the given baud rate on the next print.
+/// # Arguments
+/// * `num` - a `UsartNum` object, which defines the USART to be used.
+/// * `baud` - a i64, the baud rate of USART the user wants to set.
+pub fn set_print_usart(num: UsartNum, baud: i64) {
+    unsafe {
+        PRINT_USART = num;
+        PRINT_BAUD = baud;
+        PRINT_READY = false;
+    }
+}
+
+/// Writes formatted text to the USART chosen for printing, which is initialized
+/// with the default frame settings on the first call.
+/// Used by the `serial_print!` and `serial_println!` macros.
+/// # Arguments
+/// * `args` - a `fmt::Arguments` object, the text made by `format_args!`.
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let mut u: UsartObject = unsafe { UsartObject::new(PRINT_USART) };
+    unsafe {
+        if !PRINT_READY {
+            u.transmit_enable();
+            if u.initialize(MODE, PRINT_BAUD, STOP, SIZE, PARITY).is_err() {
+                return;
+            }
+            PRINT_READY = true;
+        }
     }
+    let _ = u.write_fmt(args);
 }
@@ -0,0 +1,468 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Interrupt driven transmission and reception for the USARTs of ATMEGA2560P.
+//! The receive complete interrupt stores every received byte in a ring buffer and the
+//! data register empty interrupt sends the bytes queued in another ring buffer, so the
+//! main loop never has to wait for the USART. Optional RTS/CTS flow control on
+//! general purpose pins keeps either side from sending faster than the other can read.
+//! See the section 22.7 and 22.8 of ATMEGA2560P datasheet.
+
+// Source code crates required
+use crate::atmega2560p::com::usart_initialize::{Usart, UsartNum};
+use crate::atmega2560p::hal::interrupts;
+use crate::atmega2560p::hal::port::Pin;
+
+// Crates which would be used in the implementation.
+use bit_field::BitField;
+use core::ptr::{read_volatile, write_volatile};
+
+/// Size of the receive and transmit ring buffers, has to be a power of two.
+/// One place is kept empty to tell a full buffer from an empty one.
+pub const BUFFER_SIZE: usize = 64;
+
+/// Fill level of the receive buffer at which RTS is deasserted.
+const HIGH_WATER: usize = BUFFER_SIZE * 3 / 4;
+
+/// Fill level of the receive buffer at which RTS is asserted again.
+const LOW_WATER: usize = BUFFER_SIZE / 4;
+
+/// Address of the SREG register, used to read the statistics free from interrupts.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+/// Fixed size first in first out queue of bytes shared with an interrupt routine.
+/// Only one side writes `head` and only the other side writes `tail`,
+/// so no critical section is needed to push and pop.
+struct RingBuffer {
+    data: [u8; BUFFER_SIZE],
+    head: u8,
+    tail: u8,
+}
+
+impl RingBuffer {
+    /// Gives an empty ring buffer.
+    const fn new() -> RingBuffer {
+        RingBuffer {
+            data: [0; BUFFER_SIZE],
+            head: 0,
+            tail: 0,
+        }
+    }
+
+    /// Gives the number of bytes in the buffer.
+    fn len(&self) -> usize {
+        let head = unsafe { read_volatile(&self.head) };
+        let tail = unsafe { read_volatile(&self.tail) };
+        (head.wrapping_sub(tail) as usize) & (BUFFER_SIZE - 1)
+    }
+
+    /// Adds a byte at the end of the buffer, false if the buffer is full.
+    fn push(&mut self, byte: u8) -> bool {
+        let head = unsafe { read_volatile(&self.head) };
+        let next = (head + 1) & (BUFFER_SIZE as u8 - 1);
+        if next == unsafe { read_volatile(&self.tail) } {
+            return false;
+        }
+        self.data[head as usize] = byte;
+        unsafe { write_volatile(&mut self.head, next) };
+        true
+    }
+
+    /// Removes the byte at the start of the buffer, if any.
+    fn pop(&mut self) -> Option<u8> {
+        let tail = unsafe { read_volatile(&self.tail) };
+        if tail == unsafe { read_volatile(&self.head) } {
+            return None;
+        }
+        let byte = self.data[tail as usize];
+        unsafe { write_volatile(&mut self.tail, (tail + 1) & (BUFFER_SIZE as u8 - 1)) };
+        Some(byte)
+    }
+
+    /// Removes all the bytes from the buffer.
+    fn clear(&mut self) {
+        let head = unsafe { read_volatile(&self.head) };
+        unsafe { write_volatile(&mut self.tail, head) };
+    }
+}
+
+/// Pins used for flow control with buffered communication, both are active low.
+/// * `rts` - Output, driven low while there is space in the receive buffer.
+/// * `cts` - Input, transmission is paused while the other side holds it high.
+#[derive(Clone, Copy)]
+pub struct FlowControl {
+    pub rts: Option<Pin>,
+    pub cts: Option<Pin>,
+}
+
+impl FlowControl {
+    /// Checks if the other side is ready to receive, always true without a CTS pin.
+    fn clear_to_send(&self) -> bool {
+        match self.cts {
+            Some(cts) => (unsafe { read_volatile(&(*cts.port).pin) } & (1 << cts.pin)) == 0,
+            None => true,
+        }
+    }
+
+    /// Drives RTS high to stop the other side, or low to let it send again.
+    fn set_rts(&self, stop: bool) {
+        if let Some(rts) = self.rts {
+            let port_val = unsafe { read_volatile(&(*rts.port).port) };
+            if (port_val & (1 << rts.pin) != 0) != stop {
+                // Writing one to PINxn toggles PORTxn without a read-modify-write.
+                unsafe { write_volatile(&mut (*rts.port).pin, 1 << rts.pin) };
+            }
+        }
+    }
+}
+
+/// Counts of the received bytes which were lost.
+#[derive(Clone, Copy)]
+pub struct UsartStats {
+    /// Bytes dropped because the receive ring buffer was full.
+    pub buffer_overflows: u16,
+    /// Data overruns reported by the USART, bytes lost before the interrupt could read them.
+    pub data_overruns: u16,
+}
+
+// Ring buffers and statistics of each USART.
+static mut RX_BUFFER: [RingBuffer; 4] = [
+    RingBuffer::new(),
+    RingBuffer::new(),
+    RingBuffer::new(),
+    RingBuffer::new(),
+];
+static mut TX_BUFFER: [RingBuffer; 4] = [
+    RingBuffer::new(),
+    RingBuffer::new(),
+    RingBuffer::new(),
+    RingBuffer::new(),
+];
+static mut STATS: [UsartStats; 4] = [UsartStats {
+    buffer_overflows: 0,
+    data_overruns: 0,
+}; 4];
+static mut BUFFERED: [bool; 4] = [false; 4];
+static mut FLOW: [Option<FlowControl>; 4] = [None; 4];
+static mut RX_HOOK: [Option<fn(u8)>; 4] = [None; 4];
+
+/// Gives the position of the USART in the buffer arrays.
+fn index(num: UsartNum) -> usize {
+    match num {
+        UsartNum::Usart0 => 0,
+        UsartNum::Usart1 => 1,
+        UsartNum::Usart2 => 2,
+        UsartNum::Usart3 => 3,
+    }
+}
+
+impl Usart {
+    /// Gives the position of this USART in the buffer arrays.
+    fn buffer_index(&self) -> usize {
+        index(self.name())
+    }
+
+    /// Starts interrupt driven reception, received bytes are stored in the receive buffer.
+    /// The USART has to be initialized and global interrupts enabled.
+    pub fn enable_buffered(&mut self) {
+        let i = self.buffer_index();
+        unsafe {
+            RX_BUFFER[i].clear();
+            BUFFERED[i] = true;
+        }
+        self.ucsrb.update(|ucsrb| {
+            ucsrb.set_bit(7, true);
+        });
+    }
+
+    /// Stops interrupt driven reception and transmission.
+    /// Bytes still in the transmit buffer are not sent.
+    pub fn disable_buffered(&mut self) {
+        self.ucsrb.update(|ucsrb| {
+            ucsrb.set_bit(7, false);
+            ucsrb.set_bit(5, false);
+        });
+        let i = self.buffer_index();
+        unsafe {
+            BUFFERED[i] = false;
+            TX_BUFFER[i].clear();
+        }
+    }
+
+    /// Queues bytes for transmission without waiting.
+    /// # Arguments
+    /// * `data` - a u8 slice, the bytes to be transmitted.
+    /// # Returns
+    /// * `a usize` - the number of bytes queued, less than the length of data if the buffer is full.
+    pub fn write_nonblocking(&mut self, data: &[u8]) -> usize {
+        let i = self.buffer_index();
+        let mut count: usize = 0;
+        for byte in data {
+            if !unsafe { TX_BUFFER[i].push(*byte) } {
+                break;
+            }
+            count += 1;
+        }
+        if count > 0 {
+            // The data register empty interrupt sends the queued bytes.
+            self.ucsrb.update(|ucsrb| {
+                ucsrb.set_bit(5, true);
+            });
+        }
+        count
+    }
+
+    /// Copies the received bytes from the receive buffer without waiting.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, where the received bytes are copied.
+    /// # Returns
+    /// * `a usize` - the number of bytes copied, zero if nothing was received.
+    pub fn read_available(&mut self, buffer: &mut [u8]) -> usize {
+        let i = self.buffer_index();
+        let mut count: usize = 0;
+        for place in buffer.iter_mut() {
+            match unsafe { RX_BUFFER[i].pop() } {
+                Some(byte) => *place = byte,
+                None => break,
+            }
+            count += 1;
+        }
+        // RTS is also changed by the receive interrupt, so the check and the change are done together.
+        interrupts::free(|_| {
+            if let Some(flow) = unsafe { FLOW[i] } {
+                if unsafe { RX_BUFFER[i].len() } <= LOW_WATER {
+                    flow.set_rts(false);
+                }
+            }
+        });
+        count
+    }
+
+    /// Gives the number of received bytes waiting in the receive buffer.
+    /// # Returns
+    /// * `a usize` - the number of bytes which can be read.
+    pub fn bytes_available(&self) -> usize {
+        unsafe { RX_BUFFER[self.buffer_index()].len() }
+    }
+
+    /// Gives the number of bytes in the transmit buffer which are not yet sent.
+    /// # Returns
+    /// * `a usize` - the number of bytes waiting for transmission.
+    pub fn bytes_pending(&self) -> usize {
+        unsafe { TX_BUFFER[self.buffer_index()].len() }
+    }
+
+    /// Enables flow control of buffered communication with the given pins.
+    /// RTS is made an output and asserted, CTS is made an input with pull-up so
+    /// that a missing connection stops the transmission.
+    /// # Arguments
+    /// * `rts` - a `Option<Pin>`, the request to send output, None if not used.
+    /// * `cts` - a `Option<Pin>`, the clear to send input, None if not used.
+    pub fn enable_flow_control(&mut self, rts: Option<Pin>, cts: Option<Pin>) {
+        let i = self.buffer_index();
+        if let Some(mut pin) = rts {
+            pin.set_output();
+        }
+        if let Some(mut pin) = cts {
+            pin.set_input_pullup();
+        }
+        let flow = FlowControl { rts, cts };
+        interrupts::free(|_| {
+            flow.set_rts(false);
+            unsafe {
+                FLOW[i] = Some(flow);
+            }
+        });
+    }
+
+    /// Disables flow control, the pins are left as they are.
+    pub fn disable_flow_control(&mut self) {
+        let i = self.buffer_index();
+        interrupts::free(|_| unsafe {
+            FLOW[i] = None;
+        });
+    }
+
+    /// Restarts a transmission which was paused by CTS.
+    /// The data register empty interrupt stops when CTS is deasserted, so this
+    /// has to be called from the main loop, or more bytes written, once it is asserted again.
+    pub fn resume_transmit(&mut self) {
+        let i = self.buffer_index();
+        let ready = match unsafe { FLOW[i] } {
+            Some(flow) => flow.clear_to_send(),
+            None => true,
+        };
+        if ready && unsafe { TX_BUFFER[i].len() } > 0 {
+            self.ucsrb.update(|ucsrb| {
+                ucsrb.set_bit(5, true);
+            });
+        }
+    }
+
+    /// Gives the counts of received bytes which were lost.
+    /// # Returns
+    /// * `a UsartStats object` - the overflow statistics.
+    pub fn stats(&self) -> UsartStats {
+        let i = self.buffer_index();
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            let stats = STATS[i];
+            write_volatile(SREG, sreg);
+            stats
+        }
+    }
+
+    /// Sets the overflow statistics back to zero.
+    pub fn clear_stats(&mut self) {
+        let i = self.buffer_index();
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            STATS[i].buffer_overflows = 0;
+            STATS[i].data_overruns = 0;
+            write_volatile(SREG, sreg);
+        }
+    }
+
+    /// Attaches a function which is called from the receive interrupt with every
+    /// byte stored in the receive buffer, such as a parser of unsolicited messages.
+    /// It runs in the interrupt, so it has to be short.
+    /// # Arguments
+    /// * `hook` - a optional function, None to detach the present one.
+    pub fn set_receive_hook(&mut self, hook: Option<fn(u8)>) {
+        let i = self.buffer_index();
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            RX_HOOK[i] = hook;
+            write_volatile(SREG, sreg);
+        }
+    }
+}
+
+/// Stores the received byte in the receive buffer of the USART.
+/// Without buffering the interrupt is disabled again.
+#[cfg_attr(not(target_arch = "avr"), allow(dead_code))]
+fn receive_complete(num: UsartNum) {
+    let usart = unsafe { Usart::new(num) };
+    let i = index(num);
+    if !unsafe { BUFFERED[i] } {
+        usart.ucsrb.update(|ucsrb| {
+            ucsrb.set_bit(7, false);
+        });
+        return;
+    }
+
+    // The flags have to be read before UDR.
+    let ucsra = usart.ucsra.read();
+    let byte = usart.udr.read();
+    unsafe {
+        if ucsra.get_bit(3) {
+            STATS[i].data_overruns = STATS[i].data_overruns.wrapping_add(1);
+        }
+        if !RX_BUFFER[i].push(byte) {
+            STATS[i].buffer_overflows = STATS[i].buffer_overflows.wrapping_add(1);
+        }
+        if let Some(flow) = FLOW[i] {
+            if RX_BUFFER[i].len() >= HIGH_WATER {
+                flow.set_rts(true);
+            }
+        }
+        if let Some(hook) = RX_HOOK[i] {
+            hook(byte);
+        }
+    }
+}
+
+/// Sends the next byte of the transmit buffer of the USART, or stops the interrupt when it is empty
+/// or when CTS is deasserted.
+#[cfg_attr(not(target_arch = "avr"), allow(dead_code))]
+fn data_register_empty(num: UsartNum) {
+    let usart = unsafe { Usart::new(num) };
+    let i = index(num);
+    if let Some(flow) = unsafe { FLOW[i] } {
+        if !flow.clear_to_send() {
+            // Paused until `resume_transmit()` is called.
+            usart.ucsrb.update(|ucsrb| {
+                ucsrb.set_bit(5, false);
+            });
+            return;
+        }
+    }
+    match unsafe { TX_BUFFER[i].pop() } {
+        Some(byte) => usart.udr.write(byte),
+        None => usart.ucsrb.update(|ucsrb| {
+            ucsrb.set_bit(5, false);
+        }),
+    }
+}
+
+/// Interrupt vector of USART0 receive complete.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_25() {
+    receive_complete(UsartNum::Usart0);
+}
+
+/// Interrupt vector of USART0 data register empty.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_26() {
+    data_register_empty(UsartNum::Usart0);
+}
+
+/// Interrupt vector of USART1 receive complete.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_36() {
+    receive_complete(UsartNum::Usart1);
+}
+
+/// Interrupt vector of USART1 data register empty.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_37() {
+    data_register_empty(UsartNum::Usart1);
+}
+
+/// Interrupt vector of USART2 receive complete.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_51() {
+    receive_complete(UsartNum::Usart2);
+}
+
+/// Interrupt vector of USART2 data register empty.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_52() {
+    data_register_empty(UsartNum::Usart2);
+}
+
+/// Interrupt vector of USART3 receive complete.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_54() {
+    receive_complete(UsartNum::Usart3);
+}
+
+/// Interrupt vector of USART3 data register empty.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_55() {
+    data_register_empty(UsartNum::Usart3);
+}
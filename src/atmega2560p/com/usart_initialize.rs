@@ -31,7 +31,7 @@ use crate::atmega2560p::hal::power;
 use crate::delay::delay_ms;
 use bit_field::BitField;
 use core::ptr::write_volatile;
-use core::{f64, u32, u8};
+use core::{u32, u8};
 use volatile::Volatile;
 
 // Some useful constants regarding bit manipulation for USART.
@@ -40,9 +40,6 @@ const USART0_XCK: u8 = 2;
 const USART1_XCK: u8 = 5;
 const USART2_XCK: u8 = 2;
 const USART3_XCK: u8 = 2;
-// System Clock Crystal Oscillator Frequency in mHz.
-const F_OSC: f64 = 1.0000;
-const MULTIPLY: f64 = 1000000.00;
 
 /// Selection of which USART is to be used.
 #[derive(Clone, Copy)]
@@ -94,6 +91,48 @@ pub enum UsartPolarity {
     Inputrise,
 }
 
+/// Errors found by the receiver in a received frame or while setting the baud rate.
+/// * `FrameError` - The first stop bit was zero, usually a baud rate mismatch.
+/// * `DataOverrun` - The receive buffer was full and at least one frame was lost.
+/// * `ParityError` - The parity bit did not match the data.
+/// * `BaudRateError` - The baud rate differs from the asked one by more than `MAX_BAUD_ERROR`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UsartError {
+    FrameError,
+    DataOverrun,
+    ParityError,
+    BaudRateError,
+}
+
+/// Largest difference between the generated and the asked baud rate, in tenths of a percent.
+/// Beyond about 2% the receiver at the other end starts to sample the wrong bits.
+pub const MAX_BAUD_ERROR: u32 = 20;
+
+/// Gives the UBRR value which comes nearest to the asked baud rate.
+/// # Arguments
+/// * `f_osc` - a u32, the system clock frequency in hertz.
+/// * `baud` - a u32, the baud rate asked by the user.
+/// * `divisor` - a u32, the clock cycles per bit for one count of UBRR, 16, 8 or 2.
+/// # Returns
+/// * `a tuple of u32` - the UBRR value and the baud rate error in tenths of a percent.
+fn baud_setting(f_osc: u32, baud: u32, divisor: u32) -> (u32, u32) {
+    let step = divisor * baud;
+    let mut ubrr = (f_osc + step / 2) / step;
+    if ubrr > 0 {
+        ubrr -= 1;
+    }
+    if ubrr > 4095 {
+        ubrr = 4095;
+    }
+    let actual = f_osc / (divisor * (ubrr + 1));
+    let diff = if actual > baud {
+        actual - baud
+    } else {
+        baud - actual
+    };
+    (ubrr, (diff as u64 * 1000 / baud as u64) as u32)
+}
+
 /// This structure contains various registers needed to control usart communication
 /// through ATMEGA2560P device.
 /// Each USARTn ( n=0,1,2,3 ) is controlled by a total of 6 registers stored through this structure.
@@ -191,13 +230,13 @@ impl UsartObject {
     /// * `a tuple` - which contains -
     ///     * `a mutable reference to Port object` - The port which controls the given USART.
     ///     * `a u8` - The index location of XCK bit for mode specific implementation.
-    fn get_port_xck(&mut self) -> (&mut port::Port, u8) {
+    pub(crate) fn get_port_xck(&mut self) -> (&mut port::Port, u8) {
         let num: UsartNum = unsafe { (*self.usart).name() };
         match num {
-            UsartNum::Usart0 => (port::Port::new(port::PortName::E), USART0_XCK),
-            UsartNum::Usart1 => (port::Port::new(port::PortName::D), USART1_XCK),
-            UsartNum::Usart2 => (port::Port::new(port::PortName::H), USART2_XCK),
-            UsartNum::Usart3 => (port::Port::new(port::PortName::J), USART3_XCK),
+            UsartNum::Usart0 => (unsafe { port::Port::new(port::PortName::E) }, USART0_XCK),
+            UsartNum::Usart1 => (unsafe { port::Port::new(port::PortName::D) }, USART1_XCK),
+            UsartNum::Usart2 => (unsafe { port::Port::new(port::PortName::H) }, USART2_XCK),
+            UsartNum::Usart3 => (unsafe { port::Port::new(port::PortName::J) }, USART3_XCK),
         }
     }
 
@@ -297,43 +336,12 @@ impl UsartObject {
     /// # Arguments
     /// * `num` - a `UsartNum` object, for which the power configurations of the USART will be set.
     pub fn set_power(&mut self, num: UsartNum) {
-        let pow: &mut power::Power;
-        unsafe {
-            pow = power::Power::new();
-        }
+        let pow = unsafe { power::Power::new() };
         match num {
-            UsartNum::Usart0 => {
-                unsafe {
-                    write_volatile(&mut pow.prr0, pow.prr0 & !(1 << 1));
-                }
-                // pow.prr0.update( |prr| {
-                //     prr.set_bit(1,false);
-                // });
-            }
-            UsartNum::Usart1 => {
-                unsafe {
-                    write_volatile(&mut pow.prr1, pow.prr1 & !(1));
-                }
-                // pow.prr1.update( |prr| {
-                //     prr.set_bit(0,false);
-                // });
-            }
-            UsartNum::Usart2 => {
-                unsafe {
-                    write_volatile(&mut pow.prr1, pow.prr1 & !(1 << 1));
-                }
-                // pow.prr1.update( |prr| {
-                //     prr.set_bit(1,false);
-                // });
-            }
-            UsartNum::Usart3 => {
-                unsafe {
-                    write_volatile(&mut pow.prr1, pow.prr1 & !(1 << 2));
-                }
-                // pow.prr1.update( |prr| {
-                //     prr.set_bit(2,false);
-                // });
-            }
+            UsartNum::Usart0 => pow.enable(power::Peripheral::Usart0),
+            UsartNum::Usart1 => pow.enable(power::Peripheral::Usart1),
+            UsartNum::Usart2 => pow.enable(power::Peripheral::Usart2),
+            UsartNum::Usart3 => pow.enable(power::Peripheral::Usart3),
         }
     }
 
@@ -345,15 +353,18 @@ impl UsartObject {
         });
     }
 
-    /// Checks for any currently undergoing recieval or transmission in the USART.
+    /// Checks for any pending transmission in the USART.
+    /// TXC is not used as it stays clear until the first frame is sent after reset.
     /// # Returns
     /// * `a boolean` - Which is false if USART is busy otherwise true.
     unsafe fn check_ongoing(&self) -> bool {
-        let ucsra = (*self.usart).ucsra.read();
-        if ucsra.get_bit(6) == false && ucsra.get_bit(7) == false {
-            true
-        } else {
-            false
+        (*self.usart).ucsra.read().get_bit(5)
+    }
+
+    /// Drops the frames left in the receive buffer, which were received with the old settings.
+    unsafe fn discard_recieved(&mut self) {
+        while (*self.usart).ucsra.read().get_bit(7) {
+            let _ = (*self.usart).udr.read();
         }
     }
 
@@ -376,24 +387,49 @@ impl UsartObject {
     /// clock generator.
     /// Set the baud rate frequency for USART.
     /// Baud rate settings is used to set the clock for USART.
+    /// In asynchronous mode both normal and double speed are tried and the one
+    /// giving the smaller baud rate error is used, so the U2X bit may differ from `mode`.
+    /// Normal speed is kept when both are equally good, as the receiver then takes
+    /// more samples of each bit, unless double speed was asked for.
     /// # Arguments
-    /// * `baud` - a i64, containing the baud rate frame to be set.
-    /// * `mode` - a `UsartModes` object,
-    fn set_clock(&mut self, baud: i64, mode: UsartModes) {
-        let ubrr: u32;
-        match mode {
-            UsartModes::Normasync => {
-                ubrr = (((F_OSC * MULTIPLY) / (16.00 * baud as f64)) - 1.00) as u32;
-            }
-            UsartModes::Douasync => {
-                ubrr = (((F_OSC * MULTIPLY) / (8.00 * baud as f64)) - 1.00) as u32;
+    /// * `baud` - a i64, the baud rate of USART the user wants to set.
+    /// * `mode` - a `UsartModes` object, which defines the mode of USART to use.
+    /// # Returns
+    /// * `a Result` - `UsartError::BaudRateError` if the error is above `MAX_BAUD_ERROR`.
+    fn set_clock(&mut self, baud: i64, mode: UsartModes) -> Result<(), UsartError> {
+        if baud <= 0 {
+            unreachable!()
+        }
+        // Effective system clock frequency, which depends on the clock prescaler.
+        let f_osc = crate::config::cpu_frequency_hz();
+        let baud = baud as u32;
+        let (ubrr, error, double) = match mode {
+            UsartModes::Normasync | UsartModes::Douasync => {
+                let (ubrr_normal, error_normal) = baud_setting(f_osc, baud, 16);
+                let (ubrr_double, error_double) = baud_setting(f_osc, baud, 8);
+                let prefer_double = match mode {
+                    UsartModes::Douasync => error_double <= error_normal,
+                    _ => error_double < error_normal,
+                };
+                if prefer_double {
+                    (ubrr_double, error_double, true)
+                } else {
+                    (ubrr_normal, error_normal, false)
+                }
             }
             UsartModes::Mastersync => {
-                ubrr = (((F_OSC * MULTIPLY) / (2.00 * baud as f64)) - 1.00) as u32;
+                let (ubrr, error) = baud_setting(f_osc, baud, 2);
+                (ubrr, error, false)
             }
             _ => unreachable!(),
+        };
+        if error > MAX_BAUD_ERROR {
+            return Err(UsartError::BaudRateError);
         }
         unsafe {
+            (*self.usart).ucsra.update(|sra| {
+                sra.set_bit(1, double);
+            });
             (*self.usart).ubrrl.update(|ubrrl| {
                 for i in 0..8 {
                     ubrrl.set_bit(i, ubrr.get_bit(i));
@@ -405,6 +441,7 @@ impl UsartObject {
                 }
             });
         }
+        Ok(())
     }
 
     /// Set the limit of data to be handled by USART.
@@ -520,6 +557,8 @@ impl UsartObject {
     /// * `size` - a `UsartDatSize` object, the size of set of bits to transmit.
     /// * `parity` - a `UsartParity` object, which gives the Parity bit mode for USART.
     /// * `stop` - a `UsartStop` object, which will be used to set the stop bits of data frame.
+    /// # Returns
+    /// * `a Result` - `UsartError::BaudRateError` if the baud rate can not be generated closely enough.
     pub unsafe fn initialize(
         &mut self,
         mode: UsartModes,
@@ -527,9 +566,9 @@ impl UsartObject {
         stop: UsartStop,
         size: UsartDataSize,
         parity: UsartParity,
-    ) {
+    ) -> Result<(), UsartError> {
         // Check that recieve and transmit buffers are completely cleared
-        // and no transmission or recieve of data is already in process.
+        // and no transmission of data is already in process.
         let mut i: i32 = 100;
         while self.check_ongoing() == false {
             if i != 0 {
@@ -539,6 +578,7 @@ impl UsartObject {
                 unreachable!()
             }
         }
+        self.discard_recieved();
 
         let num: UsartNum = (*self.usart).name();
 
@@ -551,11 +591,31 @@ impl UsartObject {
         match mode {
             UsartModes::Slavesync => {}
             UsartModes::Normasync | UsartModes::Douasync | UsartModes::Mastersync => {
-                self.set_clock(baud, mode);
+                self.set_clock(baud, mode)?;
             }
         }
 
         //  Set the frame format according to input.
         self.set_frame(stop, size, parity);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::baud_setting;
+
+    #[test]
+    fn baud_setting_matches_datasheet() {
+        // 16 MHz, values from the baud rate tables of the datasheet.
+        assert_eq!(baud_setting(16_000_000, 9600, 16), (103, 1));
+        assert_eq!(baud_setting(16_000_000, 115_200, 16), (8, 35));
+        assert_eq!(baud_setting(16_000_000, 115_200, 8), (16, 21));
+        assert_eq!(baud_setting(16_000_000, 1_000_000, 16), (0, 0));
+    }
+
+    #[test]
+    fn baud_setting_clamps_ubrr() {
+        assert_eq!(baud_setting(16_000_000, 100, 16).0, 4095);
     }
 }
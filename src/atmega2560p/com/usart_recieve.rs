@@ -19,14 +19,17 @@
 //! See the section 22 of ATMEGA2560P datasheet.
 
 // Other source code files to be used.
-use crate::atmega2560p::com::usart_initialize::UsartObject;
+use crate::atmega2560p::com::usart_initialize::{UsartError, UsartObject};
 
 // Crates which would be used in the implementation.
 // We will be using standard volatile and bit_field crates now for a better read and write.
-use crate::delay::delay_ms;
+use crate::delay::{delay_ms, delay_us};
 use bit_field::BitField;
 use core::u32;
 
+/// Time in milliseconds after which the line and number readers give up waiting for a byte.
+pub const PARSE_TIMEOUT_MS: u32 = 1000;
+
 impl UsartObject {
     /// Enables the reciever function of microcontroller, whithout enabling it no communication is possible.
     pub unsafe fn recieve_enable(&mut self) {
@@ -55,9 +58,6 @@ impl UsartObject {
     /// # Returns
     /// * `a Option<u32>` - which is NULL in case of wrong settings and read data u32 if valid input.
     pub fn recieve_data(&mut self) -> Option<u32> {
-        let ucsrc = unsafe { (*self.usart).ucsrc.read() };
-        let ucsrb = unsafe { (*self.usart).ucsrb.read() };
-
         let mut i: i32 = 10;
         while self.available() == false {
             if i != 0 {
@@ -68,6 +68,10 @@ impl UsartObject {
             }
         }
 
+        // RXB8 belongs to the received frame, so UCSRB is read only once the frame is there.
+        let ucsrc = unsafe { (*self.usart).ucsrc.read() };
+        let ucsrb = unsafe { (*self.usart).ucsrb.read() };
+
         //  Case when there is 9 bits mode.
         if ucsrc.get_bits(1..3) == 0b11 && ucsrb.get_bit(2) == true {
             let ucsra = unsafe { (*self.usart).ucsra.read() };
@@ -187,4 +191,359 @@ impl UsartObject {
             }
         }
     }
+
+    /// Reads one byte if one has been received, without waiting.
+    /// Bytes with a frame or parity error are dropped.
+    /// When buffered reception is enabled the bytes are in the receive buffer instead,
+    /// and `read_available()` has to be used.
+    /// # Returns
+    /// * `a Option<u8>` - the received byte, None if there is no byte to read.
+    pub fn try_read(&mut self) -> Option<u8> {
+        if !self.available() {
+            return None;
+        }
+        // The flags have to be read before UDR.
+        let ucsra = unsafe { (*self.usart).ucsra.read() };
+        let udr = unsafe { (*self.usart).udr.read() };
+        // FE or UPE, a data overrun does not make this byte invalid.
+        if ucsra.get_bit(4) || ucsra.get_bit(2) {
+            None
+        } else {
+            Some(udr)
+        }
+    }
+
+    /// Waits for one byte to be received, but no longer than the given time.
+    /// # Arguments
+    /// * `ms` - a u32, the time in milliseconds after which the wait is given up.
+    /// # Returns
+    /// * `a Option<u8>` - the received byte, None if no valid byte came before the timeout.
+    pub fn read_byte_timeout(&mut self, ms: u32) -> Option<u8> {
+        // The receiver is checked every 10 microseconds.
+        let mut polls = ms.saturating_mul(100);
+        loop {
+            if self.available() {
+                return self.try_read();
+            }
+            if polls == 0 {
+                return None;
+            }
+            polls -= 1;
+            delay_us(10);
+        }
+    }
+
+    /// Waits for one byte and reports the errors found by the receiver in its frame.
+    /// A data overrun means that frames before this one were lost, it is reported
+    /// as `DataOverrun` and the byte is dropped like the bytes with the other errors.
+    /// # Returns
+    /// * `a Result<u8, UsartError>` - the received byte, or the error of its frame.
+    pub fn read_byte(&mut self) -> Result<u8, UsartError> {
+        while !self.available() {}
+
+        // The flags have to be read before UDR.
+        let ucsra = unsafe { (*self.usart).ucsra.read() };
+        let udr = unsafe { (*self.usart).udr.read() };
+        match error_flags(ucsra) {
+            Some(error) => Err(error),
+            None => Ok(udr),
+        }
+    }
+
+    /// Reads one byte if one has been received, without waiting,
+    /// and reports the errors found by the receiver in its frame.
+    /// # Returns
+    /// * `a Option<Result<u8, UsartError>>` - None if there is no byte to read.
+    pub fn try_read_checked(&mut self) -> Option<Result<u8, UsartError>> {
+        if !self.available() {
+            return None;
+        }
+        Some(self.read_byte())
+    }
+
+    /// Waits for one frame of nine data bits, the USART has to be initialized with `UsartDataSize::Nine`.
+    /// RXB8 and the flags are read before UDR as reading UDR moves the receive buffer forward.
+    /// # Returns
+    /// * `a Result<u16, UsartError>` - the received nine bits, or the error of the frame.
+    pub fn read_nine_bit(&mut self) -> Result<u16, UsartError> {
+        while !self.available() {}
+
+        let ucsra = unsafe { (*self.usart).ucsra.read() };
+        let ucsrb = unsafe { (*self.usart).ucsrb.read() };
+        let udr = unsafe { (*self.usart).udr.read() };
+        match error_flags(ucsra) {
+            Some(error) => Err(error),
+            None => Ok(((ucsrb.get_bit(1) as u16) << 8) | udr as u16),
+        }
+    }
+
+    /// Enables or disables the multi-processor communication mode.
+    /// In this mode the receiver ignores all frames whose ninth bit is cleared,
+    /// so only address frames are received.
+    /// # Arguments
+    /// * `enable` - a boolean, true to accept only address frames.
+    pub fn set_multiprocessor_mode(&mut self, enable: bool) {
+        unsafe {
+            (*self.usart).ucsra.update(|ucsra| {
+                // FE, DOR and UPE have to be written zero, TXC is cleared by writing one.
+                ucsra.set_bits(2..5, 0);
+                ucsra.set_bit(6, false);
+                ucsra.set_bit(0, enable);
+            });
+        }
+    }
+
+    /// Waits on a multi-processor bus until this receiver is addressed.
+    /// Frames for other receivers are skipped, and once the address matches the
+    /// multi-processor mode is disabled so that the following data frames are received.
+    /// # Arguments
+    /// * `address` - a u8, the address of this receiver.
+    pub fn wait_for_address(&mut self, address: u8) {
+        self.set_multiprocessor_mode(true);
+        loop {
+            if let Ok(frame) = self.read_nine_bit() {
+                if frame == 0x100 | address as u16 {
+                    break;
+                }
+            }
+        }
+        self.set_multiprocessor_mode(false);
+    }
+
+    /// Reads received text into the buffer up to the end of the line.
+    /// The newline is not stored and a carriage return before it is dropped.
+    /// Reading stops at the newline, when the buffer is full or when no byte
+    /// comes for `PARSE_TIMEOUT_MS` milliseconds.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, where the line is stored.
+    /// # Returns
+    /// * `a usize` - the number of bytes stored in the buffer.
+    pub fn read_line(&mut self, buffer: &mut [u8]) -> usize {
+        let mut count: usize = 0;
+        while count < buffer.len() {
+            match self.read_byte_timeout(PARSE_TIMEOUT_MS) {
+                Some(b'\n') | None => break,
+                Some(b'\r') => {}
+                Some(byte) => {
+                    buffer[count] = byte;
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Reads the next integer from the received text, like `Serial.parseInt` of Arduino.
+    /// Characters before the number are skipped and the character after it is consumed.
+    /// # Returns
+    /// * `a Option<i32>` - the number, None if no digit came before the timeout.
+    pub fn parse_int(&mut self) -> Option<i32> {
+        parse_integer(|| self.read_byte_timeout(PARSE_TIMEOUT_MS))
+    }
+
+    /// Reads the next decimal number from the received text, like `Serial.parseFloat` of Arduino.
+    /// Characters before the number are skipped and the character after it is consumed.
+    /// # Returns
+    /// * `a Option<f32>` - the number, None if no digit came before the timeout.
+    pub fn parse_float(&mut self) -> Option<f32> {
+        parse_decimal(|| self.read_byte_timeout(PARSE_TIMEOUT_MS))
+    }
+}
+
+/// Decodes the FE, DOR and UPE flags of UCSRA.
+/// # Arguments
+/// * `ucsra` - a u8, the value of UCSRA read before UDR.
+/// # Returns
+/// * `a Option<UsartError>` - the error, None if the frame was received correctly.
+fn error_flags(ucsra: u8) -> Option<UsartError> {
+    if ucsra.get_bit(4) {
+        Some(UsartError::FrameError)
+    } else if ucsra.get_bit(2) {
+        Some(UsartError::ParityError)
+    } else if ucsra.get_bit(3) {
+        Some(UsartError::DataOverrun)
+    } else {
+        None
+    }
+}
+
+/// Skips the received bytes which can not start a number.
+/// # Arguments
+/// * `next` - a function, which gives the next received byte or None on timeout.
+/// * `fraction` - a boolean, true if a decimal point can start the number.
+/// # Returns
+/// * `a Option<u8>` - the first byte of the number, None on timeout.
+fn skip_to_number<F: FnMut() -> Option<u8>>(next: &mut F, fraction: bool) -> Option<u8> {
+    loop {
+        match next() {
+            Some(b) if b.is_ascii_digit() || b == b'-' || (fraction && b == b'.') => {
+                return Some(b)
+            }
+            Some(_) => {}
+            None => return None,
+        }
+    }
+}
+
+/// Reads an integer from a stream of received bytes.
+/// # Arguments
+/// * `next` - a function, which gives the next received byte or None on timeout.
+/// # Returns
+/// * `a Option<i32>` - the number, None if no digit was received.
+fn parse_integer<F: FnMut() -> Option<u8>>(mut next: F) -> Option<i32> {
+    let mut byte = skip_to_number(&mut next, false)?;
+    let negative = byte == b'-';
+    if negative {
+        byte = next()?;
+    }
+
+    let mut value: i32 = 0;
+    let mut digits: u8 = 0;
+    while byte.is_ascii_digit() {
+        value = value.wrapping_mul(10).wrapping_add((byte - b'0') as i32);
+        digits = digits.saturating_add(1);
+        byte = match next() {
+            Some(b) => b,
+            None => break,
+        };
+    }
+
+    if digits == 0 {
+        None
+    } else if negative {
+        Some(value.wrapping_neg())
+    } else {
+        Some(value)
+    }
+}
+
+/// Reads a decimal number from a stream of received bytes.
+/// # Arguments
+/// * `next` - a function, which gives the next received byte or None on timeout.
+/// # Returns
+/// * `a Option<f32>` - the number, None if no digit was received.
+fn parse_decimal<F: FnMut() -> Option<u8>>(mut next: F) -> Option<f32> {
+    let mut byte = skip_to_number(&mut next, true)?;
+    let negative = byte == b'-';
+    if negative {
+        byte = next()?;
+    }
+
+    let mut value: f32 = 0.0;
+    let mut scale: f32 = 1.0;
+    let mut in_fraction = false;
+    let mut digits: u8 = 0;
+    loop {
+        if byte.is_ascii_digit() {
+            let digit = (byte - b'0') as f32;
+            if in_fraction {
+                scale /= 10.0;
+                value += digit * scale;
+            } else {
+                value = value * 10.0 + digit;
+            }
+            digits = digits.saturating_add(1);
+        } else if byte == b'.' && !in_fraction {
+            in_fraction = true;
+        } else {
+            break;
+        }
+        byte = match next() {
+            Some(b) => b,
+            None => break,
+        };
+    }
+
+    if digits == 0 {
+        None
+    } else if negative {
+        Some(-value)
+    } else {
+        Some(value)
+    }
+}
+
+/// The receiver for the drivers written against the `embedded-hal` serial traits.
+/// Bytes with a frame, overrun or parity error give the error.
+/// When buffered reception is enabled the bytes are in the receive buffer instead.
+#[cfg(feature = "embedded")]
+impl embedded_hal::serial::Read<u8> for UsartObject {
+    type Error = UsartError;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        match self.try_read_checked() {
+            None => Err(nb::Error::WouldBlock),
+            Some(Ok(byte)) => Ok(byte),
+            Some(Err(error)) => Err(nb::Error::Other(error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Gives the bytes of the text one by one, then None as on a timeout.
+    fn stream(text: &'static [u8]) -> impl FnMut() -> Option<u8> {
+        let mut bytes = text.iter();
+        move || bytes.next().cloned()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        let difference = a - b;
+        difference < 1e-5 && difference > -1e-5
+    }
+
+    #[test]
+    fn skip_to_number_finds_start() {
+        assert_eq!(skip_to_number(&mut stream(b"ab -1"), false), Some(b'-'));
+        assert_eq!(skip_to_number(&mut stream(b"x.5"), false), Some(b'5'));
+        assert_eq!(skip_to_number(&mut stream(b"x.5"), true), Some(b'.'));
+        assert_eq!(skip_to_number(&mut stream(b"none"), true), None);
+    }
+
+    #[test]
+    fn parse_integer_reads_numbers() {
+        assert_eq!(parse_integer(stream(b"value: 123,")), Some(123));
+        assert_eq!(parse_integer(stream(b"-42\r\n")), Some(-42));
+        assert_eq!(parse_integer(stream(b"7")), Some(7));
+        assert_eq!(parse_integer(stream(b"-2147483648")), Some(i32::MIN));
+    }
+
+    #[test]
+    fn parse_integer_consumes_one_byte_after_number() {
+        let mut next = stream(b"12 34");
+        assert_eq!(parse_integer(&mut next), Some(12));
+        assert_eq!(next(), Some(b'3'));
+    }
+
+    #[test]
+    fn parse_integer_needs_a_digit() {
+        assert_eq!(parse_integer(stream(b"")), None);
+        assert_eq!(parse_integer(stream(b"abc")), None);
+        assert_eq!(parse_integer(stream(b"-x")), None);
+    }
+
+    #[test]
+    fn parse_integer_survives_many_digits() {
+        let mut zeros = [b'0'; 300];
+        zeros[299] = b'7';
+        let mut bytes = zeros.iter();
+        assert_eq!(parse_integer(|| bytes.next().cloned()), Some(7));
+    }
+
+    #[test]
+    fn parse_decimal_reads_numbers() {
+        assert!(close(parse_decimal(stream(b"t=3.25C")).unwrap(), 3.25));
+        assert!(close(parse_decimal(stream(b"-.5")).unwrap(), -0.5));
+        assert!(close(parse_decimal(stream(b"12")).unwrap(), 12.0));
+        assert!(close(parse_decimal(stream(b"1.2.3")).unwrap(), 1.2));
+    }
+
+    #[test]
+    fn parse_decimal_needs_a_digit() {
+        assert_eq!(parse_decimal(stream(b"")), None);
+        assert_eq!(parse_decimal(stream(b".")), None);
+        assert_eq!(parse_decimal(stream(b"-.x")), None);
+    }
 }
@@ -20,7 +20,8 @@
 // Crates which would be used in the implementation.
 // We will be using standard volatile and bit_field crates now for a better read and write.
 use bit_field::BitField;
-use core::{f64, u8, usize};
+use core::mem::MaybeUninit;
+use core::{f64, fmt, u8};
 use fixed_slice_vec::FixedSliceVec;
 
 // Other source code files to be used.
@@ -52,7 +53,7 @@ impl UsartObject {
             }
         }
 
-        let mut udr = (*self.usart).udr.read();
+        let mut udr: u8 = 0;
 
         // If the frame is ready for transmission then the appropriate place is written.
         match len {
@@ -75,6 +76,7 @@ impl UsartObject {
                 udr.set_bits(0..8, data.get_bits(0..8) as u8);
             }
         }
+        (*self.usart).udr.write(udr);
     }
 
     /// Checks that transmission buffer if ready for transmission.
@@ -107,23 +109,9 @@ impl UsartObject {
     /// This is used to disable the Transmitter and once disabled the pins used for USART
     /// return into their default I/O pin mode.
     pub fn transmit_disable(&mut self) {
-        let ucsra = unsafe { (*self.usart).ucsra.read() };
-        let mut uscra6 = ucsra.get_bit(6);
-        let mut uscra5 = ucsra.get_bit(5);
-        let mut i: i32 = 100;
-
-        // Check for data in Transmit Buffer and Transmit shift register,
-        // if data is present in either then disabling of transmitter is not effective
-        while uscra6 == false || uscra5 == false {
-            uscra6 = ucsra.get_bit(6);
-            uscra5 = ucsra.get_bit(5);
-            if i != 0 {
-                delay_ms(1000);
-                i = i - 1;
-            } else {
-                unreachable!()
-            }
-        }
+        // Wait for the data in Transmit Buffer to move to the shift register,
+        // the transmitter itself finishes the frame in the shift register before it is disabled.
+        while !unsafe { (*self.usart).ucsra.read() }.get_bit(5) {}
 
         unsafe {
             (*self.usart).ucsrb.update(|srb| {
@@ -162,14 +150,8 @@ impl UsartObject {
     /// # Arguments
     /// * `data` - a static string object, which is to be transmitted using USART.
     pub fn write_string(&mut self, data: &'static str) {
-        let mut vec: FixedSliceVec<u8> = FixedSliceVec::new(&mut []);
-
-        for c in data.chars() {
-            vec.push(c as u8);
-        }
-
-        for i in 0..(vec.len()) {
-            self.transmit_data(vec[i]);
+        for c in data.bytes() {
+            self.transmit_data(c);
         }
     }
 
@@ -177,8 +159,13 @@ impl UsartObject {
     /// # Arguments
     /// * `data` - a u32, which is to be transmitted using USART.
     pub fn write_integer(&mut self, data: u32) {
-        let mut vec: FixedSliceVec<u8> = FixedSliceVec::new(&mut []);
+        // A u32 has at most 10 decimal digits.
+        let mut storage = [MaybeUninit::<u8>::uninit(); 10];
+        let mut vec: FixedSliceVec<u8> = FixedSliceVec::new(&mut storage);
         let mut a = data;
+        if a == 0 {
+            vec.push('0' as u8);
+        }
         while a != 0 {
             let rem = a % 10;
             a = a / 10;
@@ -206,58 +193,130 @@ impl UsartObject {
     /// * `data` - a f32, which is to be transmitted using USART.
     /// * `precision` - a u32, the number of decimal precision required in the transmission.
     pub fn write_float(&mut self, data: f64, precision: u32) {
-        let mut vec: FixedSliceVec<u8> = FixedSliceVec::new(&mut []);
-        let a: f64 = data;
+        // The integer part of a i64 has at most 19 digits.
+        let mut storage = [MaybeUninit::<u8>::uninit(); 19];
+        let mut vec: FixedSliceVec<u8> = FixedSliceVec::new(&mut storage);
+        let mut a: f64 = data;
+        if a < 0.0 {
+            self.transmit_data('-' as u8);
+            a = -a;
+        }
         let mut f: f64 = a % 1.0;
-        let mut i: i64 = (a - (a % 1.0)) as i64;
-        let mut x: u32 = precision;
-        let mut n: usize = 0;
-        while f != 0.00 && x != 0 {
-            let k: i64 = ((f * 10.0) - ((f * 10.0) % 1.0)) as i64; // gives you decimal digit of data one by one from left to right
-            match k {
-                0 => vec.push('0' as u8),
-                1 => vec.push('1' as u8),
-                2 => vec.push('2' as u8),
-                3 => vec.push('3' as u8),
-                4 => vec.push('4' as u8),
-                5 => vec.push('5' as u8),
-                6 => vec.push('6' as u8),
-                7 => vec.push('7' as u8),
-                8 => vec.push('8' as u8),
-                9 => vec.push('9' as u8),
-                _ => unreachable!(),
-            }
+        let mut i: i64 = (a - f) as i64;
+        // A f64 does not hold more than 16 significant decimal digits.
+        let mut x: u32 = if precision > 16 { 16 } else { precision };
+
+        // Digits of the integer part are found from right to left, so they are sent in reverse.
+        if i == 0 {
+            vec.push('0' as u8);
+        }
+        while i != 0 {
+            vec.push('0' as u8 + (i % 10) as u8);
+            i = i / 10;
+        }
+        for ia in 0..vec.len() {
+            self.transmit_data(vec[vec.len() - 1 - ia]);
+        }
+
+        if x != 0 {
+            self.transmit_data('.' as u8);
+        }
+        while x != 0 {
+            // gives you decimal digit of data one by one from left to right
+            let k: u8 = ((f * 10.0) - ((f * 10.0) % 1.0)) as u8;
+            self.transmit_data('0' as u8 + k);
             f = (f * 10.0) % 1.0; // then f loses its left most digit (in decimal part)
             x = x - 1;
-            n = n + 1;
         }
+    }
 
-        vec.push('.' as u8);
+    /// Sends one frame of nine data bits, the USART has to be initialized with `UsartDataSize::Nine`.
+    /// The ninth bit is written to TXB8 before the lower bits are written to UDR.
+    /// # Arguments
+    /// * `data` - a u16, of which the lower nine bits are transmitted.
+    pub fn write_nine_bit(&mut self, data: u16) {
+        while !unsafe { (*self.usart).ucsra.read() }.get_bit(5) {}
+        unsafe {
+            (*self.usart).ucsrb.update(|ucsrb| {
+                ucsrb.set_bit(0, data.get_bit(8));
+            });
+            (*self.usart).udr.write(data as u8);
+        }
+    }
 
-        while i != 0 {
-            let rem = i % 10;
-            i = i / 10;
-            match rem {
-                0 => vec.push('0' as u8),
-                1 => vec.push('1' as u8),
-                2 => vec.push('2' as u8),
-                3 => vec.push('3' as u8),
-                4 => vec.push('4' as u8),
-                5 => vec.push('5' as u8),
-                6 => vec.push('6' as u8),
-                7 => vec.push('7' as u8),
-                8 => vec.push('8' as u8),
-                9 => vec.push('9' as u8),
-                _ => (),
-            }
+    /// Sends an address frame on a multi-processor bus, a nine bit frame with the ninth bit set.
+    /// Receivers in multi-processor mode only accept the data frames which follow their address.
+    /// # Arguments
+    /// * `address` - a u8, the address of the receiver.
+    pub fn send_address(&mut self, address: u8) {
+        self.write_nine_bit(0x100 | address as u16);
+    }
+
+    /// Sends a data frame on a multi-processor bus, a nine bit frame with the ninth bit cleared.
+    /// # Arguments
+    /// * `data` - a u8, the data to be sent.
+    pub fn send_bus_data(&mut self, data: u8) {
+        self.write_nine_bit(data as u16);
+    }
+}
+
+/// Formatted output through the USART, so that `write!` and `writeln!` can be used.
+/// The USART has to be initialized with the transmitter enabled.
+impl fmt::Write for UsartObject {
+    /// Sends the bytes of the string one by one.
+    /// # Arguments
+    /// * `s` - a string object, which is to be transmitted using USART.
+    /// # Returns
+    /// * `a fmt::Result` - which is always Ok.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.transmit_data(byte);
         }
+        Ok(())
+    }
+}
 
-        for ia in 0..(vec.len() - n - 1) {
-            self.transmit_data(vec[vec.len() - 1 - ia]);
+/// Formatted output through the USART with the `ufmt` crate, which is much
+/// smaller than `core::fmt` on AVR.
+#[cfg(feature = "ufmt")]
+impl ufmt_write::uWrite for UsartObject {
+    type Error = core::convert::Infallible;
+
+    /// Sends the bytes of the string one by one.
+    /// # Arguments
+    /// * `s` - a string object, which is to be transmitted using USART.
+    /// # Returns
+    /// * `a Result` - which is always Ok.
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        for byte in s.bytes() {
+            self.transmit_data(byte);
         }
+        Ok(())
+    }
+}
 
-        for ia in 0..n - 1 {
-            self.transmit_data(vec[ia]);
+/// The transmitter for the drivers written against the `embedded-hal` serial traits.
+/// The transmitter cannot fail, so the error is `Infallible`.
+#[cfg(feature = "embedded")]
+impl embedded_hal::serial::Write<u8> for UsartObject {
+    type Error = core::convert::Infallible;
+
+    /// Puts the byte in the transmit buffer if it is empty.
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        if unsafe { self.avai_write() } {
+            unsafe { (*self.usart).udr.write(byte) };
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Waits until the transmit buffer is empty, the last byte may still be shifted out.
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if unsafe { self.avai_write() } {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
         }
     }
 }
@@ -20,10 +20,11 @@
 //! This code implements the Analog Write function to write into the buffer using analog signals.
 //! Refer to section 16,17,25 and 26 of ATMEGA2560P datasheet.
 
-use crate::atmega2560p::hal::pin::{AnalogPin, DigitalPin};
+use crate::atmega2560p::hal::pin::{AnalogPin, DigitalPin, Pins};
 // Other source codes required.
 use crate::__nop;
-use crate::atmega2560p::hal::power::Power;
+use crate::atmega2560p::hal::power::{Peripheral, Power};
+use crate::config::{cpu_frequency_hz, CPU_FREQUENCY_HZ};
 
 // Crates to be used for the implementation.
 use bit_field::BitField;
@@ -54,6 +55,120 @@ pub enum TimerNo16 {
     Timer5,
 }
 
+/// Selection of the clock prescaler for the timers used in Analog Write.
+/// The PWM frequency is the CPU clock divided by the prescaler and the
+/// number of timer counts in one PWM period.
+/// `Div32` and `Div128` are only available on Timer 2.
+#[derive(Clone, Copy)]
+pub enum TimerPrescaler {
+    Div1,
+    Div8,
+    Div32,
+    Div64,
+    Div128,
+    Div256,
+    Div1024,
+}
+
+impl TimerPrescaler {
+    /// Gives the division factor applied to the CPU clock.
+    /// # Returns
+    /// * `a u32` - The number by which the CPU clock is divided.
+    pub fn factor(&self) -> u32 {
+        match self {
+            TimerPrescaler::Div1 => 1,
+            TimerPrescaler::Div8 => 8,
+            TimerPrescaler::Div32 => 32,
+            TimerPrescaler::Div64 => 64,
+            TimerPrescaler::Div128 => 128,
+            TimerPrescaler::Div256 => 256,
+            TimerPrescaler::Div1024 => 1024,
+        }
+    }
+}
+
+/// Errors in setting up the timers used in Analog Write.
+/// * `PrescalerUnavailable` - The prescaler is not available on this timer.
+/// * `ResolutionOutOfRange` - The PWM resolution is less than 2 bits or more than 16 bits.
+/// * `ModeUnavailable` - The waveform generation mode is not available on this timer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimerError {
+    PrescalerUnavailable,
+    ResolutionOutOfRange,
+    ModeUnavailable,
+}
+
+/// Errors in starting a scan with `Analog::start_scan()`.
+/// * `ChannelCount` - The channel list is empty or has more than 16 channels.
+/// * `InvalidChannel` - A channel number is 16 or more.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScanError {
+    ChannelCount,
+    InvalidChannel,
+}
+
+/// Selection of the waveform generation mode of the timers used in Analog Write.
+/// * `Normal` - The timer only counts up and overflows, no PWM is generated.
+/// * `FastPwm` - Single slope PWM, giving the highest frequency for a given prescaler.
+/// * `PhaseCorrectPwm` - Dual slope PWM, the output is symmetric which suits motor control.
+/// * `PhaseFrequencyCorrectPwm` - Dual slope PWM whose compare values are updated at the bottom,
+///   so the output stays symmetric even while the duty cycle changes. Only on 16 bit timers.
+/// * `Ctc` - Clear Timer on Compare match, the timer restarts on reaching OCRnA.
+#[derive(Clone, Copy)]
+pub enum WaveformMode {
+    Normal,
+    FastPwm,
+    PhaseCorrectPwm,
+    PhaseFrequencyCorrectPwm,
+    Ctc,
+}
+
+/// Selection of the output compare channel of a timer.
+/// Channel C is only available on 16 bit timers.
+#[derive(Clone, Copy)]
+pub enum TimerChannel {
+    A,
+    B,
+    C,
+}
+
+/// Selection of the gain applied to a differential ADC channel.
+/// `X10` and `X200` are only available for the pairs ADC1-ADC0, ADC3-ADC2,
+/// ADC9-ADC8 and ADC11-ADC10 (and each of these inputs against itself).
+#[derive(Clone, Copy)]
+pub enum AdcGain {
+    X1,
+    X10,
+    X200,
+}
+
+/// Selection of the positive input of the analog comparator.
+/// * `Ain0` - The AIN0 pin.
+/// * `Bandgap` - The internal 1.1V bandgap reference.
+#[derive(Clone, Copy)]
+pub enum ComparatorPositive {
+    Ain0,
+    Bandgap,
+}
+
+/// Selection of the negative input of the analog comparator.
+/// * `Ain1` - The AIN1 pin.
+/// * `Adc(n)` - The analog input n, selected through the ADC multiplexer.
+///   The ADC can not be used while the comparator uses it.
+#[derive(Clone, Copy)]
+pub enum ComparatorNegative {
+    Ain1,
+    Adc(u8),
+}
+
+/// Selection of the edge of the comparator output which triggers the interrupt.
+#[derive(Clone, Copy)]
+pub enum ComparatorEdge {
+    Toggle,
+    Falling,
+    Rising,
+}
+
 /// Structure to control the implementation of Integrated Analog Circuit.
 #[repr(C, packed)]
 pub struct AnalogComparator {
@@ -97,14 +212,14 @@ pub struct Timer16 {
     _pad0: u8,
     _tcntl: Volatile<u8>,
     _tcnth: Volatile<u8>,
-    _icrl: Volatile<u8>,
-    _icrh: Volatile<u8>,
+    icrl: Volatile<u8>,
+    icrh: Volatile<u8>,
     ocral: Volatile<u8>,
-    _ocrah: Volatile<u8>,
+    ocrah: Volatile<u8>,
     ocrbl: Volatile<u8>,
-    _ocrbh: Volatile<u8>,
+    ocrbh: Volatile<u8>,
     ocrcl: Volatile<u8>,
-    _ocrch: Volatile<u8>,
+    ocrch: Volatile<u8>,
 }
 
 impl Timer8 {
@@ -113,11 +228,172 @@ impl Timer8 {
     /// * `timer` - a `TimerNo8` object, which defines the Timer number for which object is to be made.
     /// # Returns
     /// * `a reference to Timer8 object` - which will be used for further implementations.
-    pub fn new(timer: TimerNo8) -> &'static mut Timer8 {
+    pub unsafe fn new(timer: TimerNo8) -> &'static mut Timer8 {
         match timer {
-            TimerNo8::Timer0 => unsafe { &mut *(0x44 as *mut Timer8) },
-            TimerNo8::Timer2 => unsafe { &mut *(0xB0 as *mut Timer8) },
+            TimerNo8::Timer0 => &mut *(0x44 as *mut Timer8),
+            TimerNo8::Timer2 => &mut *(0xB0 as *mut Timer8),
+        }
+    }
+
+    /// Returns the Timer number of the timer based on its address.
+    /// Timer8 is only made by `new()`, so any address other than Timer 0 is Timer 2.
+    pub fn name(&self) -> TimerNo8 {
+        let address = (self as *const Timer8) as usize;
+        if address == 0x44 {
+            TimerNo8::Timer0
+        } else {
+            TimerNo8::Timer2
+        }
+    }
+
+    /// Set the clock prescaler of the timer which decides the PWM frequency.
+    /// `Div32` and `Div128` can only be used with Timer 2.
+    /// # Arguments
+    /// * `prescaler` - a `TimerPrescaler` object, the division factor of the CPU clock to be used.
+    /// # Returns
+    /// * `a Result` - `TimerError::PrescalerUnavailable` if the timer does not have the prescaler.
+    pub fn set_prescaler(&mut self, prescaler: TimerPrescaler) -> Result<(), TimerError> {
+        let bits: u8 = match (self.name(), prescaler) {
+            (_, TimerPrescaler::Div1) => 0b001,
+            (_, TimerPrescaler::Div8) => 0b010,
+            (TimerNo8::Timer0, TimerPrescaler::Div64) => 0b011,
+            (TimerNo8::Timer0, TimerPrescaler::Div256) => 0b100,
+            (TimerNo8::Timer0, TimerPrescaler::Div1024) => 0b101,
+            (TimerNo8::Timer2, TimerPrescaler::Div32) => 0b011,
+            (TimerNo8::Timer2, TimerPrescaler::Div64) => 0b100,
+            (TimerNo8::Timer2, TimerPrescaler::Div128) => 0b101,
+            (TimerNo8::Timer2, TimerPrescaler::Div256) => 0b110,
+            (TimerNo8::Timer2, TimerPrescaler::Div1024) => 0b111,
+            _ => return Err(TimerError::PrescalerUnavailable),
+        };
+        self.tccrb.update(|ctrl| {
+            ctrl.set_bits(0..3, bits);
+        });
+        Ok(())
+    }
+
+    /// Gives the clock prescaler the timer is currently running with.
+    /// # Returns
+    /// * `a Option<TimerPrescaler>` - which is None if the timer is stopped or clocked externally.
+    pub fn prescaler(&self) -> Option<TimerPrescaler> {
+        let bits = self.tccrb.read().get_bits(0..3);
+        match (self.name(), bits) {
+            (_, 0b001) => Some(TimerPrescaler::Div1),
+            (_, 0b010) => Some(TimerPrescaler::Div8),
+            (TimerNo8::Timer0, 0b011) => Some(TimerPrescaler::Div64),
+            (TimerNo8::Timer0, 0b100) => Some(TimerPrescaler::Div256),
+            (TimerNo8::Timer0, 0b101) => Some(TimerPrescaler::Div1024),
+            (TimerNo8::Timer2, 0b011) => Some(TimerPrescaler::Div32),
+            (TimerNo8::Timer2, 0b100) => Some(TimerPrescaler::Div64),
+            (TimerNo8::Timer2, 0b101) => Some(TimerPrescaler::Div128),
+            (TimerNo8::Timer2, 0b110) => Some(TimerPrescaler::Div256),
+            (TimerNo8::Timer2, 0b111) => Some(TimerPrescaler::Div1024),
+            _ => None,
+        }
+    }
+
+    /// Gives the WGM bits of the timer which are split over TCCRnA and TCCRnB.
+    fn wgm(&self) -> u8 {
+        let mut wgm = self.tccra.read().get_bits(0..2);
+        wgm.set_bit(2, self.tccrb.read().get_bit(3));
+        wgm
+    }
+
+    /// Set the waveform generation mode of the timer.
+    /// The PWM modes count up to 0xFF, while `Ctc` counts up to the value in OCRnA.
+    /// `PhaseFrequencyCorrectPwm` is not available for 8 bit timers.
+    /// # Arguments
+    /// * `mode` - a `WaveformMode` object, the waveform to be generated by the timer.
+    /// # Returns
+    /// * `a Result` - `TimerError::ModeUnavailable` if the mode is `PhaseFrequencyCorrectPwm`.
+    pub fn set_waveform_mode(&mut self, mode: WaveformMode) -> Result<(), TimerError> {
+        let wgm: u8 = match mode {
+            WaveformMode::Normal => 0b000,
+            WaveformMode::PhaseCorrectPwm => 0b001,
+            WaveformMode::Ctc => 0b010,
+            WaveformMode::FastPwm => 0b011,
+            WaveformMode::PhaseFrequencyCorrectPwm => return Err(TimerError::ModeUnavailable),
+        };
+        self.tccra.update(|ctrl| {
+            ctrl.set_bits(0..2, wgm.get_bits(0..2));
+        });
+        self.tccrb.update(|ctrl| {
+            ctrl.set_bit(3, wgm.get_bit(2));
+        });
+        Ok(())
+    }
+
+    /// Gives the waveform generation mode the timer is currently set to.
+    /// # Returns
+    /// * `a WaveformMode object` - The current waveform generation mode.
+    pub fn waveform_mode(&self) -> WaveformMode {
+        match self.wgm() {
+            0b001 | 0b101 => WaveformMode::PhaseCorrectPwm,
+            0b010 => WaveformMode::Ctc,
+            0b011 | 0b111 => WaveformMode::FastPwm,
+            _ => WaveformMode::Normal,
+        }
+    }
+
+    /// Number of timer clock ticks in one PWM period for the current waveform mode.
+    fn period_ticks(&self) -> u32 {
+        let top: u32 = match self.wgm() {
+            0b010 | 0b101 | 0b111 => self.ocra.read() as u32,
+            _ => 0xFF,
+        };
+        match self.waveform_mode() {
+            WaveformMode::PhaseCorrectPwm => 2 * top, // Counts up and down.
+            WaveformMode::Ctc => 2 * (top + 1),       // Output toggles once per compare match.
+            _ => top + 1,                             // Counts up once.
+        }
+    }
+
+    /// Gives the PWM frequency the timer currently generates.
+    /// # Returns
+    /// * `a u32` - The PWM frequency in hertz, 0 if the timer is stopped.
+    pub fn pwm_frequency(&self) -> u32 {
+        match self.prescaler() {
+            Some(prescaler) => CPU_FREQUENCY_HZ / (prescaler.factor() * self.period_ticks()),
+            None => 0,
+        }
+    }
+
+    /// Set the prescaler which gives the PWM frequency closest to the one required.
+    /// # Arguments
+    /// * `frequency` - a u32, the required PWM frequency in hertz.
+    /// # Returns
+    /// * `a u32` - The PWM frequency in hertz actually set.
+    pub fn set_pwm_frequency(&mut self, frequency: u32) -> u32 {
+        let prescalers: &[TimerPrescaler] = match self.name() {
+            TimerNo8::Timer0 => &[
+                TimerPrescaler::Div1,
+                TimerPrescaler::Div8,
+                TimerPrescaler::Div64,
+                TimerPrescaler::Div256,
+                TimerPrescaler::Div1024,
+            ],
+            TimerNo8::Timer2 => &[
+                TimerPrescaler::Div1,
+                TimerPrescaler::Div8,
+                TimerPrescaler::Div32,
+                TimerPrescaler::Div64,
+                TimerPrescaler::Div128,
+                TimerPrescaler::Div256,
+                TimerPrescaler::Div1024,
+            ],
+        };
+        let ticks = self.period_ticks();
+        let mut best = prescalers[0];
+        for prescaler in prescalers.iter() {
+            let actual = CPU_FREQUENCY_HZ / (prescaler.factor() * ticks);
+            let best_actual = CPU_FREQUENCY_HZ / (best.factor() * ticks);
+            if abs_diff(actual, frequency) < abs_diff(best_actual, frequency) {
+                best = *prescaler;
+            }
         }
+        // Only the prescalers of this timer are tried.
+        let _ = self.set_prescaler(best);
+        self.pwm_frequency()
     }
 }
 
@@ -127,14 +403,245 @@ impl Timer16 {
     /// * `timer` - a `TimerNo16` object, which defines the Timer number for which object is to be made.
     /// # Returns
     /// * `a reference to Timer16 object` - which will be used for further implementations.
-    pub fn new(timer: TimerNo16) -> &'static mut Timer16 {
+    pub unsafe fn new(timer: TimerNo16) -> &'static mut Timer16 {
         match timer {
-            TimerNo16::Timer1 => unsafe { &mut *(0x80 as *mut Timer16) },
-            TimerNo16::Timer3 => unsafe { &mut *(0x90 as *mut Timer16) },
-            TimerNo16::Timer4 => unsafe { &mut *(0xA0 as *mut Timer16) },
-            TimerNo16::Timer5 => unsafe { &mut *(0x120 as *mut Timer16) },
+            TimerNo16::Timer1 => &mut *(0x80 as *mut Timer16),
+            TimerNo16::Timer3 => &mut *(0x90 as *mut Timer16),
+            TimerNo16::Timer4 => &mut *(0xA0 as *mut Timer16),
+            TimerNo16::Timer5 => &mut *(0x120 as *mut Timer16),
+        }
+    }
+
+    /// Set the clock prescaler of the timer which decides the PWM frequency.
+    /// `Div32` and `Div128` are not available for 16 bit timers.
+    /// # Arguments
+    /// * `prescaler` - a `TimerPrescaler` object, the division factor of the CPU clock to be used.
+    /// # Returns
+    /// * `a Result` - `TimerError::PrescalerUnavailable` if the timer does not have the prescaler.
+    pub fn set_prescaler(&mut self, prescaler: TimerPrescaler) -> Result<(), TimerError> {
+        let bits: u8 = match prescaler {
+            TimerPrescaler::Div1 => 0b001,
+            TimerPrescaler::Div8 => 0b010,
+            TimerPrescaler::Div64 => 0b011,
+            TimerPrescaler::Div256 => 0b100,
+            TimerPrescaler::Div1024 => 0b101,
+            _ => return Err(TimerError::PrescalerUnavailable),
+        };
+        self.tccrb.update(|ctrl| {
+            ctrl.set_bits(0..3, bits);
+        });
+        Ok(())
+    }
+
+    /// Gives the clock prescaler the timer is currently running with.
+    /// # Returns
+    /// * `a Option<TimerPrescaler>` - which is None if the timer is stopped or clocked externally.
+    pub fn prescaler(&self) -> Option<TimerPrescaler> {
+        match self.tccrb.read().get_bits(0..3) {
+            0b001 => Some(TimerPrescaler::Div1),
+            0b010 => Some(TimerPrescaler::Div8),
+            0b011 => Some(TimerPrescaler::Div64),
+            0b100 => Some(TimerPrescaler::Div256),
+            0b101 => Some(TimerPrescaler::Div1024),
+            _ => None,
+        }
+    }
+
+    /// Gives the WGM bits of the timer which are split over TCCRnA and TCCRnB.
+    fn wgm(&self) -> u8 {
+        let mut wgm = self.tccra.read().get_bits(0..2);
+        wgm.set_bits(2..4, self.tccrb.read().get_bits(3..5));
+        wgm
+    }
+
+    /// Writes the WGM bits of the timer which are split over TCCRnA and TCCRnB.
+    fn set_wgm(&mut self, wgm: u8) {
+        self.tccra.update(|ctrl| {
+            ctrl.set_bits(0..2, wgm.get_bits(0..2));
+        });
+        self.tccrb.update(|ctrl| {
+            ctrl.set_bits(3..5, wgm.get_bits(2..4));
+        });
+    }
+
+    /// Reads a 16 bit register, the low byte has to be read first.
+    fn read16(low: &Volatile<u8>, high: &Volatile<u8>) -> u16 {
+        let l = low.read() as u16;
+        let h = high.read() as u16;
+        (h << 8) | l
+    }
+
+    /// Writes a 16 bit register, the high byte has to be written first.
+    fn write16(low: &mut Volatile<u8>, high: &mut Volatile<u8>, value: u16) {
+        high.write((value >> 8) as u8);
+        low.write(value as u8);
+    }
+
+    /// Gives the value the timer counts up to in the current waveform mode.
+    fn top(&self) -> u32 {
+        match self.wgm() {
+            0b0001 | 0b0101 => 0xFF,
+            0b0010 | 0b0110 => 0x1FF,
+            0b0011 | 0b0111 => 0x3FF,
+            0b0100 | 0b1001 | 0b1011 | 0b1111 => Timer16::read16(&self.ocral, &self.ocrah) as u32,
+            0b1000 | 0b1010 | 0b1100 | 0b1110 => Timer16::read16(&self.icrl, &self.icrh) as u32,
+            _ => 0xFFFF,
+        }
+    }
+
+    /// Set the waveform generation mode of the timer.
+    /// The PWM modes count up to 0xFF, while `Ctc` counts up to the value in OCRnA.
+    /// `PhaseFrequencyCorrectPwm` uses ICRn as TOP, which is set to 0xFF here.
+    /// # Arguments
+    /// * `mode` - a `WaveformMode` object, the waveform to be generated by the timer.
+    pub fn set_waveform_mode(&mut self, mode: WaveformMode) {
+        let wgm: u8 = match mode {
+            WaveformMode::Normal => 0b0000,
+            WaveformMode::PhaseCorrectPwm => 0b0001,
+            WaveformMode::Ctc => 0b0100,
+            WaveformMode::FastPwm => 0b0101,
+            WaveformMode::PhaseFrequencyCorrectPwm => {
+                Timer16::write16(&mut self.icrl, &mut self.icrh, 0xFF);
+                0b1000
+            }
+        };
+        self.set_wgm(wgm);
+    }
+
+    /// Gives the waveform generation mode the timer is currently set to.
+    /// # Returns
+    /// * `a WaveformMode object` - The current waveform generation mode.
+    pub fn waveform_mode(&self) -> WaveformMode {
+        match self.wgm() {
+            0b0001 | 0b0010 | 0b0011 | 0b1010 | 0b1011 => WaveformMode::PhaseCorrectPwm,
+            0b1000 | 0b1001 => WaveformMode::PhaseFrequencyCorrectPwm,
+            0b0100 | 0b1100 => WaveformMode::Ctc,
+            0b0101 | 0b0110 | 0b0111 | 0b1110 | 0b1111 => WaveformMode::FastPwm,
+            _ => WaveformMode::Normal,
+        }
+    }
+
+    /// Checks if ICRn is used as TOP in the current waveform mode.
+    fn icr_top(&self) -> bool {
+        matches!(self.wgm(), 0b1000 | 0b1010 | 0b1100 | 0b1110)
+    }
+
+    /// Set the value up to which the timer counts in PWM modes by using ICRn as TOP.
+    /// Phase correct and phase and frequency correct PWM are kept as they are,
+    /// the timer is put in fast PWM mode otherwise.
+    /// # Arguments
+    /// * `top` - a u16, the maximum value of the timer counter.
+    pub fn set_pwm_top(&mut self, top: u16) {
+        Timer16::write16(&mut self.icrl, &mut self.icrh, top);
+        let wgm: u8 = match self.waveform_mode() {
+            WaveformMode::PhaseCorrectPwm => 0b1010,
+            WaveformMode::PhaseFrequencyCorrectPwm => 0b1000,
+            _ => 0b1110,
+        };
+        self.set_wgm(wgm);
+    }
+
+    /// Set the resolution of the PWM generated by the timer,
+    /// for example 10, 12 or 16 bits, by setting ICRn as TOP.
+    /// # Arguments
+    /// * `bits` - a u8, the number of bits of resolution of the compare values.
+    /// # Returns
+    /// * `a Result` - `TimerError::ResolutionOutOfRange` if `bits` is not between 2 and 16.
+    pub fn set_pwm_resolution(&mut self, bits: u8) -> Result<(), TimerError> {
+        if !(2..=16).contains(&bits) {
+            return Err(TimerError::ResolutionOutOfRange);
+        }
+        self.set_pwm_top(((1u32 << bits) - 1) as u16);
+        Ok(())
+    }
+
+    /// Write a full 16 bit value to the output compare register of a channel.
+    /// # Arguments
+    /// * `channel` - a `TimerChannel` object, the output compare channel to be written.
+    /// * `value` - a u16, the compare value which sets the duty cycle.
+    pub fn write_compare(&mut self, channel: TimerChannel, value: u16) {
+        match channel {
+            TimerChannel::A => {
+                Timer16::write16(&mut self.ocral, &mut self.ocrah, value);
+            }
+            TimerChannel::B => {
+                Timer16::write16(&mut self.ocrbl, &mut self.ocrbh, value);
+            }
+            TimerChannel::C => {
+                Timer16::write16(&mut self.ocrcl, &mut self.ocrch, value);
+            }
+        }
+    }
+
+    /// Connect the output pin of a channel to the timer for non-inverting PWM.
+    /// # Arguments
+    /// * `channel` - a `TimerChannel` object, the output compare channel whose pin is to be driven.
+    fn enable_output(&mut self, channel: TimerChannel) {
+        let bits = match channel {
+            TimerChannel::A => 6..8,
+            TimerChannel::B => 4..6,
+            TimerChannel::C => 2..4,
+        };
+        self.tccra.update(|ctrl| {
+            ctrl.set_bits(bits, 0b10);
+        });
+    }
+
+    /// Number of timer clock ticks in one PWM period for the current waveform mode.
+    fn period_ticks(&self) -> u32 {
+        let top = self.top();
+        match self.waveform_mode() {
+            WaveformMode::PhaseCorrectPwm | WaveformMode::PhaseFrequencyCorrectPwm => 2 * top,
+            WaveformMode::Ctc => 2 * (top + 1),
+            _ => top + 1,
         }
     }
+
+    /// Gives the PWM frequency the timer currently generates.
+    /// # Returns
+    /// * `a u32` - The PWM frequency in hertz, 0 if the timer is stopped.
+    pub fn pwm_frequency(&self) -> u32 {
+        match self.prescaler() {
+            Some(prescaler) => CPU_FREQUENCY_HZ / (prescaler.factor() * self.period_ticks()),
+            None => 0,
+        }
+    }
+
+    /// Set the prescaler which gives the PWM frequency closest to the one required.
+    /// # Arguments
+    /// * `frequency` - a u32, the required PWM frequency in hertz.
+    /// # Returns
+    /// * `a u32` - The PWM frequency in hertz actually set.
+    pub fn set_pwm_frequency(&mut self, frequency: u32) -> u32 {
+        let prescalers = [
+            TimerPrescaler::Div1,
+            TimerPrescaler::Div8,
+            TimerPrescaler::Div64,
+            TimerPrescaler::Div256,
+            TimerPrescaler::Div1024,
+        ];
+        let ticks = self.period_ticks();
+        let mut best = prescalers[0];
+        for prescaler in prescalers.iter() {
+            let actual = CPU_FREQUENCY_HZ / (prescaler.factor() * ticks);
+            let best_actual = CPU_FREQUENCY_HZ / (best.factor() * ticks);
+            if abs_diff(actual, frequency) < abs_diff(best_actual, frequency) {
+                best = *prescaler;
+            }
+        }
+        // Only the prescalers of this timer are tried.
+        let _ = self.set_prescaler(best);
+        self.pwm_frequency()
+    }
+}
+
+/// Absolute difference between two frequencies.
+fn abs_diff(a: u32, b: u32) -> u32 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
 }
 
 impl AnalogComparator {
@@ -144,11 +651,161 @@ impl AnalogComparator {
     pub unsafe fn new() -> &'static mut AnalogComparator {
         &mut *(0x50 as *mut AnalogComparator)
     }
+
+    /// Changes ACSR without clearing a pending ACI flag,
+    /// as writing back a set ACI bit would clear it.
+    /// # Arguments
+    /// * `f` - a closure, which changes the value to be written.
+    fn update_acsr<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut u8),
+    {
+        self.acsr.update(|acsr| {
+            f(acsr);
+            acsr.set_bit(4, false);
+        });
+    }
+
+    /// Switches on the analog comparator.
+    pub fn enable(&mut self) {
+        self.update_acsr(|acsr| {
+            acsr.set_bit(7, false);
+        });
+    }
+
+    /// Switches off the analog comparator to save power.
+    /// The interrupt is disabled first so that switching off does not trigger it.
+    pub fn disable(&mut self) {
+        self.update_acsr(|acsr| {
+            acsr.set_bit(3, false);
+        });
+        self.update_acsr(|acsr| {
+            acsr.set_bit(7, true);
+        });
+    }
+
+    /// Selects the inputs compared by the comparator.
+    /// # Arguments
+    /// * `positive` - a `ComparatorPositive` object, the positive input.
+    /// * `negative` - a `ComparatorNegative` object, the negative input.
+    pub fn set_inputs(&mut self, positive: ComparatorPositive, negative: ComparatorNegative) {
+        self.update_acsr(|acsr| {
+            acsr.set_bit(
+                6,
+                match positive {
+                    ComparatorPositive::Ain0 => false,
+                    ComparatorPositive::Bandgap => true,
+                },
+            );
+        });
+
+        match negative {
+            ComparatorNegative::Ain1 => unsafe {
+                Analog::new().adcsrb.update(|adcsrb| {
+                    adcsrb.set_bit(6, false);
+                });
+            },
+            ComparatorNegative::Adc(channel) => {
+                if channel > 15 {
+                    unreachable!();
+                }
+                unsafe {
+                    let analog = Analog::new();
+                    analog.adc_disable();
+                    analog.admux.update(|admux| {
+                        admux.set_bits(0..5, channel % 8);
+                    });
+                    analog.adcsrb.update(|adcsrb| {
+                        adcsrb.set_bit(6, true);
+                        adcsrb.set_bit(3, channel >= 8);
+                    });
+                }
+            }
+        }
+
+        // Digital input buffers of AIN0 and AIN1 are not needed.
+        unsafe {
+            Analog::new().didr1.update(|didr1| {
+                didr1.set_bits(0..2, 0b11);
+            });
+        }
+    }
+
+    /// Reads the output of the comparator.
+    /// # Returns
+    /// * `a boolean` - true if the positive input is higher than the negative input.
+    pub fn output(&self) -> bool {
+        self.acsr.read().get_bit(5)
+    }
+
+    /// Attach a handler function which is called on the selected edge of the output.
+    /// Global interrupts have to be enabled for the handler to be called.
+    /// # Arguments
+    /// * `edge` - a `ComparatorEdge` object, the edge of the output which triggers the interrupt.
+    /// * `handler` - a function, which is called from the interrupt vector.
+    pub fn set_interrupt(&mut self, edge: ComparatorEdge, handler: fn()) {
+        unsafe {
+            COMPARATOR_HANDLER = Some(handler);
+        }
+        // ACIE is cleared while changing ACIS so that no interrupt is triggered.
+        self.update_acsr(|acsr| {
+            acsr.set_bit(3, false);
+        });
+        self.update_acsr(|acsr| {
+            acsr.set_bits(
+                0..2,
+                match edge {
+                    ComparatorEdge::Toggle => 0b00,
+                    ComparatorEdge::Falling => 0b10,
+                    ComparatorEdge::Rising => 0b11,
+                },
+            );
+        });
+        // ACI is cleared by writing one to it, so a stale flag does not fire the new handler.
+        self.acsr.update(|acsr| {
+            acsr.set_bit(4, true);
+            acsr.set_bit(3, true);
+        });
+    }
+
+    /// Detach the handler function and disable the comparator interrupt.
+    pub fn clear_interrupt(&mut self) {
+        self.update_acsr(|acsr| {
+            acsr.set_bit(3, false);
+        });
+        unsafe {
+            COMPARATOR_HANDLER = None;
+        }
+    }
+
+    /// Connects the comparator output to the input capture of Timer 1,
+    /// so that the time of a zero crossing is captured by the timer.
+    /// # Arguments
+    /// * `connect` - a boolean, true to connect and false to disconnect.
+    pub fn connect_input_capture(&mut self, connect: bool) {
+        self.update_acsr(|acsr| {
+            acsr.set_bit(2, connect);
+        });
+    }
+}
+
+/// Function called on every comparator interrupt.
+static mut COMPARATOR_HANDLER: Option<fn()> = None;
+
+/// Interrupt vector of the analog comparator.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_28() {
+    if let Some(handler) = COMPARATOR_HANDLER {
+        handler();
+    }
 }
 
 impl AnalogPin {
     /// Read the signal input to the analog pin.
     /// Any analog pin can be freely used for this purpose.
+    /// The ADC is left enabled, so conversions started from the interrupt are not cut off,
+    /// but while a scan is running its results should be taken with `Analog::last()` instead.
     /// # Returns
     /// * `a u32` - Value read from the analog pin.
     pub fn read(&mut self) -> u32 {
@@ -163,14 +820,14 @@ impl AnalogPin {
 
             analog.adc_enable();
 
-            analog.analog_prescaler(2);
+            analog.apply_prescaler();
 
             analog.adc_auto_trig();
 
             match pin {
                 0 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b000);
+                        admux.set_bits(0..5, 0b000);
                     });
                     analog.didr0.update(|didr0| {
                         didr0.set_bit(0, true);
@@ -181,7 +838,7 @@ impl AnalogPin {
                 }
                 1 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b001);
+                        admux.set_bits(0..5, 0b001);
                     });
                     analog.didr0.update(|didr0| {
                         didr0.set_bit(1, true);
@@ -192,7 +849,7 @@ impl AnalogPin {
                 }
                 2 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b010);
+                        admux.set_bits(0..5, 0b010);
                     });
                     analog.didr0.update(|didr0| {
                         didr0.set_bit(2, true);
@@ -203,7 +860,7 @@ impl AnalogPin {
                 }
                 3 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b011);
+                        admux.set_bits(0..5, 0b011);
                     });
                     analog.didr0.update(|didr0| {
                         didr0.set_bit(3, true);
@@ -214,7 +871,7 @@ impl AnalogPin {
                 }
                 4 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b100);
+                        admux.set_bits(0..5, 0b100);
                     });
                     analog.didr0.update(|didr0| {
                         didr0.set_bit(4, true);
@@ -225,7 +882,7 @@ impl AnalogPin {
                 }
                 5 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b101);
+                        admux.set_bits(0..5, 0b101);
                     });
                     analog.didr0.update(|didr0| {
                         didr0.set_bit(5, true);
@@ -236,7 +893,7 @@ impl AnalogPin {
                 }
                 6 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b110);
+                        admux.set_bits(0..5, 0b110);
                     });
                     analog.didr0.update(|didr0| {
                         didr0.set_bit(6, true);
@@ -247,7 +904,7 @@ impl AnalogPin {
                 }
                 7 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b111);
+                        admux.set_bits(0..5, 0b111);
                     });
                     analog.didr0.update(|didr0| {
                         didr0.set_bit(7, true);
@@ -258,7 +915,7 @@ impl AnalogPin {
                 }
                 8 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b000);
+                        admux.set_bits(0..5, 0b000);
                     });
                     analog.didr2.update(|didr2| {
                         didr2.set_bit(0, true);
@@ -269,7 +926,7 @@ impl AnalogPin {
                 }
                 9 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b001);
+                        admux.set_bits(0..5, 0b001);
                     });
                     analog.didr2.update(|didr2| {
                         didr2.set_bit(1, true);
@@ -280,7 +937,7 @@ impl AnalogPin {
                 }
                 10 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b010);
+                        admux.set_bits(0..5, 0b010);
                     });
                     analog.didr2.update(|didr2| {
                         didr2.set_bit(2, true);
@@ -291,10 +948,10 @@ impl AnalogPin {
                 }
                 11 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b011);
+                        admux.set_bits(0..5, 0b011);
                     });
                     analog.didr2.update(|didr2| {
-                        didr2.set_bit(4, true);
+                        didr2.set_bit(3, true);
                     });
                     analog.adcsrb.update(|mux| {
                         mux.set_bit(3, true);
@@ -302,7 +959,7 @@ impl AnalogPin {
                 }
                 12 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b100);
+                        admux.set_bits(0..5, 0b100);
                     });
                     analog.didr2.update(|didr2| {
                         didr2.set_bit(4, true);
@@ -313,7 +970,7 @@ impl AnalogPin {
                 }
                 13 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b101);
+                        admux.set_bits(0..5, 0b101);
                     });
                     analog.didr2.update(|didr2| {
                         didr2.set_bit(5, true);
@@ -324,7 +981,7 @@ impl AnalogPin {
                 }
                 14 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b110);
+                        admux.set_bits(0..5, 0b110);
                     });
                     analog.didr2.update(|didr2| {
                         didr2.set_bit(6, true);
@@ -335,7 +992,7 @@ impl AnalogPin {
                 }
                 15 => {
                     analog.admux.update(|admux| {
-                        admux.set_bits(0..3, 0b111);
+                        admux.set_bits(0..5, 0b111);
                     });
                     analog.didr2.update(|didr2| {
                         didr2.set_bit(7, true);
@@ -347,38 +1004,63 @@ impl AnalogPin {
                 _ => unreachable!(),
             }
 
-            analog.adc_con_start();
+            analog.apply_reference();
 
-            // wait 25 ADC cycles
-            let mut i: i32 = 25;
-            let adcsra = analog.adcsra.read();
+            analog.adc_con_start();
 
-            while adcsra.get_bit(4) == true {
-                if i != 0 {
-                    i = i - 1;
-                    __nop();
-                    __nop(); //add delay of system clock
-                } else {
-                    unreachable!()
-                }
+            // Wait for the conversion to complete, ADSC is cleared by hardware.
+            while analog.adcsra.read().get_bit(6) {
+                __nop();
             }
+
+            // ADCL has to be read first, reading ADCH releases the result registers.
             let mut a: u32 = 0;
             a.set_bits(0..8, analog.adcl.read() as u32);
 
-            a.set_bits(8..10, analog.adch.read() as u32);
-
-            analog.adc_disable();
+            a.set_bits(8..10, analog.adch.read() as u32 & 0x3);
 
             a
         }
     }
 }
 
+/// Read the signal input to an analog pin.
+/// # Arguments
+/// * `pin` - a usize, the number of the analog pin, 0 to 15.
+/// # Returns
+/// * `a u32` - Value read from the analog pin, 10 bits wide.
+pub fn analog_read(pin: usize) -> u32 {
+    let mut pins = unsafe { Pins::new() };
+    pins.analog[pin].read()
+}
+
+/// Read an analog pin with more resolution than the ADC has by oversampling.
+/// 4^`extra_bits` samples are added up and the sum is shifted right by `extra_bits`,
+/// which works for slow signals carrying a little noise, like thermistors and load cells.
+/// # Arguments
+/// * `pin` - a usize, the number of the analog pin.
+/// * `extra_bits` - a u8, the number of bits to be added to the 10 bit result, at most 6. Larger values are taken as 6.
+/// # Returns
+/// * `a u32` - Value read from the analog pin, 10 + `extra_bits` bits wide.
+pub fn read_oversampled(pin: usize, extra_bits: u8) -> u32 {
+    let extra_bits = core::cmp::min(extra_bits, 6);
+    let mut pins = unsafe { Pins::new() };
+    let samples: u32 = 1 << (2 * extra_bits);
+    let mut sum: u32 = 0;
+    for _ in 0..samples {
+        sum += pins.analog[pin].read();
+    }
+    sum >> extra_bits
+}
+
 impl DigitalPin {
     /// This is used to write a PWM wave to a digital pin.
     /// Only 2-13 and 44-46 digital pins can be used in this function, other pins will lead to crash.
-    /// All pin except 4 and 13 are set to give output at 490 hertz.
-    /// pin 4 and 13 will give output at 980 hertz.
+    /// All pin except 4 and 13 are set to give output at 490 hertz by default.
+    /// pin 4 and 13 will give output at 980 hertz by default.
+    /// The frequency can be changed through `set_prescaler()` or `set_pwm_frequency()`
+    /// and the waveform through `set_waveform_mode()` of the timer controlling the pin,
+    /// both of which are kept by further writes.
     /// # Arguments
     /// * `value1` - a u8, value to be written on the analog pin for output.
     pub fn write(&mut self, value1: u8) {
@@ -388,18 +1070,14 @@ impl DigitalPin {
 
         match pin1 {
             4 | 13 => {
-                let pow = unsafe { Power::new() };
-                pow.prr0.set_bit(5, false);
-                // pow.prr0.update(|ctrl| {
-                //     ctrl.set_bit(5, false);
-                // });
-                let timer = Timer8::new(TimerNo8::Timer0);
-                timer.tccra.update(|ctrl| {
-                    ctrl.set_bits(0..2, 0b11);
-                });
-                timer.tccrb.update(|ctrl| {
-                    ctrl.set_bits(0..4, 0b0011);
-                });
+                unsafe { Power::new() }.enable(Peripheral::Timer0);
+                let timer = unsafe { Timer8::new(TimerNo8::Timer0) };
+                if let WaveformMode::Normal = timer.waveform_mode() {
+                    let _ = timer.set_waveform_mode(WaveformMode::FastPwm);
+                }
+                if timer.prescaler().is_none() {
+                    let _ = timer.set_prescaler(TimerPrescaler::Div64);
+                }
 
                 if pin1 == 4 {
                     timer.tccra.update(|ctrl| {
@@ -414,19 +1092,15 @@ impl DigitalPin {
                 }
             }
             9 | 10 => {
-                let pow = unsafe { Power::new() };
-                pow.prr0.set_bit(6, false);
-                // pow.prr0.update(|ctrl| {
-                //     ctrl.set_bit(6, false);
-                // });
-
-                let timer = Timer8::new(TimerNo8::Timer2);
-                timer.tccra.update(|ctrl| {
-                    ctrl.set_bits(0..2, 0b11);
-                });
-                timer.tccrb.update(|ctrl| {
-                    ctrl.set_bits(0..4, 0b0101);
-                });
+                unsafe { Power::new() }.enable(Peripheral::Timer2);
+
+                let timer = unsafe { Timer8::new(TimerNo8::Timer2) };
+                if let WaveformMode::Normal = timer.waveform_mode() {
+                    let _ = timer.set_waveform_mode(WaveformMode::FastPwm);
+                }
+                if timer.prescaler().is_none() {
+                    let _ = timer.set_prescaler(TimerPrescaler::Div128);
+                }
                 if pin1 == 9 {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(4..8, 0b0010);
@@ -440,126 +1114,153 @@ impl DigitalPin {
                 }
             }
             11 | 12 => {
-                let pow = unsafe { Power::new() };
-                pow.prr0.set_bit(3, false);
-                // pow.prr0.update(|ctrl| {
-                //     ctrl.set_bit(3, false);
-                // });
-                let timer = Timer16::new(TimerNo16::Timer1);
-                timer.tccra.update(|ctrl| {
-                    ctrl.set_bits(0..2, 0b01);
-                });
-                timer.tccrb.update(|ctrl| {
-                    ctrl.set_bits(0..5, 0b00011);
-                });
+                unsafe { Power::new() }.enable(Peripheral::Timer1);
+                let timer = unsafe { Timer16::new(TimerNo16::Timer1) };
+                if let WaveformMode::Normal = timer.waveform_mode() {
+                    timer.set_waveform_mode(WaveformMode::PhaseCorrectPwm);
+                }
+                if timer.prescaler().is_none() {
+                    let _ = timer.set_prescaler(TimerPrescaler::Div64);
+                }
                 if pin1 == 12 {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b001000);
                     });
-                    timer.ocrbl.write(value1);
+                    timer.write_compare(TimerChannel::B, value1 as u16);
                 } else {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b100000);
                     });
-                    timer.ocral.write(value1);
+                    timer.write_compare(TimerChannel::A, value1 as u16);
                 }
             }
             2 | 3 | 5 => {
-                let pow = unsafe { Power::new() };
-                pow.prr1.set_bit(3, false);
-                // pow.prr1.update(|ctrl| {
-                //     ctrl.set_bit(3, false);
-                // });
-                let timer = Timer16::new(TimerNo16::Timer3);
-                timer.tccra.update(|ctrl| {
-                    ctrl.set_bits(0..2, 0b01);
-                });
-                timer.tccrb.update(|ctrl| {
-                    ctrl.set_bits(0..5, 0b00011);
-                });
+                unsafe { Power::new() }.enable(Peripheral::Timer3);
+                let timer = unsafe { Timer16::new(TimerNo16::Timer3) };
+                if let WaveformMode::Normal = timer.waveform_mode() {
+                    timer.set_waveform_mode(WaveformMode::PhaseCorrectPwm);
+                }
+                if timer.prescaler().is_none() {
+                    let _ = timer.set_prescaler(TimerPrescaler::Div64);
+                }
 
                 if pin1 == 2 {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b001000);
                     });
-                    timer.ocrbl.write(value1);
+                    timer.write_compare(TimerChannel::B, value1 as u16);
                 } else if pin1 == 5 {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b100000);
                     });
-                    timer.ocral.write(value1);
+                    timer.write_compare(TimerChannel::A, value1 as u16);
                 } else {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b000010);
                     });
-                    timer.ocrcl.write(value1);
+                    timer.write_compare(TimerChannel::C, value1 as u16);
                 }
             }
             6 | 7 | 8 => {
-                let timer = Timer16::new(TimerNo16::Timer4);
-                let pow = unsafe { Power::new() };
-                pow.prr1.set_bit(4, false);
-                // pow.prr1.update(|ctrl| {
-                //     ctrl.set_bit(4, false);
-                // });
-                timer.tccra.update(|ctrl| {
-                    ctrl.set_bits(0..2, 0b01);
-                });
-                timer.tccrb.update(|ctrl| {
-                    ctrl.set_bits(0..5, 0b00011);
-                });
+                let timer = unsafe { Timer16::new(TimerNo16::Timer4) };
+                unsafe { Power::new() }.enable(Peripheral::Timer4);
+                if let WaveformMode::Normal = timer.waveform_mode() {
+                    timer.set_waveform_mode(WaveformMode::PhaseCorrectPwm);
+                }
+                if timer.prescaler().is_none() {
+                    let _ = timer.set_prescaler(TimerPrescaler::Div64);
+                }
 
                 if pin1 == 7 {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b001000);
                     });
-                    timer.ocrbl.write(value1);
+                    timer.write_compare(TimerChannel::B, value1 as u16);
                 } else if pin1 == 6 {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b100000);
                     });
-                    timer.ocral.write(value1);
+                    timer.write_compare(TimerChannel::A, value1 as u16);
                 } else {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b000010);
                     });
-                    timer.ocrcl.write(value1);
+                    timer.write_compare(TimerChannel::C, value1 as u16);
                 }
             }
             44 | 45 | 46 => {
-                let pow = unsafe { Power::new() };
-                pow.prr1.set_bit(5, false);
-                // pow.prr1.update(|ctrl| {
-                //     ctrl.set_bit(5, false);
-                // });
-                let timer = Timer16::new(TimerNo16::Timer5);
-                timer.tccra.update(|ctrl| {
-                    ctrl.set_bits(0..2, 0b01);
-                });
-                timer.tccrb.update(|ctrl| {
-                    ctrl.set_bits(0..5, 0b00011);
-                });
+                unsafe { Power::new() }.enable(Peripheral::Timer5);
+                let timer = unsafe { Timer16::new(TimerNo16::Timer5) };
+                if let WaveformMode::Normal = timer.waveform_mode() {
+                    timer.set_waveform_mode(WaveformMode::PhaseCorrectPwm);
+                }
+                if timer.prescaler().is_none() {
+                    let _ = timer.set_prescaler(TimerPrescaler::Div64);
+                }
 
                 if pin1 == 45 {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b001000);
                     });
-                    timer.ocrbl.write(value1);
+                    timer.write_compare(TimerChannel::B, value1 as u16);
                 } else if pin1 == 46 {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b100000);
                     });
-                    timer.ocral.write(value1);
+                    timer.write_compare(TimerChannel::A, value1 as u16);
                 } else {
                     timer.tccra.update(|ctrl| {
                         ctrl.set_bits(2..8, 0b000010);
                     });
-                    timer.ocrcl.write(value1);
+                    timer.write_compare(TimerChannel::C, value1 as u16);
                 }
             }
             _ => unreachable!(),
         }
     }
+
+    /// This is used to write a high resolution PWM wave to a digital pin controlled by a 16 bit timer.
+    /// Only 2-3, 5-8, 11-12 and 44-46 digital pins can be used in this function, other pins will lead to crash.
+    /// If no resolution was chosen through `set_pwm_resolution()` of the timer, 16 bit
+    /// fast PWM is used which gives output at 244 hertz when the timer was stopped.
+    /// # Arguments
+    /// * `value` - a u16, value to be written on the pin for output.
+    pub fn write16(&mut self, value: u16) {
+        self.pin.set_output();
+
+        let (timer_no, channel) = match self.pinno {
+            11 => (TimerNo16::Timer1, TimerChannel::A),
+            12 => (TimerNo16::Timer1, TimerChannel::B),
+            5 => (TimerNo16::Timer3, TimerChannel::A),
+            2 => (TimerNo16::Timer3, TimerChannel::B),
+            3 => (TimerNo16::Timer3, TimerChannel::C),
+            6 => (TimerNo16::Timer4, TimerChannel::A),
+            7 => (TimerNo16::Timer4, TimerChannel::B),
+            8 => (TimerNo16::Timer4, TimerChannel::C),
+            46 => (TimerNo16::Timer5, TimerChannel::A),
+            45 => (TimerNo16::Timer5, TimerChannel::B),
+            44 => (TimerNo16::Timer5, TimerChannel::C),
+            _ => unreachable!(),
+        };
+
+        let pow = unsafe { Power::new() };
+        match timer_no {
+            TimerNo16::Timer1 => pow.enable(Peripheral::Timer1),
+            TimerNo16::Timer3 => pow.enable(Peripheral::Timer3),
+            TimerNo16::Timer4 => pow.enable(Peripheral::Timer4),
+            TimerNo16::Timer5 => pow.enable(Peripheral::Timer5),
+        };
+
+        let timer = unsafe { Timer16::new(timer_no) };
+        if !timer.icr_top() {
+            timer.set_pwm_top(0xFFFF);
+        }
+        if timer.prescaler().is_none() {
+            let _ = timer.set_prescaler(TimerPrescaler::Div1);
+        }
+        timer.enable_output(channel);
+        timer.write_compare(channel, value);
+    }
 }
 
 impl Analog {
@@ -600,24 +1301,12 @@ impl Analog {
 
     /// Set the appropriate power mode for ADC.
     pub fn power_adc_enable(&mut self) {
-        {
-            let pow = unsafe { Power::new() };
-            pow.prr0.set_bit(0, true);
-            // self.prr0.update(|aden| {
-            //     aden.set_bit(0, true);
-            // });
-        }
+        unsafe { Power::new() }.disable(Peripheral::Adc);
     }
 
     /// Reset the power mode after the ADC implementation.
     pub fn power_adc_disable(&mut self) {
-        {
-            let pow = unsafe { Power::new() };
-            pow.prr0.set_bit(0, false);
-            // self.prr0.update(|aden| {
-            //     aden.set_bit(0, false);
-            // });
-        }
+        unsafe { Power::new() }.enable(Peripheral::Adc);
     }
 
     /// Set prescaler for the ADC.
@@ -663,33 +1352,553 @@ impl Analog {
             _ => unreachable!(),
         }
     }
+
+    /// Selects the input channel of the ADC.
+    /// # Arguments
+    /// * `channel` - a u8, the number of the analog input, 0 to 15.
+    pub fn select_channel(&mut self, channel: u8) {
+        match channel {
+            0..=7 => {
+                self.admux.update(|admux| {
+                    admux.set_bits(0..5, channel);
+                });
+                self.adcsrb.update(|mux| {
+                    mux.set_bit(3, false);
+                });
+            }
+            8..=15 => {
+                self.admux.update(|admux| {
+                    admux.set_bits(0..5, channel - 8);
+                });
+                self.adcsrb.update(|mux| {
+                    mux.set_bit(3, true);
+                });
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Chooses the smallest prescaler which keeps the ADC clock at or below
+    /// 200 kHz for the present CPU frequency, needed for full 10 bit accuracy.
+    /// # Returns
+    /// * `a u8` - the division factor of the ADC clock.
+    pub fn auto_prescaler() -> u8 {
+        for factor in [2, 4, 8, 16, 32, 64].iter() {
+            if cpu_frequency_hz() / (*factor as u32) <= 200_000 {
+                return *factor;
+            }
+        }
+        128
+    }
+
+    /// Fixes the prescaler used by conversions instead of computing it from the
+    /// CPU frequency. A faster ADC clock gives faster conversions at the cost of accuracy.
+    /// # Arguments
+    /// * `factor` - an Option of u8, the division factor of the ADC clock, or None to go back to automatic selection.
+    pub fn set_prescaler_override(&mut self, factor: Option<u8>) {
+        unsafe {
+            ADC_PRESCALER = factor;
+        }
+    }
+
+    /// Sets the prescaler of the ADC, using the override if one is set.
+    fn apply_prescaler(&mut self) {
+        let factor = match unsafe { ADC_PRESCALER } {
+            Some(factor) => factor,
+            None => Analog::auto_prescaler(),
+        };
+        self.analog_prescaler(factor);
+    }
+
+    /// Gives the MUX5:0 value of a differential channel pair.
+    /// Both inputs have to be from the same group of ADC0-7 or ADC8-15.
+    fn differential_mux(positive: u8, negative: u8, gain: AdcGain) -> u8 {
+        let bank: u8 = match (positive, negative) {
+            (0..=7, 0..=7) => 0x00,
+            (8..=15, 8..=15) => 0x20,
+            _ => unreachable!(),
+        };
+        let pos = positive % 8;
+        let neg = negative % 8;
+
+        let mux = match (gain, neg, pos) {
+            (AdcGain::X10, 0, 0..=1) => 0x08 + pos,
+            (AdcGain::X200, 0, 0..=1) => 0x0A + pos,
+            (AdcGain::X10, 2, 2..=3) => 0x0C + pos - 2,
+            (AdcGain::X200, 2, 2..=3) => 0x0E + pos - 2,
+            (AdcGain::X1, 1, 0..=7) => 0x10 + pos,
+            (AdcGain::X1, 2, 0..=5) => 0x18 + pos,
+            _ => unreachable!(),
+        };
+        bank | mux
+    }
+
+    /// Read the difference between two analog inputs, amplified by the gain.
+    /// Only the pairs listed in section 26.8 of the datasheet are available, mainly
+    /// any input against ADC1/ADC9 or ADC2/ADC10 with unity gain.
+    /// # Arguments
+    /// * `positive` - a u8, the analog input connected to the positive side.
+    /// * `negative` - a u8, the analog input connected to the negative side.
+    /// * `gain` - a `AdcGain` object, the gain of the differential amplifier.
+    /// # Returns
+    /// * `a i16` - the signed result, from -512 to 511.
+    pub fn read_differential(&mut self, positive: u8, negative: u8, gain: AdcGain) -> i16 {
+        let mux = Analog::differential_mux(positive, negative, gain);
+
+        self.power_adc_disable(); //PRADC disable to enable ADC
+        self.adc_enable();
+        self.apply_prescaler();
+        self.adc_auto_trig();
+
+        self.admux.update(|admux| {
+            admux.set_bits(0..5, mux & 0x1F);
+        });
+        self.adcsrb.update(|adcsrb| {
+            adcsrb.set_bit(3, mux.get_bit(5));
+        });
+
+        self.apply_reference();
+        self.adc_con_start();
+        while !self.is_done() {
+            __nop();
+        }
+
+        // The result is a 10 bit two's complement number.
+        let raw = self.result() as i16;
+        if raw & 0x200 != 0 {
+            raw - 0x400
+        } else {
+            raw
+        }
+    }
+
+    /// Sets the reference voltage used by all further conversions.
+    /// The first conversion after a change is discarded so that the reference can settle.
+    /// # Arguments
+    /// * `reftype` - a `RefType` object, the reference voltage of the ADC.
+    pub fn set_reference(&mut self, reftype: RefType) {
+        unsafe {
+            ADC_REFERENCE = reftype;
+            REFERENCE_SETTLED = false;
+        }
+    }
+
+    /// Gives the reference voltage used by the conversions.
+    /// # Returns
+    /// * `a RefType object` - the reference voltage of the ADC.
+    pub fn reference(&self) -> RefType {
+        unsafe { ADC_REFERENCE }
+    }
+
+    /// Writes the selected reference to ADMUX, and after a change of reference
+    /// runs one conversion whose result is thrown away.
+    fn apply_reference(&mut self) {
+        let refs: u8 = match unsafe { ADC_REFERENCE } {
+            RefType::DEFAULT => 0b01,
+            RefType::INTERNAL1V1 => 0b10,
+            RefType::INTERNAL2V56 => 0b11,
+            RefType::EXTERNAL => 0b00,
+        };
+        self.admux.update(|admux| {
+            admux.set_bits(6..8, refs);
+        });
+
+        if unsafe { REFERENCE_SETTLED } {
+            return;
+        }
+
+        // The conversion complete interrupt is held back during the dummy conversion.
+        let adie = self.adcsra.read().get_bit(3);
+        self.adcsra.update(|adcsra| {
+            adcsra.set_bit(3, false);
+        });
+        self.adc_con_start();
+        while !self.is_done() {
+            __nop();
+        }
+        self.result();
+        self.adcsra.update(|adcsra| {
+            adcsra.set_bit(4, true);
+            adcsra.set_bit(3, adie);
+        });
+        unsafe {
+            REFERENCE_SETTLED = true;
+        }
+    }
+
+    /// Starts a conversion on a channel and returns without waiting for it.
+    /// Use `is_done()` and `result()` to get the value, or attach a callback
+    /// with `set_conversion_callback()` to get it from the interrupt.
+    /// # Arguments
+    /// * `channel` - a u8, the number of the analog input, 0 to 15.
+    pub fn start_conversion(&mut self, channel: u8) {
+        self.power_adc_disable(); //PRADC disable to enable ADC
+        self.adc_enable();
+        self.apply_prescaler();
+        self.select_channel(channel);
+        self.apply_reference();
+        self.adc_con_start();
+    }
+
+    /// Checks if the conversion started last has completed.
+    /// # Returns
+    /// * `a boolean` - true if the conversion is complete.
+    pub fn is_done(&self) -> bool {
+        !self.adcsra.read().get_bit(6)
+    }
+
+    /// Gives the result of the last completed conversion.
+    /// # Returns
+    /// * `a u16` - the converted value, 10 bits wide.
+    pub fn result(&mut self) -> u16 {
+        // ADCL has to be read first, reading ADCH releases the result registers.
+        let low = self.adcl.read() as u16;
+        let high = self.adch.read() as u16;
+        (high & 0x3) << 8 | low
+    }
+
+    /// Attach a function which is called with the result whenever a conversion completes.
+    /// Global interrupts have to be enabled for the callback to be called.
+    /// # Arguments
+    /// * `callback` - a function, which gets the result of the conversion.
+    pub fn set_conversion_callback(&mut self, callback: fn(u16)) {
+        unsafe {
+            ADC_CALLBACK = Some(callback);
+        }
+        // Clear a pending flag by writing one to ADIF, then set ADIE.
+        self.adcsra.update(|adcsra| {
+            adcsra.set_bit(4, true);
+            adcsra.set_bit(3, true);
+        });
+    }
+
+    /// Detach the conversion complete callback and disable the ADC interrupt.
+    pub fn clear_conversion_callback(&mut self) {
+        self.adcsra.update(|adcsra| {
+            adcsra.set_bit(3, false);
+        });
+        unsafe {
+            ADC_CALLBACK = None;
+        }
+    }
+
+    /// Starts converting the given channels one after the other without stopping.
+    /// The free running mode is not used, as a channel change there only applies one conversion later.
+    /// Instead each conversion is started from the conversion complete interrupt of the
+    /// previous one, and its result is stored for its channel, to be read with `last()`.
+    /// Global interrupts have to be enabled for the scan to run.
+    /// # Arguments
+    /// * `channels` - a slice of u8, the analog inputs to be scanned, at most 16.
+    /// # Returns
+    /// * `a Result` - a `ScanError` if the channel list can not be scanned.
+    pub fn start_scan(&mut self, channels: &[u8]) -> Result<(), ScanError> {
+        if channels.is_empty() || channels.len() > 16 {
+            return Err(ScanError::ChannelCount);
+        }
+        if channels.iter().any(|channel| *channel >= 16) {
+            return Err(ScanError::InvalidChannel);
+        }
+        unsafe {
+            for (i, channel) in channels.iter().enumerate() {
+                SCAN_CHANNELS[i] = *channel;
+            }
+            SCAN_LEN = channels.len();
+            SCAN_INDEX = 0;
+        }
+        self.adc_auto_trig();
+        self.adcsra.update(|adcsra| {
+            adcsra.set_bit(4, true);
+            adcsra.set_bit(3, true);
+        });
+        self.start_conversion(channels[0]);
+        Ok(())
+    }
+
+    /// Stops the scan started by `start_scan()`, the last results are kept.
+    pub fn stop_scan(&mut self) {
+        unsafe {
+            SCAN_LEN = 0;
+        }
+        if unsafe { ADC_CALLBACK.is_none() } {
+            self.adcsra.update(|adcsra| {
+                adcsra.set_bit(3, false);
+            });
+        }
+    }
+
+    /// Gives the latest result of a channel converted by the scan.
+    /// # Arguments
+    /// * `channel` - a u8, the number of the analog input.
+    /// # Returns
+    /// * `a u16` - the last converted value of the channel, 10 bits wide.
+    pub fn last(&self, channel: u8) -> u16 {
+        unsafe { SCAN_RESULTS[channel as usize] }
+    }
+
+    /// Stores the result of a scan conversion and starts the next one.
+    #[cfg_attr(not(target_arch = "avr"), allow(dead_code))]
+    fn scan_next(&mut self, value: u16) {
+        unsafe {
+            if SCAN_LEN == 0 {
+                return;
+            }
+            SCAN_RESULTS[SCAN_CHANNELS[SCAN_INDEX] as usize] = value;
+            SCAN_INDEX = (SCAN_INDEX + 1) % SCAN_LEN;
+            self.select_channel(SCAN_CHANNELS[SCAN_INDEX]);
+        }
+        self.adc_con_start();
+    }
+}
+
+/// Reference voltage used by the conversions.
+static mut ADC_REFERENCE: RefType = RefType::DEFAULT;
+
+/// Cleared when the reference changes, so the next conversion is thrown away.
+static mut REFERENCE_SETTLED: bool = false;
+
+/// Division factor of the ADC clock chosen by the user, None for automatic selection.
+static mut ADC_PRESCALER: Option<u8> = None;
+
+/// Function called with the result of every completed conversion.
+static mut ADC_CALLBACK: Option<fn(u16)> = None;
+
+/// Channels converted by the scan, in order.
+static mut SCAN_CHANNELS: [u8; 16] = [0; 16];
+
+/// Number of channels in the scan, 0 when no scan is running.
+static mut SCAN_LEN: usize = 0;
+
+/// Position in `SCAN_CHANNELS` of the conversion going on.
+static mut SCAN_INDEX: usize = 0;
+
+/// Latest result of each channel converted by the scan.
+static mut SCAN_RESULTS: [u16; 16] = [0; 16];
+
+/// Interrupt vector of ADC conversion complete.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_29() {
+    let analog = Analog::new();
+    let value = analog.result();
+    analog.scan_next(value);
+    if let Some(callback) = ADC_CALLBACK {
+        callback(value);
+    }
 }
 
 /// Function to create a reference for Analog signals.
+/// The reference is applied by the next conversion, after one dummy conversion to let it settle.
 /// # Arguments
 /// * `reftype` - a `RefType` object, the type of reference setup required for the analog pins.
 pub fn analog_reference(reftype: RefType) {
-    let analog = unsafe { Analog::new() };
-    match reftype {
-        RefType::DEFAULT => {
-            analog.admux.update(|admux| {
-                admux.set_bits(6..8, 0b01);
-            });
+    unsafe { Analog::new() }.set_reference(reftype);
+}
+
+/// Timer driving a PWM output.
+#[cfg(feature = "embedded")]
+#[derive(Clone, Copy)]
+enum PwmTimer {
+    Timer8(TimerNo8),
+    Timer16(TimerNo16),
+}
+
+/// A PWM output for the drivers written against the `embedded-hal` PWM trait,
+/// made from a digital pin with `DigitalPin::into_pwm()`.
+/// The duty cycle goes from 0 to `get_max_duty()`, which is 255 on the 8 bit timers
+/// and the TOP of the timer on the 16 bit timers. The frequency and waveform set
+/// on the timer before are kept, and are shared with its other channels.
+#[cfg(feature = "embedded")]
+pub struct PwmChannel {
+    pin: DigitalPin,
+    timer: PwmTimer,
+    channel: TimerChannel,
+    duty: u16,
+}
+
+#[cfg(feature = "embedded")]
+impl DigitalPin {
+    /// Turns a pin driven by a timer into a PWM output, which starts disabled with the pin low.
+    /// Only 2-13 and 44-46 digital pins can be used, other pins will lead to crash.
+    /// If the timer was stopped the 8 bit timers run fast PWM at Div64,
+    /// and the 16 bit timers 16 bit fast PWM at the CPU clock, as in `write()` and `write16()`.
+    /// # Returns
+    /// * `a PwmChannel object` - the PWM output.
+    pub fn into_pwm(mut self) -> PwmChannel {
+        let (timer, channel) = match self.pinno {
+            13 => (PwmTimer::Timer8(TimerNo8::Timer0), TimerChannel::A),
+            4 => (PwmTimer::Timer8(TimerNo8::Timer0), TimerChannel::B),
+            10 => (PwmTimer::Timer8(TimerNo8::Timer2), TimerChannel::A),
+            9 => (PwmTimer::Timer8(TimerNo8::Timer2), TimerChannel::B),
+            11 => (PwmTimer::Timer16(TimerNo16::Timer1), TimerChannel::A),
+            12 => (PwmTimer::Timer16(TimerNo16::Timer1), TimerChannel::B),
+            5 => (PwmTimer::Timer16(TimerNo16::Timer3), TimerChannel::A),
+            2 => (PwmTimer::Timer16(TimerNo16::Timer3), TimerChannel::B),
+            3 => (PwmTimer::Timer16(TimerNo16::Timer3), TimerChannel::C),
+            6 => (PwmTimer::Timer16(TimerNo16::Timer4), TimerChannel::A),
+            7 => (PwmTimer::Timer16(TimerNo16::Timer4), TimerChannel::B),
+            8 => (PwmTimer::Timer16(TimerNo16::Timer4), TimerChannel::C),
+            46 => (PwmTimer::Timer16(TimerNo16::Timer5), TimerChannel::A),
+            45 => (PwmTimer::Timer16(TimerNo16::Timer5), TimerChannel::B),
+            44 => (PwmTimer::Timer16(TimerNo16::Timer5), TimerChannel::C),
+            _ => unreachable!(),
+        };
+        self.set_output();
+        self.low();
+        match timer {
+            PwmTimer::Timer8(timer_no) => {
+                let peripheral = match timer_no {
+                    TimerNo8::Timer0 => Peripheral::Timer0,
+                    TimerNo8::Timer2 => Peripheral::Timer2,
+                };
+                unsafe { Power::new() }.enable(peripheral);
+                let timer = unsafe { Timer8::new(timer_no) };
+                if let WaveformMode::Normal = timer.waveform_mode() {
+                    let _ = timer.set_waveform_mode(WaveformMode::FastPwm);
+                }
+                if timer.prescaler().is_none() {
+                    let _ = timer.set_prescaler(TimerPrescaler::Div64);
+                }
+            }
+            PwmTimer::Timer16(timer_no) => {
+                let peripheral = match timer_no {
+                    TimerNo16::Timer1 => Peripheral::Timer1,
+                    TimerNo16::Timer3 => Peripheral::Timer3,
+                    TimerNo16::Timer4 => Peripheral::Timer4,
+                    TimerNo16::Timer5 => Peripheral::Timer5,
+                };
+                unsafe { Power::new() }.enable(peripheral);
+                let timer = unsafe { Timer16::new(timer_no) };
+                if !timer.icr_top() {
+                    timer.set_pwm_top(0xFFFF);
+                }
+                if timer.prescaler().is_none() {
+                    let _ = timer.set_prescaler(TimerPrescaler::Div1);
+                }
+            }
         }
-        RefType::INTERNAL1V1 => {
-            analog.admux.update(|admux| {
-                admux.set_bits(6..8, 0b10);
-            });
+        PwmChannel {
+            pin: self,
+            timer,
+            channel,
+            duty: 0,
         }
-        RefType::INTERNAL2V56 => {
-            analog.admux.update(|admux| {
-                admux.set_bits(6..8, 0b11);
-            });
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl PwmChannel {
+    /// Sets the compare output mode bits of the channel,
+    /// 0b10 connects the pin for non-inverting PWM and 0b00 gives it back to the port.
+    fn set_output_mode(&mut self, mode: u8) {
+        let bits = match self.channel {
+            TimerChannel::A => 6..8,
+            TimerChannel::B => 4..6,
+            TimerChannel::C => 2..4,
+        };
+        match self.timer {
+            PwmTimer::Timer8(timer_no) => unsafe { Timer8::new(timer_no) }.tccra.update(|ctrl| {
+                ctrl.set_bits(bits, mode);
+            }),
+            PwmTimer::Timer16(timer_no) => unsafe { Timer16::new(timer_no) }.tccra.update(|ctrl| {
+                ctrl.set_bits(bits, mode);
+            }),
         }
-        RefType::EXTERNAL => {
-            analog.admux.update(|admux| {
-                admux.set_bits(6..8, 0b00);
-            });
+    }
+
+    /// Gives back the pin, which is disconnected from the timer and left low.
+    /// # Returns
+    /// * `a DigitalPin object` - the pin.
+    pub fn release(mut self) -> DigitalPin {
+        self.set_output_mode(0b00);
+        self.pin
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::PwmPin for PwmChannel {
+    type Duty = u16;
+
+    /// Gives the pin back to the port, where it is driven low.
+    fn disable(&mut self) {
+        self.set_output_mode(0b00);
+    }
+
+    fn enable(&mut self) {
+        self.set_output_mode(0b10);
+    }
+
+    fn get_duty(&self) -> Self::Duty {
+        self.duty
+    }
+
+    fn get_max_duty(&self) -> Self::Duty {
+        match self.timer {
+            PwmTimer::Timer8(_) => 0xFF,
+            PwmTimer::Timer16(timer_no) => unsafe { Timer16::new(timer_no) }.top() as u16,
+        }
+    }
+
+    /// Sets the duty cycle, values above the maximum give the maximum.
+    fn set_duty(&mut self, duty: Self::Duty) {
+        self.duty = duty.min(self.get_max_duty());
+        match self.timer {
+            PwmTimer::Timer8(timer_no) => {
+                let timer = unsafe { Timer8::new(timer_no) };
+                match self.channel {
+                    TimerChannel::A => timer.ocra.write(self.duty as u8),
+                    _ => timer.ocrb.write(self.duty as u8),
+                }
+            }
+            PwmTimer::Timer16(timer_no) => {
+                unsafe { Timer16::new(timer_no) }.write_compare(self.channel, self.duty);
+            }
+        }
+    }
+}
+
+/// Makes the types of the analog inputs for the `embedded-hal` ADC traits,
+/// which need the channel of a pin to be known from its type.
+#[cfg(feature = "embedded")]
+macro_rules! adc_channels {
+    ($($name:ident: $channel:expr),*) => {
+        $(
+            /// Analog input for `embedded_hal::adc::OneShot`, its pin has to be an input without pull-up.
+            pub struct $name;
+
+            impl embedded_hal::adc::Channel<Analog> for $name {
+                type ID = u8;
+
+                fn channel() -> u8 {
+                    $channel
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "embedded")]
+adc_channels!(
+    A0: 0, A1: 1, A2: 2, A3: 3, A4: 4, A5: 5, A6: 6, A7: 7,
+    A8: 8, A9: 9, A10: 10, A11: 11, A12: 12, A13: 13, A14: 14, A15: 15
+);
+
+/// The ADC for the drivers written against the `embedded-hal` ADC trait.
+/// The conversion is started and waited for in one call, with the prescaler and
+/// reference chosen for the ADC, and the 10 bit result is given.
+#[cfg(feature = "embedded")]
+impl<PIN> embedded_hal::adc::OneShot<Analog, u16, PIN> for Analog
+where
+    PIN: embedded_hal::adc::Channel<Analog, ID = u8>,
+{
+    type Error = core::convert::Infallible;
+
+    fn read(&mut self, _pin: &mut PIN) -> nb::Result<u16, Self::Error> {
+        self.adc_auto_trig();
+        self.start_conversion(PIN::channel());
+        // Wait for the conversion to complete, ADSC is cleared by hardware.
+        while !self.is_done() {
+            __nop();
         }
+        Ok(self.result())
     }
 }
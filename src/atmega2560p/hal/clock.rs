@@ -0,0 +1,112 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Runtime control of the system clock prescaler of the ATMEGA2560P chip.
+//! Slowing down the core between events reduces the power consumption.
+//! Section 10.12 and 10.13.2 of ATmega2560P datasheet.
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+// Source codes to be used here.
+use crate::config;
+
+/// Address of the SREG register, used to keep interrupts away from the timed sequence.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+/// Division factor of the system clock.
+#[derive(Clone, Copy)]
+pub enum ClockPrescaler {
+    Div1,
+    Div2,
+    Div4,
+    Div8,
+    Div16,
+    Div32,
+    Div64,
+    Div128,
+    Div256,
+}
+
+impl ClockPrescaler {
+    /// Gives the CLKPS3:0 bits of the prescaler, which is also the power of two it divides by.
+    fn bits(&self) -> u8 {
+        match self {
+            ClockPrescaler::Div1 => 0,
+            ClockPrescaler::Div2 => 1,
+            ClockPrescaler::Div4 => 2,
+            ClockPrescaler::Div8 => 3,
+            ClockPrescaler::Div16 => 4,
+            ClockPrescaler::Div32 => 5,
+            ClockPrescaler::Div64 => 6,
+            ClockPrescaler::Div128 => 7,
+            ClockPrescaler::Div256 => 8,
+        }
+    }
+}
+
+/// Contains the clock prescale register.
+///
+/// * `CLKPR`: *Clock Prescale Register*. CLKPCE (bit 7) has to be written to one
+/// alone, and the CLKPS3:0 bits then have to be written within four cycles.
+#[repr(C, packed)]
+pub struct Clock {
+    clkpr: u8,
+}
+
+impl Clock {
+    /// Creates a new reference to the Clock structure at a specified location.
+    /// # Returns
+    /// * `a reference to Clock object` - which will be used for further implementations.
+    pub unsafe fn new() -> &'static mut Clock {
+        &mut *(0x61 as *mut Clock)
+    }
+
+    /// Changes the system clock prescaler using the timed CLKPCE sequence.
+    /// The new effective frequency is given by `config::cpu_frequency_hz()`, which
+    /// the delay functions follow at once. A USART which is already running keeps
+    /// its old baud register, so it has to be initialized again after the change.
+    /// # Arguments
+    /// * `prescaler` - a `ClockPrescaler` object, the division factor of the system clock.
+    pub fn set_prescaler(&mut self, prescaler: ClockPrescaler) {
+        unsafe {
+            // Keep interrupts away so that the second write is within four cycles.
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+
+            write_volatile(&mut self.clkpr, 0x80);
+            write_volatile(&mut self.clkpr, prescaler.bits());
+
+            write_volatile(SREG, sreg);
+        }
+        config::set_clock_division(prescaler.bits());
+    }
+
+    /// Gives the present division factor of the system clock.
+    /// # Returns
+    /// * `a u16` - the factor by which the system clock is divided.
+    pub fn division(&self) -> u16 {
+        let clkps = unsafe { read_volatile(&self.clkpr) } & 0x0F;
+        1 << clkps
+    }
+}
+
+/// Changes the system clock prescaler.
+/// # Arguments
+/// * `prescaler` - a `ClockPrescaler` object, the division factor of the system clock.
+pub fn set_clock_prescaler(prescaler: ClockPrescaler) {
+    unsafe { Clock::new() }.set_prescaler(prescaler);
+}
@@ -0,0 +1,141 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Reading and writing of the EEPROM of the ATMEGA2560P chip, which keeps its data when
+//! the power is off, such as settings and calibrations. A byte is written in about
+//! 3.4 ms and lasts for about 100000 writes, so the update functions only write the
+//! bytes which change.
+//! Section 8 of ATmega2560P datasheet.
+
+// Crates to be used for the implementation.
+use bit_field::BitField;
+use volatile::Volatile;
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+/// Size of the EEPROM in bytes.
+pub const EEPROM_SIZE: usize = 4096;
+
+/// Address of the SREG register, used to keep interrupts away from the timed sequence.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+/// Contains the registers controlling the EEPROM.
+///
+/// * `EECR`: *EEPROM Control Register*. EERE starts a read, EEPE starts a write and
+/// stays set until it is complete, and EEMPE has to be set at most four cycles before
+/// EEPE. EEPM selects erasing and writing in one operation when it is zero.
+///
+/// * `EEDR`: *EEPROM Data Register*. Holds the byte to be written or the byte read.
+///
+/// * `EEARL` and `EEARH`: *EEPROM Address Registers*. Hold the address of the byte.
+#[repr(C, packed)]
+pub struct Eeprom {
+    eecr: Volatile<u8>,
+    eedr: Volatile<u8>,
+    eearl: Volatile<u8>,
+    eearh: Volatile<u8>,
+}
+
+impl Eeprom {
+    /// Creates a new reference to the Eeprom structure at a specified location.
+    /// # Returns
+    /// * `a reference to Eeprom object` - which will be used for further implementations.
+    pub unsafe fn new() -> &'static mut Eeprom {
+        &mut *(0x3F as *mut Eeprom)
+    }
+
+    /// Checks if a write is still going on.
+    /// # Returns
+    /// * `a boolean` - true if the EEPROM is busy.
+    pub fn is_busy(&self) -> bool {
+        self.eecr.read().get_bit(1)
+    }
+
+    /// Reads one byte, after the write going on if any.
+    /// # Arguments
+    /// * `address` - a u16, the address of the byte, below `EEPROM_SIZE`.
+    /// # Returns
+    /// * `a u8` - the byte, 0xFF if it was never written.
+    pub fn read_byte(&mut self, address: u16) -> u8 {
+        self.select(address);
+        self.eecr.update(|eecr| {
+            eecr.set_bit(0, true);
+        });
+        self.eedr.read()
+    }
+
+    /// Erases and writes one byte with interrupts held off for the timed sequence.
+    /// The write goes on in the background for about 3.4 ms.
+    /// # Arguments
+    /// * `address` - a u16, the address of the byte, below `EEPROM_SIZE`.
+    /// * `value` - a u8, the byte to be written.
+    pub fn write_byte(&mut self, address: u16, value: u8) {
+        self.select(address);
+        self.eedr.write(value);
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            llvm_asm!("sbi 0x1F, 2
+                       sbi 0x1F, 1"
+                     :::: "volatile");
+            write_volatile(SREG, sreg);
+        }
+    }
+
+    /// Writes one byte only if it is different from the one stored.
+    /// # Arguments
+    /// * `address` - a u16, the address of the byte, below `EEPROM_SIZE`.
+    /// * `value` - a u8, the byte to be stored.
+    pub fn update_byte(&mut self, address: u16, value: u8) {
+        if self.read_byte(address) != value {
+            self.write_byte(address, value);
+        }
+    }
+
+    /// Reads bytes starting from an address.
+    /// # Arguments
+    /// * `address` - a u16, the address of the first byte.
+    /// * `buffer` - a u8 slice, which is filled with the bytes, it has to fit in the EEPROM.
+    pub fn read(&mut self, address: u16, buffer: &mut [u8]) {
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_byte(address + i as u16);
+        }
+    }
+
+    /// Stores bytes starting from an address, writing only the ones which change.
+    /// # Arguments
+    /// * `address` - a u16, the address of the first byte.
+    /// * `data` - a u8 slice, the bytes to be stored, they have to fit in the EEPROM.
+    pub fn update(&mut self, address: u16, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            self.update_byte(address + i as u16, *byte);
+        }
+    }
+
+    /// Waits for the write going on and sets the address, with erase and write in one operation.
+    fn select(&mut self, address: u16) {
+        if address as usize >= EEPROM_SIZE {
+            unreachable!()
+        }
+        while self.is_busy() {}
+        self.eearh.write((address >> 8) as u8);
+        self.eearl.write(address as u8);
+        self.eecr.update(|eecr| {
+            eecr.set_bits(4..6, 0);
+        });
+    }
+}
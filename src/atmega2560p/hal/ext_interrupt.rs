@@ -0,0 +1,376 @@
+// RustDuino : A generic HAL implementation for Arduino Boards in Rust
+// Copyright (C) 2021 Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! External interrupts INT0 to INT7 of the ATMEGA2560P chip are controlled here.
+//! A handler function can be attached to each line which is called from the
+//! interrupt vector whenever the selected trigger occurs on the pin.
+//! The pin change interrupts are handled here as well, with one handler
+//! for each group of eight pins.
+//! Section 15 of ATMEGA2560P datasheet.
+
+// Crates to be used for the implementation.
+use bit_field::BitField;
+use volatile::Volatile;
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+// Source codes to be used here.
+use crate::atmega2560p::hal::interrupts;
+use crate::atmega2560p::hal::port::PortName;
+
+/// Selection of the external interrupt line.
+/// `Int0` to `Int3` are on digital pins 21, 20, 19 and 18 respectively,
+/// `Int4` and `Int5` are on digital pins 2 and 3 while `Int6` and `Int7`
+/// are on PE6 and PE7 which are not available on the Arduino Mega headers.
+#[derive(Clone, Copy)]
+pub enum IntLine {
+    Int0,
+    Int1,
+    Int2,
+    Int3,
+    Int4,
+    Int5,
+    Int6,
+    Int7,
+}
+
+/// Selection of the event on the pin which triggers the interrupt.
+/// * `LowLevel` - The interrupt is triggered as long as the pin is low.
+/// * `Change` - Any logical change on the pin triggers the interrupt.
+/// * `Falling` - The falling edge on the pin triggers the interrupt.
+/// * `Rising` - The rising edge on the pin triggers the interrupt.
+#[derive(Clone, Copy)]
+pub enum Trigger {
+    LowLevel,
+    Change,
+    Falling,
+    Rising,
+}
+
+/// Contains the registers controlling the external interrupts.
+///
+/// * `EIFR`: *External Interrupt Flag Register*. The flag of a line is set when
+/// its trigger occurs and cleared when the interrupt routine is executed.
+///
+/// * `EIMSK`: *External Interrupt Mask Register*. Writing one to the bit of a line
+/// enables the interrupt request of that line.
+///
+/// * `EICRA` and `EICRB`: *External Interrupt Control Registers*. Two bits for each line
+/// select the event which triggers the interrupt, EICRA controls `Int0` to `Int3`
+/// and EICRB controls `Int4` to `Int7`.
+#[repr(C, packed)]
+pub struct ExtInterrupt {
+    eifr: Volatile<u8>,
+    eimsk: Volatile<u8>,
+    _pad: [u8; 0x2B],
+    eicra: Volatile<u8>,
+    eicrb: Volatile<u8>,
+}
+
+/// Handler functions attached to each external interrupt line.
+static mut HANDLERS: [Option<fn()>; 8] = [None; 8];
+
+/// Address of PCICR, the pin change interrupt control register.
+const PCICR: *mut u8 = 0x68 as *mut u8;
+
+/// Address of PCMSK0, followed by PCMSK1 and PCMSK2.
+const PCMSK0: *mut u8 = 0x6B as *mut u8;
+
+/// Address of PCIFR, the pin change interrupt flag register.
+const PCIFR: *mut u8 = 0x3B as *mut u8;
+
+/// Handler functions attached to each pin change group.
+static mut PIN_CHANGE_HANDLERS: [Option<fn()>; 3] = [None; 3];
+
+impl IntLine {
+    /// Gives the bit of the line in the EIMSK and EIFR registers.
+    fn index(&self) -> u8 {
+        match self {
+            IntLine::Int0 => 0,
+            IntLine::Int1 => 1,
+            IntLine::Int2 => 2,
+            IntLine::Int3 => 3,
+            IntLine::Int4 => 4,
+            IntLine::Int5 => 5,
+            IntLine::Int6 => 6,
+            IntLine::Int7 => 7,
+        }
+    }
+}
+
+impl ExtInterrupt {
+    /// Creates a new reference to the External Interrupt structure at a specified location.
+    /// # Returns
+    /// * `a reference to ExtInterrupt object` - which will be used for further implementations.
+    pub unsafe fn new() -> &'static mut ExtInterrupt {
+        &mut *(0x3C as *mut ExtInterrupt)
+    }
+
+    /// Set the event which triggers the interrupt of the line.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be configured.
+    /// * `trigger` - a `Trigger` object, the event on the pin which triggers the interrupt.
+    pub fn set_trigger(&mut self, line: IntLine, trigger: Trigger) {
+        let bits: u8 = match trigger {
+            Trigger::LowLevel => 0b00,
+            Trigger::Change => 0b01,
+            Trigger::Falling => 0b10,
+            Trigger::Rising => 0b11,
+        };
+        let index = line.index();
+        if index < 4 {
+            let pos = 2 * index;
+            self.eicra.update(|eicra| {
+                eicra.set_bits(pos..pos + 2, bits);
+            });
+        } else {
+            let pos = 2 * (index - 4);
+            self.eicrb.update(|eicrb| {
+                eicrb.set_bits(pos..pos + 2, bits);
+            });
+        }
+    }
+
+    /// Enable the interrupt request of the line.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be enabled.
+    pub fn enable(&mut self, line: IntLine) {
+        self.eimsk.update(|eimsk| {
+            eimsk.set_bit(line.index(), true);
+        });
+    }
+
+    /// Disable the interrupt request of the line.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be disabled.
+    pub fn disable(&mut self, line: IntLine) {
+        self.eimsk.update(|eimsk| {
+            eimsk.set_bit(line.index(), false);
+        });
+    }
+
+    /// Clears a pending interrupt of the line by writing one to its flag.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line whose flag is to be cleared.
+    pub fn clear_flag(&mut self, line: IntLine) {
+        self.eifr.write(1 << line.index());
+    }
+
+    /// Attach a handler function to the line which will be called on every trigger.
+    /// Global interrupts have to be enabled for the handler to be called.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be used.
+    /// * `trigger` - a `Trigger` object, the event on the pin which triggers the interrupt.
+    /// * `handler` - a function, which is called from the interrupt vector of the line.
+    pub fn attach(&mut self, line: IntLine, trigger: Trigger, handler: fn()) {
+        interrupts::free(|_| {
+            self.disable(line);
+            unsafe {
+                HANDLERS[line.index() as usize] = Some(handler);
+            }
+            self.set_trigger(line, trigger);
+            self.clear_flag(line);
+            self.enable(line);
+        });
+    }
+
+    /// Detach the handler function of the line and disable its interrupt.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be released.
+    pub fn detach(&mut self, line: IntLine) {
+        self.disable(line);
+        unsafe {
+            HANDLERS[line.index() as usize] = None;
+        }
+    }
+}
+
+/// Attach a handler function to a external interrupt line.
+/// # Arguments
+/// * `line` - a `IntLine` object, the external interrupt line to be used.
+/// * `trigger` - a `Trigger` object, the event on the pin which triggers the interrupt.
+/// * `handler` - a function, which is called from the interrupt vector of the line.
+pub fn attach_interrupt(line: IntLine, trigger: Trigger, handler: fn()) {
+    unsafe { ExtInterrupt::new() }.attach(line, trigger, handler);
+}
+
+/// Detach the handler function of a external interrupt line.
+/// # Arguments
+/// * `line` - a `IntLine` object, the external interrupt line to be released.
+pub fn detach_interrupt(line: IntLine) {
+    unsafe { ExtInterrupt::new() }.detach(line);
+}
+
+/// Calls the handler attached to the line, if any.
+#[cfg_attr(not(target_arch = "avr"), allow(dead_code))]
+fn dispatch(index: usize) {
+    if let Some(handler) = unsafe { HANDLERS[index] } {
+        handler();
+    }
+}
+
+/// Interrupt vector of INT0.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_1() {
+    dispatch(0);
+}
+
+/// Interrupt vector of INT1.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_2() {
+    dispatch(1);
+}
+
+/// Interrupt vector of INT2.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_3() {
+    dispatch(2);
+}
+
+/// Interrupt vector of INT3.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_4() {
+    dispatch(3);
+}
+
+/// Interrupt vector of INT4.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_5() {
+    dispatch(4);
+}
+
+/// Interrupt vector of INT5.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_6() {
+    dispatch(5);
+}
+
+/// Interrupt vector of INT6.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_7() {
+    dispatch(6);
+}
+
+/// Interrupt vector of INT7.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_8() {
+    dispatch(7);
+}
+
+/// Gives the port and the bit of a digital pin with a pin change interrupt.
+/// These are digital pins 10 to 13 and 50 to 53 on port B, pin 0 on PE0,
+/// pins 15 and 14 on PJ0 and PJ1, and the analog pins A8 to A15, that is
+/// digital pins 62 to 69, on port K.
+/// # Arguments
+/// * `pin` - a usize, the number of the digital pin.
+/// # Returns
+/// * `a tuple (PortName, u8)` - the port and the bit of the pin.
+pub(crate) fn pin_change_port(pin: usize) -> (PortName, u8) {
+    match pin {
+        10..=13 => (PortName::B, (pin - 6) as u8),
+        50..=53 => (PortName::B, (53 - pin) as u8),
+        0 => (PortName::E, 0),
+        15 => (PortName::J, 0),
+        14 => (PortName::J, 1),
+        62..=69 => (PortName::K, (pin - 62) as u8),
+        _ => unreachable!(),
+    }
+}
+
+/// Gives the pin change group and the bit of the pin in its PCMSK register.
+/// Port B is PCINT0 to PCINT7, PE0 is PCINT8 followed by PJ0 to PJ6,
+/// and port K is PCINT16 to PCINT23.
+fn pin_change_mask(pin: usize) -> (usize, u8) {
+    match pin_change_port(pin) {
+        (PortName::B, bit) => (0, bit),
+        (PortName::E, _) => (1, 0),
+        (PortName::J, bit) => (1, bit + 1),
+        (_, bit) => (2, bit),
+    }
+}
+
+/// Attach a handler function to the pin change interrupt of a pin.
+/// The handler is called on every change of any enabled pin of the group, so
+/// pins of the same group share one handler and attaching replaces it.
+/// Global interrupts have to be enabled for the handler to be called.
+/// # Arguments
+/// * `pin` - a usize, the number of the digital pin which has a pin change interrupt.
+/// * `handler` - a function, which is called from the interrupt vector of the group.
+pub fn attach_pin_change(pin: usize, handler: fn()) {
+    let (group, bit) = pin_change_mask(pin);
+    interrupts::free(|_| unsafe {
+        PIN_CHANGE_HANDLERS[group] = Some(handler);
+        let pcmsk = PCMSK0.add(group);
+        write_volatile(pcmsk, read_volatile(pcmsk) | (1 << bit));
+        // The flag is cleared by writing one to it.
+        write_volatile(PCIFR, 1 << group);
+        write_volatile(PCICR, read_volatile(PCICR) | (1 << group));
+    });
+}
+
+/// Disable the pin change interrupt of a pin.
+/// The handler of the group is detached when no pin of the group is left enabled.
+/// # Arguments
+/// * `pin` - a usize, the number of the digital pin which has a pin change interrupt.
+pub fn detach_pin_change(pin: usize) {
+    let (group, bit) = pin_change_mask(pin);
+    unsafe {
+        let pcmsk = PCMSK0.add(group);
+        write_volatile(pcmsk, read_volatile(pcmsk) & !(1 << bit));
+        if read_volatile(pcmsk) == 0 {
+            write_volatile(PCICR, read_volatile(PCICR) & !(1 << group));
+            PIN_CHANGE_HANDLERS[group] = None;
+        }
+    }
+}
+
+/// Calls the handler attached to the pin change group, if any.
+#[cfg_attr(not(target_arch = "avr"), allow(dead_code))]
+fn dispatch_pin_change(group: usize) {
+    if let Some(handler) = unsafe { PIN_CHANGE_HANDLERS[group] } {
+        handler();
+    }
+}
+
+/// Interrupt vector of pin change group 0.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_9() {
+    dispatch_pin_change(0);
+}
+
+/// Interrupt vector of pin change group 1.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_10() {
+    dispatch_pin_change(1);
+}
+
+/// Interrupt vector of pin change group 2.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_11() {
+    dispatch_pin_change(2);
+}
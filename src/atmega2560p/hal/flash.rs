@@ -0,0 +1,243 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Self-programming of the flash memory of the ATMEGA2560P chip with the SPM instruction.
+//! A page is written by erasing it, filling the temporary page buffer word by word
+//! and then writing the buffer to the page.
+//! The SPM instruction only works when it is executed from the boot loader section,
+//! so code using this module has to be placed there and the BOOTSZ fuses set to match.
+//! The fuse and lock bits can be read from anywhere.
+//! Section 29 of ATmega2560P datasheet.
+
+// Crates to be used for the implementation.
+use bit_field::BitField;
+use volatile::Volatile;
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+/// Size of one flash page in bytes.
+pub const SPM_PAGESIZE: usize = 256;
+
+/// Address of the SREG register, used to keep interrupts away from the timed sequence.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+/// Address of the RAMPZ register, which holds bit 16 and up of the flash address.
+const RAMPZ: *mut u8 = 0x5B as *mut u8;
+
+/// Address of the EECR register, flash can not be written while the EEPROM is written.
+const EECR: *mut u8 = 0x3F as *mut u8;
+
+// Commands written to SPMCSR just before SPM.
+const SPMEN: u8 = 1 << 0;
+const PGERS: u8 = 1 << 1;
+const PGWRT: u8 = 1 << 2;
+const BLBSET: u8 = 1 << 3;
+const RWWSRE: u8 = 1 << 4;
+
+/// Selection of the fuse or lock byte to be read.
+#[derive(Clone, Copy)]
+pub enum Fuse {
+    Low,
+    High,
+    Extended,
+    Lock,
+}
+
+impl Fuse {
+    /// Gives the value of the Z pointer which selects the byte.
+    fn address(&self) -> u16 {
+        match self {
+            Fuse::Low => 0x0000,
+            Fuse::Lock => 0x0001,
+            Fuse::Extended => 0x0002,
+            Fuse::High => 0x0003,
+        }
+    }
+}
+
+/// All fuse and lock bytes of the chip.
+/// A programmed bit reads as zero.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FuseBytes {
+    pub low: u8,
+    pub high: u8,
+    pub extended: u8,
+    pub lock: u8,
+}
+
+/// Contains the register controlling self-programming.
+///
+/// * `SPMCSR`: *Store Program Memory Control and Status Register*. The command
+/// is written here and SPM has to follow within four cycles. SPMEN stays set
+/// until the operation is complete and RWWSB is set while the read-while-write
+/// section is busy or not yet re-enabled.
+#[repr(C, packed)]
+pub struct Flash {
+    spmcsr: Volatile<u8>,
+}
+
+impl Flash {
+    /// Creates a new reference to the Flash structure at a specified location.
+    /// # Returns
+    /// * `a reference to Flash object` - which will be used for further implementations.
+    pub unsafe fn new() -> &'static mut Flash {
+        &mut *(0x57 as *mut Flash)
+    }
+
+    /// Checks if the last SPM operation is still going on.
+    /// # Returns
+    /// * `a boolean` - true if the flash is busy.
+    pub fn is_busy(&self) -> bool {
+        self.spmcsr.read().get_bit(0)
+    }
+
+    /// Checks if the read-while-write section is blocked after a write.
+    /// # Returns
+    /// * `a boolean` - true if `rww_enable()` has to be called before reading it.
+    pub fn is_rww_busy(&self) -> bool {
+        self.spmcsr.read().get_bit(6)
+    }
+
+    /// Waits for the flash and the EEPROM to finish their present operation.
+    fn wait(&self) {
+        while self.is_busy() {}
+        while unsafe { read_volatile(EECR) } & (1 << 1) != 0 {}
+    }
+
+    /// Runs one SPM command with interrupts held off.
+    /// # Arguments
+    /// * `command` - a u8, the value written to SPMCSR.
+    /// * `address` - a u32, the byte address placed in RAMPZ and the Z pointer.
+    /// * `word` - a u16, the data placed in r1:r0, used by the page fill command.
+    #[inline(never)]
+    fn spm(&mut self, command: u8, address: u32, word: u16) {
+        self.wait();
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            let rampz = read_volatile(RAMPZ);
+            write_volatile(RAMPZ, (address >> 16) as u8);
+            llvm_asm!("movw r0, $2
+                       out 0x37, $0
+                       spm
+                       clr r1"
+                     :
+                     : "r" (command), "z" (address as u16), "r" (word)
+                     : "r0", "r1"
+                     : "volatile");
+            write_volatile(RAMPZ, rampz);
+            write_volatile(SREG, sreg);
+        }
+        self.wait();
+    }
+
+    /// Erases the page containing the address, all its bytes become 0xFF.
+    /// # Arguments
+    /// * `address` - a u32, any byte address inside the page.
+    pub fn page_erase(&mut self, address: u32) {
+        self.spm(PGERS | SPMEN, address, 0);
+    }
+
+    /// Writes one word into the temporary page buffer.
+    /// # Arguments
+    /// * `address` - a u32, the byte address of the word, only its position in the page is used.
+    /// * `word` - a u16, the data to be stored, lower byte at the lower address.
+    pub fn page_fill(&mut self, address: u32, word: u16) {
+        if address % 2 != 0 {
+            unreachable!()
+        }
+        self.spm(SPMEN, address, word);
+    }
+
+    /// Writes the temporary page buffer to the page containing the address.
+    /// The page has to be erased first.
+    /// # Arguments
+    /// * `address` - a u32, any byte address inside the page.
+    pub fn page_write(&mut self, address: u32) {
+        self.spm(PGWRT | SPMEN, address, 0);
+    }
+
+    /// Enables reading of the read-while-write section again after an erase or write.
+    pub fn rww_enable(&mut self) {
+        self.spm(RWWSRE | SPMEN, 0, 0);
+    }
+
+    /// Erases and writes a whole page and enables the read-while-write section again.
+    /// # Arguments
+    /// * `address` - a u32, the byte address of the start of the page.
+    /// * `data` - a u8 array, the contents of the page.
+    pub fn write_page(&mut self, address: u32, data: &[u8; SPM_PAGESIZE]) {
+        if address as usize % SPM_PAGESIZE != 0 {
+            unreachable!()
+        }
+        self.page_erase(address);
+        for i in (0..SPM_PAGESIZE).step_by(2) {
+            let word = (data[i] as u16) | ((data[i + 1] as u16) << 8);
+            self.page_fill(address + i as u32, word);
+        }
+        self.page_write(address);
+        self.rww_enable();
+    }
+
+    /// Reads one fuse or lock byte using LPM right after BLBSET and SPMEN are set.
+    /// # Arguments
+    /// * `fuse` - a `Fuse` object, the byte to be read.
+    /// # Returns
+    /// * `a u8` - the value of the byte, programmed bits read as zero.
+    pub fn read_fuse(&mut self, fuse: Fuse) -> u8 {
+        self.wait();
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            let value = lpm_fuse(BLBSET | SPMEN, fuse.address());
+            write_volatile(SREG, sreg);
+            value
+        }
+    }
+
+    /// Reads all the fuse and lock bytes, so that they can be compared with
+    /// the expected clock and brown-out settings at start up.
+    /// # Returns
+    /// * `a FuseBytes object` - the values of all the bytes.
+    pub fn read_fuses(&mut self) -> FuseBytes {
+        FuseBytes {
+            low: self.read_fuse(Fuse::Low),
+            high: self.read_fuse(Fuse::High),
+            extended: self.read_fuse(Fuse::Extended),
+            lock: self.read_fuse(Fuse::Lock),
+        }
+    }
+}
+
+/// Writes the command to SPMCSR and reads the selected byte with LPM within three cycles.
+#[cfg(target_arch = "avr")]
+unsafe fn lpm_fuse(command: u8, address: u16) -> u8 {
+    let value: u8;
+    llvm_asm!("out 0x37, $1
+               lpm $0, Z"
+             : "=r" (value)
+             : "r" (command), "z" (address)
+             :
+             : "volatile");
+    value
+}
+
+/// Fuses can not be read on other targets, all bits read as unprogrammed.
+#[cfg(not(target_arch = "avr"))]
+unsafe fn lpm_fuse(_command: u8, _address: u16) -> u8 {
+    0xFF
+}
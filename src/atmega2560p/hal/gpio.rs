@@ -0,0 +1,404 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Type-state I/O pins for ATMEGA2560P where the mode of the pin is a part of its type.
+//! A `Pin<Input>` can only be read and a `Pin<Output>` can only be driven, so using a
+//! pin in the wrong mode is caught by the compiler. The dynamic `Pins` array is still
+//! available and typed pins can be made from and turned back into its pins.
+//! Section 13.2 to 13.4 of ATMEGA2560P datasheet.
+
+// Crates to be used for the implementation.
+use core::marker::PhantomData;
+use core::ptr::{read_volatile, write_volatile};
+
+// Source codes to be used here.
+use crate::atmega2560p::hal::pin::{AnalogPin, DigitalPin};
+use crate::atmega2560p::hal::port;
+use crate::atmega2560p::hal::port::PortName;
+
+/// Floating input mode, the pull-up resistor is disabled.
+pub struct Input;
+
+/// Input mode with the internal pull-up resistor enabled.
+pub struct InputPullUp;
+
+/// Push-pull output mode.
+pub struct Output;
+
+/// Open-drain output mode, the pin is either driven low or left floating,
+/// so that a pull-up resistor or another device on the line sets the level.
+/// Used for shared lines like 1-Wire.
+pub struct OpenDrain;
+
+/// A single I/O pin whose mode is given by the type parameter `MODE`.
+/// `MODE` can be `Input`, `InputPullUp`, `Output` or `OpenDrain`.
+pub struct Pin<MODE> {
+    pin: port::Pin,
+    _mode: PhantomData<MODE>,
+}
+
+impl Pin<Input> {
+    /// Creates a new pin which is configured as a floating input.
+    /// # Arguments
+    /// * `port_name` - a `PortName` object, the port to which the pin belongs.
+    /// * `pin` - a usize, the bit of the pin in the port.
+    /// # Returns
+    /// * `maybe a Pin<Input> object` - None if the pin number is invalid.
+    pub unsafe fn new(port_name: PortName, pin: usize) -> Option<Pin<Input>> {
+        match port::Pin::new(port_name, pin) {
+            Some(pin) => Some(Pin::<Input>::from_pin(pin)),
+            None => None,
+        }
+    }
+
+    /// Wraps the dynamic pin and configures it as a floating input.
+    fn from_pin(pin: port::Pin) -> Pin<Input> {
+        let mut typed = Pin {
+            pin,
+            _mode: PhantomData,
+        };
+        typed.set_ddr(false);
+        typed.set_port(false);
+        typed
+    }
+}
+
+impl<MODE> Pin<MODE> {
+    /// Writes the DDRxn bit of the pin.
+    fn set_ddr(&mut self, value: bool) {
+        let mut ddr_val = unsafe { read_volatile(&mut (*self.pin.port).ddr) };
+        if value {
+            ddr_val |= 0x1 << self.pin.pin;
+        } else {
+            ddr_val &= !(0x1 << self.pin.pin);
+        }
+        unsafe { write_volatile(&mut (*self.pin.port).ddr, ddr_val) }
+    }
+
+    /// Writes the PORTxn bit of the pin.
+    fn set_port(&mut self, value: bool) {
+        let mut port_val = unsafe { read_volatile(&mut (*self.pin.port).port) };
+        if value {
+            port_val |= 0x1 << self.pin.pin;
+        } else {
+            port_val &= !(0x1 << self.pin.pin);
+        }
+        unsafe { write_volatile(&mut (*self.pin.port).port, port_val) }
+    }
+
+    /// Changes the type of the pin without touching the registers.
+    fn into_mode<NEW>(self) -> Pin<NEW> {
+        Pin {
+            pin: self.pin,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Configures the pin as a floating input.
+    /// # Returns
+    /// * `a Pin<Input> object` - the same pin in input mode.
+    pub fn into_input(mut self) -> Pin<Input> {
+        self.set_ddr(false);
+        self.set_port(false);
+        self.into_mode()
+    }
+
+    /// Configures the pin as an input with the pull-up resistor enabled.
+    /// # Returns
+    /// * `a Pin<InputPullUp> object` - the same pin in pull-up input mode.
+    pub fn into_pull_up_input(mut self) -> Pin<InputPullUp> {
+        self.set_ddr(false);
+        self.set_port(true);
+        self.into_mode()
+    }
+
+    /// Configures the pin as an output which is driven low.
+    /// # Returns
+    /// * `a Pin<Output> object` - the same pin in output mode.
+    pub fn into_output(mut self) -> Pin<Output> {
+        self.set_port(false);
+        self.set_ddr(true);
+        self.into_mode()
+    }
+
+    /// Configures the pin as an open-drain output which is released.
+    /// # Returns
+    /// * `a Pin<OpenDrain> object` - the same pin in open-drain mode.
+    pub fn into_open_drain(mut self) -> Pin<OpenDrain> {
+        self.set_ddr(false);
+        self.set_port(false);
+        self.into_mode()
+    }
+
+    /// Gives back the dynamic pin so that it can be used with the older API.
+    /// The pin keeps its present configuration.
+    /// # Returns
+    /// * `a Pin object` - the dynamic pin of the port module.
+    pub fn downgrade(self) -> port::Pin {
+        self.pin
+    }
+}
+
+/// Reads the PINxn bit of the pin.
+fn read_pin(pin: &port::Pin) -> bool {
+    let pin_val = unsafe { read_volatile(&(*pin.port).pin) };
+    pin_val & (0x1 << pin.pin) != 0
+}
+
+impl Pin<Input> {
+    /// Checks the logic level on the pin.
+    /// # Returns
+    /// * `a boolean` - true if the pin is high.
+    pub fn is_high(&self) -> bool {
+        read_pin(&self.pin)
+    }
+
+    /// Checks the logic level on the pin.
+    /// # Returns
+    /// * `a boolean` - true if the pin is low.
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+}
+
+impl Pin<InputPullUp> {
+    /// Checks the logic level on the pin.
+    /// # Returns
+    /// * `a boolean` - true if the pin is high.
+    pub fn is_high(&self) -> bool {
+        read_pin(&self.pin)
+    }
+
+    /// Checks the logic level on the pin.
+    /// # Returns
+    /// * `a boolean` - true if the pin is low.
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+}
+
+impl Pin<Output> {
+    /// Drives the pin high.
+    pub fn high(&mut self) {
+        self.set_port(true);
+    }
+
+    /// Drives the pin low.
+    pub fn low(&mut self) {
+        self.set_port(false);
+    }
+
+    /// Toggles the pin by writing one to its PINxn bit.
+    pub fn toggle(&mut self) {
+        unsafe { write_volatile(&mut (*self.pin.port).pin, 0x1 << self.pin.pin) }
+    }
+
+    /// Checks the level the pin is being driven to.
+    /// # Returns
+    /// * `a boolean` - true if the pin is set high.
+    pub fn is_set_high(&self) -> bool {
+        let port_val = unsafe { read_volatile(&(*self.pin.port).port) };
+        port_val & (0x1 << self.pin.pin) != 0
+    }
+
+    /// Checks the level the pin is being driven to.
+    /// # Returns
+    /// * `a boolean` - true if the pin is set low.
+    pub fn is_set_low(&self) -> bool {
+        !self.is_set_high()
+    }
+}
+
+impl Pin<OpenDrain> {
+    /// Releases the line, it is pulled high unless another device holds it low.
+    /// PORTxn stays zero, so only DDRxn is changed.
+    pub fn high(&mut self) {
+        self.set_ddr(false);
+    }
+
+    /// Drives the line low.
+    pub fn low(&mut self) {
+        self.set_ddr(true);
+    }
+
+    /// Checks the logic level on the line.
+    /// # Returns
+    /// * `a boolean` - true if the line is high.
+    pub fn is_high(&self) -> bool {
+        read_pin(&self.pin)
+    }
+
+    /// Checks the logic level on the line.
+    /// # Returns
+    /// * `a boolean` - true if the line is low.
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+}
+
+impl DigitalPin {
+    /// Converts the digital pin into a typed floating input pin.
+    /// # Returns
+    /// * `a Pin<Input> object` - the typed pin.
+    pub fn into_input(self) -> Pin<Input> {
+        Pin::<Input>::from_pin(self.pin)
+    }
+
+    /// Converts the digital pin into a typed input pin with the pull-up enabled.
+    /// # Returns
+    /// * `a Pin<InputPullUp> object` - the typed pin.
+    pub fn into_pull_up_input(self) -> Pin<InputPullUp> {
+        self.into_input().into_pull_up_input()
+    }
+
+    /// Converts the digital pin into a typed output pin.
+    /// # Returns
+    /// * `a Pin<Output> object` - the typed pin.
+    pub fn into_output(self) -> Pin<Output> {
+        self.into_input().into_output()
+    }
+
+    /// Converts the digital pin into a typed open-drain pin.
+    /// # Returns
+    /// * `a Pin<OpenDrain> object` - the typed pin.
+    pub fn into_open_drain(self) -> Pin<OpenDrain> {
+        self.into_input().into_open_drain()
+    }
+}
+
+impl AnalogPin {
+    /// Converts the analog pin into a typed floating input pin.
+    /// # Returns
+    /// * `a Pin<Input> object` - the typed pin.
+    pub fn into_input(self) -> Pin<Input> {
+        Pin::<Input>::from_pin(self.pin)
+    }
+
+    /// Converts the analog pin into a typed input pin with the pull-up enabled.
+    /// # Returns
+    /// * `a Pin<InputPullUp> object` - the typed pin.
+    pub fn into_pull_up_input(self) -> Pin<InputPullUp> {
+        self.into_input().into_pull_up_input()
+    }
+
+    /// Converts the analog pin into a typed output pin.
+    /// # Returns
+    /// * `a Pin<Output> object` - the typed pin.
+    pub fn into_output(self) -> Pin<Output> {
+        self.into_input().into_output()
+    }
+
+    /// Converts the analog pin into a typed open-drain pin.
+    /// # Returns
+    /// * `a Pin<OpenDrain> object` - the typed pin.
+    pub fn into_open_drain(self) -> Pin<OpenDrain> {
+        self.into_input().into_open_drain()
+    }
+}
+
+/// Typed pins for the drivers written against the `embedded-hal` digital traits.
+/// The pins cannot fail, so the error is `Infallible`.
+#[cfg(feature = "embedded")]
+impl embedded_hal::digital::v2::InputPin for Pin<Input> {
+    type Error = core::convert::Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::<Input>::is_high(self))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::<Input>::is_low(self))
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::digital::v2::InputPin for Pin<InputPullUp> {
+    type Error = core::convert::Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::<InputPullUp>::is_high(self))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::<InputPullUp>::is_low(self))
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::digital::v2::OutputPin for Pin<Output> {
+    type Error = core::convert::Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.high();
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.low();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::digital::v2::StatefulOutputPin for Pin<Output> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::<Output>::is_set_high(self))
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::<Output>::is_set_low(self))
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::digital::v2::ToggleableOutputPin for Pin<Output> {
+    type Error = core::convert::Infallible;
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        Pin::<Output>::toggle(self);
+        Ok(())
+    }
+}
+
+/// An open-drain pin is both an output and an input, as drivers of shared
+/// lines like the DHT sensors release the line and then read it.
+#[cfg(feature = "embedded")]
+impl embedded_hal::digital::v2::OutputPin for Pin<OpenDrain> {
+    type Error = core::convert::Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.high();
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.low();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::digital::v2::InputPin for Pin<OpenDrain> {
+    type Error = core::convert::Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::<OpenDrain>::is_high(self))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(Pin::<OpenDrain>::is_low(self))
+    }
+}
@@ -0,0 +1,159 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! General purpose I/O registers GPIOR0, GPIOR1 and GPIOR2 of the ATMEGA2560P chip.
+//! They are not used by any peripheral and can be accessed in a few cycles, so
+//! they make cheap flag stores shared between interrupt routines and main code.
+//! Section 8.5 of ATmega2560P datasheet.
+
+// Crates to be used for the implementation.
+use bit_field::BitField;
+use volatile::Volatile;
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+/// Address of the SREG register, used to make read-modify-write of a flag atomic.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+/// Selection of the general purpose I/O register.
+#[derive(Clone, Copy)]
+pub enum GpioRegister {
+    Gpior0,
+    Gpior1,
+    Gpior2,
+}
+
+/// Contains the general purpose I/O registers.
+///
+/// * `GPIOR0`: *General Purpose I/O Register 0*. Bit accessible with the
+/// single cycle `SBI`, `CBI`, `SBIS` and `SBIC` instructions.
+///
+/// * `GPIOR1`: *General Purpose I/O Register 1*.
+///
+/// * `GPIOR2`: *General Purpose I/O Register 2*.
+#[repr(C, packed)]
+pub struct GeneralPurpose {
+    gpior0: Volatile<u8>,
+    _pad: [u8; 0x0B],
+    gpior1: Volatile<u8>,
+    gpior2: Volatile<u8>,
+}
+
+impl GeneralPurpose {
+    /// Creates a new reference to the General Purpose structure at a specified location.
+    /// # Returns
+    /// * `a reference to GeneralPurpose object` - which will be used for further implementations.
+    pub unsafe fn new() -> &'static mut GeneralPurpose {
+        &mut *(0x3E as *mut GeneralPurpose)
+    }
+
+    /// Gives the register selected by `reg`.
+    fn register(&mut self, reg: GpioRegister) -> &mut Volatile<u8> {
+        match reg {
+            GpioRegister::Gpior0 => &mut self.gpior0,
+            GpioRegister::Gpior1 => &mut self.gpior1,
+            GpioRegister::Gpior2 => &mut self.gpior2,
+        }
+    }
+
+    /// Reads the whole register.
+    /// # Arguments
+    /// * `reg` - a `GpioRegister` object, the register to be read.
+    /// # Returns
+    /// * `a u8` - the value of the register.
+    pub fn read(&mut self, reg: GpioRegister) -> u8 {
+        self.register(reg).read()
+    }
+
+    /// Writes the whole register.
+    /// # Arguments
+    /// * `reg` - a `GpioRegister` object, the register to be written.
+    /// * `value` - a u8, the value to be written.
+    pub fn write(&mut self, reg: GpioRegister, value: u8) {
+        self.register(reg).write(value);
+    }
+
+    /// Sets or clears one bit of the register with interrupts held off,
+    /// so that an interrupt routine can not lose the change.
+    fn write_flag(&mut self, reg: GpioRegister, bit: u8, value: bool) {
+        if bit > 7 {
+            unreachable!()
+        }
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            self.register(reg).update(|val| {
+                val.set_bit(bit, value);
+            });
+            write_volatile(SREG, sreg);
+        }
+    }
+
+    /// Sets one flag of the register.
+    /// # Arguments
+    /// * `reg` - a `GpioRegister` object, the register holding the flag.
+    /// * `bit` - a u8, the bit of the flag, from 0 to 7.
+    pub fn set_flag(&mut self, reg: GpioRegister, bit: u8) {
+        self.write_flag(reg, bit, true);
+    }
+
+    /// Clears one flag of the register.
+    /// # Arguments
+    /// * `reg` - a `GpioRegister` object, the register holding the flag.
+    /// * `bit` - a u8, the bit of the flag, from 0 to 7.
+    pub fn clear_flag(&mut self, reg: GpioRegister, bit: u8) {
+        self.write_flag(reg, bit, false);
+    }
+
+    /// Checks one flag of the register.
+    /// # Arguments
+    /// * `reg` - a `GpioRegister` object, the register holding the flag.
+    /// * `bit` - a u8, the bit of the flag, from 0 to 7.
+    /// # Returns
+    /// * `a boolean` - true if the flag is set.
+    pub fn is_set(&mut self, reg: GpioRegister, bit: u8) -> bool {
+        if bit > 7 {
+            unreachable!()
+        }
+        self.register(reg).read().get_bit(bit)
+    }
+
+    /// Checks one flag of the register and clears it in the same step,
+    /// so that an event set by an interrupt routine is handled only once.
+    /// # Arguments
+    /// * `reg` - a `GpioRegister` object, the register holding the flag.
+    /// * `bit` - a u8, the bit of the flag, from 0 to 7.
+    /// # Returns
+    /// * `a boolean` - true if the flag was set.
+    pub fn take_flag(&mut self, reg: GpioRegister, bit: u8) -> bool {
+        if bit > 7 {
+            unreachable!()
+        }
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            let set = self.register(reg).read().get_bit(bit);
+            if set {
+                self.register(reg).update(|val| {
+                    val.set_bit(bit, false);
+                });
+            }
+            write_volatile(SREG, sreg);
+            set
+        }
+    }
+}
@@ -18,7 +18,12 @@
 //! Section 7.4 of the manual
 
 // Crates required in the code for reading and writing to registers.
+use crate::sync::CriticalSection;
 use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Address of SREG, whose bit 7 enables the global interrupts.
+const SREG: *mut u8 = 0x5F as *mut u8;
 
 /// This contains the registers to be manipulated for controlling global interrupts setup.
 /// This represents struct for Globalinterrupts and is used to control sreg register.
@@ -55,3 +60,233 @@ impl Interrupt {
         }
     }
 }
+
+/// Runs the closure with the global interrupts disabled and then restores SREG,
+/// so the interrupts are enabled again only if they were enabled before.
+/// The closure is given a critical section token, which is needed to reach the data
+/// shared with the interrupt handlers through `sync::Mutex`.
+/// # Arguments
+/// * `f` - a closure, which takes a `CriticalSection` token and gives a value of any type.
+/// # Returns
+/// * `the value given by the closure`.
+pub fn free<F, R>(f: F) -> R
+where
+    F: FnOnce(CriticalSection) -> R,
+{
+    unsafe {
+        let sreg = read_volatile(SREG);
+        write_volatile(SREG, sreg & 0x7F);
+        compiler_fence(Ordering::SeqCst);
+
+        let result = f(CriticalSection::new());
+
+        compiler_fence(Ordering::SeqCst);
+        write_volatile(SREG, sreg);
+        result
+    }
+}
+
+/// Registers a function as the handler of an interrupt, given by its name in the datasheet
+/// like `TIMER1_COMPA` or `USART0_RX`, and emits the `__vector_N` function of the ATMEGA2560P for it.
+/// The application needs `#![feature(abi_avr_interrupt)]` for the handler to be compiled.
+///
+/// The vectors used by the drivers of this library (INT0-7, PCINT0-2, WDT, TIMER4_CAPT, TIMER4_OVF, USART0-3 RX and UDRE, ADC and ANALOG_COMP)
+/// are refused with a compile error, their handlers are attached through the drivers instead.
+/// When both chip features are enabled, as for the documentation, only the ATMEGA328P macro is defined.
+///
+/// ```ignore
+/// fn tick() {
+///     // Runs on every compare match of Timer 1 channel A.
+/// }
+///
+/// rustduino::interrupt!(TIMER1_COMPA, tick);
+/// ```
+#[cfg(not(feature = "atmega328p"))]
+#[macro_export]
+macro_rules! interrupt {
+    (INT0, $handler:path) => {
+        compile_error!("the INT0 vector is defined by the library, attach the handler through its driver");
+    };
+    (INT1, $handler:path) => {
+        compile_error!("the INT1 vector is defined by the library, attach the handler through its driver");
+    };
+    (INT2, $handler:path) => {
+        compile_error!("the INT2 vector is defined by the library, attach the handler through its driver");
+    };
+    (INT3, $handler:path) => {
+        compile_error!("the INT3 vector is defined by the library, attach the handler through its driver");
+    };
+    (INT4, $handler:path) => {
+        compile_error!("the INT4 vector is defined by the library, attach the handler through its driver");
+    };
+    (INT5, $handler:path) => {
+        compile_error!("the INT5 vector is defined by the library, attach the handler through its driver");
+    };
+    (INT6, $handler:path) => {
+        compile_error!("the INT6 vector is defined by the library, attach the handler through its driver");
+    };
+    (INT7, $handler:path) => {
+        compile_error!("the INT7 vector is defined by the library, attach the handler through its driver");
+    };
+    (PCINT0, $handler:path) => {
+        compile_error!("the PCINT0 vector is defined by the library, attach the handler through its driver");
+    };
+    (PCINT1, $handler:path) => {
+        compile_error!("the PCINT1 vector is defined by the library, attach the handler through its driver");
+    };
+    (PCINT2, $handler:path) => {
+        compile_error!("the PCINT2 vector is defined by the library, attach the handler through its driver");
+    };
+    (WDT, $handler:path) => {
+        compile_error!("the WDT vector is defined by the library, attach the handler through its driver");
+    };
+    (TIMER2_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_13, $handler);
+    };
+    (TIMER2_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_14, $handler);
+    };
+    (TIMER2_OVF, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_15, $handler);
+    };
+    (TIMER1_CAPT, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_16, $handler);
+    };
+    (TIMER1_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_17, $handler);
+    };
+    (TIMER1_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_18, $handler);
+    };
+    (TIMER1_COMPC, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_19, $handler);
+    };
+    (TIMER1_OVF, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_20, $handler);
+    };
+    (TIMER0_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_21, $handler);
+    };
+    (TIMER0_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_22, $handler);
+    };
+    (TIMER0_OVF, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_23, $handler);
+    };
+    (SPI_STC, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_24, $handler);
+    };
+    (USART0_RX, $handler:path) => {
+        compile_error!("the USART0_RX vector is defined by the library, attach the handler through its driver");
+    };
+    (USART0_UDRE, $handler:path) => {
+        compile_error!("the USART0_UDRE vector is defined by the library, attach the handler through its driver");
+    };
+    (USART0_TX, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_27, $handler);
+    };
+    (ANALOG_COMP, $handler:path) => {
+        compile_error!("the ANALOG_COMP vector is defined by the library, attach the handler through its driver");
+    };
+    (ADC, $handler:path) => {
+        compile_error!("the ADC vector is defined by the library, attach the handler through its driver");
+    };
+    (EE_READY, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_30, $handler);
+    };
+    (TIMER3_CAPT, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_31, $handler);
+    };
+    (TIMER3_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_32, $handler);
+    };
+    (TIMER3_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_33, $handler);
+    };
+    (TIMER3_COMPC, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_34, $handler);
+    };
+    (TIMER3_OVF, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_35, $handler);
+    };
+    (USART1_RX, $handler:path) => {
+        compile_error!("the USART1_RX vector is defined by the library, attach the handler through its driver");
+    };
+    (USART1_UDRE, $handler:path) => {
+        compile_error!("the USART1_UDRE vector is defined by the library, attach the handler through its driver");
+    };
+    (USART1_TX, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_38, $handler);
+    };
+    (TWI, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_39, $handler);
+    };
+    (SPM_READY, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_40, $handler);
+    };
+    (TIMER4_CAPT, $handler:path) => {
+        compile_error!("the TIMER4_CAPT vector is defined by the library, attach the handler through its driver");
+    };
+    (TIMER4_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_42, $handler);
+    };
+    (TIMER4_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_43, $handler);
+    };
+    (TIMER4_COMPC, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_44, $handler);
+    };
+    (TIMER4_OVF, $handler:path) => {
+        compile_error!("the TIMER4_OVF vector is defined by the library, attach the handler through its driver");
+    };
+    (TIMER5_CAPT, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_46, $handler);
+    };
+    (TIMER5_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_47, $handler);
+    };
+    (TIMER5_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_48, $handler);
+    };
+    (TIMER5_COMPC, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_49, $handler);
+    };
+    (TIMER5_OVF, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_50, $handler);
+    };
+    (USART2_RX, $handler:path) => {
+        compile_error!("the USART2_RX vector is defined by the library, attach the handler through its driver");
+    };
+    (USART2_UDRE, $handler:path) => {
+        compile_error!("the USART2_UDRE vector is defined by the library, attach the handler through its driver");
+    };
+    (USART2_TX, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_53, $handler);
+    };
+    (USART3_RX, $handler:path) => {
+        compile_error!("the USART3_RX vector is defined by the library, attach the handler through its driver");
+    };
+    (USART3_UDRE, $handler:path) => {
+        compile_error!("the USART3_UDRE vector is defined by the library, attach the handler through its driver");
+    };
+    (USART3_TX, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_56, $handler);
+    };
+    ($other:ident, $handler:path) => {
+        compile_error!(concat!("unknown interrupt vector ", stringify!($other)));
+    };
+}
+
+/// Emits the interrupt vector calling the handler, used by `interrupt!`.
+#[cfg(not(feature = "atmega328p"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __interrupt_vector {
+    ($vector:ident, $handler:path) => {
+        #[cfg(target_arch = "avr")]
+        #[no_mangle]
+        pub unsafe extern "avr-interrupt" fn $vector() {
+            $handler();
+        }
+    };
+}
@@ -54,9 +54,10 @@ impl Pins {
     /// Returns all pins at once as a single struct.
     /// No new memory is created, just the already created space is given
     /// a name so it is a memory mapped I/O.
+    /// Use `peripherals::Peripherals::take()` to get the only owner without unsafe code.
     /// # Returns
     /// * `a Pins object` - used to control all pins of AVR chip at one place.
-    pub fn new() -> Pins {
+    pub unsafe fn new() -> Pins {
         Pins {
             analog: [
                 AnalogPin {
@@ -351,7 +352,7 @@ impl Pins {
 /// * `a u32` - The pin number which is to be used.
 /// # Returns
 /// * `a Pin object` - The memory mapped I/O object to control the Digital Pin.
-pub fn make_pin(pin: u32) -> Pin {
+pub unsafe fn make_pin(pin: u32) -> Pin {
     match pin {
         0 => return Pin::new(PortName::E, 0).unwrap(),
         1 => return Pin::new(PortName::E, 1).unwrap(),
@@ -52,6 +52,45 @@ pub enum Peripherals {
     USART1,
 }
 
+/// Selection of a peripheral whose clock is controlled by the power reduction registers.
+#[derive(Clone, Copy)]
+pub enum Peripheral {
+    Twi,
+    Timer0,
+    Timer1,
+    Timer2,
+    Timer3,
+    Timer4,
+    Timer5,
+    Spi,
+    Usart0,
+    Usart1,
+    Usart2,
+    Usart3,
+    Adc,
+}
+
+impl Peripheral {
+    /// Gives the power reduction register (0 for PRR0 and 1 for PRR1) and the bit of the peripheral.
+    fn location(&self) -> (u8, u8) {
+        match self {
+            Peripheral::Twi => (0, 7),
+            Peripheral::Timer2 => (0, 6),
+            Peripheral::Timer0 => (0, 5),
+            Peripheral::Timer1 => (0, 3),
+            Peripheral::Spi => (0, 2),
+            Peripheral::Usart0 => (0, 1),
+            Peripheral::Adc => (0, 0),
+            Peripheral::Timer5 => (1, 5),
+            Peripheral::Timer4 => (1, 4),
+            Peripheral::Timer3 => (1, 3),
+            Peripheral::Usart3 => (1, 2),
+            Peripheral::Usart2 => (1, 1),
+            Peripheral::Usart1 => (1, 0),
+        }
+    }
+}
+
 /// Contains registers to control the functioning of clocks in the chip.
 /// It would be used to control the power modes of the ATMEGA2560P chip as mentioned
 /// in the enum `Options` above.
@@ -69,6 +108,47 @@ impl Power {
         &mut *(0x64 as *mut Power)
     }
 
+    /// Gives the power reduction register holding the bit of a peripheral.
+    fn prr(&mut self, register: u8) -> &mut u8 {
+        match register {
+            0 => &mut self.prr0,
+            _ => &mut self.prr1,
+        }
+    }
+
+    /// Stops the clock of a peripheral to save power.
+    /// # Arguments
+    /// * `peripheral` - a `Peripheral` object, the peripheral to be shut down.
+    pub fn disable(&mut self, peripheral: Peripheral) {
+        let (register, bit) = peripheral.location();
+        let prr = self.prr(register);
+        unsafe {
+            write_volatile(prr, read_volatile(prr) | (1 << bit));
+        }
+    }
+
+    /// Starts the clock of a peripheral so that it can be used.
+    /// # Arguments
+    /// * `peripheral` - a `Peripheral` object, the peripheral to be powered.
+    pub fn enable(&mut self, peripheral: Peripheral) {
+        let (register, bit) = peripheral.location();
+        let prr = self.prr(register);
+        unsafe {
+            write_volatile(prr, read_volatile(prr) & !(1 << bit));
+        }
+    }
+
+    /// Checks if the clock of a peripheral is running.
+    /// # Arguments
+    /// * `peripheral` - a `Peripheral` object, the peripheral to be checked.
+    /// # Returns
+    /// * `a boolean` - true if the peripheral is powered.
+    pub fn is_enabled(&mut self, peripheral: Peripheral) -> bool {
+        let (register, bit) = peripheral.location();
+        let prr = self.prr(register);
+        unsafe { read_volatile(prr) & (1 << bit) == 0 }
+    }
+
     /// This is the function for disabling the clock system of your choice.
     /// It would create a new element of the structure power
     /// which would be used to control various clock gating features of the
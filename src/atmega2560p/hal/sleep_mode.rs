@@ -17,6 +17,9 @@
 //! Implementation of Sleep Modes of ATMEGA2560P.
 //! Section 11.10.1 of the manual.
 //! Also references from Section 11.4.
+//! Unlike the ATMEGA328P, this chip has no BODS and BODSE bits in MCUCR, so the
+//! brown-out detector can not be turned off during sleep from software. It can
+//! only be disabled altogether through the BODLEVEL fuses.
 
 // Crates required in the code for reading and writing to registers.
 use core::ptr::{read_volatile, write_volatile};
@@ -104,4 +107,18 @@ impl Sleep {
             write_volatile(&mut self.smcr, smcr);
         }
     }
+
+    /// Sets `SE`, enters the selected sleep mode and clears `SE` after waking up.
+    ///
+    /// Global interrupts are enabled by `SEI` right before `SLEEP`, and the
+    /// instruction after `SEI` always runs before a pending interrupt, so an
+    /// interrupt can not slip in before sleep and leave the chip asleep with no wake up.
+    pub fn sleep_enable_and_wait(&mut self) {
+        self.enable();
+        unsafe {
+            llvm_asm!("sei
+                       sleep" :::: "volatile");
+        }
+        self.disable();
+    }
 }
@@ -0,0 +1,304 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Bit-banged 1-Wire master for ATMEGA328P on any I/O pin in open-drain mode.
+//! The line needs a pull-up resistor of about 4.7 kΩ. Standard speed timing is
+//! used and every time slot is done with interrupts held off, as an interrupt in
+//! the middle of a slot would corrupt it.
+//! ROM search follows the algorithm of Maxim application note 187.
+//! `<https://www.analog.com/en/app-notes/1wire-search-algorithm.html>`
+
+// Source code crates required
+use crate::atmega328p::hal::gpio::{OpenDrain, Pin};
+use crate::delay::delay_us;
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+/// Address of the SREG register, used to keep interrupts away from a time slot.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+// ROM commands.
+const READ_ROM: u8 = 0x33;
+const MATCH_ROM: u8 = 0x55;
+const SKIP_ROM: u8 = 0xCC;
+const SEARCH_ROM: u8 = 0xF0;
+const ALARM_SEARCH: u8 = 0xEC;
+
+/// Calculates the 8 bit CRC used by 1-Wire devices, polynomial x^8 + x^5 + x^4 + 1.
+/// The CRC over data followed by its CRC byte is zero.
+/// # Arguments
+/// * `data` - a u8 slice, the bytes to be checked.
+/// # Returns
+/// * `a u8` - the CRC of the bytes.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for byte in data.iter() {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+/// A 1-Wire bus on one pin, with the state of the ROM search.
+pub struct OneWire {
+    pin: Pin<OpenDrain>,
+    rom: [u8; 8],
+    last_discrepancy: u8,
+    last_device: bool,
+}
+
+impl OneWire {
+    /// Creates the bus, the line is released.
+    /// # Arguments
+    /// * `pin` - a `Pin<OpenDrain>` object, the pin of the data line.
+    /// # Returns
+    /// * `a OneWire object` - which will be used to talk to the devices.
+    pub fn new(mut pin: Pin<OpenDrain>) -> OneWire {
+        pin.high();
+        OneWire {
+            pin,
+            rom: [0; 8],
+            last_discrepancy: 0,
+            last_device: false,
+        }
+    }
+
+    /// Sends the reset pulse and checks for the presence pulse of the devices.
+    /// # Returns
+    /// * `a boolean` - true if at least one device answered.
+    pub fn reset(&mut self) -> bool {
+        // A line held low by a device or a missing pull-up can not be reset.
+        let mut retries: u8 = 125;
+        while self.pin.is_low() {
+            if retries == 0 {
+                return false;
+            }
+            retries -= 1;
+            delay_us(2);
+        }
+
+        self.pin.low();
+        delay_us(480);
+        let present = unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            self.pin.high();
+            delay_us(70);
+            let present = self.pin.is_low();
+            write_volatile(SREG, sreg);
+            present
+        };
+        delay_us(410);
+        present
+    }
+
+    /// Sends one bit in a write time slot.
+    /// # Arguments
+    /// * `bit` - a boolean, the bit to be sent.
+    pub fn write_bit(&mut self, bit: bool) {
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            self.pin.low();
+            if bit {
+                delay_us(6);
+                self.pin.high();
+                delay_us(64);
+            } else {
+                delay_us(60);
+                self.pin.high();
+                delay_us(10);
+            }
+            write_volatile(SREG, sreg);
+        }
+    }
+
+    /// Reads one bit in a read time slot.
+    /// # Returns
+    /// * `a boolean` - the bit sent by the device.
+    pub fn read_bit(&mut self) -> bool {
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            self.pin.low();
+            delay_us(6);
+            self.pin.high();
+            delay_us(9);
+            let bit = self.pin.is_high();
+            write_volatile(SREG, sreg);
+            delay_us(55);
+            bit
+        }
+    }
+
+    /// Sends one byte, lowest bit first.
+    /// # Arguments
+    /// * `byte` - a u8, the byte to be sent.
+    pub fn write_byte(&mut self, byte: u8) {
+        for i in 0..8 {
+            self.write_bit(byte & (1 << i) != 0);
+        }
+    }
+
+    /// Reads one byte, lowest bit first.
+    /// # Returns
+    /// * `a u8` - the byte sent by the device.
+    pub fn read_byte(&mut self) -> u8 {
+        let mut byte: u8 = 0;
+        for i in 0..8 {
+            if self.read_bit() {
+                byte |= 1 << i;
+            }
+        }
+        byte
+    }
+
+    /// Sends the bytes in order.
+    /// # Arguments
+    /// * `data` - a u8 slice, the bytes to be sent.
+    pub fn write(&mut self, data: &[u8]) {
+        for byte in data.iter() {
+            self.write_byte(*byte);
+        }
+    }
+
+    /// Reads bytes until the buffer is full.
+    /// # Arguments
+    /// * `data` - a mutable u8 slice, where the bytes are stored.
+    pub fn read(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            *byte = self.read_byte();
+        }
+    }
+
+    /// Selects one device by its ROM code, the next function command goes only to it.
+    /// The bus has to be reset first.
+    /// # Arguments
+    /// * `rom` - a u8 array, the ROM code of the device.
+    pub fn select(&mut self, rom: &[u8; 8]) {
+        self.write_byte(MATCH_ROM);
+        self.write(rom);
+    }
+
+    /// Selects all the devices, used when there is only one device on the bus
+    /// or to start a conversion on every device at once.
+    /// The bus has to be reset first.
+    pub fn skip(&mut self) {
+        self.write_byte(SKIP_ROM);
+    }
+
+    /// Reads the ROM code when there is only one device on the bus.
+    /// # Returns
+    /// * `a Option<[u8; 8]>` - the ROM code, None if no device answered or the CRC is wrong.
+    pub fn read_rom(&mut self) -> Option<[u8; 8]> {
+        if !self.reset() {
+            return None;
+        }
+        self.write_byte(READ_ROM);
+        let mut rom = [0; 8];
+        self.read(&mut rom);
+        if crc8(&rom) != 0 {
+            return None;
+        }
+        Some(rom)
+    }
+
+    /// Starts the ROM search again from the first device.
+    pub fn reset_search(&mut self) {
+        self.rom = [0; 8];
+        self.last_discrepancy = 0;
+        self.last_device = false;
+    }
+
+    /// Finds the next device on the bus, call it until None to find all devices.
+    /// # Returns
+    /// * `a Option<[u8; 8]>` - the ROM code of the next device, None when all were found.
+    pub fn search(&mut self) -> Option<[u8; 8]> {
+        self.search_with(SEARCH_ROM)
+    }
+
+    /// Finds the next device which has its alarm flag set.
+    /// # Returns
+    /// * `a Option<[u8; 8]>` - the ROM code of the next device, None when all were found.
+    pub fn alarm_search(&mut self) -> Option<[u8; 8]> {
+        self.search_with(ALARM_SEARCH)
+    }
+
+    /// One pass of the ROM search, each bit of the ROM code is read together with
+    /// its complement and a zero is chosen at a new discrepancy.
+    fn search_with(&mut self, command: u8) -> Option<[u8; 8]> {
+        if self.last_device || !self.reset() {
+            self.reset_search();
+            return None;
+        }
+        self.write_byte(command);
+
+        let mut last_zero: u8 = 0;
+        for bit_number in 1..65u8 {
+            let byte = ((bit_number - 1) / 8) as usize;
+            let mask = 1 << ((bit_number - 1) % 8);
+
+            let bit = self.read_bit();
+            let complement = self.read_bit();
+            if bit && complement {
+                // No device answered.
+                self.reset_search();
+                return None;
+            }
+
+            let direction = if bit != complement {
+                bit
+            } else if bit_number < self.last_discrepancy {
+                self.rom[byte] & mask != 0
+            } else {
+                bit_number == self.last_discrepancy
+            };
+            if !bit && !complement && !direction {
+                last_zero = bit_number;
+            }
+
+            if direction {
+                self.rom[byte] |= mask;
+            } else {
+                self.rom[byte] &= !mask;
+            }
+            self.write_bit(direction);
+        }
+
+        if crc8(&self.rom) != 0 {
+            self.reset_search();
+            return None;
+        }
+        self.last_discrepancy = last_zero;
+        self.last_device = last_zero == 0;
+        Some(self.rom)
+    }
+
+    /// Gives back the pin of the data line.
+    /// # Returns
+    /// * `a Pin<OpenDrain> object` - the pin, released.
+    pub fn release(self) -> Pin<OpenDrain> {
+        self.pin
+    }
+}
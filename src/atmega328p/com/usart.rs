@@ -25,10 +25,11 @@
 use crate::atmega328p::com::serial::Serial;
 use crate::atmega328p::com::usart_initialize::Usart;
 use crate::atmega328p::com::usart_initialize::{
-    UsartDataSize, UsartModes, UsartNum, UsartParity, UsartPolarity, UsartStop,
+    UsartDataSize, UsartError, UsartModes, UsartNum, UsartParity, UsartPolarity, UsartStop,
 };
 
 // Standard datatypes to be used
+use core::fmt::{self, Write};
 use core::{f64, u32};
 
 // Default setting parameters for various modes of USART in case user want's to skip them.
@@ -58,20 +59,20 @@ impl Serial {
 impl Usart {
     /// This function can be use to initialize with default settings.
     /// Like Mode:Normal asynchronuous,stopbit:one,data bit:8,parity type:no
-    pub unsafe fn begin(&mut self) {
+    pub unsafe fn begin(&mut self) -> Result<(), UsartError> {
         self.transmit_enable();
         self.recieve_enable();
-        self.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+        self.initialize(MODE, BAUD, STOP, SIZE, PARITY)
     }
 
     /// This function can be use to initialize with baud rate and remaining settings will be set to default
     /// Like Mode:Normal asynchronuous,stopbit:one,data bit:8,parity type:no
     /// # Arguments
     /// * `baud1` - a i64, the baud rate of USART the user wants to set.
-    pub unsafe fn begin_set_baud(&mut self, baud1: i64) {
+    pub unsafe fn begin_set_baud(&mut self, baud1: i64) -> Result<(), UsartError> {
         self.transmit_enable();
         self.recieve_enable();
-        self.initialize(MODE, baud1, STOP, SIZE, PARITY);
+        self.initialize(MODE, baud1, STOP, SIZE, PARITY)
     }
 
     /// This function can be used to stop the functioning of USART.
@@ -87,12 +88,15 @@ impl Usart {
 /// Then the string given by the user is transmitted through the USART.
 /// # Arguments
 /// * `data` - a string object, which is to be transmitted using USART.
-pub fn println_string(data: &'static str) {
+pub fn println_string(data: &'static str) -> Result<(), UsartError> {
     let u: &mut Usart = unsafe { Usart::new(NUM) };
     u.transmit_enable();
-    u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
-    u.write_string(data);
+    let result = u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+    if result.is_ok() {
+        u.write_string(data);
+    }
     u.transmit_disable();
+    result
 }
 
 /// Main println() function for using USART according to default used values.
@@ -102,12 +106,15 @@ pub fn println_string(data: &'static str) {
 /// This will be used to transmit integer data.
 /// # Arguments
 /// * `data` - a u32, which is to be transmitted using USART.
-pub fn println_integer(data: u32) {
+pub fn println_integer(data: u32) -> Result<(), UsartError> {
     let u: &mut Usart = unsafe { Usart::new(NUM) };
     u.transmit_enable();
-    u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
-    u.write_integer(data);
+    let result = u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+    if result.is_ok() {
+        u.write_integer(data);
+    }
     u.transmit_disable();
+    result
 }
 
 /// Main println() function for using USART according to default used values.
@@ -118,12 +125,15 @@ pub fn println_integer(data: u32) {
 /// # Arguments
 /// * `data` - a f32, which is to be transmitted using USART.
 /// * `precision` - a u32, the number of decimal precision required in the transmission.
-pub fn println_float(data: f64, precision: u32) {
+pub fn println_float(data: f64, precision: u32) -> Result<(), UsartError> {
     let u: &mut Usart = unsafe { Usart::new(NUM) };
     u.transmit_enable();
-    u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
-    u.write_float(data, precision);
+    let result = u.initialize(MODE, BAUD, STOP, SIZE, PARITY);
+    if result.is_ok() {
+        u.write_float(data, precision);
+    }
     u.transmit_disable();
+    result
 }
 
 /// println() function for using USART according to default used values and user defined value of baud rate.
@@ -133,12 +143,15 @@ pub fn println_float(data: f64, precision: u32) {
 /// # Arguments
 /// * `data` - a string object, which is to be transmitted using USART.
 /// * `baud1` - a i64, the baud rate of USART the user wants to set.
-pub fn println_set_baud(data: &'static str, baud1: i64) {
+pub fn println_set_baud(data: &'static str, baud1: i64) -> Result<(), UsartError> {
     let u: &mut Usart = unsafe { Usart::new(NUM) };
     u.transmit_enable();
-    u.initialize(MODE, baud1, STOP, SIZE, PARITY);
-    u.write_string(data);
+    let result = u.initialize(MODE, baud1, STOP, SIZE, PARITY);
+    if result.is_ok() {
+        u.write_string(data);
+    }
     u.transmit_disable();
+    result
 }
 
 /// Main println() function for using USART according to default used values and user defined value of frame.
@@ -155,12 +168,15 @@ pub fn println_set_frame(
     size1: UsartDataSize,
     parity1: UsartParity,
     stop1: UsartStop,
-) {
+) -> Result<(), UsartError> {
     let u: &mut Usart = unsafe { Usart::new(NUM) };
     u.transmit_enable();
-    u.initialize(MODE, BAUD, stop1, size1, parity1);
-    u.write_string(data);
+    let result = u.initialize(MODE, BAUD, stop1, size1, parity1);
+    if result.is_ok() {
+        u.write_string(data);
+    }
     u.transmit_disable();
+    result
 }
 
 /// Main println() function for using USART according to user defined mode parameters.
@@ -183,10 +199,51 @@ pub fn println_detail(
     size1: UsartDataSize,
     parity1: UsartParity,
     stop1: UsartStop,
-) {
+) -> Result<(), UsartError> {
     let u: &mut Usart = unsafe { Usart::new(num1) };
     u.transmit_enable();
-    u.initialize(mode1, baud1, stop1, size1, parity1);
-    u.write_string(data);
+    let result = u.initialize(mode1, baud1, stop1, size1, parity1);
+    if result.is_ok() {
+        u.write_string(data);
+    }
     u.transmit_disable();
+    result
+}
+
+// USART used by the `serial_print!` and `serial_println!` macros.
+static mut PRINT_USART: UsartNum = NUM;
+static mut PRINT_BAUD: i64 = BAUD;
+static mut PRINT_READY: bool = false;
+
+/// Chooses the USART used by the `serial_print!` and `serial_println!` macros.
+/// It is initialized again with the given baud rate on the next print.
+/// # Arguments
+/// * `num` - a `UsartNum` object, which defines the USART to be used.
+/// * `baud` - a i64, the baud rate of USART the user wants to set.
+pub fn set_print_usart(num: UsartNum, baud: i64) {
+    unsafe {
+        PRINT_USART = num;
+        PRINT_BAUD = baud;
+        PRINT_READY = false;
+    }
+}
+
+/// Writes formatted text to the USART chosen for printing, which is initialized
+/// with the default frame settings on the first call.
+/// Used by the `serial_print!` and `serial_println!` macros.
+/// # Arguments
+/// * `args` - a `fmt::Arguments` object, the text made by `format_args!`.
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let u: &mut Usart = unsafe { Usart::new(PRINT_USART) };
+    unsafe {
+        if !PRINT_READY {
+            u.transmit_enable();
+            if u.initialize(MODE, PRINT_BAUD, STOP, SIZE, PARITY).is_err() {
+                return;
+            }
+            PRINT_READY = true;
+        }
+    }
+    let _ = u.write_fmt(args);
 }
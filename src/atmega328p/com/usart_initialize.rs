@@ -25,7 +25,7 @@
 use crate::delay::delay_ms;
 use bit_field::BitField;
 use core::ptr::write_volatile;
-use core::{f64, u32, u8};
+use core::{u32, u8};
 use volatile::Volatile;
 
 // Source code crates required
@@ -36,9 +36,6 @@ use crate::atmega328p::hal::power;
 // Some useful constants regarding bit manipulation for USART.
 // Position of clock mode adjuster (xck) bit.
 const USART0_XCK: u8 = 4;
-// System Clock Crystal Oscillator Frequency in mHz.
-const F_OSC: f64 = 1.0000;
-const MULTIPLY: f64 = 1000000.00;
 
 /// Selection of which USART is to be used.
 #[derive(Clone, Copy)]
@@ -87,6 +84,48 @@ pub enum UsartPolarity {
     Inputrise,
 }
 
+/// Errors found by the receiver in a received frame or while setting the baud rate.
+/// * `FrameError` - The first stop bit was zero, usually a baud rate mismatch.
+/// * `DataOverrun` - The receive buffer was full and at least one frame was lost.
+/// * `ParityError` - The parity bit did not match the data.
+/// * `BaudRateError` - The baud rate differs from the asked one by more than `MAX_BAUD_ERROR`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UsartError {
+    FrameError,
+    DataOverrun,
+    ParityError,
+    BaudRateError,
+}
+
+/// Largest difference between the generated and the asked baud rate, in tenths of a percent.
+/// Beyond about 2% the receiver at the other end starts to sample the wrong bits.
+pub const MAX_BAUD_ERROR: u32 = 20;
+
+/// Gives the UBRR value which comes nearest to the asked baud rate.
+/// # Arguments
+/// * `f_osc` - a u32, the system clock frequency in hertz.
+/// * `baud` - a u32, the baud rate asked by the user.
+/// * `divisor` - a u32, the clock cycles per bit for one count of UBRR, 16, 8 or 2.
+/// # Returns
+/// * `a tuple of u32` - the UBRR value and the baud rate error in tenths of a percent.
+fn baud_setting(f_osc: u32, baud: u32, divisor: u32) -> (u32, u32) {
+    let step = divisor * baud;
+    let mut ubrr = (f_osc + step / 2) / step;
+    if ubrr > 0 {
+        ubrr -= 1;
+    }
+    if ubrr > 4095 {
+        ubrr = 4095;
+    }
+    let actual = f_osc / (divisor * (ubrr + 1));
+    let diff = if actual > baud {
+        actual - baud
+    } else {
+        baud - actual
+    };
+    (ubrr, (diff as u64 * 1000 / baud as u64) as u32)
+}
+
 /// This structure contains various registers needed to control usart communication
 /// through ATMEGA320P device.
 /// USART0 is controlled by a total of 6 registers stored through this structure.
@@ -150,11 +189,11 @@ impl Usart {
     /// * `a tuple` - which contains -
     ///     * `a mutable reference to Port object` - The port which controls the given USART.
     ///     * `a u8` - The index location of XCK bit for mode specific implementation.
-    fn get_port_xck(&mut self) -> (&mut port::Port, u8) {
+    pub(crate) fn get_port_xck(&mut self) -> (&mut port::Port, u8) {
         let num: UsartNum = self.get_num();
 
         match num {
-            UsartNum::Usart0 => (port::Port::new(port::PortName::D), USART0_XCK),
+            UsartNum::Usart0 => (unsafe { port::Port::new(port::PortName::D) }, USART0_XCK),
         }
     }
 
@@ -253,14 +292,10 @@ impl Usart {
     /// # Arguments
     /// * `num` - a `UsartNum` object, for which the power configurations of the USART will be set.
     pub fn set_power(&mut self, num: UsartNum) {
-        let pow: &mut power::Power;
-
-        pow = power::Power::new();
+        let pow = unsafe { power::Power::new() };
 
         match num {
-            UsartNum::Usart0 => unsafe {
-                write_volatile(&mut pow.prr, pow.prr & !(1 << 1));
-            },
+            UsartNum::Usart0 => pow.enable(power::Peripheral::Usart0),
         }
     }
 
@@ -282,23 +317,48 @@ impl Usart {
     /// clock generator.
     /// Set the baud rate frequency for USART.
     /// Baud rate settings is used to set the clock for USART.
+    /// In asynchronous mode both normal and double speed are tried and the one
+    /// giving the smaller baud rate error is used, so the U2X bit may differ from `mode`.
+    /// Normal speed is kept when both are equally good, as the receiver then takes
+    /// more samples of each bit, unless double speed was asked for.
     /// # Arguments
-    /// * `mode` - a `UsartModes` object, which defines the mode of USART to use.
     /// * `baud` - a i64, the baud rate of USART the user wants to set.
-    fn set_clock(&mut self, baud: i64, mode: UsartModes) {
-        let ubrr: u32;
-        match mode {
-            UsartModes::Normasync => {
-                ubrr = (((F_OSC * MULTIPLY) / (16.00 * baud as f64)) - 1.00) as u32;
-            }
-            UsartModes::Douasync => {
-                ubrr = (((F_OSC * MULTIPLY) / (8.00 * baud as f64)) - 1.00) as u32;
+    /// * `mode` - a `UsartModes` object, which defines the mode of USART to use.
+    /// # Returns
+    /// * `a Result` - `UsartError::BaudRateError` if the error is above `MAX_BAUD_ERROR`.
+    fn set_clock(&mut self, baud: i64, mode: UsartModes) -> Result<(), UsartError> {
+        if baud <= 0 {
+            unreachable!()
+        }
+        // Effective system clock frequency, which depends on the clock prescaler.
+        let f_osc = crate::config::cpu_frequency_hz();
+        let baud = baud as u32;
+        let (ubrr, error, double) = match mode {
+            UsartModes::Normasync | UsartModes::Douasync => {
+                let (ubrr_normal, error_normal) = baud_setting(f_osc, baud, 16);
+                let (ubrr_double, error_double) = baud_setting(f_osc, baud, 8);
+                let prefer_double = match mode {
+                    UsartModes::Douasync => error_double <= error_normal,
+                    _ => error_double < error_normal,
+                };
+                if prefer_double {
+                    (ubrr_double, error_double, true)
+                } else {
+                    (ubrr_normal, error_normal, false)
+                }
             }
             UsartModes::Mastersync => {
-                ubrr = (((F_OSC * MULTIPLY) / (2.00 * baud as f64)) - 1.00) as u32;
+                let (ubrr, error) = baud_setting(f_osc, baud, 2);
+                (ubrr, error, false)
             }
             _ => unreachable!(),
+        };
+        if error > MAX_BAUD_ERROR {
+            return Err(UsartError::BaudRateError);
         }
+        self.ucsra.update(|sra| {
+            sra.set_bit(1, double);
+        });
         self.ubrrl.update(|ubrrl| {
             for i in 0..8 {
                 ubrrl.set_bit(i, ubrr.get_bit(i));
@@ -309,6 +369,7 @@ impl Usart {
                 ubrrh.set_bit(i, ubrr.get_bit(i + 8));
             }
         });
+        Ok(())
     }
 
     ///  Sets the limit of data to be handled by USART.
@@ -424,6 +485,8 @@ impl Usart {
     /// * `size` - a `UsartDatSize` object, the size of set of bits to transmit.
     /// * `parity` - a `UsartParity` object, which gives the Parity bit mode for USART.
     /// * `stop` - a `UsartStop` object, which will be used to set the stop bits of data frame.
+    /// # Returns
+    /// * `a Result` - `UsartError::BaudRateError` if the baud rate can not be generated closely enough.
     pub fn initialize(
         &mut self,
         mode: UsartModes,
@@ -431,7 +494,7 @@ impl Usart {
         stop: UsartStop,
         size: UsartDataSize,
         parity: UsartParity,
-    ) {
+    ) -> Result<(), UsartError> {
         // Check that recieve and transmit buffers are completely cleared
         // and no transmission or recieve of data is already in process.
         let mut i: i32 = 10;
@@ -452,16 +515,15 @@ impl Usart {
         self.mode_select(mode); //  Set the USART at the given mode.
 
         //  Set the clock for USART according to user input.
-        match mode {
-            UsartModes::Slavesync => {}
-            _ => {
-                self.set_clock(baud, mode);
-            }
-        }
+        let clock = match mode {
+            UsartModes::Slavesync => Ok(()),
+            _ => self.set_clock(baud, mode),
+        };
 
         //  Set the frame format according to input.
         self.set_frame(stop, size, parity);
 
         self.enable(); //  Enable Global interrupts.
+        clock
     }
 }
@@ -0,0 +1,129 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Loopback self-test for the USART of ATMEGA328P, useful for production testing of boards.
+//! A known pattern is sent and every byte has to come back unchanged before a timeout.
+//! The chip has no internal loopback, so TXD (digital pin 1) has to be wired to
+//! RXD (digital pin 0), or the port connected to a device which echoes what it receives.
+//! See the section 19 of ATMEGA328P datasheet.
+
+// Source code crates required
+use crate::atmega328p::com::usart_initialize::Usart;
+
+// Crates which would be used in the implementation.
+use crate::delay::delay_us;
+use bit_field::BitField;
+
+/// Pattern sent by `self_test()`, it has alternating bits, all zeros, all ones
+/// and single bits at both ends of the byte, so stuck or swapped lines show up.
+pub const LOOPBACK_PATTERN: [u8; 10] = [0x55, 0xAA, 0x00, 0xFF, 0x0F, 0xF0, 0x01, 0x80, 0x7E, 0x81];
+
+/// Time in milliseconds to wait for each byte to come back.
+pub const LOOPBACK_TIMEOUT_MS: u32 = 50;
+
+/// Result of a loopback test.
+/// * `sent` - the number of bytes sent.
+/// * `received` - the number of bytes which came back before the timeout.
+/// * `mismatches` - the number of bytes which came back with a different value.
+/// * `errors` - the number of bytes which came back with a frame, parity or overrun error.
+/// * `first_failure` - the position in the pattern of the first byte which failed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LoopbackReport {
+    pub sent: u16,
+    pub received: u16,
+    pub mismatches: u16,
+    pub errors: u16,
+    pub first_failure: Option<u16>,
+}
+
+impl LoopbackReport {
+    /// Checks if every byte came back unchanged and without errors.
+    /// # Returns
+    /// * `a boolean` - true if the test passed.
+    pub fn passed(&self) -> bool {
+        self.received == self.sent && self.mismatches == 0 && self.errors == 0
+    }
+}
+
+impl Usart {
+    /// Sends every byte of the pattern and checks that it is received back.
+    /// The USART has to be initialized, the transmitter and the receiver are enabled here.
+    /// Buffered reception has to be off, as its interrupt would take the bytes.
+    /// # Arguments
+    /// * `pattern` - a u8 slice, the bytes to be sent.
+    /// # Returns
+    /// * `a LoopbackReport object` - the counts of the bytes which came back right and wrong.
+    pub fn loopback_test(&mut self, pattern: &[u8]) -> LoopbackReport {
+        if self.ucsrb.read().get_bit(7) {
+            unreachable!()
+        }
+        self.transmit_enable();
+        self.recieve_enable();
+
+        // Frames received before the test are dropped.
+        while self.available() {
+            let _ = self.udr.read();
+        }
+
+        let mut report = LoopbackReport {
+            sent: 0,
+            received: 0,
+            mismatches: 0,
+            errors: 0,
+            first_failure: None,
+        };
+        for (i, &byte) in pattern.iter().enumerate() {
+            self.transmit_data(byte);
+            report.sent += 1;
+
+            // The receiver is checked every 10 microseconds.
+            let mut polls = LOOPBACK_TIMEOUT_MS * 100;
+            let mut reply = None;
+            while reply.is_none() && polls != 0 {
+                reply = self.try_read_checked();
+                polls -= 1;
+                delay_us(10);
+            }
+
+            let failed = match reply {
+                Some(Ok(value)) => {
+                    report.received += 1;
+                    if value != byte {
+                        report.mismatches += 1;
+                    }
+                    value != byte
+                }
+                Some(Err(_)) => {
+                    report.received += 1;
+                    report.errors += 1;
+                    true
+                }
+                None => true,
+            };
+            if failed && report.first_failure.is_none() {
+                report.first_failure = Some(i as u16);
+            }
+        }
+        report
+    }
+
+    /// Runs the loopback test with `LOOPBACK_PATTERN`.
+    /// # Returns
+    /// * `a LoopbackReport object` - the counts of the bytes which came back right and wrong.
+    pub fn self_test(&mut self) -> LoopbackReport {
+        self.loopback_test(&LOOPBACK_PATTERN)
+    }
+}
@@ -0,0 +1,151 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! The USART of ATMEGA328P used as a SPI master (MSPIM).
+//! XCK gives the clock, TXD the data out (MOSI) and RXD the data in (MISO),
+//! so a second SPI bus is available next to the SPI module. There is no SS pin,
+//! the chip select of the device has to be driven with a general purpose pin.
+//! Section 20 of ATmega328P datasheet.
+
+// Source code crates required
+use crate::atmega328p::com::spi::{BitOrder, SpiMode, SpiSettings};
+use crate::atmega328p::com::usart_initialize::{Usart, UsartNum};
+
+// Crates to be used for the implementation.
+use bit_field::BitField;
+use core::ptr::{read_volatile, write_volatile};
+
+// Bits of UCSRnC in master SPI mode.
+const UCPOL: u8 = 0;
+const UCPHA: u8 = 1;
+const UDORD: u8 = 2;
+
+/// A USART working as a SPI master.
+pub struct UsartSpi {
+    usart: &'static mut Usart,
+}
+
+impl UsartSpi {
+    /// Puts the USART into master SPI mode with the given settings,
+    /// the transmitter and the receiver are enabled.
+    /// # Arguments
+    /// * `num` - a `UsartNum` object, the USART to be used.
+    /// * `settings` - a `SpiSettings` object, the clock rate, mode and bit order of the bus.
+    /// # Returns
+    /// * `a UsartSpi object` - which will be used for the transfers.
+    pub unsafe fn new(num: UsartNum, settings: &SpiSettings) -> UsartSpi {
+        let usart = Usart::new(num);
+        usart.set_power(num);
+
+        // The baud rate register has to be zero while the mode is changed.
+        usart.ubrrl.write(0);
+        usart.ubrrh.write(0);
+
+        // XCK is the clock output.
+        let (port, xck) = usart.get_port_xck();
+        write_volatile(&mut port.ddr, read_volatile(&port.ddr) | 1 << xck);
+
+        usart.ucsrc.update(|src| {
+            src.set_bits(6..8, 0b11);
+        });
+        usart.ucsrb.update(|srb| {
+            srb.set_bit(3, true);
+            srb.set_bit(4, true);
+        });
+
+        let mut spi = UsartSpi { usart };
+        spi.configure(settings);
+        spi
+    }
+
+    /// Applies the clock rate, mode and bit order of a device.
+    /// The clock is the system clock divided by 2 * (UBRR + 1) and the
+    /// highest one which does not go above `clock_hz` is used.
+    /// # Arguments
+    /// * `settings` - a `SpiSettings` object, the settings to be applied.
+    pub fn configure(&mut self, settings: &SpiSettings) {
+        if settings.clock_hz == 0 {
+            unreachable!()
+        }
+        let f_cpu = crate::config::cpu_frequency_hz();
+        let step = 2 * settings.clock_hz;
+        let mut ubrr = (f_cpu + step - 1) / step;
+        if ubrr > 0 {
+            ubrr -= 1;
+        }
+        if ubrr > 4095 {
+            ubrr = 4095;
+        }
+
+        let (cpol, cpha) = match settings.mode {
+            SpiMode::Mode0 => (false, false),
+            SpiMode::Mode1 => (false, true),
+            SpiMode::Mode2 => (true, false),
+            SpiMode::Mode3 => (true, true),
+        };
+        self.usart.ucsrc.update(|src| {
+            src.set_bit(UCPOL, cpol);
+            src.set_bit(UCPHA, cpha);
+            src.set_bit(UDORD, settings.bit_order == BitOrder::LsbFirst);
+        });
+        self.usart.ubrrh.write((ubrr >> 8) as u8);
+        self.usart.ubrrl.write(ubrr as u8);
+    }
+
+    /// Sends one byte and receives one byte at the same time.
+    /// # Arguments
+    /// * `data` - a u8, the byte to be sent.
+    /// # Returns
+    /// * `a u8` - the byte received.
+    pub fn transfer_byte(&mut self, data: u8) -> u8 {
+        while !self.usart.ucsra.read().get_bit(5) {}
+        self.usart.udr.write(data);
+        while !self.usart.ucsra.read().get_bit(7) {}
+        self.usart.udr.read()
+    }
+
+    /// Sends the bytes of the buffer and replaces each with the byte received.
+    /// # Arguments
+    /// * `data` - a mutable u8 slice, the bytes to be sent.
+    pub fn transfer(&mut self, data: &mut [u8]) {
+        for byte in data.iter_mut() {
+            *byte = self.transfer_byte(*byte);
+        }
+    }
+
+    /// Sends the bytes and drops the bytes received.
+    /// # Arguments
+    /// * `data` - a u8 slice, the bytes to be sent.
+    pub fn write(&mut self, data: &[u8]) {
+        for byte in data.iter() {
+            self.transfer_byte(*byte);
+        }
+    }
+
+    /// Disables the transmitter and the receiver and puts the USART back
+    /// into asynchronous mode, so that it can be initialized again.
+    pub fn end(self) {
+        self.usart.ucsrb.update(|srb| {
+            srb.set_bit(3, false);
+            srb.set_bit(4, false);
+        });
+        self.usart.ucsrc.update(|src| {
+            // Asynchronous mode with 8 data bits, the value after reset.
+            src.set_bits(6..8, 0b00);
+            src.set_bits(0..3, 0b110);
+        });
+    }
+}
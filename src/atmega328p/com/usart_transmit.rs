@@ -25,7 +25,7 @@ use crate::delay::delay_ms;
 // Crates which would be used in the implementation.
 // We will be using standard volatile and bit_field crates now for a better read and write.
 use bit_field::BitField;
-use core::{f64, u8, usize};
+use core::{f64, fmt, u8, usize};
 use fixed_slice_vec::FixedSliceVec;
 
 // This is a implementation for Usart
@@ -261,4 +261,92 @@ impl Usart {
             vec.push(vec[ia]);
         }
     }
+
+    /// Sends one frame of nine data bits, the USART has to be initialized with `UsartDataSize::Nine`.
+    /// The ninth bit is written to TXB8 before the lower bits are written to UDR.
+    /// # Arguments
+    /// * `data` - a u16, of which the lower nine bits are transmitted.
+    pub fn write_nine_bit(&mut self, data: u16) {
+        while !self.ucsra.read().get_bit(5) {}
+        self.ucsrb.update(|ucsrb| {
+            ucsrb.set_bit(0, data.get_bit(8));
+        });
+        self.udr.write(data as u8);
+    }
+
+    /// Sends an address frame on a multi-processor bus, a nine bit frame with the ninth bit set.
+    /// Receivers in multi-processor mode only accept the data frames which follow their address.
+    /// # Arguments
+    /// * `address` - a u8, the address of the receiver.
+    pub fn send_address(&mut self, address: u8) {
+        self.write_nine_bit(0x100 | address as u16);
+    }
+
+    /// Sends a data frame on a multi-processor bus, a nine bit frame with the ninth bit cleared.
+    /// # Arguments
+    /// * `data` - a u8, the data to be sent.
+    pub fn send_bus_data(&mut self, data: u8) {
+        self.write_nine_bit(data as u16);
+    }
+}
+
+/// Formatted output through the USART, so that `write!` and `writeln!` can be used.
+/// The USART has to be initialized with the transmitter enabled.
+impl fmt::Write for Usart {
+    /// Sends the bytes of the string one by one.
+    /// # Arguments
+    /// * `s` - a string object, which is to be transmitted using USART.
+    /// # Returns
+    /// * `a fmt::Result` - which is always Ok.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.transmit_data(byte);
+        }
+        Ok(())
+    }
+}
+
+/// Formatted output through the USART with the `ufmt` crate, which is much
+/// smaller than `core::fmt` on AVR.
+#[cfg(feature = "ufmt")]
+impl ufmt_write::uWrite for Usart {
+    type Error = core::convert::Infallible;
+
+    /// Sends the bytes of the string one by one.
+    /// # Arguments
+    /// * `s` - a string object, which is to be transmitted using USART.
+    /// # Returns
+    /// * `a Result` - which is always Ok.
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        for byte in s.bytes() {
+            self.transmit_data(byte);
+        }
+        Ok(())
+    }
+}
+
+/// The transmitter for the drivers written against the `embedded-hal` serial traits.
+/// The transmitter cannot fail, so the error is `Infallible`.
+#[cfg(feature = "embedded")]
+impl embedded_hal::serial::Write<u8> for &mut Usart {
+    type Error = core::convert::Infallible;
+
+    /// Puts the byte in the transmit buffer if it is empty.
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        if self.avai_write() {
+            self.udr.write(byte);
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Waits until the transmit buffer is empty, the last byte may still be shifted out.
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if self.avai_write() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
 }
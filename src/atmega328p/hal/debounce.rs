@@ -0,0 +1,124 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Debounced reading of a push button or switch connected to a digital pin.
+//! The pin is sampled at a fixed interval and a new level is accepted only
+//! after it has been read the same number of times given by the stable count.
+
+// Source codes to be used here.
+use crate::atmega328p::hal::pin::DigitalPin;
+use crate::delay::delay_ms;
+
+/// Debouncer wrapping a digital pin configured as input with pull-up.
+/// The button is expected to connect the pin to ground, so it is pressed
+/// when the pin is low.
+pub struct Debouncer {
+    pin: DigitalPin,
+    interval_ms: u32,
+    stable_count: u8,
+    count: u8,
+    sample: bool,
+    level: bool,
+    rose: bool,
+    fell: bool,
+}
+
+impl Debouncer {
+    /// Creates a new debouncer and configures the pin as input with pull-up.
+    /// # Arguments
+    /// * `pin` - a `DigitalPin` object, the pin to which the button is connected.
+    /// * `interval_ms` - a u32, the time in milliseconds between two samples.
+    /// * `stable_count` - a u8, the number of equal samples needed to accept a new level.
+    /// # Returns
+    /// * `a Debouncer object` - which will be used for further implementations.
+    pub fn new(mut pin: DigitalPin, interval_ms: u32, stable_count: u8) -> Debouncer {
+        pin.set_input_pullup();
+        let level = pin.read() != 0;
+        Debouncer {
+            pin,
+            interval_ms,
+            stable_count,
+            count: 0,
+            sample: level,
+            level,
+            rose: false,
+            fell: false,
+        }
+    }
+
+    /// Waits for the sampling interval and then takes one sample of the pin.
+    /// Has to be called regularly, `rose()` and `fell()` report the change
+    /// found by the last call only.
+    pub fn update(&mut self) {
+        self.rose = false;
+        self.fell = false;
+
+        delay_ms(self.interval_ms);
+        let sample = self.pin.read() != 0;
+
+        if sample != self.sample {
+            // The pin is still bouncing, start counting again.
+            self.sample = sample;
+            self.count = 0;
+        } else if self.count < self.stable_count {
+            self.count += 1;
+        }
+
+        if self.count >= self.stable_count && self.sample != self.level {
+            self.level = self.sample;
+            if self.level {
+                self.rose = true;
+            } else {
+                self.fell = true;
+            }
+        }
+    }
+
+    /// Returns the debounced level of the pin.
+    /// # Returns
+    /// * `a boolean` - true if the pin is high.
+    pub fn is_high(&self) -> bool {
+        self.level
+    }
+
+    /// Checks if the button is pressed, that is the debounced level is low.
+    /// # Returns
+    /// * `a boolean` - true if the button is pressed.
+    pub fn is_pressed(&self) -> bool {
+        !self.level
+    }
+
+    /// Checks if the debounced level went from low to high in the last `update()`.
+    /// # Returns
+    /// * `a boolean` - true on a rising edge, that is when the button is released.
+    pub fn rose(&self) -> bool {
+        self.rose
+    }
+
+    /// Checks if the debounced level went from high to low in the last `update()`.
+    /// # Returns
+    /// * `a boolean` - true on a falling edge, that is when the button is pressed.
+    pub fn fell(&self) -> bool {
+        self.fell
+    }
+
+    /// Gives back the pin used by the debouncer.
+    /// # Returns
+    /// * `a DigitalPin object` - the pin of the button.
+    pub fn release(self) -> DigitalPin {
+        self.pin
+    }
+}
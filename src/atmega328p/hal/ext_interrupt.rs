@@ -0,0 +1,301 @@
+// RustDuino : A generic HAL implementation for Arduino Boards in Rust
+// Copyright (C) 2021 Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! External interrupts INT0 and INT1 of the ATMEGA328P chip are controlled here.
+//! A handler function can be attached to each line which is called from the
+//! interrupt vector whenever the selected trigger occurs on the pin.
+//! The pin change interrupts of the digital pins are handled here as well,
+//! with one handler for each group of eight pins.
+//! Section 12 of ATMEGA328P datasheet.
+
+// Crates to be used for the implementation.
+use bit_field::BitField;
+use volatile::Volatile;
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+// Source codes to be used here.
+use crate::atmega328p::hal::interrupts;
+use crate::atmega328p::hal::port::PortName;
+
+/// Selection of the external interrupt line.
+/// `Int0` is on digital pin 2 and `Int1` is on digital pin 3.
+#[derive(Clone, Copy)]
+pub enum IntLine {
+    Int0,
+    Int1,
+}
+
+/// Selection of the event on the pin which triggers the interrupt.
+/// * `LowLevel` - The interrupt is triggered as long as the pin is low.
+/// * `Change` - Any logical change on the pin triggers the interrupt.
+/// * `Falling` - The falling edge on the pin triggers the interrupt.
+/// * `Rising` - The rising edge on the pin triggers the interrupt.
+#[derive(Clone, Copy)]
+pub enum Trigger {
+    LowLevel,
+    Change,
+    Falling,
+    Rising,
+}
+
+/// Contains the registers controlling the external interrupts.
+///
+/// * `EIFR`: *External Interrupt Flag Register*. The flag of a line is set when
+/// its trigger occurs and cleared when the interrupt routine is executed.
+///
+/// * `EIMSK`: *External Interrupt Mask Register*. Writing one to the bit of a line
+/// enables the interrupt request of that line.
+///
+/// * `EICRA`: *External Interrupt Control Register A*. Two bits for each line select
+/// the event which triggers the interrupt.
+#[repr(C, packed)]
+pub struct ExtInterrupt {
+    eifr: Volatile<u8>,
+    eimsk: Volatile<u8>,
+    _pad: [u8; 0x2B],
+    eicra: Volatile<u8>,
+}
+
+/// Handler functions attached to each external interrupt line.
+static mut HANDLERS: [Option<fn()>; 2] = [None, None];
+
+/// Address of PCICR, the pin change interrupt control register.
+const PCICR: *mut u8 = 0x68 as *mut u8;
+
+/// Address of PCMSK0, followed by PCMSK1 and PCMSK2.
+const PCMSK0: *mut u8 = 0x6B as *mut u8;
+
+/// Address of PCIFR, the pin change interrupt flag register.
+const PCIFR: *mut u8 = 0x3B as *mut u8;
+
+/// Handler functions attached to each pin change group.
+static mut PIN_CHANGE_HANDLERS: [Option<fn()>; 3] = [None; 3];
+
+impl IntLine {
+    /// Gives the bit of the line in the EIMSK and EIFR registers.
+    fn index(&self) -> u8 {
+        match self {
+            IntLine::Int0 => 0,
+            IntLine::Int1 => 1,
+        }
+    }
+}
+
+impl ExtInterrupt {
+    /// Creates a new reference to the External Interrupt structure at a specified location.
+    /// # Returns
+    /// * `a reference to ExtInterrupt object` - which will be used for further implementations.
+    pub unsafe fn new() -> &'static mut ExtInterrupt {
+        &mut *(0x3C as *mut ExtInterrupt)
+    }
+
+    /// Set the event which triggers the interrupt of the line.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be configured.
+    /// * `trigger` - a `Trigger` object, the event on the pin which triggers the interrupt.
+    pub fn set_trigger(&mut self, line: IntLine, trigger: Trigger) {
+        let bits: u8 = match trigger {
+            Trigger::LowLevel => 0b00,
+            Trigger::Change => 0b01,
+            Trigger::Falling => 0b10,
+            Trigger::Rising => 0b11,
+        };
+        let pos = 2 * line.index();
+        self.eicra.update(|eicra| {
+            eicra.set_bits(pos..pos + 2, bits);
+        });
+    }
+
+    /// Enable the interrupt request of the line.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be enabled.
+    pub fn enable(&mut self, line: IntLine) {
+        self.eimsk.update(|eimsk| {
+            eimsk.set_bit(line.index(), true);
+        });
+    }
+
+    /// Disable the interrupt request of the line.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be disabled.
+    pub fn disable(&mut self, line: IntLine) {
+        self.eimsk.update(|eimsk| {
+            eimsk.set_bit(line.index(), false);
+        });
+    }
+
+    /// Clears a pending interrupt of the line by writing one to its flag.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line whose flag is to be cleared.
+    pub fn clear_flag(&mut self, line: IntLine) {
+        self.eifr.write(1 << line.index());
+    }
+
+    /// Attach a handler function to the line which will be called on every trigger.
+    /// Global interrupts have to be enabled for the handler to be called.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be used.
+    /// * `trigger` - a `Trigger` object, the event on the pin which triggers the interrupt.
+    /// * `handler` - a function, which is called from the interrupt vector of the line.
+    pub fn attach(&mut self, line: IntLine, trigger: Trigger, handler: fn()) {
+        interrupts::free(|_| {
+            self.disable(line);
+            unsafe {
+                HANDLERS[line.index() as usize] = Some(handler);
+            }
+            self.set_trigger(line, trigger);
+            self.clear_flag(line);
+            self.enable(line);
+        });
+    }
+
+    /// Detach the handler function of the line and disable its interrupt.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line to be released.
+    pub fn detach(&mut self, line: IntLine) {
+        self.disable(line);
+        unsafe {
+            HANDLERS[line.index() as usize] = None;
+        }
+    }
+}
+
+/// Attach a handler function to a external interrupt line.
+/// # Arguments
+/// * `line` - a `IntLine` object, the external interrupt line to be used.
+/// * `trigger` - a `Trigger` object, the event on the pin which triggers the interrupt.
+/// * `handler` - a function, which is called from the interrupt vector of the line.
+pub fn attach_interrupt(line: IntLine, trigger: Trigger, handler: fn()) {
+    unsafe { ExtInterrupt::new() }.attach(line, trigger, handler);
+}
+
+/// Detach the handler function of a external interrupt line.
+/// # Arguments
+/// * `line` - a `IntLine` object, the external interrupt line to be released.
+pub fn detach_interrupt(line: IntLine) {
+    unsafe { ExtInterrupt::new() }.detach(line);
+}
+
+/// Calls the handler attached to the line, if any.
+#[cfg_attr(not(target_arch = "avr"), allow(dead_code))]
+fn dispatch(index: usize) {
+    if let Some(handler) = unsafe { HANDLERS[index] } {
+        handler();
+    }
+}
+
+/// Interrupt vector of INT0.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_1() {
+    dispatch(0);
+}
+
+/// Interrupt vector of INT1.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_2() {
+    dispatch(1);
+}
+
+/// Gives the port and the bit of a digital pin with a pin change interrupt.
+/// Digital pins 0 to 7 are PD0 to PD7 and pins 8 to 13 are PB0 to PB5.
+/// # Arguments
+/// * `pin` - a usize, the number of the digital pin, 0 to 13.
+/// # Returns
+/// * `a tuple (PortName, u8)` - the port and the bit of the pin.
+pub(crate) fn pin_change_port(pin: usize) -> (PortName, u8) {
+    match pin {
+        0..=7 => (PortName::D, pin as u8),
+        8..=13 => (PortName::B, (pin - 8) as u8),
+        _ => unreachable!(),
+    }
+}
+
+/// Gives the pin change group and the bit of the pin in its PCMSK register.
+/// Pins 0 to 7 are PCINT16 to PCINT23 and pins 8 to 13 are PCINT0 to PCINT5.
+pub(crate) fn pin_change_mask(pin: usize) -> (usize, u8) {
+    match pin_change_port(pin) {
+        (PortName::D, bit) => (2, bit),
+        (_, bit) => (0, bit),
+    }
+}
+
+/// Attach a handler function to the pin change interrupt of a pin.
+/// The handler is called on every change of any enabled pin of the group, so
+/// pins of the same group share one handler and attaching replaces it.
+/// Global interrupts have to be enabled for the handler to be called.
+/// # Arguments
+/// * `pin` - a usize, the number of the digital pin 0 to 13.
+/// * `handler` - a function, which is called from the interrupt vector of the group.
+pub fn attach_pin_change(pin: usize, handler: fn()) {
+    let (group, bit) = pin_change_mask(pin);
+    interrupts::free(|_| unsafe {
+        PIN_CHANGE_HANDLERS[group] = Some(handler);
+        let pcmsk = PCMSK0.add(group);
+        write_volatile(pcmsk, read_volatile(pcmsk) | (1 << bit));
+        // The flag is cleared by writing one to it.
+        write_volatile(PCIFR, 1 << group);
+        write_volatile(PCICR, read_volatile(PCICR) | (1 << group));
+    });
+}
+
+/// Disable the pin change interrupt of a pin.
+/// The handler of the group is detached when no pin of the group is left enabled.
+/// # Arguments
+/// * `pin` - a usize, the number of the digital pin 0 to 13.
+pub fn detach_pin_change(pin: usize) {
+    let (group, bit) = pin_change_mask(pin);
+    unsafe {
+        let pcmsk = PCMSK0.add(group);
+        write_volatile(pcmsk, read_volatile(pcmsk) & !(1 << bit));
+        if read_volatile(pcmsk) == 0 {
+            write_volatile(PCICR, read_volatile(PCICR) & !(1 << group));
+            PIN_CHANGE_HANDLERS[group] = None;
+        }
+    }
+}
+
+/// Calls the handler attached to the pin change group, if any.
+#[cfg_attr(not(target_arch = "avr"), allow(dead_code))]
+fn dispatch_pin_change(group: usize) {
+    if let Some(handler) = unsafe { PIN_CHANGE_HANDLERS[group] } {
+        handler();
+    }
+}
+
+/// Interrupt vector of pin change group 0.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_3() {
+    dispatch_pin_change(0);
+}
+
+/// Interrupt vector of pin change group 1.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_4() {
+    dispatch_pin_change(1);
+}
+
+/// Interrupt vector of pin change group 2.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_5() {
+    dispatch_pin_change(2);
+}
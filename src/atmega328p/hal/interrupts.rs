@@ -17,7 +17,12 @@
 //! Global interrupts configured in the ATMEGA328P chip is controlled here.
 //! Section 6.3 of the manual
 
+use crate::sync::CriticalSection;
 use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{compiler_fence, Ordering};
+
+/// Address of SREG, whose bit 7 enables the global interrupts.
+const SREG: *mut u8 = 0x5F as *mut u8;
 
 /// SREG (Status control Register)
 /// The status register contains information about the result of the most recently executed arithmetic instruction. This
@@ -54,8 +59,142 @@ impl Interrupt {
     pub fn enable(&mut self) {
         unsafe {
             let mut ctrl_sreg = read_volatile(&self.sreg);
-            ctrl_sreg &= 0xFF;
+            ctrl_sreg |= 0x80;
             write_volatile(&mut self.sreg, ctrl_sreg);
         }
     }
 }
+
+/// Runs the closure with the global interrupts disabled and then restores SREG,
+/// so the interrupts are enabled again only if they were enabled before.
+/// The closure is given a critical section token, which is needed to reach the data
+/// shared with the interrupt handlers through `sync::Mutex`.
+/// # Arguments
+/// * `f` - a closure, which takes a `CriticalSection` token and gives a value of any type.
+/// # Returns
+/// * `the value given by the closure`.
+pub fn free<F, R>(f: F) -> R
+where
+    F: FnOnce(CriticalSection) -> R,
+{
+    unsafe {
+        let sreg = read_volatile(SREG);
+        write_volatile(SREG, sreg & 0x7F);
+        compiler_fence(Ordering::SeqCst);
+
+        let result = f(CriticalSection::new());
+
+        compiler_fence(Ordering::SeqCst);
+        write_volatile(SREG, sreg);
+        result
+    }
+}
+
+/// Registers a function as the handler of an interrupt, given by its name in the datasheet
+/// like `TIMER1_COMPA` or `USART_RX`, and emits the `__vector_N` function of the ATMEGA328P for it.
+/// The application needs `#![feature(abi_avr_interrupt)]` for the handler to be compiled.
+///
+/// The vectors used by the drivers of this library (INT0, INT1, PCINT0-2, WDT, TIMER1_CAPT, TIMER1_OVF, USART_RX, USART_UDRE, ADC and ANALOG_COMP)
+/// are refused with a compile error, their handlers are attached through the drivers instead.
+///
+/// ```ignore
+/// fn tick() {
+///     // Runs on every compare match of Timer 1 channel A.
+/// }
+///
+/// rustduino::interrupt!(TIMER1_COMPA, tick);
+/// ```
+#[macro_export]
+macro_rules! interrupt {
+    (INT0, $handler:path) => {
+        compile_error!("the INT0 vector is defined by the library, attach the handler through its driver");
+    };
+    (INT1, $handler:path) => {
+        compile_error!("the INT1 vector is defined by the library, attach the handler through its driver");
+    };
+    (PCINT0, $handler:path) => {
+        compile_error!("the PCINT0 vector is defined by the library, attach the handler through its driver");
+    };
+    (PCINT1, $handler:path) => {
+        compile_error!("the PCINT1 vector is defined by the library, attach the handler through its driver");
+    };
+    (PCINT2, $handler:path) => {
+        compile_error!("the PCINT2 vector is defined by the library, attach the handler through its driver");
+    };
+    (WDT, $handler:path) => {
+        compile_error!("the WDT vector is defined by the library, attach the handler through its driver");
+    };
+    (TIMER2_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_7, $handler);
+    };
+    (TIMER2_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_8, $handler);
+    };
+    (TIMER2_OVF, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_9, $handler);
+    };
+    (TIMER1_CAPT, $handler:path) => {
+        compile_error!("the TIMER1_CAPT vector is defined by the library, attach the handler through its driver");
+    };
+    (TIMER1_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_11, $handler);
+    };
+    (TIMER1_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_12, $handler);
+    };
+    (TIMER1_OVF, $handler:path) => {
+        compile_error!("the TIMER1_OVF vector is defined by the library, attach the handler through its driver");
+    };
+    (TIMER0_COMPA, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_14, $handler);
+    };
+    (TIMER0_COMPB, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_15, $handler);
+    };
+    (TIMER0_OVF, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_16, $handler);
+    };
+    (SPI_STC, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_17, $handler);
+    };
+    (USART_RX, $handler:path) => {
+        compile_error!("the USART_RX vector is defined by the library, attach the handler through its driver");
+    };
+    (USART_UDRE, $handler:path) => {
+        compile_error!("the USART_UDRE vector is defined by the library, attach the handler through its driver");
+    };
+    (USART_TX, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_20, $handler);
+    };
+    (ADC, $handler:path) => {
+        compile_error!("the ADC vector is defined by the library, attach the handler through its driver");
+    };
+    (EE_READY, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_22, $handler);
+    };
+    (ANALOG_COMP, $handler:path) => {
+        compile_error!("the ANALOG_COMP vector is defined by the library, attach the handler through its driver");
+    };
+    (TWI, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_24, $handler);
+    };
+    (SPM_READY, $handler:path) => {
+        $crate::__interrupt_vector!(__vector_25, $handler);
+    };
+    ($other:ident, $handler:path) => {
+        compile_error!(concat!("unknown interrupt vector ", stringify!($other)));
+    };
+}
+
+/// Emits the interrupt vector calling the handler, used by `interrupt!`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __interrupt_vector {
+    ($vector:ident, $handler:path) => {
+        #[cfg(target_arch = "avr")]
+        #[no_mangle]
+        pub unsafe extern "avr-interrupt" fn $vector() {
+            $handler();
+        }
+    };
+}
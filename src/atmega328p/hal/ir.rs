@@ -0,0 +1,479 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Infrared remote control of the ATMEGA328P chip, with the NEC and RC5 protocols.
+//! Frames are received from a TSOP style receiver, whose output is low while the
+//! 38 kHz carrier is seen. Every edge of its output raises a pin change interrupt,
+//! and the time since the edge before is taken from Timer 1, which counts freely
+//! with a prescaler of 64. Frames are sent by switching the carrier made by
+//! Timer 2 on digital pin 3, which drives the IR LED through a transistor.
+//! Timer 1 can not be used for PWM on pins 9 and 10 while receiving, and
+//! Timer 2 can not be used for PWM on pins 3 and 11 while sending.
+
+// Crates to be used for the implementation.
+use bit_field::BitField;
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+// Source codes to be used here.
+use crate::atmega328p::hal::analog::{Timer16, TimerNo16, TimerPrescaler, WaveformMode};
+use crate::atmega328p::hal::ext_interrupt::{
+    attach_pin_change, detach_pin_change, pin_change_port,
+};
+use crate::atmega328p::hal::port::{self, Port, PortName};
+use crate::atmega328p::hal::power::{Peripheral, Power};
+use crate::config::CPU_FREQUENCY_HZ;
+use crate::delay::delay_us;
+
+/// Address of the SREG register, used to take the received frame atomically.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+/// Address of TCNT1L, followed by TCNT1H.
+const TCNT1L: *mut u8 = 0x84 as *mut u8;
+
+// Registers of Timer 2, which makes the carrier.
+const TCCR2A: *mut u8 = 0xB0 as *mut u8;
+const TCCR2B: *mut u8 = 0xB1 as *mut u8;
+const OCR2A: *mut u8 = 0xB3 as *mut u8;
+const OCR2B: *mut u8 = 0xB4 as *mut u8;
+
+/// Frequency of the carrier in hertz.
+pub const IR_CARRIER_HZ: u32 = 38_000;
+
+// Timings of the NEC protocol in microseconds.
+const NEC_LEADER_MARK: u32 = 9000;
+const NEC_LEADER_SPACE: u32 = 4500;
+const NEC_REPEAT_SPACE: u32 = 2250;
+const NEC_BIT_MARK: u32 = 560;
+const NEC_ONE_SPACE: u32 = 1690;
+const NEC_ZERO_SPACE: u32 = 560;
+
+/// Half of one bit of the RC5 protocol in microseconds.
+const RC5_HALF_BIT: u32 = 889;
+
+/// Infrared protocol of the remote.
+/// * `Nec` - pulse distance coding with 8 or 16 bit addresses, used by most cheap remotes.
+/// * `Rc5` - Manchester coding with 5 bit addresses, used by Philips remotes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum IrProtocol {
+    Nec,
+    Rc5,
+}
+
+/// One command received from a remote.
+/// * `address` - the address of the device, 16 bits only for extended NEC.
+/// * `command` - the key pressed, 7 bits for RC5.
+/// * `repeat` - true if the key is being held down.
+/// * `toggle` - the RC5 toggle bit, which changes every time a key is pressed again.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct IrCommand {
+    pub protocol: IrProtocol,
+    pub address: u16,
+    pub command: u8,
+    pub repeat: bool,
+    pub toggle: bool,
+}
+
+/// Checks if a measured duration is within a quarter of the expected one.
+fn matches(us: u32, expected: u32) -> bool {
+    us >= expected - expected / 4 && us <= expected + expected / 4
+}
+
+/// State of the frame being received, kept between the edges.
+struct Decoder {
+    protocol: IrProtocol,
+    port: PortName,
+    bit: u8,
+    last_edge: u16,
+    state: u8,
+    count: u8,
+    bits: u32,
+    last_command: Option<IrCommand>,
+    result: Option<IrCommand>,
+}
+
+/// Decoder used by the pin change interrupt.
+static mut DECODER: Option<Decoder> = None;
+
+// States of the decoder.
+const IDLE: u8 = 0;
+const LEADER_SPACE: u8 = 1;
+const DATA: u8 = 2;
+
+impl Decoder {
+    /// Takes one edge of the receiver output.
+    /// # Arguments
+    /// * `now` - a u16, the value of Timer 1 at the edge.
+    fn edge(&mut self, now: u16) {
+        let ticks = now.wrapping_sub(self.last_edge) as u32;
+        self.last_edge = now;
+        let us = ticks * 64 / (CPU_FREQUENCY_HZ / 1_000_000);
+        // The output is low during a mark, so a high level means a mark has just ended.
+        let mark = unsafe { Port::new(self.port) }
+            .read_byte()
+            .get_bit(self.bit);
+        match self.protocol {
+            IrProtocol::Nec => self.nec(mark, us),
+            IrProtocol::Rc5 => self.rc5(mark, us),
+        }
+    }
+
+    /// Stores a complete command for `IrReceiver::read()`.
+    fn finish(&mut self, command: IrCommand) {
+        self.last_command = Some(command);
+        self.result = Some(command);
+        self.state = IDLE;
+    }
+
+    /// Steps the NEC decoder by the mark or space which has just ended.
+    fn nec(&mut self, mark: bool, us: u32) {
+        match self.state {
+            IDLE => {
+                if mark && matches(us, NEC_LEADER_MARK) {
+                    self.state = LEADER_SPACE;
+                }
+            }
+            LEADER_SPACE => {
+                if !mark && matches(us, NEC_LEADER_SPACE) {
+                    self.state = DATA;
+                    self.count = 0;
+                    self.bits = 0;
+                } else if !mark && matches(us, NEC_REPEAT_SPACE) {
+                    self.state = IDLE;
+                    if let Some(mut command) = self.last_command {
+                        command.repeat = true;
+                        self.result = Some(command);
+                    }
+                } else {
+                    self.state = IDLE;
+                }
+            }
+            _ => {
+                if mark {
+                    if !matches(us, NEC_BIT_MARK) {
+                        self.state = IDLE;
+                    }
+                    return;
+                }
+                let one = if matches(us, NEC_ONE_SPACE) {
+                    true
+                } else if matches(us, NEC_ZERO_SPACE) {
+                    false
+                } else {
+                    self.state = IDLE;
+                    return;
+                };
+                self.bits.set_bit(self.count, one);
+                self.count += 1;
+                if self.count < 32 {
+                    return;
+                }
+
+                // Address, inverted address, command and inverted command, LSB first.
+                let address = self.bits.get_bits(0..8) as u8;
+                let inverse_address = self.bits.get_bits(8..16) as u8;
+                let command = self.bits.get_bits(16..24) as u8;
+                if command != !(self.bits.get_bits(24..32) as u8) {
+                    self.state = IDLE;
+                    return;
+                }
+                // Extended NEC uses the inverted address byte as the upper address byte.
+                let address = if inverse_address == !address {
+                    address as u16
+                } else {
+                    self.bits.get_bits(0..16) as u16
+                };
+                self.finish(IrCommand {
+                    protocol: IrProtocol::Nec,
+                    address,
+                    command,
+                    repeat: false,
+                    toggle: false,
+                });
+            }
+        }
+    }
+
+    /// Steps the RC5 decoder by the mark or space which has just ended.
+    /// The levels of the half bits are collected in `bits`, 1 for a mark.
+    fn rc5(&mut self, mark: bool, us: u32) {
+        if self.state == IDLE {
+            // The first start bit is a one, a space followed by a mark.
+            if !mark {
+                self.state = DATA;
+                self.count = 1;
+                self.bits = 0;
+            }
+            return;
+        }
+        let halves = if matches(us, RC5_HALF_BIT) {
+            1
+        } else if matches(us, 2 * RC5_HALF_BIT) {
+            2
+        } else {
+            self.state = IDLE;
+            return;
+        };
+        for _ in 0..halves {
+            if self.count < 28 {
+                self.bits.set_bit(self.count, mark);
+                self.count += 1;
+            }
+        }
+        if self.count < 27 {
+            return;
+        }
+        // A last bit of zero ends with a space, which has no edge after it.
+        if self.count == 27 {
+            let last = !self.bits.get_bit(26);
+            self.bits.set_bit(27, last);
+        }
+
+        let mut frame: u16 = 0;
+        for i in 0..14 {
+            let first = self.bits.get_bit(2 * i);
+            let second = self.bits.get_bit(2 * i + 1);
+            if first == second {
+                self.state = IDLE;
+                return;
+            }
+            frame = (frame << 1) | second as u16;
+        }
+
+        // The second start bit is the inverted bit 6 of the command.
+        let mut command = frame.get_bits(0..6) as u8;
+        command.set_bit(6, !frame.get_bit(12));
+        let address = frame.get_bits(6..11);
+        let toggle = frame.get_bit(11);
+        let repeat = match self.last_command {
+            Some(last) => {
+                last.toggle == toggle && last.command == command && last.address == address
+            }
+            None => false,
+        };
+        self.finish(IrCommand {
+            protocol: IrProtocol::Rc5,
+            address,
+            command,
+            repeat,
+            toggle,
+        });
+    }
+}
+
+/// Handler attached to the pin change interrupt of the receiver pin.
+fn on_edge() {
+    // TCNT1L has to be read first, which latches TCNT1H.
+    let now = unsafe {
+        let low = read_volatile(TCNT1L);
+        let high = read_volatile(TCNT1L.add(1));
+        ((high as u16) << 8) | low as u16
+    };
+    if let Some(decoder) = unsafe { DECODER.as_mut() } {
+        decoder.edge(now);
+    }
+}
+
+/// Receives commands from a remote through a TSOP receiver.
+pub struct IrReceiver {
+    pin: usize,
+}
+
+impl IrReceiver {
+    /// Starts Timer 1 and the pin change interrupt of the receiver pin.
+    /// Global interrupts are enabled here.
+    /// # Arguments
+    /// * `pin` - a usize, the digital pin connected to the receiver output, 0 to 13.
+    /// * `protocol` - a `IrProtocol` object, the protocol of the remote.
+    /// # Returns
+    /// * `a IrReceiver object` - which will be used to read the commands.
+    pub fn new(pin: usize, protocol: IrProtocol) -> IrReceiver {
+        let (port_name, bit) = pin_change_port(pin);
+        match unsafe { port::Pin::new(port_name, bit) } {
+            Some(mut input) => input.set_input(),
+            None => unreachable!(),
+        }
+
+        unsafe { Power::new() }.enable(Peripheral::Timer1);
+        let timer = unsafe { Timer16::new(TimerNo16::Timer1) };
+        timer.set_waveform_mode(WaveformMode::Normal);
+        let _ = timer.set_prescaler(TimerPrescaler::Div64);
+
+        unsafe {
+            DECODER = Some(Decoder {
+                protocol,
+                port: port_name,
+                bit,
+                last_edge: 0,
+                state: IDLE,
+                count: 0,
+                bits: 0,
+                last_command: None,
+                result: None,
+            });
+        }
+        attach_pin_change(pin, on_edge);
+        IrReceiver { pin }
+    }
+
+    /// Takes the last command received, without waiting.
+    /// # Returns
+    /// * `a Option<IrCommand>` - the command, None if nothing was received since the last call.
+    pub fn read(&mut self) -> Option<IrCommand> {
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            let result = match DECODER.as_mut() {
+                Some(decoder) => decoder.result.take(),
+                None => None,
+            };
+            write_volatile(SREG, sreg);
+            result
+        }
+    }
+
+    /// Stops the pin change interrupt of the receiver pin.
+    /// Timer 1 is left running.
+    pub fn release(self) {
+        detach_pin_change(self.pin);
+        unsafe {
+            DECODER = None;
+        }
+    }
+}
+
+/// Sends commands through an IR LED driven from digital pin 3.
+pub struct IrTransmitter {
+    _private: (),
+}
+
+impl IrTransmitter {
+    /// Sets Timer 2 to make the 38 kHz carrier with a duty cycle of one third.
+    /// The carrier only reaches the pin during marks.
+    /// # Returns
+    /// * `a IrTransmitter object` - which will be used to send the commands.
+    pub fn new() -> IrTransmitter {
+        match unsafe { port::Pin::new(PortName::D, 3) } {
+            Some(mut led) => {
+                led.set_output();
+                led.low();
+            }
+            None => unreachable!(),
+        }
+
+        unsafe { Power::new() }.enable(Peripheral::Timer2);
+        let top = (CPU_FREQUENCY_HZ / 8 / IR_CARRIER_HZ - 1) as u8;
+        unsafe {
+            // Fast PWM with OCR2A as top, counted at a eighth of the CPU clock.
+            write_volatile(TCCR2A, 0b11);
+            write_volatile(TCCR2B, (1 << 3) | 0b010);
+            write_volatile(OCR2A, top);
+            write_volatile(OCR2B, top / 3);
+        }
+        IrTransmitter { _private: () }
+    }
+
+    /// Sends the carrier for the given time.
+    fn mark(&mut self, us: u32) {
+        unsafe {
+            write_volatile(TCCR2A, read_volatile(TCCR2A) | (0b10 << 4));
+        }
+        delay_us(us);
+    }
+
+    /// Disconnects the carrier from the pin, which is then held low.
+    fn carrier_off(&mut self) {
+        unsafe {
+            write_volatile(TCCR2A, read_volatile(TCCR2A) & !(0b11 << 4));
+        }
+    }
+
+    /// Keeps the LED off for the given time.
+    fn space(&mut self, us: u32) {
+        self.carrier_off();
+        delay_us(us);
+    }
+
+    /// Sends one NEC frame.
+    /// # Arguments
+    /// * `address` - a u16, the address of the device, addresses above 0xFF are sent as extended NEC.
+    /// * `command` - a u8, the key to be sent.
+    pub fn send_nec(&mut self, address: u16, command: u8) {
+        let address_bits: u32 = if address > 0xFF {
+            address as u32
+        } else {
+            (address as u32) | ((!address as u32 & 0xFF) << 8)
+        };
+        let frame = address_bits | ((command as u32) << 16) | ((!command as u32) << 24);
+
+        self.mark(NEC_LEADER_MARK);
+        self.space(NEC_LEADER_SPACE);
+        for i in 0..32 {
+            self.mark(NEC_BIT_MARK);
+            if frame.get_bit(i) {
+                self.space(NEC_ONE_SPACE);
+            } else {
+                self.space(NEC_ZERO_SPACE);
+            }
+        }
+        self.mark(NEC_BIT_MARK);
+        self.carrier_off();
+    }
+
+    /// Sends the NEC repeat code, which is sent every 110 milliseconds while a key is held.
+    pub fn send_nec_repeat(&mut self) {
+        self.mark(NEC_LEADER_MARK);
+        self.space(NEC_REPEAT_SPACE);
+        self.mark(NEC_BIT_MARK);
+        self.carrier_off();
+    }
+
+    /// Sends one RC5 frame.
+    /// # Arguments
+    /// * `address` - a u8, the address of the device, 0 to 31.
+    /// * `command` - a u8, the key to be sent, 0 to 127.
+    /// * `toggle` - a boolean, to be changed every time the key is pressed again.
+    pub fn send_rc5(&mut self, address: u8, command: u8, toggle: bool) {
+        if address > 0x1F || command > 0x7F {
+            unreachable!()
+        }
+        let mut frame: u16 = 0;
+        frame.set_bit(13, true);
+        frame.set_bit(12, !command.get_bit(6));
+        frame.set_bit(11, toggle);
+        frame.set_bits(6..11, address as u16);
+        frame.set_bits(0..6, command as u16 & 0x3F);
+
+        // A one is a space followed by a mark, a zero a mark followed by a space.
+        for i in (0..14).rev() {
+            if frame.get_bit(i) {
+                self.space(RC5_HALF_BIT);
+                self.mark(RC5_HALF_BIT);
+            } else {
+                self.mark(RC5_HALF_BIT);
+                self.space(RC5_HALF_BIT);
+            }
+        }
+        self.carrier_off();
+    }
+}
+
+impl Default for IrTransmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
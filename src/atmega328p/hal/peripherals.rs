@@ -0,0 +1,127 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Single entry point to all the peripherals of the ATMEGA328P chip.
+//! `Peripherals::take()` gives the peripherals only once so that safe code
+//! can not create two mutable owners of the same registers.
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+// Source codes to be used here.
+use crate::atmega328p::hal::analog::Analog;
+use crate::atmega328p::hal::clock::Clock;
+use crate::atmega328p::hal::ext_interrupt::ExtInterrupt;
+use crate::atmega328p::hal::gpior::GeneralPurpose;
+use crate::atmega328p::hal::interrupts::Interrupt;
+use crate::atmega328p::hal::pin::Pins;
+use crate::atmega328p::hal::power::Power;
+use crate::atmega328p::hal::sleep_mode::Sleep;
+use crate::atmega328p::hal::watchdog::WatchDog;
+
+#[cfg(feature = "com")]
+use crate::atmega328p::com::i2c::Twi;
+#[cfg(feature = "com")]
+use crate::atmega328p::com::usart_initialize::{Usart, UsartNum};
+
+/// Address of the SREG register, used to make `take()` free from interrupts.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+/// Set once the peripherals have been given out.
+static mut TAKEN: bool = false;
+
+/// All the peripherals of the chip, each owned exactly once.
+pub struct Peripherals {
+    /// All analog and digital pins.
+    pub pins: Pins,
+
+    /// Watchdog timer.
+    pub watchdog: &'static mut WatchDog,
+
+    /// Analog to digital converter.
+    pub analog: &'static mut Analog,
+
+    /// Sleep mode control.
+    pub sleep: &'static mut Sleep,
+
+    /// Power reduction control.
+    pub power: &'static mut Power,
+
+    /// System clock prescaler.
+    pub clock: &'static mut Clock,
+
+    /// Global interrupt control.
+    pub interrupt: &'static mut Interrupt,
+
+    /// External interrupts INT0 and INT1.
+    pub ext_interrupt: &'static mut ExtInterrupt,
+
+    /// General purpose I/O registers.
+    pub gpior: &'static mut GeneralPurpose,
+
+    /// USART0.
+    #[cfg(feature = "com")]
+    pub usart0: &'static mut Usart,
+
+    /// Two wire interface.
+    #[cfg(feature = "com")]
+    pub twi: &'static mut Twi,
+}
+
+impl Peripherals {
+    /// Gives all the peripherals of the chip, only on the first call.
+    /// # Returns
+    /// * `maybe a Peripherals object` - None if the peripherals were already taken.
+    pub fn take() -> Option<Peripherals> {
+        unsafe {
+            // Keep interrupts away while the flag is checked and set.
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+
+            let peripherals = if TAKEN {
+                None
+            } else {
+                Some(Peripherals::steal())
+            };
+
+            write_volatile(SREG, sreg);
+            peripherals
+        }
+    }
+
+    /// Gives all the peripherals of the chip without checking if they were already taken.
+    /// The caller has to make sure that no two owners use the same registers.
+    /// # Returns
+    /// * `a Peripherals object` - containing all the peripherals.
+    pub unsafe fn steal() -> Peripherals {
+        TAKEN = true;
+        Peripherals {
+            pins: Pins::new(),
+            watchdog: WatchDog::new(),
+            analog: Analog::new(),
+            sleep: Sleep::new(),
+            power: Power::new(),
+            clock: Clock::new(),
+            interrupt: Interrupt::new(),
+            ext_interrupt: ExtInterrupt::new(),
+            gpior: GeneralPurpose::new(),
+            #[cfg(feature = "com")]
+            usart0: Usart::new(UsartNum::Usart0),
+            #[cfg(feature = "com")]
+            twi: Twi::new(),
+        }
+    }
+}
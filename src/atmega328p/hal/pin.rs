@@ -50,9 +50,10 @@ impl Pins {
     /// Returns all pins at once as a single struct.
     /// No new memory is created, just the already created space is given
     /// a name so it is a memory mapped I/O.
+    /// Use `peripherals::Peripherals::take()` to get the only owner without unsafe code.
     /// # Returns
     /// * `a Pins object` - used to control all pins of AVR chip at one place.
-    pub fn new() -> Pins {
+    pub unsafe fn new() -> Pins {
         Pins {
             analog: [
                 AnalogPin {
@@ -147,7 +148,7 @@ impl Pins {
 /// * `a u32` - The pin number which is to be used.
 /// # Returns
 /// * `a Pin object` - The memory mapped I/O object to control the Digital Pin.
-fn _make_pin(pin: u8) -> Pin {
+unsafe fn _make_pin(pin: u8) -> Pin {
     match pin {
         0 => return Pin::new(PortName::D, 0).unwrap(),
         1 => return Pin::new(PortName::D, 1).unwrap(),
@@ -55,15 +55,14 @@ pub struct Port {
 
 impl Port {
     /// Creates a Port of given PortName.
+    /// Use `peripherals::Peripherals::take()` to get the only owner without unsafe code.
     /// # Returns
     /// * `a mutable reference of Port Object` - which will be used for further implementations.
-    pub fn new(port_name: PortName) -> &'static mut Port {
-        unsafe {
-            &mut *match port_name {
-                PortName::B => 0x23 as *mut Port,
-                PortName::C => 0x26 as *mut Port,
-                PortName::D => 0x29 as *mut Port,
-            }
+    pub unsafe fn new(port_name: PortName) -> &'static mut Port {
+        &mut *match port_name {
+            PortName::B => 0x23 as *mut Port,
+            PortName::C => 0x26 as *mut Port,
+            PortName::D => 0x29 as *mut Port,
         }
     }
 
@@ -81,6 +80,39 @@ impl Port {
             _ => unreachable!(),
         }
     }
+
+    /// Sets the direction of all 8 pins of the port in one write to DDRx.
+    /// # Arguments
+    /// * `value` - a u8, one bit per pin, 1 for output and 0 for input.
+    pub fn write_direction(&mut self, value: u8) {
+        unsafe { write_volatile(&mut self.ddr, value) }
+    }
+
+    /// Writes all 8 bits of the PORTx register at once.
+    /// Output pins are driven to the written value and input pins get their
+    /// pull-up resistors enabled where the bit is one.
+    /// # Arguments
+    /// * `value` - a u8, the value to be written to the port.
+    pub fn write_byte(&mut self, value: u8) {
+        unsafe { write_volatile(&mut self.port, value) }
+    }
+
+    /// Reads the logic level of all 8 pins of the port from PINx.
+    /// # Returns
+    /// * `a u8` - one bit per pin, 1 if the pin is high.
+    pub fn read_byte(&self) -> u8 {
+        unsafe { read_volatile(&self.pin) }
+    }
+
+    /// Writes only the bits of PORTx selected by the mask, other bits are left unchanged.
+    /// # Arguments
+    /// * `mask` - a u8, the bits of the port to be changed.
+    /// * `value` - a u8, the new value of the selected bits.
+    pub fn update_byte(&mut self, mask: u8, value: u8) {
+        let port_val = unsafe { read_volatile(&self.port) };
+        let port_val = (port_val & !mask) | (value & mask);
+        unsafe { write_volatile(&mut self.port, port_val) }
+    }
 }
 
 /// Represents a single `Pin`.
@@ -116,7 +148,7 @@ impl Pin {
     /// Creates a Port of given PortName.
     /// # Returns
     /// * `maybe a Pin object` - which will be used for further implementations.
-    pub fn new(port_name: PortName, pin: u8) -> Option<Pin> {
+    pub unsafe fn new(port_name: PortName, pin: u8) -> Option<Pin> {
         Port::new(port_name).pin(pin)
     }
 
@@ -200,6 +232,21 @@ impl Pin {
     pub fn set_output(&mut self) {
         self.set_mode(IOMode::Output);
     }
+
+    /// Change pin mode to input by changing the DDR bit of that pin to 0.
+    pub fn set_input(&mut self) {
+        self.set_mode(IOMode::Input);
+    }
+
+    /// Change pin mode to input with the internal pull-up resistor enabled.
+    /// The DDxn bit is cleared and then the PORTxn bit is set.
+    pub fn set_input_pullup(&mut self) {
+        self.set_mode(IOMode::Input);
+
+        // Read the PORTxn register and set the bit of the pin.
+        let port_val = unsafe { read_volatile(&mut (*self.port).port) };
+        unsafe { write_volatile(&mut (*self.port).port, port_val | (0x1 << self.pin)) }
+    }
 }
 
 impl AnalogPin {
@@ -215,14 +262,25 @@ impl DigitalPin {
         self.pin.set_mode(IOMode::Output);
     }
 
+    /// Change pin mode to Input by changing the value of DDxn register.
+    pub fn set_input(&mut self) {
+        self.pin.set_mode(IOMode::Input);
+    }
+
+    /// Change pin mode to input with the internal pull-up resistor enabled,
+    /// so that the pin reads high when nothing pulls it low.
+    pub fn set_input_pullup(&mut self) {
+        self.pin.set_input_pullup();
+    }
+
     /// Returns the I/O state of the Digital Pin.
     /// # Returns
     /// * `a u8` - The read data from the digital pin.    
     pub fn read(&mut self) -> u8 {
-        let port_val = unsafe { read_volatile(&mut (*self.pin.port).port) };
+        // The level on the pin is read from PINxn, so inputs with pull-up work as well.
+        let pin_val = unsafe { read_volatile(&mut (*self.pin.port).pin) };
 
-        // Check if value of PORTxn is already high, toggle if it isn't.
-        if port_val & (1 << self.pin.pin) == 0 {
+        if pin_val & (1 << self.pin.pin) == 0 {
             return 0;
         } else {
             return 1;
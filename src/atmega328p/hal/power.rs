@@ -37,6 +37,33 @@ pub enum Peripherals {
     ADC,
 }
 
+/// Selection of a peripheral whose clock is controlled by the power reduction register.
+#[derive(Clone, Copy)]
+pub enum Peripheral {
+    Twi,
+    Timer2,
+    Timer0,
+    Timer1,
+    Spi,
+    Usart0,
+    Adc,
+}
+
+impl Peripheral {
+    /// Gives the bit of the peripheral in PRR.
+    fn bit(&self) -> u8 {
+        match self {
+            Peripheral::Twi => 7,
+            Peripheral::Timer2 => 6,
+            Peripheral::Timer0 => 5,
+            Peripheral::Timer1 => 3,
+            Peripheral::Spi => 2,
+            Peripheral::Usart0 => 1,
+            Peripheral::Adc => 0,
+        }
+    }
+}
+
 ///registers controlling power management
 ///
 ///Power Reduction Register control bits for power management.
@@ -47,10 +74,41 @@ pub struct Power {
 
 impl Power {
     /// Creates a new reference to the Sleep structure at a specified location.
+    /// Use `peripherals::Peripherals::take()` to get the only owner without unsafe code.
     /// # Returns
     /// * `a reference Power` - used for further power implementations.    
-    pub fn new() -> &'static mut Self {
-        unsafe { &mut *(0x64 as *mut Self) }
+    pub unsafe fn new() -> &'static mut Self {
+        &mut *(0x64 as *mut Self)
+    }
+
+    /// Stops the clock of a peripheral to save power.
+    /// # Arguments
+    /// * `peripheral` - a `Peripheral` object, the peripheral to be shut down.
+    pub fn disable(&mut self, peripheral: Peripheral) {
+        unsafe {
+            let prr = core::ptr::read_volatile(&self.prr);
+            core::ptr::write_volatile(&mut self.prr, prr | (1 << peripheral.bit()));
+        }
+    }
+
+    /// Starts the clock of a peripheral so that it can be used.
+    /// # Arguments
+    /// * `peripheral` - a `Peripheral` object, the peripheral to be powered.
+    pub fn enable(&mut self, peripheral: Peripheral) {
+        unsafe {
+            let prr = core::ptr::read_volatile(&self.prr);
+            core::ptr::write_volatile(&mut self.prr, prr & !(1 << peripheral.bit()));
+        }
+    }
+
+    /// Checks if the clock of a peripheral is running.
+    /// # Arguments
+    /// * `peripheral` - a `Peripheral` object, the peripheral to be checked.
+    /// # Returns
+    /// * `a boolean` - true if the peripheral is powered.
+    pub fn is_enabled(&self, peripheral: Peripheral) -> bool {
+        let prr = unsafe { core::ptr::read_volatile(&self.prr) };
+        prr & (1 << peripheral.bit()) == 0
     }
 
     /// Power control for functioning of Two Wire Interface.
@@ -121,13 +179,13 @@ impl Power {
     /// * `mode` - a `Peripherals` object, to set the power mode to disable clocks in a specific defined mode.
     pub fn disable_clock(mode: Peripherals) {
         match mode {
-            Peripherals::TWI => Power::twi(&mut Power::new()),
-            Peripherals::Timer2 => Power::timer2(&mut Power::new()),
-            Peripherals::Timer0 => Power::timer0(&mut Power::new()),
-            Peripherals::Timer1 => Power::timer1(&mut Power::new()),
-            Peripherals::SPI => Power::spi(&mut Power::new()),
-            Peripherals::USART0 => Power::usart0(&mut Power::new()),
-            Peripherals::ADC => Power::adc(&mut Power::new()),
+            Peripherals::TWI => Power::twi(unsafe { Power::new() }),
+            Peripherals::Timer2 => Power::timer2(unsafe { Power::new() }),
+            Peripherals::Timer0 => Power::timer0(unsafe { Power::new() }),
+            Peripherals::Timer1 => Power::timer1(unsafe { Power::new() }),
+            Peripherals::SPI => Power::spi(unsafe { Power::new() }),
+            Peripherals::USART0 => Power::usart0(unsafe { Power::new() }),
+            Peripherals::ADC => Power::adc(unsafe { Power::new() }),
         }
     }
 }
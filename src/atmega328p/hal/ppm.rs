@@ -0,0 +1,341 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! PPM sum signals of RC receivers and transmitters on the ATMEGA328P chip.
+//! A PPM stream carries up to eight servo channels in one wire, each channel
+//! being the time between the starts of two pulses, followed by a long sync gap.
+//! The stream is decoded with the input capture unit of Timer 1 on digital pin 8,
+//! and generated with the fast PWM mode of Timer 1 on digital pin 10.
+//! The timer counts at an eighth of the CPU clock, so it can be used for only one
+//! of these at a time and neither for PWM on pins 9 and 10 nor for `ir::IrReceiver`.
+//! Section 16 of ATmega328P datasheet.
+
+// Core Crate functions required in the code for reading and writing to registers.
+use core::ptr::{read_volatile, write_volatile};
+
+// Source codes to be used here.
+use crate::atmega328p::hal::interrupts;
+use crate::atmega328p::hal::port::{self, PortName};
+use crate::atmega328p::hal::power::{Peripheral, Power};
+use crate::config::CPU_FREQUENCY_HZ;
+
+/// Address of the SREG register, used to share the channels with the interrupts.
+const SREG: *mut u8 = 0x5F as *mut u8;
+
+// Registers of Timer 1.
+const TIFR1: *mut u8 = 0x36 as *mut u8;
+const TIMSK1: *mut u8 = 0x6F as *mut u8;
+const TCCR1A: *mut u8 = 0x80 as *mut u8;
+const TCCR1B: *mut u8 = 0x81 as *mut u8;
+const TCNT1L: *mut u8 = 0x84 as *mut u8;
+const ICR1L: *mut u8 = 0x86 as *mut u8;
+const OCR1AL: *mut u8 = 0x88 as *mut u8;
+const OCR1BL: *mut u8 = 0x8A as *mut u8;
+
+/// Largest number of channels in one frame.
+pub const PPM_MAX_CHANNELS: usize = 8;
+
+/// Least number of channels for a frame to be taken as valid.
+pub const PPM_MIN_CHANNELS: usize = 4;
+
+/// Gaps longer than this, in microseconds, are taken as the sync gap.
+pub const PPM_SYNC_US: u16 = 2700;
+
+/// Length of one generated frame in microseconds.
+pub const PPM_FRAME_US: u16 = 22500;
+
+/// Length of the pulse which starts every generated channel, in microseconds.
+pub const PPM_PULSE_US: u16 = 300;
+
+/// Shortest and longest generated channel in microseconds.
+pub const PPM_MIN_US: u16 = 800;
+pub const PPM_MAX_US: u16 = 2200;
+
+/// Converts a time in microseconds to ticks of the timer.
+fn us_to_ticks(us: u16) -> u16 {
+    (us as u32 * (CPU_FREQUENCY_HZ / 1_000_000) / 8) as u16
+}
+
+/// Converts ticks of the timer to a time in microseconds.
+fn ticks_to_us(ticks: u16) -> u16 {
+    (ticks as u32 * 8 / (CPU_FREQUENCY_HZ / 1_000_000)) as u16
+}
+
+/// Reads a 16 bit register of the timer, the low byte has to be read first.
+unsafe fn read16(low: *mut u8) -> u16 {
+    let l = read_volatile(low);
+    let h = read_volatile(low.add(1));
+    ((h as u16) << 8) | l as u16
+}
+
+/// Writes a 16 bit register of the timer, the high byte has to be written first.
+unsafe fn write16(low: *mut u8, value: u16) {
+    write_volatile(low.add(1), (value >> 8) as u8);
+    write_volatile(low, value as u8);
+}
+
+/// Channels measured by the input capture interrupt.
+struct Capture {
+    last: u16,
+    index: usize,
+    channels: [u16; PPM_MAX_CHANNELS],
+    frame: [u16; PPM_MAX_CHANNELS],
+    count: usize,
+    ready: bool,
+}
+
+static mut CAPTURE: Capture = Capture {
+    last: 0,
+    index: 0,
+    channels: [0; PPM_MAX_CHANNELS],
+    frame: [0; PPM_MAX_CHANNELS],
+    count: 0,
+    ready: false,
+};
+
+impl Capture {
+    /// Takes the time of one captured edge.
+    fn edge(&mut self, now: u16) {
+        let us = ticks_to_us(now.wrapping_sub(self.last));
+        self.last = now;
+        if us >= PPM_SYNC_US {
+            if self.index >= PPM_MIN_CHANNELS {
+                self.frame = self.channels;
+                self.count = self.index;
+                self.ready = true;
+            }
+            self.index = 0;
+        } else if self.index < PPM_MAX_CHANNELS {
+            self.channels[self.index] = us;
+            self.index += 1;
+        }
+    }
+}
+
+/// Decodes a PPM stream on digital pin 8.
+/// The time between two rising edges is measured, which gives the channels
+/// for streams of either polarity.
+pub struct PpmDecoder {
+    _private: (),
+}
+
+impl PpmDecoder {
+    /// Starts Timer 1 and its input capture interrupt.
+    /// Global interrupts are enabled here.
+    /// # Returns
+    /// * `a PpmDecoder object` - which will be used to read the channels.
+    pub fn new() -> PpmDecoder {
+        match unsafe { port::Pin::new(PortName::B, 0) } {
+            Some(mut input) => input.set_input(),
+            None => unreachable!(),
+        }
+        unsafe { Power::new() }.enable(Peripheral::Timer1);
+        unsafe {
+            CAPTURE.index = 0;
+            CAPTURE.ready = false;
+            write_volatile(TCCR1A, 0);
+            // Noise canceler, rising edge and a prescaler of 8 in normal mode.
+            write_volatile(TCCR1B, (1 << 7) | (1 << 6) | 0b010);
+            // The flag is cleared by writing one to it.
+            write_volatile(TIFR1, 1 << 5);
+            write_volatile(TIMSK1, read_volatile(TIMSK1) | (1 << 5));
+            interrupts::Interrupt::enable(&mut interrupts::Interrupt::new());
+        }
+        PpmDecoder { _private: () }
+    }
+
+    /// Takes the last complete frame, without waiting.
+    /// # Arguments
+    /// * `channels` - a mutable u16 array, where the channels are stored in microseconds.
+    /// # Returns
+    /// * `a Option<usize>` - the number of channels in the frame, None if no new frame came.
+    pub fn read(&mut self, channels: &mut [u16; PPM_MAX_CHANNELS]) -> Option<usize> {
+        unsafe {
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            let result = if CAPTURE.ready {
+                CAPTURE.ready = false;
+                *channels = CAPTURE.frame;
+                Some(CAPTURE.count)
+            } else {
+                None
+            };
+            write_volatile(SREG, sreg);
+            result
+        }
+    }
+
+    /// Stops the input capture interrupt, Timer 1 is left running.
+    pub fn release(self) {
+        unsafe {
+            write_volatile(TIMSK1, read_volatile(TIMSK1) & !(1 << 5));
+        }
+    }
+}
+
+impl Default for PpmDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Channels sent by the overflow interrupt.
+struct Output {
+    channels: [u16; PPM_MAX_CHANNELS],
+    count: usize,
+    next: usize,
+}
+
+static mut OUTPUT: Output = Output {
+    channels: [0; PPM_MAX_CHANNELS],
+    count: 0,
+    next: 0,
+};
+
+impl Output {
+    /// Gives the length of a slot of the frame, the sync gap after the channels.
+    fn slot_us(&self, slot: usize) -> u16 {
+        if slot < self.count {
+            return self.channels[slot];
+        }
+        let used: u16 = self.channels[..self.count].iter().sum();
+        let sync = PPM_FRAME_US.saturating_sub(used);
+        if sync < PPM_SYNC_US + PPM_PULSE_US {
+            PPM_SYNC_US + PPM_PULSE_US
+        } else {
+            sync
+        }
+    }
+
+    /// Loads the length of the slot after the one which has just started.
+    /// OCR1A is double buffered and the buffer is taken at the end of the present slot.
+    /// The counter reaches BOTTOM one timer tick after the overflow, which is before
+    /// the interrupt routine gets here, so the write never lands in the present slot.
+    fn load_next(&mut self) {
+        let ticks = us_to_ticks(self.slot_us(self.next));
+        unsafe { write16(OCR1AL, ticks - 1) };
+        self.next = (self.next + 1) % (self.count + 1);
+    }
+}
+
+/// Generates a PPM stream on digital pin 10.
+pub struct PpmEncoder {
+    _private: (),
+}
+
+impl PpmEncoder {
+    /// Starts Timer 1 in fast PWM mode with OCR1A as top, every slot being one period.
+    /// All channels start at 1500 microseconds. Global interrupts are enabled here.
+    /// # Arguments
+    /// * `channels` - a usize, the number of channels in a frame, 1 to 8.
+    /// * `inverted` - a boolean, true for pulses which are low, as needed by some transmitters.
+    /// # Returns
+    /// * `a PpmEncoder object` - which will be used to set the channels.
+    pub fn new(channels: usize, inverted: bool) -> PpmEncoder {
+        if channels == 0 || channels > PPM_MAX_CHANNELS {
+            unreachable!()
+        }
+        match unsafe { port::Pin::new(PortName::B, 2) } {
+            Some(mut output) => output.set_output(),
+            None => unreachable!(),
+        }
+        unsafe { Power::new() }.enable(Peripheral::Timer1);
+
+        let com: u8 = if inverted { 0b11 } else { 0b10 };
+        unsafe {
+            OUTPUT.channels = [1500; PPM_MAX_CHANNELS];
+            OUTPUT.count = channels;
+            OUTPUT.next = 0;
+
+            // The first slot is written directly while the timer is stopped in normal mode.
+            write_volatile(TCCR1B, 0);
+            write_volatile(TCCR1A, 0);
+            write16(TCNT1L, 0);
+            OUTPUT.load_next();
+            write16(OCR1BL, us_to_ticks(PPM_PULSE_US));
+
+            // Fast PWM with OCR1A as top, the second slot goes to the buffer of OCR1A.
+            write_volatile(TCCR1A, (com << 4) | 0b11);
+            write_volatile(TCCR1B, 0b11 << 3);
+            OUTPUT.load_next();
+
+            // The timer is started with a prescaler of 8.
+            write_volatile(TIFR1, 1 << 0);
+            write_volatile(TIMSK1, read_volatile(TIMSK1) | (1 << 0));
+            write_volatile(TCCR1B, (0b11 << 3) | 0b010);
+            interrupts::Interrupt::enable(&mut interrupts::Interrupt::new());
+        }
+        PpmEncoder { _private: () }
+    }
+
+    /// Sets one channel, which is sent from the next frame on.
+    /// # Arguments
+    /// * `channel` - a usize, the channel from 0.
+    /// * `us` - a u16, the channel in microseconds, limited to `PPM_MIN_US` and `PPM_MAX_US`.
+    pub fn set_channel(&mut self, channel: usize, us: u16) {
+        let us = us.clamp(PPM_MIN_US, PPM_MAX_US);
+        unsafe {
+            if channel >= OUTPUT.count {
+                unreachable!()
+            }
+            let sreg = read_volatile(SREG);
+            write_volatile(SREG, sreg & 0x7F);
+            OUTPUT.channels[channel] = us;
+            write_volatile(SREG, sreg);
+        }
+    }
+
+    /// Sets all channels of the frame at once.
+    /// # Arguments
+    /// * `channels` - a u16 slice, the channels in microseconds, one for each channel of the frame.
+    pub fn set_channels(&mut self, channels: &[u16]) {
+        for (i, &us) in channels.iter().enumerate() {
+            self.set_channel(i, us);
+        }
+    }
+
+    /// Stops the timer and the stream, the pin is left low.
+    pub fn stop(self) {
+        unsafe {
+            write_volatile(TIMSK1, read_volatile(TIMSK1) & !(1 << 0));
+            write_volatile(TCCR1B, 0);
+            write_volatile(TCCR1A, 0);
+        }
+    }
+}
+
+/// Takes the time of the edge held in ICR1, called from the input capture interrupt.
+#[cfg_attr(not(target_arch = "avr"), allow(dead_code))]
+fn on_capture() {
+    unsafe {
+        let now = read16(ICR1L);
+        CAPTURE.edge(now);
+    }
+}
+
+/// Interrupt vector of Timer 1 input capture.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_10() {
+    on_capture();
+}
+
+/// Interrupt vector of Timer 1 overflow, which is at the top of every slot.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_13() {
+    OUTPUT.load_next();
+}
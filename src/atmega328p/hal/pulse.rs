@@ -0,0 +1,81 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Measurement of the length of a pulse on a digital pin by polling the pin.
+//! Used for sensors like the HC-SR04 ultrasonic sensor and for reading RC receivers.
+//! Section 13.2.4 of ATmega328P datasheet.
+
+// Crates to be used for the implementation.
+use core::ptr::read_volatile;
+
+// Source codes to be used here.
+use crate::atmega328p::hal::pin::Pins;
+use crate::config::cpu_frequency_hz;
+
+/// Approximate number of clock cycles taken by one pass of the polling loop.
+const LOOP_CYCLES: u64 = 16;
+
+/// Measures the length of a pulse on a digital pin.
+/// Waits for the pin to go to `level`, then measures how long it stays there.
+/// A pulse which is already going on when the function is called is skipped.
+/// Interrupts taking place during the pulse make the result larger, so they
+/// should be disabled for accurate readings.
+/// # Arguments
+/// * `pin` - a usize, the number of the digital pin.
+/// * `level` - a u8, 1 to measure a high pulse and 0 to measure a low pulse.
+/// * `timeout_us` - a u32, the time in microseconds after which the wait is given up.
+/// # Returns
+/// * `a u32` - the length of the pulse in microseconds, 0 if no pulse completed before the timeout.
+pub fn pulse_in(pin: usize, level: u8, timeout_us: u32) -> u32 {
+    let pins = unsafe { Pins::new() };
+    let digital = pins.digital[pin];
+    let port = digital.pin.port;
+    let mask: u8 = 0x1 << digital.pin.pin;
+    let state: u8 = if level == 0 { 0 } else { mask };
+
+    // Worked out in cycles, as clocks below 1 MHz have less than one cycle per microsecond.
+    let frequency = cpu_frequency_hz() as u64;
+    let max_loops = timeout_us as u64 * frequency / 1_000_000 / LOOP_CYCLES;
+    let max_loops = core::cmp::min(max_loops, u32::MAX as u64) as u32;
+    let mut loops: u32 = 0;
+
+    // Wait for any previous pulse to end.
+    while unsafe { read_volatile(&(*port).pin) } & mask == state {
+        loops += 1;
+        if loops >= max_loops {
+            return 0;
+        }
+    }
+
+    // Wait for the pulse to start.
+    while unsafe { read_volatile(&(*port).pin) } & mask != state {
+        loops += 1;
+        if loops >= max_loops {
+            return 0;
+        }
+    }
+
+    // Wait for the pulse to end while counting its width.
+    let mut width: u32 = 0;
+    while unsafe { read_volatile(&(*port).pin) } & mask == state {
+        width += 1;
+        if loops + width >= max_loops {
+            return 0;
+        }
+    }
+
+    (width as u64 * LOOP_CYCLES * 1_000_000 / frequency) as u32
+}
@@ -24,7 +24,8 @@ pub enum BitOrder {
     MSBFIRST,
 }
 
-/// It sets the shift register according to the input from the user.
+/// Reads one byte from a shift register like the 74HC165 by bit-banging
+/// the clock pin, so it works on any pair of digital pins.
 /// # Arguments
 /// * `datapin` - a usize, containing the number of the digital pin from which data will be read.
 /// * `clockpin` - a usize, containing the number of the digital pin from which clock source will be adjusted.
@@ -33,11 +34,13 @@ pub enum BitOrder {
 /// * `a u8` - The value stored in the shift register.
 pub fn shift_in(datapin: usize, clockpin: usize, bit_order: BitOrder) -> u8 {
     let mut value: u8 = 0;
-    let mut i: u8 = 0;
-    let pins = Pins::new();
+    let pins = unsafe { Pins::new() };
     let mut data = pins.digital[datapin];
     let mut clock = pins.digital[clockpin];
-    loop {
+    data.set_input();
+    clock.set_output();
+
+    for i in 0..8 {
         clock.high();
 
         match bit_order {
@@ -46,27 +49,25 @@ pub fn shift_in(datapin: usize, clockpin: usize, bit_order: BitOrder) -> u8 {
         }
 
         clock.low();
-
-        i += 1;
-        if i == 7 {
-            return value;
-        }
     }
+    value
 }
 
-/// Stores value in the Shift Register.
+/// Writes one byte to a shift register like the 74HC595 by bit-banging
+/// the data and clock pins, so it works on any pair of digital pins.
 /// # Arguments
-/// * `datapin` - a usize, containing the number of the digital pin from which data will be read.
+/// * `datapin` - a usize, containing the number of the digital pin to which data will be written.
 /// * `clockpin` - a usize, containing the number of the digital pin from which clock source will be adjusted.
 /// * `bit_order` - a `BitOrder` object, to specify the order of bits in the shift register.
 /// * `value` - a mutable u8, which will store the value which is to be written.
 pub fn shift_out(datapin: usize, clockpin: usize, bit_order: BitOrder, mut value: u8) {
-    let mut i: u8 = 0;
-    let pins = Pins::new();
+    let pins = unsafe { Pins::new() };
     let mut data = pins.digital[datapin];
     let mut clock = pins.digital[clockpin];
+    data.set_output();
+    clock.set_output();
 
-    loop {
+    for _ in 0..8 {
         match bit_order {
             BitOrder::LSBFIRST => {
                 if value & 1 == 1 {
@@ -88,10 +89,5 @@ pub fn shift_out(datapin: usize, clockpin: usize, bit_order: BitOrder, mut value
         }
         clock.high();
         clock.low();
-
-        i += 1;
-        if i == 7 {
-            return;
-        }
     }
 }
@@ -19,6 +19,22 @@
 
 use core;
 
+// Source codes to be used here.
+use crate::atmega328p::hal::ext_interrupt::{pin_change_mask, ExtInterrupt, IntLine, Trigger};
+use crate::atmega328p::hal::watchdog::{WatchDog, WatchdogMode, WatchdogTimeout};
+
+/// Address of PCICR, the pin change interrupt control register.
+const PCICR: *mut u8 = 0x68 as *mut u8;
+
+/// Address of PCMSK0, followed by PCMSK1 and PCMSK2.
+const PCMSK0: *mut u8 = 0x6B as *mut u8;
+
+/// Address of PCIFR, the pin change interrupt flag register.
+const PCIFR: *mut u8 = 0x3B as *mut u8;
+
+/// Address of UCSR0B, the USART0 control register holding RXCIE0.
+const UCSR0B: *mut u8 = 0xC1 as *mut u8;
+
 /// Contains sleep modes.
 /// # Modes Explanantion
 /// * **Idle**: This  mode makes the MCU enter idle mode, stopping the CPU but
@@ -76,10 +92,11 @@ pub struct Sleep {
 
 impl Sleep {
     /// Returns mutable reference to `Sleep` struct to control power management.
+    /// Use `peripherals::Peripherals::take()` to get the only owner without unsafe code.
     /// # Returns
     /// * `a reference to Sleep object` - which will be used for further implementations.    
-    pub fn new() -> &'static mut Self {
-        unsafe { &mut *(0x53 as *mut Self) }
+    pub unsafe fn new() -> &'static mut Self {
+        &mut *(0x53 as *mut Self)
     }
 
     /// Enable `MCU` to enter sleep mode.
@@ -133,6 +150,156 @@ impl Sleep {
             core::ptr::write_volatile(&mut self.smcr, 0x0);
         }
     }
+
+    /// Selects the sleep mode without setting the `SE` bit.
+    /// The sleep mode is entered by `sleep_enable_and_wait()`.
+    /// # Arguments
+    /// * `mode` - a `SleepMode` object, the mode to be entered on sleep.
+    pub fn select_mode(&mut self, mode: SleepMode) {
+        let sm: u8 = match mode {
+            SleepMode::Idle => 0b000,
+            SleepMode::ADCNR => 0b001,
+            SleepMode::PowerDown => 0b010,
+            SleepMode::PowerSave => 0b011,
+            SleepMode::Standby => 0b110,
+            SleepMode::ExtStandby => 0b111,
+            SleepMode::Disable => {
+                self.disable();
+                return;
+            }
+        };
+        unsafe {
+            core::ptr::write_volatile(&mut self.smcr, sm << 1);
+        }
+    }
+
+    /// Sets `SE`, enters the selected sleep mode and clears `SE` after waking up.
+    ///
+    /// Global interrupts are enabled by `SEI` right before `SLEEP`, and the
+    /// instruction after `SEI` always runs before a pending interrupt. So the
+    /// caller can disable interrupts, check that there is nothing left to do,
+    /// and call this function without an interrupt slipping in before sleep
+    /// and leaving the chip asleep with no wake up.
+    pub fn sleep_enable_and_wait(&mut self) {
+        unsafe {
+            let smcr = core::ptr::read_volatile(&self.smcr);
+            core::ptr::write_volatile(&mut self.smcr, smcr | 0x1);
+            llvm_asm!("sei
+                       sleep" :::: "volatile");
+            let smcr = core::ptr::read_volatile(&self.smcr);
+            core::ptr::write_volatile(&mut self.smcr, smcr & !0x1);
+        }
+    }
+
+    /// Same as `sleep_enable_and_wait()` but also turns off the brown-out detector
+    /// during sleep, which cuts the current in power-down from about 20 uA to 1 uA.
+    ///
+    /// BODS and BODSE in MCUCR are set together, then BODS is set with BODSE
+    /// cleared within four cycles, and `SLEEP` has to follow within three cycles,
+    /// so the whole sequence is done in assembly. Only works in power-down and
+    /// power-save modes, the detector is turned on again on wake up.
+    pub fn sleep_without_bod(&mut self) {
+        unsafe {
+            let smcr = core::ptr::read_volatile(&self.smcr);
+            core::ptr::write_volatile(&mut self.smcr, smcr | 0x1);
+            llvm_asm!("in r24, 0x35
+                       ori r24, 0x60
+                       out 0x35, r24
+                       andi r24, 0xDF
+                       out 0x35, r24
+                       sei
+                       sleep" ::: "r24" : "volatile");
+            let smcr = core::ptr::read_volatile(&self.smcr);
+            core::ptr::write_volatile(&mut self.smcr, smcr & !0x1);
+        }
+    }
+}
+
+/// Sleeps until INT0 (digital pin 2) is pulled low.
+/// The low level trigger is used as edges can not wake the chip from power-down.
+/// The interrupt is disabled again after waking up.
+/// # Arguments
+/// * `mode` - a `SleepMode` object, the mode to sleep in.
+pub fn sleep_until_int0(mode: SleepMode) {
+    let ext = unsafe { ExtInterrupt::new() };
+    ext.set_trigger(IntLine::Int0, Trigger::LowLevel);
+    ext.clear_flag(IntLine::Int0);
+    ext.enable(IntLine::Int0);
+
+    let sleep = unsafe { Sleep::new() };
+    sleep.select_mode(mode);
+    sleep.sleep_enable_and_wait();
+
+    ext.disable(IntLine::Int0);
+}
+
+/// Sleeps until the level of a digital pin changes.
+/// The pin change interrupt of the pin is disabled again after waking up.
+/// A handler attached to the group through `ext_interrupt::attach_pin_change()` is still called.
+/// # Arguments
+/// * `pin` - a usize, the number of the digital pin, 0 to 13.
+/// * `mode` - a `SleepMode` object, the mode to sleep in.
+pub fn sleep_until_pin_change(pin: usize, mode: SleepMode) {
+    let (group, bit) = pin_change_mask(pin);
+
+    unsafe {
+        let pcmsk = PCMSK0.add(group);
+        core::ptr::write_volatile(pcmsk, core::ptr::read_volatile(pcmsk) | (1 << bit));
+        // A change from before would wake the chip at once, the flag is cleared by writing one to it.
+        core::ptr::write_volatile(PCIFR, 1 << group);
+        core::ptr::write_volatile(PCICR, core::ptr::read_volatile(PCICR) | (1 << group));
+    }
+
+    let sleep = unsafe { Sleep::new() };
+    sleep.select_mode(mode);
+    sleep.sleep_enable_and_wait();
+
+    unsafe {
+        let pcmsk = PCMSK0.add(group);
+        core::ptr::write_volatile(pcmsk, core::ptr::read_volatile(pcmsk) & !(1 << bit));
+        if core::ptr::read_volatile(pcmsk) == 0 {
+            core::ptr::write_volatile(PCICR, core::ptr::read_volatile(PCICR) & !(1 << group));
+        }
+    }
+}
+
+/// Sleeps for one watchdog period, waking up on the watchdog interrupt.
+/// # Arguments
+/// * `timeout` - a `WatchdogTimeout` object, the time to sleep.
+/// * `mode` - a `SleepMode` object, the mode to sleep in.
+pub fn sleep_until_watchdog(timeout: WatchdogTimeout, mode: SleepMode) {
+    let watchdog = unsafe { WatchDog::new() };
+    watchdog.start(timeout, WatchdogMode::Interrupt);
+
+    let sleep = unsafe { Sleep::new() };
+    sleep.select_mode(mode);
+    sleep.sleep_enable_and_wait();
+
+    watchdog.disable();
+}
+
+/// Sleeps in idle mode until USART0 receives a byte.
+/// Idle is the only mode in which the USART keeps running. The receive
+/// interrupt is disabled again on wake up and the byte is left in the receiver,
+/// unless buffered reception is enabled in which case the byte is in the receive buffer.
+pub fn sleep_until_usart_rx() {
+    unsafe {
+        core::ptr::write_volatile(UCSR0B, core::ptr::read_volatile(UCSR0B) | (1 << 7));
+    }
+
+    let sleep = unsafe { Sleep::new() };
+    sleep.select_mode(SleepMode::Idle);
+    sleep.sleep_enable_and_wait();
+}
+
+/// Interrupt vector of USART0 receive complete, used to wake up the chip.
+/// The interrupt is disabled so that the byte stays in the receiver for the program.
+/// With the `com` feature the vector is given by `com::usart_buffer`, which does the
+/// same unless buffered reception is enabled.
+#[cfg(all(target_arch = "avr", not(feature = "com")))]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_18() {
+    core::ptr::write_volatile(UCSR0B, core::ptr::read_volatile(UCSR0B) & !(1 << 7));
 }
 
 /// Enables the Chosen power mode.
@@ -140,12 +307,12 @@ impl Sleep {
 /// * `mode` - a `SleepMode` object, to select the mode to be activated.
 pub fn enable_mode(mode: SleepMode) {
     match mode {
-        SleepMode::Idle => Sleep::idle(&mut Sleep::new()),
-        SleepMode::ADCNR => Sleep::adcnr(&mut Sleep::new()),
-        SleepMode::PowerDown => Sleep::power_down(&mut Sleep::new()),
-        SleepMode::PowerSave => Sleep::power_save(&mut Sleep::new()),
-        SleepMode::Standby => Sleep::standby(&mut Sleep::new()),
-        SleepMode::ExtStandby => Sleep::ext_standby(&mut Sleep::new()),
-        SleepMode::Disable => Sleep::disable(&mut Sleep::new()),
+        SleepMode::Idle => Sleep::idle(unsafe { Sleep::new() }),
+        SleepMode::ADCNR => Sleep::adcnr(unsafe { Sleep::new() }),
+        SleepMode::PowerDown => Sleep::power_down(unsafe { Sleep::new() }),
+        SleepMode::PowerSave => Sleep::power_save(unsafe { Sleep::new() }),
+        SleepMode::Standby => Sleep::standby(unsafe { Sleep::new() }),
+        SleepMode::ExtStandby => Sleep::ext_standby(unsafe { Sleep::new() }),
+        SleepMode::Disable => Sleep::disable(unsafe { Sleep::new() }),
     }
 }
@@ -17,9 +17,106 @@
 //! Control on Watchdog timer in ATMEGA328P
 //! Watchdog timer 10.9 of the manual.
 
+use crate::__wdr;
 use crate::atmega328p::hal::interrupts;
+use crate::atmega328p::hal::sleep_mode::{sleep_until_watchdog, SleepMode};
 use core::ptr::{read_volatile, write_volatile};
 
+/// Selection of the time after which the watchdog times out.
+/// The times are for the 128 kHz watchdog oscillator at 5V.
+#[derive(Clone, Copy)]
+pub enum WatchdogTimeout {
+    Ms16,
+    Ms32,
+    Ms64,
+    Ms125,
+    Ms250,
+    Ms500,
+    S1,
+    S2,
+    S4,
+    S8,
+}
+
+/// Selection of the action taken by the watchdog on a time-out.
+/// * `Interrupt` - The watchdog interrupt is executed and the timer keeps running.
+/// * `Reset` - The chip is reset.
+/// * `InterruptReset` - The interrupt is executed first, and on the next time-out
+///   the chip is reset unless the handler enables the interrupt again.
+#[derive(Clone, Copy)]
+pub enum WatchdogMode {
+    Interrupt,
+    Reset,
+    InterruptReset,
+}
+
+impl WatchdogTimeout {
+    /// Gives the WDP3:0 bits of the timeout, placed at their position in WDTCSR.
+    fn bits(&self) -> u8 {
+        let wdp: u8 = match self {
+            WatchdogTimeout::Ms16 => 0,
+            WatchdogTimeout::Ms32 => 1,
+            WatchdogTimeout::Ms64 => 2,
+            WatchdogTimeout::Ms125 => 3,
+            WatchdogTimeout::Ms250 => 4,
+            WatchdogTimeout::Ms500 => 5,
+            WatchdogTimeout::S1 => 6,
+            WatchdogTimeout::S2 => 7,
+            WatchdogTimeout::S4 => 8,
+            WatchdogTimeout::S8 => 9,
+        };
+        // WDP3 is bit 5 and WDP2:0 are bits 2:0.
+        ((wdp & 0x8) << 2) | (wdp & 0x7)
+    }
+
+    /// Gives the length of the timeout.
+    /// # Returns
+    /// * `a u32` - the timeout in milliseconds.
+    pub fn millis(&self) -> u32 {
+        match self {
+            WatchdogTimeout::Ms16 => 16,
+            WatchdogTimeout::Ms32 => 32,
+            WatchdogTimeout::Ms64 => 64,
+            WatchdogTimeout::Ms125 => 125,
+            WatchdogTimeout::Ms250 => 250,
+            WatchdogTimeout::Ms500 => 500,
+            WatchdogTimeout::S1 => 1000,
+            WatchdogTimeout::S2 => 2000,
+            WatchdogTimeout::S4 => 4000,
+            WatchdogTimeout::S8 => 8000,
+        }
+    }
+}
+
+/// Cause of the last reset of the chip, read from MCUSR.
+/// * `PowerOn` - The supply voltage came up.
+/// * `External` - The RESET pin was pulled low.
+/// * `BrownOut` - The supply voltage fell below the brown-out level.
+/// * `Watchdog` - The watchdog timed out in reset mode.
+/// * `Unknown` - No flag was set, for example because a bootloader cleared MCUSR.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ResetReason {
+    PowerOn,
+    External,
+    BrownOut,
+    Watchdog,
+    Unknown,
+}
+
+/// Reset reason read from MCUSR the first time it was needed.
+static mut RESET_REASON: Option<ResetReason> = None;
+
+/// Handle to a running watchdog in reset mode, given by `WatchDog::enable()`.
+/// The watchdog can only be fed or disabled through the handle, and `feed()`
+/// has to be called before every time-out or the chip is reset.
+#[must_use]
+pub struct WatchdogHandle {
+    watchdog: &'static mut WatchDog,
+}
+
+/// Handler function called from the watchdog interrupt.
+static mut WATCHDOG_HANDLER: Option<fn()> = None;
+
 /// MCUSR (MCU Status Register)
 /// The MCU status register provides information on which reset source caused an MCU reset.
 ///
@@ -33,7 +130,7 @@ use core::ptr::{read_volatile, write_volatile};
 #[repr(C, packed)]
 pub struct WatchDog {
     mcusr: u8,
-    _pad: [u8; 10],
+    _pad: [u8; 11],
     wdtcsr: u8,
 }
 
@@ -42,7 +139,7 @@ impl WatchDog {
     /// # Returns
     /// * `a reference to Watchdog structure` - for further implementations.
     pub unsafe fn new() -> &'static mut WatchDog {
-        &mut *(0x55 as *mut WatchDog)
+        &mut *(0x54 as *mut WatchDog)
     }
 
     /// Resets watchdog timer.
@@ -56,6 +153,7 @@ impl WatchDog {
 
     /// Disables watchdog
     pub fn disable(&mut self) {
+        self.reset_reason();
         unsafe {
             interrupts::Interrupt::disable(&mut interrupts::Interrupt::new());
             WatchDog::reset_watchdog(&mut WatchDog::new());
@@ -66,4 +164,178 @@ impl WatchDog {
             interrupts::Interrupt::enable(&mut interrupts::Interrupt::new());
         }
     }
+
+    /// Starts the watchdog with the given timeout and action.
+    /// Uses the timed sequence of setting WDCE and WDE followed by the new
+    /// value within four clock cycles, with global interrupts disabled.
+    /// SREG is restored afterwards, so the global interrupts are not enabled here.
+    /// # Arguments
+    /// * `timeout` - a `WatchdogTimeout` object, the time after which the watchdog times out.
+    /// * `mode` - a `WatchdogMode` object, the action taken on a time-out.
+    pub fn start(&mut self, timeout: WatchdogTimeout, mode: WatchdogMode) {
+        let mut value = timeout.bits();
+        value |= match mode {
+            WatchdogMode::Interrupt => 1 << 6,
+            WatchdogMode::Reset => 1 << 3,
+            WatchdogMode::InterruptReset => (1 << 6) | (1 << 3),
+        };
+
+        interrupts::free(|_| {
+            __wdr();
+            unsafe {
+                let ctrl_wdtcsr = read_volatile(&self.wdtcsr);
+                write_volatile(&mut self.wdtcsr, ctrl_wdtcsr | (1 << 4) | (1 << 3));
+                write_volatile(&mut self.wdtcsr, value);
+            }
+        });
+    }
+
+    /// Gives the cause of the last reset.
+    /// MCUSR is decoded on the first call and then cleared, so later calls and
+    /// `disable()` do not lose the reason.
+    /// # Returns
+    /// * `a ResetReason object` - the cause of the last reset.
+    pub fn reset_reason(&mut self) -> ResetReason {
+        if let Some(reason) = unsafe { RESET_REASON } {
+            return reason;
+        }
+
+        let mcusr = unsafe { read_volatile(&self.mcusr) };
+        let reason = if mcusr & (1 << 0) != 0 {
+            ResetReason::PowerOn
+        } else if mcusr & (1 << 2) != 0 {
+            ResetReason::BrownOut
+        } else if mcusr & (1 << 1) != 0 {
+            ResetReason::External
+        } else if mcusr & (1 << 3) != 0 {
+            ResetReason::Watchdog
+        } else {
+            ResetReason::Unknown
+        };
+
+        unsafe {
+            write_volatile(&mut self.mcusr, 0x00);
+            RESET_REASON = Some(reason);
+        }
+        reason
+    }
+
+    /// Enables the watchdog in system reset mode.
+    /// The watchdog is moved into the returned handle, so it can not be
+    /// reconfigured while it is running.
+    /// # Arguments
+    /// * `timeout` - a `WatchdogTimeout` object, the time within which the watchdog has to be fed.
+    /// # Returns
+    /// * `a WatchdogHandle object` - used to feed and disable the watchdog.
+    pub fn enable(&'static mut self, timeout: WatchdogTimeout) -> WatchdogHandle {
+        self.start(timeout, WatchdogMode::Reset);
+        WatchdogHandle { watchdog: self }
+    }
+
+    /// Attach a handler function which is called from the watchdog interrupt.
+    /// # Arguments
+    /// * `handler` - a function, which is called on every interrupt time-out.
+    pub fn set_interrupt_handler(&mut self, handler: fn()) {
+        unsafe {
+            WATCHDOG_HANDLER = Some(handler);
+        }
+    }
+
+    /// Enables the watchdog interrupt again, needed in `InterruptReset` mode
+    /// to go on with interrupts instead of resetting on the next time-out.
+    pub fn enable_interrupt(&mut self) {
+        unsafe {
+            let ctrl_wdtcsr = read_volatile(&self.wdtcsr);
+            write_volatile(&mut self.wdtcsr, ctrl_wdtcsr | (1 << 6));
+        }
+    }
+}
+
+impl WatchdogHandle {
+    /// Feeds the watchdog, so that the timeout starts again.
+    pub fn feed(&mut self) {
+        __wdr();
+    }
+
+    /// Stops the watchdog and gives it back.
+    /// # Returns
+    /// * `a reference to Watchdog structure` - for further implementations.
+    pub fn disable(self) -> &'static mut WatchDog {
+        self.watchdog.disable();
+        self.watchdog
+    }
+}
+
+/// Sleeps in power-down mode for about the given time, using the watchdog
+/// interrupt to wake up. Longer times are made of several watchdog periods,
+/// so the time is rounded down to a multiple of 16 ms and is only as accurate
+/// as the watchdog oscillator. Global interrupts are needed for the wake up,
+/// so they are enabled right before every sleep and are left enabled on return.
+/// # Arguments
+/// * `ms` - a u32, the time to sleep in milliseconds.
+pub fn sleep_for(ms: u32) {
+    let timeouts = [
+        WatchdogTimeout::S8,
+        WatchdogTimeout::S4,
+        WatchdogTimeout::S2,
+        WatchdogTimeout::S1,
+        WatchdogTimeout::Ms500,
+        WatchdogTimeout::Ms250,
+        WatchdogTimeout::Ms125,
+        WatchdogTimeout::Ms64,
+        WatchdogTimeout::Ms32,
+        WatchdogTimeout::Ms16,
+    ];
+
+    let mut remaining = ms;
+    for timeout in timeouts.iter() {
+        while remaining >= timeout.millis() {
+            sleep_until_watchdog(*timeout, SleepMode::PowerDown);
+            remaining -= timeout.millis();
+        }
+    }
+}
+
+/// Interrupt vector of the watchdog time-out.
+#[cfg(target_arch = "avr")]
+#[no_mangle]
+pub unsafe extern "avr-interrupt" fn __vector_6() {
+    if let Some(handler) = WATCHDOG_HANDLER {
+        handler();
+    }
+}
+
+/// The watchdog for portable code written against the `embedded-hal` watchdog traits.
+/// `start()` runs it in system reset mode, as `enable()` does.
+#[cfg(feature = "embedded")]
+impl embedded_hal::watchdog::WatchdogEnable for WatchDog {
+    type Time = WatchdogTimeout;
+
+    fn start<T>(&mut self, period: T)
+    where
+        T: Into<WatchdogTimeout>,
+    {
+        WatchDog::start(self, period.into(), WatchdogMode::Reset);
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::watchdog::Watchdog for WatchDog {
+    fn feed(&mut self) {
+        __wdr();
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::watchdog::WatchdogDisable for WatchDog {
+    fn disable(&mut self) {
+        WatchDog::disable(self);
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::watchdog::Watchdog for WatchdogHandle {
+    fn feed(&mut self) {
+        WatchdogHandle::feed(self);
+    }
 }
@@ -44,6 +44,27 @@ const CPU_FREQUENCY_HZ_IMPL: u32 = value_from_env!("AVR_CPU_FREQUENCY_HZ": u32);
 #[cfg(not(target_arch = "avr"))]
 const CPU_FREQUENCY_HZ_IMPL: u32 = DEFAULT_CPU_FREQUENCY_WHEN_NOT_AVR_HZ;
 
+/// Division of the system clock set by the clock prescaler, as a power of two.
+static mut CLOCK_DIVISION: u8 = 0;
+
+/// The effective clock frequency of the CPU after the clock prescaler.
+/// Equal to `CPU_FREQUENCY_HZ` unless the prescaler was changed at runtime,
+/// delay and baud rate calculations use this value.
+/// # Returns
+/// * `a u32` - the frequency of the CPU clock in Hz.
+pub fn cpu_frequency_hz() -> u32 {
+    CPU_FREQUENCY_HZ >> unsafe { CLOCK_DIVISION }
+}
+
+/// Records the division of the system clock after the prescaler is changed.
+/// # Arguments
+/// * `division` - a u8, the power of two by which the clock is divided.
+pub(crate) fn set_clock_division(division: u8) {
+    unsafe {
+        CLOCK_DIVISION = division;
+    }
+}
+
 #[cfg(test)]
 mod test {
     #[test]
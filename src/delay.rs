@@ -75,7 +75,72 @@ pub fn delay_ms(ms: u32) {
 pub fn delay_us(us: u32) {
     // nanoseconds
     let ns = us * 1000;
-    let ns_lp = 1000000000 / (crate::config::CPU_FREQUENCY_HZ / 4);
+    let ns_lp = 1000000000 / (crate::config::cpu_frequency_hz() / 4);
     let loops = (ns / ns_lp) as u32;
     delay(loops);
 }
+
+/// Delay provider for the drivers written against the `embedded-hal` delay traits.
+/// Long delays are split into parts, as the delay functions count in nanoseconds
+/// and would overflow after about four seconds.
+#[cfg(feature = "embedded")]
+#[derive(Clone, Copy, Default)]
+pub struct Delay;
+
+#[cfg(feature = "embedded")]
+impl Delay {
+    /// Creates a delay provider.
+    /// # Returns
+    /// * `a Delay object` - which is given to the drivers.
+    pub fn new() -> Delay {
+        Delay
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::delay::DelayMs<u32> for Delay {
+    fn delay_ms(&mut self, ms: u32) {
+        for _ in 0..ms / 1000 {
+            delay_ms(1000);
+        }
+        delay_ms(ms % 1000);
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::delay::DelayMs<u16> for Delay {
+    fn delay_ms(&mut self, ms: u16) {
+        embedded_hal::blocking::delay::DelayMs::<u32>::delay_ms(self, ms as u32);
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::delay::DelayMs<u8> for Delay {
+    fn delay_ms(&mut self, ms: u8) {
+        delay_ms(ms as u32);
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::delay::DelayUs<u32> for Delay {
+    fn delay_us(&mut self, us: u32) {
+        for _ in 0..us / 1_000_000 {
+            delay_ms(1000);
+        }
+        delay_us(us % 1_000_000);
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::delay::DelayUs<u16> for Delay {
+    fn delay_us(&mut self, us: u16) {
+        delay_us(us as u32);
+    }
+}
+
+#[cfg(feature = "embedded")]
+impl embedded_hal::blocking::delay::DelayUs<u8> for Delay {
+    fn delay_us(&mut self, us: u8) {
+        delay_us(us as u32);
+    }
+}
@@ -0,0 +1,182 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Heap allocator over a static buffer, enabled with the `alloc` feature so that
+//! `Vec`, `String` and `Box` of the `alloc` crate can be used.
+//! Free blocks are kept in a list sorted by address and merged with their neighbours
+//! when memory is given back, so the small heap does not fragment quickly.
+//! The heap takes `HEAP_SIZE` bytes of SRAM, out of 2 KB on the ATmega328P and 8 KB on the ATmega2560P.
+
+#[cfg(all(feature = "atmega2560p", not(feature = "atmega328p")))]
+use crate::atmega2560p::hal::interrupts;
+#[cfg(feature = "atmega328p")]
+use crate::atmega328p::hal::interrupts;
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::{align_of, size_of};
+use core::ptr::null_mut;
+
+/// Size of the heap in bytes.
+#[cfg(feature = "atmega328p")]
+pub const HEAP_SIZE: usize = 512;
+
+/// Size of the heap in bytes.
+#[cfg(not(feature = "atmega328p"))]
+pub const HEAP_SIZE: usize = 2048;
+
+/// Header written at the start of every free block.
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock,
+}
+
+/// Smallest block which can be tracked, allocations are rounded up to it.
+const MIN_BLOCK: usize = size_of::<FreeBlock>();
+
+static mut HEAP: [u8; HEAP_SIZE] = [0; HEAP_SIZE];
+static mut FREE_LIST: *mut FreeBlock = null_mut();
+static mut HEAP_READY: bool = false;
+
+/// The allocator used for the `alloc` crate, registered as the global allocator.
+pub struct Heap;
+
+#[global_allocator]
+static ALLOCATOR: Heap = Heap;
+
+/// Rounds the address up to the given alignment, which is a power of two.
+fn align_up(address: usize, align: usize) -> usize {
+    (address + align - 1) & !(align - 1)
+}
+
+/// Size actually taken from the heap for a layout, the same when allocating and freeing.
+fn block_size(layout: &Layout) -> usize {
+    align_up(layout.size().max(MIN_BLOCK), align_of::<FreeBlock>())
+}
+
+/// Turns the whole buffer into one free block on the first use of the heap.
+unsafe fn init() {
+    let start = align_up(HEAP.as_mut_ptr() as usize, align_of::<FreeBlock>());
+    let end = HEAP.as_mut_ptr() as usize + HEAP_SIZE;
+    let block = start as *mut FreeBlock;
+    (*block).size = end - start;
+    (*block).next = null_mut();
+    FREE_LIST = block;
+    HEAP_READY = true;
+}
+
+/// Gives the number of free bytes left on the heap.
+/// # Returns
+/// * `a usize` - the total size of the free blocks, which may be split in several parts.
+pub fn free_bytes() -> usize {
+    interrupts::free(|_| unsafe {
+        if !HEAP_READY {
+            init();
+        }
+        let mut total = 0;
+        let mut block = FREE_LIST;
+        while !block.is_null() {
+            total += (*block).size;
+            block = (*block).next;
+        }
+        total
+    })
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let size = block_size(&layout);
+        let align = layout.align().max(align_of::<FreeBlock>());
+
+        // The interrupts are disabled so that interrupt handlers may allocate too.
+        interrupts::free(|_| {
+            if !HEAP_READY {
+                init();
+            }
+
+            let mut prev: *mut FreeBlock = null_mut();
+            let mut block = FREE_LIST;
+            while !block.is_null() {
+                let block_start = block as usize;
+                let block_end = block_start + (*block).size;
+                let next = (*block).next;
+
+                // Space left in front of the allocation must be able to hold a free block.
+                let mut start = align_up(block_start, align);
+                if start != block_start && start - block_start < MIN_BLOCK {
+                    start = align_up(block_start + MIN_BLOCK, align);
+                }
+                let end = start + size;
+
+                // Space left behind the allocation is either none or another free block.
+                if end <= block_end && (end == block_end || block_end - end >= MIN_BLOCK) {
+                    let mut after = next;
+                    if end != block_end {
+                        let rest = end as *mut FreeBlock;
+                        (*rest).size = block_end - end;
+                        (*rest).next = next;
+                        after = rest;
+                    }
+
+                    if start != block_start {
+                        (*block).size = start - block_start;
+                        (*block).next = after;
+                    } else if prev.is_null() {
+                        FREE_LIST = after;
+                    } else {
+                        (*prev).next = after;
+                    }
+                    return start as *mut u8;
+                }
+
+                prev = block;
+                block = next;
+            }
+            null_mut()
+        })
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let size = block_size(&layout);
+        let start = ptr as usize;
+
+        interrupts::free(|_| {
+            // Find the free blocks before and after the returned one.
+            let mut prev: *mut FreeBlock = null_mut();
+            let mut next = FREE_LIST;
+            while !next.is_null() && (next as usize) < start {
+                prev = next;
+                next = (*next).next;
+            }
+
+            let block = ptr as *mut FreeBlock;
+            (*block).size = size;
+            (*block).next = next;
+
+            if !next.is_null() && start + size == next as usize {
+                (*block).size += (*next).size;
+                (*block).next = (*next).next;
+            }
+
+            if prev.is_null() {
+                FREE_LIST = block;
+            } else if prev as usize + (*prev).size == start {
+                (*prev).size += (*block).size;
+                (*prev).next = (*block).next;
+            } else {
+                (*prev).next = block;
+            }
+        })
+    }
+}
@@ -2,6 +2,10 @@
 #![deny(warnings)]
 #![feature(asm)]
 #![feature(llvm_asm)]
+#![feature(abi_avr_interrupt)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
 /// Library for AVR ATMEGA2560P Micro-controller
 /// For more information see the data sheet provided below
@@ -18,17 +22,39 @@ pub mod atmega2560p {
 
         pub mod power;
 
+        pub mod clock;
+
         pub mod port;
 
         pub mod interrupts;
 
+        pub mod ext_interrupt;
+
+        pub mod gpior;
+
+        pub mod flash;
+
+        pub mod eeprom;
+
         pub mod pin;
 
+        pub mod gpio;
+
         pub mod analog;
 
         pub mod digital;
 
+        pub mod debounce;
+
+        pub mod pulse;
+
         pub mod shift;
+
+        pub mod ir;
+
+        pub mod ppm;
+
+        pub mod peripherals;
     }
 
     /// Communication Control Library
@@ -44,7 +70,24 @@ pub mod atmega2560p {
 
         pub mod usart_recieve;
 
+        pub mod usart_buffer;
+
+        pub mod usart_loopback;
+
         pub mod i2c;
+
+        pub mod spi;
+
+        pub mod usart_spi;
+
+        pub mod one_wire;
+
+        pub mod midi;
+
+        pub mod lin;
+
+        pub mod sim800;
+        pub mod slip;
     }
 }
 
@@ -68,6 +111,8 @@ pub mod atmega328p {
     pub mod hal {
         pub mod power;
 
+        pub mod clock;
+
         pub mod sleep_mode;
 
         pub mod watchdog;
@@ -76,13 +121,33 @@ pub mod atmega328p {
 
         pub mod interrupts;
 
+        pub mod ext_interrupt;
+
+        pub mod gpior;
+
+        pub mod flash;
+
+        pub mod eeprom;
+
         pub mod pin;
 
+        pub mod gpio;
+
         pub mod analog;
 
         pub mod digital;
 
+        pub mod debounce;
+
+        pub mod pulse;
+
         pub mod shift;
+
+        pub mod ir;
+
+        pub mod ppm;
+
+        pub mod peripherals;
     }
 
     /// Communication Control Library
@@ -98,7 +163,24 @@ pub mod atmega328p {
 
         pub mod usart_recieve;
 
+        pub mod usart_buffer;
+
+        pub mod usart_loopback;
+
         pub mod i2c;
+
+        pub mod spi;
+
+        pub mod usart_spi;
+
+        pub mod one_wire;
+
+        pub mod midi;
+
+        pub mod lin;
+
+        pub mod sim800;
+        pub mod slip;
     }
 }
 
@@ -106,6 +188,30 @@ pub mod atmega328p {
 #[doc(hidden)]
 pub use atmega328p::*;
 
+/// Prints to a serial port like `Serial.print` of Arduino.
+/// USART0 is used unless another one is chosen with `com::usart::set_print_usart`,
+/// and it is initialized on the first print.
+#[cfg(feature = "com")]
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => {
+        $crate::com::usart::_print(format_args!($($arg)*))
+    };
+}
+
+/// Prints to a serial port followed by a new line, like `Serial.println` of Arduino.
+#[cfg(feature = "com")]
+#[macro_export]
+macro_rules! serial_println {
+    () => {
+        $crate::serial_print!("\r\n")
+    };
+    ($($arg:tt)*) => {{
+        $crate::serial_print!($($arg)*);
+        $crate::serial_print!("\r\n");
+    }};
+}
+
 /// Sensor control for AVR Chips
 /// For more information see the following links.
 /// `<https://server4.eca.ir/eshop/AHT10/Aosong_AHT10_en_draft_0c.pdf>`
@@ -124,6 +230,52 @@ pub mod llvm;
 #[doc(hidden)]
 pub use llvm::*;
 
+/// Constant data stored in the flash memory
+pub mod progmem;
+
 /// Configuration setup and time control
 pub mod config;
 pub mod delay;
+
+/// Panic handler reporting over a USART
+#[cfg(feature = "panic-serial")]
+pub mod panic;
+
+/// Data shared between interrupt handlers and the main program
+#[cfg(any(feature = "atmega328p", feature = "atmega2560p"))]
+pub mod sync;
+
+/// Implementation of the `critical-section` crate over SREG, enabled with the
+/// `critical-section-impl` feature, so that `critical_section::with` works on the chip.
+/// SREG is at the same address on both chips, so it is registered only once.
+#[cfg(feature = "critical-section-impl")]
+struct SregCriticalSection;
+
+#[cfg(feature = "critical-section-impl")]
+critical_section::set_impl!(SregCriticalSection);
+
+#[cfg(feature = "critical-section-impl")]
+unsafe impl critical_section::Impl for SregCriticalSection {
+    unsafe fn acquire() -> u8 {
+        let sreg = core::ptr::read_volatile(0x5F as *const u8);
+        core::ptr::write_volatile(0x5F as *mut u8, sreg & 0x7F);
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        sreg
+    }
+
+    unsafe fn release(sreg: u8) {
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+        core::ptr::write_volatile(0x5F as *mut u8, sreg);
+    }
+}
+
+/// Software timers for deferred and repeated actions
+#[cfg(any(feature = "atmega328p", feature = "atmega2560p"))]
+pub mod soft_timer;
+
+/// Heap allocator for the `alloc` crate
+#[cfg(all(
+    feature = "alloc",
+    any(feature = "atmega328p", feature = "atmega2560p")
+))]
+pub mod heap;
@@ -19,3 +19,15 @@
 pub fn __nop() {
     unsafe { llvm_asm!("nop") }
 }
+
+/// The `__wdr` function is equivalent to the WDR machine instruction.
+/// It resets the watchdog timer so that it starts counting its timeout again.
+pub fn __wdr() {
+    unsafe { llvm_asm!("wdr") }
+}
+
+/// The `__sleep` function is equivalent to the SLEEP machine instruction.
+/// The chip enters the sleep mode selected in SMCR if the sleep enable bit is set.
+pub fn __sleep() {
+    unsafe { llvm_asm!("sleep") }
+}
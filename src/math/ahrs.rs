@@ -0,0 +1,346 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Attitude and heading estimation from the readings of an IMU like the MPU6050.
+//! The Madgwick and Mahony filters turn the gyroscope rates into an orientation
+//! and correct its drift with the direction of gravity from the accelerometer,
+//! and with the direction of north from a magnetometer when one is given.
+//! The orientation is kept as a quaternion `[w, x, y, z]` and can be read as Euler angles.
+//! `<https://x-io.co.uk/open-source-imu-and-ahrs-algorithms/>`
+
+use micromath::F32Ext;
+
+/// Gives the quaternion scaled to a length of one, the same quaternion if it is zero.
+fn normalized(q: [f32; 4]) -> [f32; 4] {
+    let norm = q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3];
+    if norm == 0.0 {
+        return q;
+    }
+    let scale = norm.invsqrt();
+    [q[0] * scale, q[1] * scale, q[2] * scale, q[3] * scale]
+}
+
+/// Gives the vector scaled to a length of one, or None if it is zero.
+fn unit(v: [f32; 3]) -> Option<[f32; 3]> {
+    let norm = v[0] * v[0] + v[1] * v[1] + v[2] * v[2];
+    if norm == 0.0 {
+        return None;
+    }
+    let scale = norm.invsqrt();
+    Some([v[0] * scale, v[1] * scale, v[2] * scale])
+}
+
+/// Turns an orientation quaternion into Euler angles.
+/// # Arguments
+/// * `q` - a f32 array, the quaternion `[w, x, y, z]`.
+/// # Returns
+/// * `a tuple of three f32` - the roll, pitch and yaw in degrees.
+pub fn quaternion_to_euler(q: [f32; 4]) -> (f32, f32, f32) {
+    let [w, x, y, z] = q;
+    let roll = (w * x + y * z).atan2(0.5 - x * x - y * y);
+    let pitch = (-2.0 * (x * z - w * y)).clamp(-1.0, 1.0).asin();
+    let yaw = (x * y + w * z).atan2(0.5 - y * y - z * z);
+    (roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees())
+}
+
+/// Madgwick filter, which corrects the orientation by a gradient descent step.
+pub struct Madgwick {
+    beta: f32,
+    q: [f32; 4],
+}
+
+impl Madgwick {
+    /// Creates the filter, level and facing the x axis.
+    /// # Arguments
+    /// * `beta` - a f32, the gain of the correction, 0.1 is usual. Larger values follow the accelerometer faster but let in more of its noise.
+    /// # Returns
+    /// * `a Madgwick object` - the filter.
+    pub fn new(beta: f32) -> Madgwick {
+        Madgwick {
+            beta,
+            q: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    /// Updates the orientation with the gyroscope and accelerometer.
+    /// # Arguments
+    /// * `gyro` - a f32 array, the rates around x, y and z in radians per second.
+    /// * `accel` - a f32 array, the acceleration along x, y and z in any unit.
+    /// * `dt` - a f32, the time since the last update in seconds.
+    pub fn update_imu(&mut self, gyro: [f32; 3], accel: [f32; 3], dt: f32) {
+        let [q0, q1, q2, q3] = self.q;
+        let [gx, gy, gz] = gyro;
+
+        // Rate of change of the quaternion from the gyroscope.
+        let mut dq = [
+            0.5 * (-q1 * gx - q2 * gy - q3 * gz),
+            0.5 * (q0 * gx + q2 * gz - q3 * gy),
+            0.5 * (q0 * gy - q1 * gz + q3 * gx),
+            0.5 * (q0 * gz + q1 * gy - q2 * gx),
+        ];
+
+        if let Some([ax, ay, az]) = unit(accel) {
+            let (q0q0, q1q1, q2q2, q3q3) = (q0 * q0, q1 * q1, q2 * q2, q3 * q3);
+            let s = normalized([
+                4.0 * q0 * q2q2 + 2.0 * q2 * ax + 4.0 * q0 * q1q1 - 2.0 * q1 * ay,
+                4.0 * q1 * q3q3 - 2.0 * q3 * ax + 4.0 * q0q0 * q1 - 2.0 * q0 * ay - 4.0 * q1
+                    + 8.0 * q1 * q1q1
+                    + 8.0 * q1 * q2q2
+                    + 4.0 * q1 * az,
+                4.0 * q0q0 * q2 + 2.0 * q0 * ax + 4.0 * q2 * q3q3 - 2.0 * q3 * ay - 4.0 * q2
+                    + 8.0 * q2 * q1q1
+                    + 8.0 * q2 * q2q2
+                    + 4.0 * q2 * az,
+                4.0 * q1q1 * q3 - 2.0 * q1 * ax + 4.0 * q2q2 * q3 - 2.0 * q2 * ay,
+            ]);
+            for i in 0..4 {
+                dq[i] -= self.beta * s[i];
+            }
+        }
+
+        self.integrate(dq, dt);
+    }
+
+    /// Updates the orientation with the gyroscope, accelerometer and magnetometer,
+    /// which also keeps the yaw from drifting.
+    /// # Arguments
+    /// * `gyro` - a f32 array, the rates around x, y and z in radians per second.
+    /// * `accel` - a f32 array, the acceleration along x, y and z in any unit.
+    /// * `mag` - a f32 array, the magnetic field along x, y and z in any unit, in the axes of the accelerometer.
+    /// * `dt` - a f32, the time since the last update in seconds.
+    pub fn update(&mut self, gyro: [f32; 3], accel: [f32; 3], mag: [f32; 3], dt: f32) {
+        let (accel, [mx, my, mz]) = match (unit(accel), unit(mag)) {
+            (Some(accel), Some(mag)) => (accel, mag),
+            _ => return self.update_imu(gyro, accel, dt),
+        };
+        let [ax, ay, az] = accel;
+        let [q0, q1, q2, q3] = self.q;
+        let [gx, gy, gz] = gyro;
+
+        let mut dq = [
+            0.5 * (-q1 * gx - q2 * gy - q3 * gz),
+            0.5 * (q0 * gx + q2 * gz - q3 * gy),
+            0.5 * (q0 * gy - q1 * gz + q3 * gx),
+            0.5 * (q0 * gz + q1 * gy - q2 * gx),
+        ];
+
+        let (q0q0, q0q1, q0q2, q0q3) = (q0 * q0, q0 * q1, q0 * q2, q0 * q3);
+        let (q1q1, q1q2, q1q3) = (q1 * q1, q1 * q2, q1 * q3);
+        let (q2q2, q2q3, q3q3) = (q2 * q2, q2 * q3, q3 * q3);
+
+        // Direction of the earth's magnetic field, in the horizontal and vertical parts.
+        let hx = mx * q0q0 - 2.0 * q0 * my * q3
+            + 2.0 * q0 * mz * q2
+            + mx * q1q1
+            + 2.0 * q1 * my * q2
+            + 2.0 * q1 * mz * q3
+            - mx * q2q2
+            - mx * q3q3;
+        let hy = 2.0 * q0 * mx * q3 + my * q0q0 - 2.0 * q0 * mz * q1 + 2.0 * q1 * mx * q2
+            - my * q1q1
+            + my * q2q2
+            + 2.0 * q2 * mz * q3
+            - my * q3q3;
+        let bx2 = (hx * hx + hy * hy).sqrt();
+        let bz2 = -2.0 * q0 * mx * q2 + 2.0 * q0 * my * q1 + mz * q0q0 + 2.0 * q1 * mx * q3
+            - mz * q1q1
+            + 2.0 * q2 * my * q3
+            - mz * q2q2
+            + mz * q3q3;
+        let bx4 = 2.0 * bx2;
+        let bz4 = 2.0 * bz2;
+
+        // Errors of the expected gravity and field against the measured ones.
+        let fax = 2.0 * q1q3 - 2.0 * q0q2 - ax;
+        let fay = 2.0 * q0q1 + 2.0 * q2q3 - ay;
+        let faz = 1.0 - 2.0 * q1q1 - 2.0 * q2q2 - az;
+        let fmx = bx2 * (0.5 - q2q2 - q3q3) + bz2 * (q1q3 - q0q2) - mx;
+        let fmy = bx2 * (q1q2 - q0q3) + bz2 * (q0q1 + q2q3) - my;
+        let fmz = bx2 * (q0q2 + q1q3) + bz2 * (0.5 - q1q1 - q2q2) - mz;
+
+        let s = normalized([
+            -2.0 * q2 * fax + 2.0 * q1 * fay - bz2 * q2 * fmx
+                + (-bx2 * q3 + bz2 * q1) * fmy
+                + bx2 * q2 * fmz,
+            2.0 * q3 * fax + 2.0 * q0 * fay - 4.0 * q1 * faz
+                + bz2 * q3 * fmx
+                + (bx2 * q2 + bz2 * q0) * fmy
+                + (bx2 * q3 - bz4 * q1) * fmz,
+            -2.0 * q0 * fax + 2.0 * q3 * fay - 4.0 * q2 * faz
+                + (-bx4 * q2 - bz2 * q0) * fmx
+                + (bx2 * q1 + bz2 * q3) * fmy
+                + (bx2 * q0 - bz4 * q2) * fmz,
+            2.0 * q1 * fax
+                + 2.0 * q2 * fay
+                + (-bx4 * q3 + bz2 * q1) * fmx
+                + (-bx2 * q0 + bz2 * q2) * fmy
+                + bx2 * q1 * fmz,
+        ]);
+        for i in 0..4 {
+            dq[i] -= self.beta * s[i];
+        }
+
+        self.integrate(dq, dt);
+    }
+
+    /// Moves the quaternion by its rate of change over the time step.
+    fn integrate(&mut self, dq: [f32; 4], dt: f32) {
+        self.q = normalized([
+            self.q[0] + dq[0] * dt,
+            self.q[1] + dq[1] * dt,
+            self.q[2] + dq[2] * dt,
+            self.q[3] + dq[3] * dt,
+        ]);
+    }
+
+    /// Gives the orientation.
+    /// # Returns
+    /// * `a f32 array` - the quaternion `[w, x, y, z]`.
+    pub fn quaternion(&self) -> [f32; 4] {
+        self.q
+    }
+
+    /// Gives the orientation as Euler angles.
+    /// # Returns
+    /// * `a tuple of three f32` - the roll, pitch and yaw in degrees.
+    pub fn euler(&self) -> (f32, f32, f32) {
+        quaternion_to_euler(self.q)
+    }
+}
+
+/// Mahony filter, which corrects the gyroscope rates with a proportional and integral feedback.
+/// The integral part learns the bias of the gyroscope.
+pub struct Mahony {
+    kp: f32,
+    ki: f32,
+    q: [f32; 4],
+    integral: [f32; 3],
+}
+
+impl Mahony {
+    /// Creates the filter, level and facing the x axis.
+    /// # Arguments
+    /// * `kp` - a f32, the proportional gain, 1.0 is usual.
+    /// * `ki` - a f32, the integral gain, zero to not learn the gyroscope bias.
+    /// # Returns
+    /// * `a Mahony object` - the filter.
+    pub fn new(kp: f32, ki: f32) -> Mahony {
+        Mahony {
+            kp,
+            ki,
+            q: [1.0, 0.0, 0.0, 0.0],
+            integral: [0.0; 3],
+        }
+    }
+
+    /// Updates the orientation with the gyroscope and accelerometer.
+    /// # Arguments
+    /// * `gyro` - a f32 array, the rates around x, y and z in radians per second.
+    /// * `accel` - a f32 array, the acceleration along x, y and z in any unit.
+    /// * `dt` - a f32, the time since the last update in seconds.
+    pub fn update_imu(&mut self, gyro: [f32; 3], accel: [f32; 3], dt: f32) {
+        let error = unit(accel).map(|[ax, ay, az]| {
+            let [vx, vy, vz] = self.half_gravity();
+            [ay * vz - az * vy, az * vx - ax * vz, ax * vy - ay * vx]
+        });
+        self.correct_and_integrate(gyro, error, dt);
+    }
+
+    /// Updates the orientation with the gyroscope, accelerometer and magnetometer,
+    /// which also keeps the yaw from drifting.
+    /// # Arguments
+    /// * `gyro` - a f32 array, the rates around x, y and z in radians per second.
+    /// * `accel` - a f32 array, the acceleration along x, y and z in any unit.
+    /// * `mag` - a f32 array, the magnetic field along x, y and z in any unit, in the axes of the accelerometer.
+    /// * `dt` - a f32, the time since the last update in seconds.
+    pub fn update(&mut self, gyro: [f32; 3], accel: [f32; 3], mag: [f32; 3], dt: f32) {
+        let ([ax, ay, az], [mx, my, mz]) = match (unit(accel), unit(mag)) {
+            (Some(accel), Some(mag)) => (accel, mag),
+            _ => return self.update_imu(gyro, accel, dt),
+        };
+        let [q0, q1, q2, q3] = self.q;
+        let (q0q1, q0q2, q0q3) = (q0 * q1, q0 * q2, q0 * q3);
+        let (q1q1, q1q2, q1q3) = (q1 * q1, q1 * q2, q1 * q3);
+        let (q2q2, q2q3, q3q3) = (q2 * q2, q2 * q3, q3 * q3);
+
+        // Direction of the earth's magnetic field, in the horizontal and vertical parts.
+        let hx = 2.0 * (mx * (0.5 - q2q2 - q3q3) + my * (q1q2 - q0q3) + mz * (q1q3 + q0q2));
+        let hy = 2.0 * (mx * (q1q2 + q0q3) + my * (0.5 - q1q1 - q3q3) + mz * (q2q3 - q0q1));
+        let bx = (hx * hx + hy * hy).sqrt();
+        let bz = 2.0 * (mx * (q1q3 - q0q2) + my * (q2q3 + q0q1) + mz * (0.5 - q1q1 - q2q2));
+
+        let [vx, vy, vz] = self.half_gravity();
+        let wx = bx * (0.5 - q2q2 - q3q3) + bz * (q1q3 - q0q2);
+        let wy = bx * (q1q2 - q0q3) + bz * (q0q1 + q2q3);
+        let wz = bx * (q0q2 + q1q3) + bz * (0.5 - q1q1 - q2q2);
+
+        let error = [
+            (ay * vz - az * vy) + (my * wz - mz * wy),
+            (az * vx - ax * vz) + (mz * wx - mx * wz),
+            (ax * vy - ay * vx) + (mx * wy - my * wx),
+        ];
+        self.correct_and_integrate(gyro, Some(error), dt);
+    }
+
+    /// Gives half of the direction of gravity expected from the orientation.
+    fn half_gravity(&self) -> [f32; 3] {
+        let [q0, q1, q2, q3] = self.q;
+        [
+            q1 * q3 - q0 * q2,
+            q0 * q1 + q2 * q3,
+            q0 * q0 - 0.5 + q3 * q3,
+        ]
+    }
+
+    /// Adds the feedback of the error to the rates and moves the quaternion by them.
+    fn correct_and_integrate(&mut self, gyro: [f32; 3], error: Option<[f32; 3]>, dt: f32) {
+        let mut g = gyro;
+        if let Some(e) = error {
+            for i in 0..3 {
+                if self.ki > 0.0 {
+                    self.integral[i] += 2.0 * self.ki * e[i] * dt;
+                    g[i] += self.integral[i];
+                } else {
+                    self.integral[i] = 0.0;
+                }
+                g[i] += 2.0 * self.kp * e[i];
+            }
+        }
+
+        let [gx, gy, gz] = [g[0] * 0.5 * dt, g[1] * 0.5 * dt, g[2] * 0.5 * dt];
+        let [q0, q1, q2, q3] = self.q;
+        self.q = normalized([
+            q0 + (-q1 * gx - q2 * gy - q3 * gz),
+            q1 + (q0 * gx + q2 * gz - q3 * gy),
+            q2 + (q0 * gy - q1 * gz + q3 * gx),
+            q3 + (q0 * gz + q1 * gy - q2 * gx),
+        ]);
+    }
+
+    /// Gives the orientation.
+    /// # Returns
+    /// * `a f32 array` - the quaternion `[w, x, y, z]`.
+    pub fn quaternion(&self) -> [f32; 4] {
+        self.q
+    }
+
+    /// Gives the orientation as Euler angles.
+    /// # Returns
+    /// * `a tuple of three f32` - the roll, pitch and yaw in degrees.
+    pub fn euler(&self) -> (f32, f32, f32) {
+        quaternion_to_euler(self.q)
+    }
+}
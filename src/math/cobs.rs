@@ -0,0 +1,293 @@
+// RustDuino : A generic HAL implementation for Arduino Boards in Rust
+// Copyright (C) 2021 Devansh Kumar Jha, Indian Institute of Technology Kanpur
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Consistent Overhead Byte Stuffing of packets.
+//! The zero bytes of a packet are removed by splitting it into blocks, each led by
+//! a code byte giving the distance to the next zero. The encoded packet then holds
+//! no zero byte, so a single zero can mark the end of every packet on a serial link.
+//! At most one byte is added for every 254 bytes of the packet.
+//! `<http://www.stuartcheshire.org/papers/COBSforToN.pdf>`
+
+/// Errors found while decoding a packet.
+/// * `Overflow` - the decoded packet is longer than the output buffer.
+/// * `Truncated` - the packet ended before the block given by the last code byte.
+/// * `ZeroByte` - a zero byte was found inside the encoded packet.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CobsError {
+    Overflow,
+    Truncated,
+    ZeroByte,
+}
+
+/// Gives the largest length of an encoded packet, without its zero delimiter.
+/// # Arguments
+/// * `len` - a usize, the length of the packet.
+/// # Returns
+/// * `a usize` - the number of bytes which is always enough for the encoded packet.
+pub const fn cobs_max_encoded_len(len: usize) -> usize {
+    len + len / 254 + 1
+}
+
+/// Encodes one packet. The zero delimiter is not added, it has to be sent after the packet.
+/// # Arguments
+/// * `packet` - a u8 slice, the packet to be encoded.
+/// * `output` - a mutable u8 slice, where the encoded bytes are stored.
+/// # Returns
+/// * `a Option<usize>` - the number of encoded bytes, None if the output is too small.
+pub fn cobs_encode(packet: &[u8], output: &mut [u8]) -> Option<usize> {
+    if output.is_empty() {
+        return None;
+    }
+    let mut code_index = 0;
+    let mut code: u8 = 1;
+    let mut len = 1;
+    for &byte in packet.iter() {
+        if byte != 0 {
+            if len >= output.len() {
+                return None;
+            }
+            output[len] = byte;
+            len += 1;
+            code += 1;
+        }
+        if byte == 0 || code == 0xFF {
+            if len >= output.len() {
+                return None;
+            }
+            output[code_index] = code;
+            code_index = len;
+            len += 1;
+            code = 1;
+        }
+    }
+    output[code_index] = code;
+    Some(len)
+}
+
+/// Decodes one packet, given without its zero delimiter.
+/// # Arguments
+/// * `encoded` - a u8 slice, the encoded packet.
+/// * `output` - a mutable u8 slice, where the decoded packet is stored.
+/// # Returns
+/// * `a Result<usize, CobsError>` - the length of the decoded packet, or the error found in it.
+pub fn cobs_decode(encoded: &[u8], output: &mut [u8]) -> Result<usize, CobsError> {
+    let mut len = 0;
+    let mut i = 0;
+    while i < encoded.len() {
+        let code = encoded[i];
+        if code == 0 {
+            return Err(CobsError::ZeroByte);
+        }
+        i += 1;
+        for _ in 1..code {
+            if i >= encoded.len() {
+                return Err(CobsError::Truncated);
+            }
+            if encoded[i] == 0 {
+                return Err(CobsError::ZeroByte);
+            }
+            if len >= output.len() {
+                return Err(CobsError::Overflow);
+            }
+            output[len] = encoded[i];
+            len += 1;
+            i += 1;
+        }
+        // Every block except the last and the full ones stands for a zero after it.
+        if code != 0xFF && i < encoded.len() {
+            if len >= output.len() {
+                return Err(CobsError::Overflow);
+            }
+            output[len] = 0;
+            len += 1;
+        }
+    }
+    Ok(len)
+}
+
+/// Decodes packets from the received bytes, one byte at a time, so that no
+/// buffer for the encoded packet is needed. A zero byte ends every packet.
+/// The packet is kept in the buffer given to the decoder until the next byte is pushed.
+pub struct CobsDecoder<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+    code: u8,
+    remaining: u8,
+    error: Option<CobsError>,
+}
+
+impl<'a> CobsDecoder<'a> {
+    /// Creates a decoder which stores the packets in the given buffer.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, as long as the largest packet expected.
+    /// # Returns
+    /// * `a CobsDecoder object` - which will be given the received bytes.
+    pub fn new(buffer: &'a mut [u8]) -> CobsDecoder<'a> {
+        CobsDecoder {
+            buffer,
+            len: 0,
+            code: 0,
+            remaining: 0,
+            error: None,
+        }
+    }
+
+    /// Drops the part of the packet received so far.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.code = 0;
+        self.remaining = 0;
+        self.error = None;
+    }
+
+    /// Stores one decoded byte, or notes the overflow.
+    fn store(&mut self, byte: u8) {
+        if self.len >= self.buffer.len() {
+            self.error = Some(CobsError::Overflow);
+        } else {
+            self.buffer[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    /// Takes one received byte.
+    /// Empty packets, such as extra zero bytes between packets, are skipped.
+    /// # Arguments
+    /// * `byte` - a u8, the received byte.
+    /// # Returns
+    /// * `a Option<Result<usize, CobsError>>` - the length of the packet completed by the byte,
+    ///   or the error found in it. None while the packet is not complete.
+    pub fn push(&mut self, byte: u8) -> Option<Result<usize, CobsError>> {
+        if byte == 0 {
+            let result = match self.error {
+                Some(error) => Some(Err(error)),
+                None if self.code == 0 => None,
+                None if self.remaining != 0 => Some(Err(CobsError::Truncated)),
+                None => Some(Ok(self.len)),
+            };
+            self.reset();
+            return result;
+        }
+        if self.error.is_some() {
+            return None;
+        }
+
+        if self.remaining == 0 {
+            // A new block starts, so the block before it stood for a zero.
+            if self.code != 0 && self.code != 0xFF {
+                self.store(0);
+            }
+            self.code = byte;
+            self.remaining = byte - 1;
+        } else {
+            self.store(byte);
+            self.remaining -= 1;
+        }
+        None
+    }
+
+    /// Gives the packet completed by the last call of `push()`.
+    /// # Arguments
+    /// * `len` - a usize, the length returned by `push()`.
+    /// # Returns
+    /// * `a u8 slice` - the decoded packet.
+    pub fn packet(&self, len: usize) -> &[u8] {
+        &self.buffer[..len]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Encodes and decodes a packet, checking that the packet comes back unchanged.
+    fn round_trip(packet: &[u8]) {
+        let mut encoded = [0u8; 700];
+        let len = cobs_encode(packet, &mut encoded).unwrap();
+        assert!(len <= cobs_max_encoded_len(packet.len()));
+        assert!(encoded[..len].iter().all(|&byte| byte != 0));
+
+        let mut decoded = [0u8; 700];
+        let decoded_len = cobs_decode(&encoded[..len], &mut decoded).unwrap();
+        assert_eq!(&decoded[..decoded_len], packet);
+
+        let mut buffer = [0u8; 700];
+        let mut decoder = CobsDecoder::new(&mut buffer);
+        for &byte in encoded[..len].iter() {
+            assert_eq!(decoder.push(byte), None);
+        }
+        if packet.is_empty() {
+            assert_eq!(decoder.push(0), Some(Ok(0)));
+        } else {
+            let stream_len = decoder.push(0).unwrap().unwrap();
+            assert_eq!(decoder.packet(stream_len), packet);
+        }
+    }
+
+    #[test]
+    fn encodes_known_packets() {
+        let cases: [(&[u8], &[u8]); 5] = [
+            (&[0x00], &[0x01, 0x01]),
+            (&[0x00, 0x00], &[0x01, 0x01, 0x01]),
+            (&[0x11, 0x22, 0x00, 0x33], &[0x03, 0x11, 0x22, 0x02, 0x33]),
+            (&[0x11, 0x22, 0x33, 0x44], &[0x05, 0x11, 0x22, 0x33, 0x44]),
+            (&[0x11, 0x00, 0x00, 0x00], &[0x02, 0x11, 0x01, 0x01, 0x01]),
+        ];
+        for (packet, expected) in cases.iter() {
+            let mut encoded = [0u8; 8];
+            let len = cobs_encode(packet, &mut encoded).unwrap();
+            assert_eq!(&encoded[..len], *expected);
+        }
+    }
+
+    #[test]
+    fn round_trips_packets() {
+        round_trip(&[]);
+        round_trip(&[0x00]);
+        round_trip(&[0x01, 0x00, 0xFF, 0x00, 0x00, 0x7F]);
+
+        // Long packets with and without zeros, around the 254 byte blocks.
+        let mut packet = [0u8; 600];
+        for len in [253, 254, 255, 508, 600].iter() {
+            for (i, byte) in packet.iter_mut().enumerate() {
+                *byte = (i % 255) as u8 + 1;
+            }
+            round_trip(&packet[..*len]);
+            for (i, byte) in packet.iter_mut().enumerate() {
+                *byte = (i % 256) as u8;
+            }
+            round_trip(&packet[..*len]);
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut output = [0u8; 4];
+        assert_eq!(cobs_encode(&[0x11, 0x22, 0x33, 0x44], &mut output), None);
+        assert_eq!(
+            cobs_decode(&[0x03, 0x11], &mut output),
+            Err(CobsError::Truncated)
+        );
+        assert_eq!(
+            cobs_decode(&[0x03, 0x00, 0x11], &mut output),
+            Err(CobsError::ZeroByte)
+        );
+        assert_eq!(
+            cobs_decode(&[0x06, 0x11, 0x22, 0x33, 0x44, 0x55], &mut output),
+            Err(CobsError::Overflow)
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Complementary filter giving the roll and pitch of an IMU like the MPU6050.
+//! The angles from integrating the gyroscope are smooth but drift, the angles from the
+//! direction of gravity are steady but noisy, so the filter takes most of the first
+//! and a little of the second at every update.
+//! It is the simplest way to fuse the readings, see `Madgwick` or `KalmanAngle` for better ones.
+
+use micromath::F32Ext;
+
+/// Complementary filter for roll and pitch.
+pub struct ComplementaryFilter {
+    alpha: f32,
+    roll: f32,
+    pitch: f32,
+    last_ms: Option<u32>,
+    started: bool,
+}
+
+impl ComplementaryFilter {
+    /// Creates the filter with the weight which suits the MPU6050 sampled at 50 to 200 Hz.
+    /// # Returns
+    /// * `a ComplementaryFilter object` - the filter.
+    pub fn new() -> ComplementaryFilter {
+        ComplementaryFilter::with_alpha(0.98)
+    }
+
+    /// Creates the filter with another weight of the gyroscope.
+    /// # Arguments
+    /// * `alpha` - a f32, from 0 to 1, the part of the angle taken from the gyroscope at each update.
+    /// # Returns
+    /// * `a ComplementaryFilter object` - the filter.
+    pub fn with_alpha(alpha: f32) -> ComplementaryFilter {
+        ComplementaryFilter {
+            alpha,
+            roll: 0.0,
+            pitch: 0.0,
+            last_ms: None,
+            started: false,
+        }
+    }
+
+    /// Updates the angles with a pair of readings. The first update takes the angles
+    /// from the accelerometer alone.
+    /// # Arguments
+    /// * `accel` - a f32 array, the acceleration along x, y and z in any unit.
+    /// * `gyro` - a f32 array, the rates around x, y and z in degrees per second.
+    /// * `dt` - a f32, the time since the last update in seconds.
+    /// # Returns
+    /// * `a tuple of two f32` - the roll and pitch in degrees.
+    pub fn update(&mut self, accel: [f32; 3], gyro: [f32; 3], dt: f32) -> (f32, f32) {
+        let [ax, ay, az] = accel;
+        let accel_roll = ay.atan2(az).to_degrees();
+        let accel_pitch = (-ax).atan2((ay * ay + az * az).sqrt()).to_degrees();
+
+        if !self.started {
+            self.roll = accel_roll;
+            self.pitch = accel_pitch;
+            self.started = true;
+        } else {
+            self.roll = self.alpha * (self.roll + gyro[0] * dt) + (1.0 - self.alpha) * accel_roll;
+            self.pitch =
+                self.alpha * (self.pitch + gyro[1] * dt) + (1.0 - self.alpha) * accel_pitch;
+        }
+        (self.roll, self.pitch)
+    }
+
+    /// Updates the angles with a pair of readings taken at a time in milliseconds,
+    /// such as `soft_timer::now()`, so the time step is found by the filter.
+    /// # Arguments
+    /// * `accel` - a f32 array, the acceleration along x, y and z in any unit.
+    /// * `gyro` - a f32 array, the rates around x, y and z in degrees per second.
+    /// * `now_ms` - a u32, the time of the readings in milliseconds, which may wrap around.
+    /// # Returns
+    /// * `a tuple of two f32` - the roll and pitch in degrees.
+    pub fn update_at(&mut self, accel: [f32; 3], gyro: [f32; 3], now_ms: u32) -> (f32, f32) {
+        let dt = match self.last_ms {
+            Some(last) => now_ms.wrapping_sub(last) as f32 / 1000.0,
+            None => 0.0,
+        };
+        self.last_ms = Some(now_ms);
+        self.update(accel, gyro, dt)
+    }
+
+    /// Gives the roll, the angle around the x axis.
+    /// # Returns
+    /// * `a f32` - the roll in degrees.
+    pub fn roll(&self) -> f32 {
+        self.roll
+    }
+
+    /// Gives the pitch, the angle around the y axis.
+    /// # Returns
+    /// * `a f32` - the pitch in degrees.
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Forgets the angles, so that the next update takes them from the accelerometer.
+    pub fn reset(&mut self) {
+        self.started = false;
+        self.last_ms = None;
+    }
+}
+
+impl Default for ComplementaryFilter {
+    fn default() -> ComplementaryFilter {
+        ComplementaryFilter::new()
+    }
+}
@@ -0,0 +1,180 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Cyclic redundancy checks used by the protocols and devices of the library.
+//! The CRCs are computed bit by bit instead of with lookup tables, which would take
+//! up to a kilobyte of flash each, as the messages on the AVR are short.
+//! The check value of every CRC for the ASCII bytes `123456789` is given in its documentation.
+//! `<https://reveng.sourceforge.io/crc-catalogue/>`
+
+/// CRC-8 with polynomial x^8 + x^2 + x + 1, used by SMBus and many I2C sensors.
+/// Check value 0xF4.
+/// # Arguments
+/// * `data` - a u8 slice, the bytes to be checked.
+/// # Returns
+/// * `a u8` - the CRC of the bytes.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for &byte in data.iter() {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-8 of Maxim with polynomial x^8 + x^5 + x^4 + 1, used by 1-Wire devices.
+/// The CRC over data followed by its CRC byte is zero. Check value 0xA1.
+/// # Arguments
+/// * `data` - a u8 slice, the bytes to be checked.
+/// # Returns
+/// * `a u8` - the CRC of the bytes.
+pub fn crc8_maxim(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for &byte in data.iter() {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x01 != 0 {
+                (crc >> 1) ^ 0x8C
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-7 with polynomial x^7 + x^3 + 1, which protects the commands of SD cards.
+/// The command byte sent is `(crc7(command) << 1) | 1`. Check value 0x75.
+/// # Arguments
+/// * `data` - a u8 slice, the bytes to be checked.
+/// # Returns
+/// * `a u8` - the CRC of the bytes, in the lower 7 bits.
+pub fn crc7(data: &[u8]) -> u8 {
+    // Computed in the upper 7 bits, so that the polynomial lines up with the data bytes.
+    let mut crc: u8 = 0;
+    for &byte in data.iter() {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ (0x09 << 1)
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc >> 1
+}
+
+/// CRC-16 of Modbus RTU with the reflected polynomial 0xA001 and starting value 0xFFFF.
+/// It is sent low byte first after the frame. Check value 0x4B37.
+/// # Arguments
+/// * `data` - a u8 slice, the bytes to be checked.
+/// # Returns
+/// * `a u16` - the CRC of the bytes.
+pub fn crc16_modbus(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data.iter() {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 0x0001 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-16 of CCITT (XMODEM) with polynomial 0x1021 and starting value 0, which
+/// protects the data blocks of SD cards and XMODEM transfers. Check value 0x31C3.
+/// # Arguments
+/// * `data` - a u8 slice, the bytes to be checked.
+/// # Returns
+/// * `a u16` - the CRC of the bytes.
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in data.iter() {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-32 of Ethernet, ZIP and PNG with the reflected polynomial 0xEDB88320.
+/// Check value 0xCBF43926.
+/// # Arguments
+/// * `data` - a u8 slice, the bytes to be checked.
+/// # Returns
+/// * `a u32` - the CRC of the bytes.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xFFFF_FFFF;
+    for &byte in data.iter() {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 0x0000_0001 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CHECK: &[u8] = b"123456789";
+
+    #[test]
+    fn check_values() {
+        assert_eq!(crc8(CHECK), 0xF4);
+        assert_eq!(crc8_maxim(CHECK), 0xA1);
+        assert_eq!(crc7(CHECK), 0x75);
+        assert_eq!(crc16_modbus(CHECK), 0x4B37);
+        assert_eq!(crc16_ccitt(CHECK), 0x31C3);
+        assert_eq!(crc32(CHECK), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn crc8_maxim_of_data_and_crc_is_zero() {
+        let mut data = [0u8; 10];
+        data[..9].copy_from_slice(CHECK);
+        data[9] = crc8_maxim(CHECK);
+        assert_eq!(crc8_maxim(&data), 0);
+    }
+
+    #[test]
+    fn crc7_of_sd_go_idle_command() {
+        // CMD0 is sent with the well known CRC byte 0x95.
+        let command = [0x40, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!((crc7(&command) << 1) | 1, 0x95);
+    }
+}
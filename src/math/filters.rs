@@ -0,0 +1,211 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Filters for smoothing noisy readings of the ADC and sensors one sample at a time.
+//! They keep their samples in fixed size arrays, so no heap is needed, and use
+//! integer arithmetic only.
+
+/// Average of the last `N` samples.
+pub struct MovingAverage<const N: usize> {
+    samples: [i32; N],
+    sum: i32,
+    index: usize,
+    count: usize,
+}
+
+impl<const N: usize> MovingAverage<N> {
+    /// Creates the filter with no samples.
+    /// # Returns
+    /// * `a MovingAverage object` - the filter.
+    pub fn new() -> MovingAverage<N> {
+        if N == 0 {
+            unreachable!();
+        }
+        MovingAverage {
+            samples: [0; N],
+            sum: 0,
+            index: 0,
+            count: 0,
+        }
+    }
+
+    /// Adds a sample, replacing the oldest one once `N` samples are held.
+    /// # Arguments
+    /// * `sample` - a i32, the new reading.
+    /// # Returns
+    /// * `a i32` - the average of the samples held.
+    pub fn update(&mut self, sample: i32) -> i32 {
+        if self.count == N {
+            self.sum -= self.samples[self.index];
+        } else {
+            self.count += 1;
+        }
+        self.samples[self.index] = sample;
+        self.sum += sample;
+        self.index = (self.index + 1) % N;
+        self.value()
+    }
+
+    /// Gives the average of the samples held.
+    /// # Returns
+    /// * `a i32` - the average, zero before the first sample.
+    pub fn value(&self) -> i32 {
+        if self.count == 0 {
+            return 0;
+        }
+        self.sum / self.count as i32
+    }
+
+    /// Removes all the samples.
+    pub fn reset(&mut self) {
+        self.sum = 0;
+        self.index = 0;
+        self.count = 0;
+    }
+}
+
+impl<const N: usize> Default for MovingAverage<N> {
+    fn default() -> MovingAverage<N> {
+        MovingAverage::new()
+    }
+}
+
+/// Median of the last `N` samples, which removes single spikes from the readings
+/// without moving the value like an average would. An odd `N` is best.
+pub struct MedianFilter<const N: usize> {
+    samples: [i32; N],
+    index: usize,
+    count: usize,
+}
+
+impl<const N: usize> MedianFilter<N> {
+    /// Creates the filter with no samples.
+    /// # Returns
+    /// * `a MedianFilter object` - the filter.
+    pub fn new() -> MedianFilter<N> {
+        if N == 0 {
+            unreachable!();
+        }
+        MedianFilter {
+            samples: [0; N],
+            index: 0,
+            count: 0,
+        }
+    }
+
+    /// Adds a sample, replacing the oldest one once `N` samples are held.
+    /// # Arguments
+    /// * `sample` - a i32, the new reading.
+    /// # Returns
+    /// * `a i32` - the median of the samples held.
+    pub fn update(&mut self, sample: i32) -> i32 {
+        self.samples[self.index] = sample;
+        self.index = (self.index + 1) % N;
+        if self.count < N {
+            self.count += 1;
+        }
+        self.value()
+    }
+
+    /// Gives the median of the samples held.
+    /// # Returns
+    /// * `a i32` - the middle sample, or the lower of the two middle ones for an even count, zero before the first sample.
+    pub fn value(&self) -> i32 {
+        if self.count == 0 {
+            return 0;
+        }
+        // Insertion sort of a copy, which is quick for the few samples of a filter.
+        let mut sorted = self.samples;
+        let sorted = &mut sorted[..self.count];
+        for i in 1..sorted.len() {
+            let mut j = i;
+            while j > 0 && sorted[j - 1] > sorted[j] {
+                sorted.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        sorted[(sorted.len() - 1) / 2]
+    }
+
+    /// Removes all the samples.
+    pub fn reset(&mut self) {
+        self.index = 0;
+        self.count = 0;
+    }
+}
+
+impl<const N: usize> Default for MedianFilter<N> {
+    fn default() -> MedianFilter<N> {
+        MedianFilter::new()
+    }
+}
+
+/// Exponential moving average, where every sample moves the value by a part of the difference.
+/// The part is 1 / 2^`shift`, so no division is needed. The value is kept with `shift`
+/// extra bits, so small changes are not lost to rounding.
+pub struct ExponentialFilter {
+    shift: u8,
+    state: i32,
+    started: bool,
+}
+
+impl ExponentialFilter {
+    /// Creates the filter.
+    /// # Arguments
+    /// * `shift` - a u8, the smoothing from 0 to 15, each step halves how fast the value follows the samples.
+    /// # Returns
+    /// * `a ExponentialFilter object` - the filter.
+    pub fn new(shift: u8) -> ExponentialFilter {
+        if shift > 15 {
+            unreachable!();
+        }
+        ExponentialFilter {
+            shift,
+            state: 0,
+            started: false,
+        }
+    }
+
+    /// Adds a sample. The first sample sets the value directly.
+    /// # Arguments
+    /// * `sample` - a i32, the new reading, within 16 bits.
+    /// # Returns
+    /// * `a i32` - the filtered value.
+    pub fn update(&mut self, sample: i32) -> i32 {
+        if !self.started {
+            self.state = sample << self.shift;
+            self.started = true;
+        } else {
+            self.state += sample - self.value();
+        }
+        self.value()
+    }
+
+    /// Gives the filtered value.
+    /// # Returns
+    /// * `a i32` - the value, rounded to the nearest whole number.
+    pub fn value(&self) -> i32 {
+        if self.shift == 0 {
+            return self.state;
+        }
+        (self.state + (1 << (self.shift - 1))) >> self.shift
+    }
+
+    /// Forgets the value, so that the next sample sets it again.
+    pub fn reset(&mut self) {
+        self.started = false;
+    }
+}
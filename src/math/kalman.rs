@@ -0,0 +1,332 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Kalman filters for smoothing sensor readings and for fusing the angle given by an
+//! accelerometer with the rate given by a gyroscope, as on the MPU6050.
+//! Each filter comes in a `f32` variant and a fixed point variant using Q16.16 numbers
+//! (an i32 where 65536 stands for 1), which avoids the slow software floats of the AVR.
+//! `<https://github.com/TKJElectronics/KalmanFilter>`
+
+/// One in Q16.16 fixed point.
+pub const Q16_ONE: i32 = 1 << 16;
+
+/// Multiplies two Q16.16 numbers.
+fn q_mul(a: i32, b: i32) -> i32 {
+    ((a as i64 * b as i64) >> 16) as i32
+}
+
+/// Divides two Q16.16 numbers.
+fn q_div(a: i32, b: i32) -> i32 {
+    (((a as i64) << 16) / b as i64) as i32
+}
+
+/// Kalman filter for one value which is measured with noise, like an analog input.
+pub struct Kalman {
+    q: f32,
+    r: f32,
+    x: f32,
+    p: f32,
+}
+
+impl Kalman {
+    /// Creates the filter.
+    /// # Arguments
+    /// * `q` - a f32, the process noise, how fast the true value may change.
+    /// * `r` - a f32, the measurement noise, the variance of the readings.
+    /// * `initial` - a f32, the starting estimate.
+    /// # Returns
+    /// * `a Kalman object` - the filter.
+    pub fn new(q: f32, r: f32, initial: f32) -> Kalman {
+        Kalman {
+            q,
+            r,
+            x: initial,
+            p: 1.0,
+        }
+    }
+
+    /// Adds a measurement to the estimate.
+    /// # Arguments
+    /// * `measurement` - a f32, the new reading.
+    /// # Returns
+    /// * `a f32` - the new estimate.
+    pub fn update(&mut self, measurement: f32) -> f32 {
+        self.p += self.q;
+        let k = self.p / (self.p + self.r);
+        self.x += k * (measurement - self.x);
+        self.p *= 1.0 - k;
+        self.x
+    }
+
+    /// Gives the current estimate.
+    /// # Returns
+    /// * `a f32` - the estimate.
+    pub fn value(&self) -> f32 {
+        self.x
+    }
+}
+
+/// Kalman filter for one value in Q16.16 fixed point.
+pub struct KalmanFixed {
+    q: i32,
+    r: i32,
+    x: i32,
+    p: i32,
+}
+
+impl KalmanFixed {
+    /// Creates the filter.
+    /// # Arguments
+    /// * `q` - a i32, the process noise in Q16.16.
+    /// * `r` - a i32, the measurement noise in Q16.16.
+    /// * `initial` - a i32, the starting estimate in Q16.16.
+    /// # Returns
+    /// * `a KalmanFixed object` - the filter.
+    pub fn new(q: i32, r: i32, initial: i32) -> KalmanFixed {
+        KalmanFixed {
+            q,
+            r,
+            x: initial,
+            p: Q16_ONE,
+        }
+    }
+
+    /// Adds a measurement to the estimate.
+    /// # Arguments
+    /// * `measurement` - a i32, the new reading in Q16.16.
+    /// # Returns
+    /// * `a i32` - the new estimate in Q16.16.
+    pub fn update(&mut self, measurement: i32) -> i32 {
+        self.p += self.q;
+        let k = q_div(self.p, self.p + self.r);
+        self.x += q_mul(k, measurement - self.x);
+        self.p = q_mul(self.p, Q16_ONE - k);
+        self.x
+    }
+
+    /// Gives the current estimate.
+    /// # Returns
+    /// * `a i32` - the estimate in Q16.16.
+    pub fn value(&self) -> i32 {
+        self.x
+    }
+}
+
+/// Kalman filter with two states, the angle and the bias of the gyroscope.
+/// The gyroscope rate moves the angle between measurements and the angle from the
+/// accelerometer corrects it, while the drift of the gyroscope is learned as the bias.
+pub struct KalmanAngle {
+    q_angle: f32,
+    q_bias: f32,
+    r_measure: f32,
+    angle: f32,
+    bias: f32,
+    rate: f32,
+    p: [[f32; 2]; 2],
+}
+
+impl KalmanAngle {
+    /// Creates the filter with the noise values which suit the MPU6050.
+    /// # Returns
+    /// * `a KalmanAngle object` - the filter, starting at an angle of zero.
+    pub fn new() -> KalmanAngle {
+        KalmanAngle::with_noise(0.001, 0.003, 0.03)
+    }
+
+    /// Creates the filter with other noise values.
+    /// # Arguments
+    /// * `q_angle` - a f32, the process noise of the angle.
+    /// * `q_bias` - a f32, the process noise of the gyroscope bias.
+    /// * `r_measure` - a f32, the noise of the accelerometer angle.
+    /// # Returns
+    /// * `a KalmanAngle object` - the filter, starting at an angle of zero.
+    pub fn with_noise(q_angle: f32, q_bias: f32, r_measure: f32) -> KalmanAngle {
+        KalmanAngle {
+            q_angle,
+            q_bias,
+            r_measure,
+            angle: 0.0,
+            bias: 0.0,
+            rate: 0.0,
+            p: [[0.0; 2]; 2],
+        }
+    }
+
+    /// Sets the angle, usually to the accelerometer angle at start up.
+    /// # Arguments
+    /// * `angle` - a f32, the angle in degrees.
+    pub fn set_angle(&mut self, angle: f32) {
+        self.angle = angle;
+    }
+
+    /// Adds a pair of readings to the estimate.
+    /// # Arguments
+    /// * `new_angle` - a f32, the angle from the accelerometer in degrees.
+    /// * `new_rate` - a f32, the rate from the gyroscope in degrees per second.
+    /// * `dt` - a f32, the time since the last update in seconds.
+    /// # Returns
+    /// * `a f32` - the new angle in degrees.
+    pub fn update(&mut self, new_angle: f32, new_rate: f32, dt: f32) -> f32 {
+        // Predict the angle from the rate without the bias.
+        self.rate = new_rate - self.bias;
+        self.angle += dt * self.rate;
+
+        let p = &mut self.p;
+        p[0][0] += dt * (dt * p[1][1] - p[0][1] - p[1][0] + self.q_angle);
+        p[0][1] -= dt * p[1][1];
+        p[1][0] -= dt * p[1][1];
+        p[1][1] += self.q_bias * dt;
+
+        // Correct with the measured angle.
+        let s = p[0][0] + self.r_measure;
+        let k0 = p[0][0] / s;
+        let k1 = p[1][0] / s;
+        let y = new_angle - self.angle;
+        self.angle += k0 * y;
+        self.bias += k1 * y;
+
+        let p00 = p[0][0];
+        let p01 = p[0][1];
+        p[0][0] -= k0 * p00;
+        p[0][1] -= k0 * p01;
+        p[1][0] -= k1 * p00;
+        p[1][1] -= k1 * p01;
+
+        self.angle
+    }
+
+    /// Gives the current angle.
+    /// # Returns
+    /// * `a f32` - the angle in degrees.
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
+    /// Gives the rate of the last update with the bias removed.
+    /// # Returns
+    /// * `a f32` - the rate in degrees per second.
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+}
+
+impl Default for KalmanAngle {
+    fn default() -> KalmanAngle {
+        KalmanAngle::new()
+    }
+}
+
+/// Kalman filter with the angle and the gyroscope bias in Q16.16 fixed point.
+/// See `KalmanAngle` for the working of the filter.
+pub struct KalmanAngleFixed {
+    q_angle: i32,
+    q_bias: i32,
+    r_measure: i32,
+    angle: i32,
+    bias: i32,
+    rate: i32,
+    p: [[i32; 2]; 2],
+}
+
+impl KalmanAngleFixed {
+    /// Creates the filter with the noise values which suit the MPU6050.
+    /// # Returns
+    /// * `a KalmanAngleFixed object` - the filter, starting at an angle of zero.
+    pub fn new() -> KalmanAngleFixed {
+        // 0.001, 0.003 and 0.03 in Q16.16.
+        KalmanAngleFixed::with_noise(66, 197, 1966)
+    }
+
+    /// Creates the filter with other noise values.
+    /// # Arguments
+    /// * `q_angle` - a i32, the process noise of the angle in Q16.16.
+    /// * `q_bias` - a i32, the process noise of the gyroscope bias in Q16.16.
+    /// * `r_measure` - a i32, the noise of the accelerometer angle in Q16.16.
+    /// # Returns
+    /// * `a KalmanAngleFixed object` - the filter, starting at an angle of zero.
+    pub fn with_noise(q_angle: i32, q_bias: i32, r_measure: i32) -> KalmanAngleFixed {
+        KalmanAngleFixed {
+            q_angle,
+            q_bias,
+            r_measure,
+            angle: 0,
+            bias: 0,
+            rate: 0,
+            p: [[0; 2]; 2],
+        }
+    }
+
+    /// Sets the angle, usually to the accelerometer angle at start up.
+    /// # Arguments
+    /// * `angle` - a i32, the angle in degrees in Q16.16.
+    pub fn set_angle(&mut self, angle: i32) {
+        self.angle = angle;
+    }
+
+    /// Adds a pair of readings to the estimate.
+    /// # Arguments
+    /// * `new_angle` - a i32, the angle from the accelerometer in degrees in Q16.16.
+    /// * `new_rate` - a i32, the rate from the gyroscope in degrees per second in Q16.16.
+    /// * `dt` - a i32, the time since the last update in seconds in Q16.16.
+    /// # Returns
+    /// * `a i32` - the new angle in degrees in Q16.16.
+    pub fn update(&mut self, new_angle: i32, new_rate: i32, dt: i32) -> i32 {
+        self.rate = new_rate - self.bias;
+        self.angle += q_mul(dt, self.rate);
+
+        let p = &mut self.p;
+        p[0][0] += q_mul(dt, q_mul(dt, p[1][1]) - p[0][1] - p[1][0] + self.q_angle);
+        p[0][1] -= q_mul(dt, p[1][1]);
+        p[1][0] -= q_mul(dt, p[1][1]);
+        p[1][1] += q_mul(self.q_bias, dt);
+
+        let s = p[0][0] + self.r_measure;
+        let k0 = q_div(p[0][0], s);
+        let k1 = q_div(p[1][0], s);
+        let y = new_angle - self.angle;
+        self.angle += q_mul(k0, y);
+        self.bias += q_mul(k1, y);
+
+        let p00 = p[0][0];
+        let p01 = p[0][1];
+        p[0][0] -= q_mul(k0, p00);
+        p[0][1] -= q_mul(k0, p01);
+        p[1][0] -= q_mul(k1, p00);
+        p[1][1] -= q_mul(k1, p01);
+
+        self.angle
+    }
+
+    /// Gives the current angle.
+    /// # Returns
+    /// * `a i32` - the angle in degrees in Q16.16.
+    pub fn angle(&self) -> i32 {
+        self.angle
+    }
+
+    /// Gives the rate of the last update with the bias removed.
+    /// # Returns
+    /// * `a i32` - the rate in degrees per second in Q16.16.
+    pub fn rate(&self) -> i32 {
+        self.rate
+    }
+}
+
+impl Default for KalmanAngleFixed {
+    fn default() -> KalmanAngleFixed {
+        KalmanAngleFixed::new()
+    }
+}
@@ -14,15 +14,52 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
-/// Maps a number from one data type to the another data type in a uniform ratio scale.
+/// Maps a number from one range to another in a uniform ratio scale, like `map()` of Arduino.
+/// The ranges may be reversed and the value may lie outside of the input range, in which
+/// case the result lies outside of the output range as well, use `constrain()` to limit it.
+/// The product is computed in 64 bits so that it can not overflow.
 /// # Arguments
-/// * `val` - a u64, the value which is to be mapped to any other data type.
-/// * `in_min` - a u64, the minimum value possible in the data type of `val`.
-/// * `in_max` - a u64, the maximum value possible in the data type of `val`.
-/// * `out_min` - a u64, the minimum value possible in the required data type for `val`.
-/// * `out_max` - a u64, the maximum value possible in the required data type for `val`.
+/// * `val` - a i32, the value which is to be mapped.
+/// * `in_min` - a i32, the value of the input range mapped to `out_min`.
+/// * `in_max` - a i32, the value of the input range mapped to `out_max`.
+/// * `out_min` - a i32, the lower end of the output range.
+/// * `out_max` - a i32, the upper end of the output range.
 /// # Returns
-/// * `a u64` - the final value of `val` in the required data type.
-pub fn map(val: u64, in_min: u64, in_max: u64, out_min: u64, out_max: u64) -> u64 {
-    (val - in_min) * (out_max - out_min) / (in_max - in_min) + out_min
+/// * `a i32` - the value in the output range, rounded towards `out_min`.
+pub fn map(val: i32, in_min: i32, in_max: i32, out_min: i32, out_max: i32) -> i32 {
+    if in_min == in_max {
+        unreachable!();
+    }
+    let scaled = (val as i64 - in_min as i64) * (out_max as i64 - out_min as i64)
+        / (in_max as i64 - in_min as i64);
+    (scaled + out_min as i64) as i32
+}
+
+/// Limits a value to a range, like `constrain()` of Arduino.
+/// # Arguments
+/// * `val` - the value to be limited.
+/// * `low` - the lowest value allowed.
+/// * `high` - the highest value allowed, not below `low`.
+/// # Returns
+/// * `the value` - `low` if `val` is below it, `high` if `val` is above it, else `val`.
+pub fn constrain<T: PartialOrd>(val: T, low: T, high: T) -> T {
+    if val < low {
+        low
+    } else if val > high {
+        high
+    } else {
+        val
+    }
+}
+
+/// Gives the number at a fraction of the way between two numbers, for fading and ramps.
+/// # Arguments
+/// * `from` - a i32, the number given for a fraction of 0.
+/// * `to` - a i32, the number given for a fraction of 255.
+/// * `fraction` - a u8, how far to go from `from` to `to`, in 255ths.
+/// # Returns
+/// * `a i32` - the number between `from` and `to`.
+pub fn lerp(from: i32, to: i32, fraction: u8) -> i32 {
+    let step = (to as i64 - from as i64) * fraction as i64 / 255;
+    (from as i64 + step) as i32
 }
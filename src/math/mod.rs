@@ -14,10 +14,26 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
+mod ahrs;
+mod cobs;
+mod complementary;
+mod crc;
+mod filters;
+mod kalman;
 mod map;
+mod prng;
+mod trig;
 
+pub use ahrs::*;
+pub use cobs::*;
+pub use complementary::*;
+pub use crc::*;
+pub use filters::*;
+pub use kalman::*;
 pub use map::*;
 pub use micromath::*;
+pub use prng::*;
+pub use trig::*;
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "random")] {
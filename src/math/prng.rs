@@ -0,0 +1,120 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Pseudo random number generator using the xorshift32 algorithm, which needs only shifts
+//! and XORs on four bytes of state and so runs fast on the AVR.
+//! `seed_from_adc()` gathers a seed from the noise of a floating analog pin, so the
+//! numbers are different after every reset.
+//! For more information `<https://www.jstatsoft.org/article/view/v008i14>`
+
+#[cfg(any(feature = "atmega328p", feature = "atmega2560p"))]
+use crate::hal::analog::analog_read;
+
+/// Xorshift32 random number generator. Not suitable for cryptography.
+#[derive(Clone)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    /// Creates a generator giving the same numbers for the same seed.
+    /// # Arguments
+    /// * `seed` - a u32, the starting state. Zero is replaced, as xorshift would give only zeros.
+    /// # Returns
+    /// * `a Rng object` - the generator.
+    pub fn new(seed: u32) -> Rng {
+        Rng {
+            state: if seed == 0 { 0x2545_F491 } else { seed },
+        }
+    }
+
+    /// Creates a generator seeded from the noise on an analog pin, see `seed_from_adc()`.
+    /// # Arguments
+    /// * `pin` - a usize, the number of an analog pin which is not connected.
+    /// # Returns
+    /// * `a Rng object` - the generator.
+    #[cfg(any(feature = "atmega328p", feature = "atmega2560p"))]
+    pub fn from_adc(pin: usize) -> Rng {
+        Rng::new(seed_from_adc(pin))
+    }
+
+    /// Gives the next random number.
+    /// # Returns
+    /// * `a u32` - a number spread evenly over all the u32 values except zero.
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Gives the next random number, from the upper bits which are the most random.
+    /// # Returns
+    /// * `a u16` - a random number.
+    pub fn next_u16(&mut self) -> u16 {
+        (self.next_u32() >> 16) as u16
+    }
+
+    /// Gives the next random number, from the upper bits which are the most random.
+    /// # Returns
+    /// * `a u8` - a random number.
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u32() >> 24) as u8
+    }
+
+    /// Gives a random number in a range, for example a random backoff time.
+    /// # Arguments
+    /// * `low` - a u32, the smallest number which can be given.
+    /// * `high` - a u32, the number above the largest one which can be given.
+    /// # Returns
+    /// * `a u32` - a number from `low` up to `high - 1`.
+    pub fn range(&mut self, low: u32, high: u32) -> u32 {
+        if high <= low {
+            unreachable!();
+        }
+        let span = (high - low) as u64;
+        low + ((self.next_u32() as u64 * span) >> 32) as u32
+    }
+
+    /// Fills the buffer with random bytes.
+    /// # Arguments
+    /// * `buffer` - a mutable reference to a u8 slice, which is filled.
+    pub fn fill_bytes(&mut self, buffer: &mut [u8]) {
+        for chunk in buffer.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// Gathers a seed from the noise on an analog pin which is left floating.
+/// The lowest bits of the readings change randomly, every reading is mixed into
+/// the seed with a multiplicative hash so that all 32 bits depend on this noise.
+/// # Arguments
+/// * `pin` - a usize, the number of an analog pin which is not connected.
+/// # Returns
+/// * `a u32` - the seed.
+#[cfg(any(feature = "atmega328p", feature = "atmega2560p"))]
+pub fn seed_from_adc(pin: usize) -> u32 {
+    let mut seed: u32 = 0;
+    for _ in 0..64 {
+        let reading = analog_read(pin);
+        seed = (seed.rotate_left(5) ^ reading).wrapping_mul(0x9E37_79B1);
+    }
+    seed
+}
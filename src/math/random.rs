@@ -30,12 +30,11 @@ pub enum Generator {
 /// Controls the implementation of Random Number Generators.
 /// # Elements
 /// * `pins` - structure containing array to control all pins of micro-controller.
-/// * `mpu` - a static mutable reference to the pointer location to control MPU6050 gyroscope.
+/// * `mpu` - a `MPU6050` object, to control the MPU6050 gyroscope.
 /// * `mode` - a `Generator` object, which stores the implementation method for random number generator.
-#[repr(C, packed)]
 pub struct RandomNumberGenerator {
     pins: Pins,
-    mpu: &'static mut MPU6050<'static>,
+    mpu: MPU6050,
     mode: Generator,
 }
 
@@ -46,8 +45,8 @@ impl RandomNumberGenerator {
     /// * `a struct of type Random Number Generator` - to be used for the struct's implementation.
     pub fn new(mode1: Generator) -> RandomNumberGenerator {
         RandomNumberGenerator {
-            pins: Pins::new(),
-            mpu: MPU6050::new(),
+            pins: unsafe { Pins::new() },
+            mpu: MPU6050::default(),
             mode: mode1,
         }
     }
@@ -214,22 +213,24 @@ pub fn push_right(val: u8, change: u8) -> u8 {
 /// # Returns
 /// * `a tuple of 6 u8's` - The x,y,z axes accelerations and gyroscopic detections by MPU6050 sensor respectively.
 pub fn generate_mpu() -> (u8, u8, u8, u8, u8, u8) {
-    let obj = RandomNumberGenerator::new(Generator::Mpu);
+    let mut obj = RandomNumberGenerator::new(Generator::Mpu);
 
-    obj.mpu
+    // The readings are used as noise even if the sensor did not start.
+    let _ = obj
+        .mpu
         .begin(MPUdpsT::MPU6050Scale250DPS, MPURangeT::MPU6050Range2G);
 
-    obj.mpu.read_gyro();
+    let gyro = obj.mpu.read_gyro_raw();
     delay_ms(1000);
 
-    obj.mpu.read_accel();
+    let accel = obj.mpu.read_accel_raw();
     delay_ms(1000);
 
-    let d: u8 = obj.mpu.gyro_output[0] as u8;
-    let e: u8 = obj.mpu.gyro_output[1] as u8;
-    let f: u8 = obj.mpu.gyro_output[2] as u8;
-    let a: u8 = obj.mpu.accel_output[0] as u8;
-    let b: u8 = obj.mpu.accel_output[1] as u8;
-    let c: u8 = obj.mpu.accel_output[2] as u8;
+    let d: u8 = gyro[0] as u8;
+    let e: u8 = gyro[1] as u8;
+    let f: u8 = gyro[2] as u8;
+    let a: u8 = accel[0] as u8;
+    let b: u8 = accel[1] as u8;
+    let c: u8 = accel[2] as u8;
     (a, b, c, d, e, f)
 }
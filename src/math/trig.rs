@@ -0,0 +1,135 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Trigonometry and square root in integer arithmetic, for the heading of a magnetometer
+//! or the tilt of an accelerometer. The AVR has no floating point unit, so the `f32`
+//! functions of `F32Ext` (re-exported from micromath) are slow. These use lookup tables
+//! stored in flash and work on whole or tenths of degrees.
+//! Sines are given as Q15 fixed point numbers, where 32767 stands for 1.
+
+crate::progmem! {
+    /// Sine of 0 to 90 degrees in Q15.
+    static SIN_TABLE: [i16; 91] = [
+        0, 572, 1144, 1715, 2286, 2856, 3425, 3993, 4560, 5126,
+        5690, 6252, 6813, 7371, 7927, 8481, 9032, 9580, 10126, 10668,
+        11207, 11743, 12275, 12803, 13328, 13848, 14364, 14876, 15383, 15886,
+        16383, 16876, 17364, 17846, 18323, 18794, 19260, 19720, 20173, 20621,
+        21062, 21497, 21925, 22347, 22762, 23170, 23571, 23964, 24351, 24730,
+        25101, 25465, 25821, 26169, 26509, 26841, 27165, 27481, 27788, 28087,
+        28377, 28659, 28932, 29196, 29451, 29697, 29934, 30162, 30381, 30591,
+        30791, 30982, 31163, 31335, 31498, 31650, 31794, 31927, 32051, 32165,
+        32269, 32364, 32448, 32523, 32587, 32642, 32687, 32722, 32747, 32762,
+        32767,
+    ];
+
+    /// Arc tangent of 0/64 to 64/64 in tenths of degrees.
+    static ATAN_TABLE: [u16; 65] = [
+        0, 9, 18, 27, 36, 45, 54, 62, 71, 80,
+        89, 98, 106, 115, 123, 132, 140, 149, 157, 165,
+        174, 182, 190, 198, 206, 213, 221, 229, 236, 244,
+        251, 258, 266, 273, 280, 287, 294, 300, 307, 314,
+        320, 326, 333, 339, 345, 351, 357, 363, 369, 374,
+        380, 386, 391, 396, 402, 407, 412, 417, 422, 427,
+        432, 436, 441, 445, 450,
+    ];
+}
+
+/// Gives the sine of an angle.
+/// # Arguments
+/// * `degrees` - a i16, the angle in degrees, of any size.
+/// # Returns
+/// * `a i16` - the sine in Q15, from -32767 to 32767.
+pub fn sin_deg(degrees: i16) -> i16 {
+    let angle = (degrees as i32).rem_euclid(360) as usize;
+    match angle {
+        0..=90 => SIN_TABLE.load_at(angle),
+        91..=180 => SIN_TABLE.load_at(180 - angle),
+        181..=270 => -SIN_TABLE.load_at(angle - 180),
+        _ => -SIN_TABLE.load_at(360 - angle),
+    }
+}
+
+/// Gives the cosine of an angle.
+/// # Arguments
+/// * `degrees` - a i16, the angle in degrees, of any size.
+/// # Returns
+/// * `a i16` - the cosine in Q15, from -32767 to 32767.
+pub fn cos_deg(degrees: i16) -> i16 {
+    sin_deg((degrees as i32 + 90).rem_euclid(360) as i16)
+}
+
+/// Gives the angle of the point (x, y) from the x axis, like `atan2` but in tenths of degrees.
+/// The arc tangent table is interpolated, so the error is below a tenth of a degree.
+/// For a magnetometer lying flat the heading is `atan2_deg(y, x)`.
+/// # Arguments
+/// * `y` - a i32, the y coordinate.
+/// * `x` - a i32, the x coordinate.
+/// # Returns
+/// * `a i16` - the angle from -1800 to 1800 tenths of degrees, 0 for the origin.
+pub fn atan2_deg(y: i32, x: i32) -> i16 {
+    if x == 0 && y == 0 {
+        return 0;
+    }
+    let ax = (x as i64).abs();
+    let ay = (y as i64).abs();
+
+    // Angle of the smaller over the larger coordinate, which is from 0 to 45 degrees.
+    let (small, large) = if ay <= ax { (ay, ax) } else { (ax, ay) };
+    let ratio = (small * 64 * 256 / large) as usize;
+    let index = ratio >> 8;
+    let fraction = (ratio & 0xFF) as i32;
+    let mut angle = ATAN_TABLE.load_at(index) as i32;
+    if index < 64 {
+        let next = ATAN_TABLE.load_at(index + 1) as i32;
+        angle += ((next - angle) * fraction + 128) >> 8;
+    }
+
+    if ay > ax {
+        angle = 900 - angle;
+    }
+    if x < 0 {
+        angle = 1800 - angle;
+    }
+    if y < 0 {
+        angle = -angle;
+    }
+    angle as i16
+}
+
+/// Gives the square root of a number, rounded down.
+/// Works bit by bit with shifts and additions only.
+/// # Arguments
+/// * `value` - a u32, the number.
+/// # Returns
+/// * `a u16` - the largest number whose square is not above `value`.
+pub fn isqrt(value: u32) -> u16 {
+    let mut rest = value;
+    let mut root: u32 = 0;
+    let mut bit: u32 = 1 << 30;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rest >= root + bit {
+            rest -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root as u16
+}
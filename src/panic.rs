@@ -0,0 +1,74 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Panic handler which reports the panic over a USART, enabled with the `panic-serial` feature.
+//! The location of the panic is printed to the USART chosen for `serial_print!`
+//! (see `com::usart::set_print_usart`) and the chip is then halted or reset.
+//! Applications using this feature must not define their own `#[panic_handler]`.
+
+use crate::hal::interrupts::Interrupt;
+use crate::hal::watchdog::{WatchDog, WatchdogMode, WatchdogTimeout};
+use core::panic::PanicInfo;
+
+/// What the chip does after the panic has been reported.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PanicAction {
+    /// Stops with the interrupts disabled, so the message stays on the terminal.
+    Halt,
+    /// Resets the chip through the watchdog, restarting the application.
+    Reset,
+}
+
+static mut PANIC_ACTION: PanicAction = PanicAction::Halt;
+
+/// Chooses what the chip does after a panic is reported. It halts by default.
+/// # Arguments
+/// * `action` - a `PanicAction` object, to halt or reset after the report.
+pub fn set_panic_action(action: PanicAction) {
+    unsafe {
+        PANIC_ACTION = action;
+    }
+}
+
+/// Prints `panicked at <file>:<line>:<column>` and then halts or resets the chip.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    unsafe {
+        Interrupt::new().disable();
+    }
+
+    crate::serial_print!("panicked");
+    if let Some(location) = info.location() {
+        crate::serial_print!(
+            " at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+    }
+    crate::serial_println!();
+
+    if unsafe { PANIC_ACTION } == PanicAction::Reset {
+        // The shortest timeout is still long enough for the last byte to leave the USART.
+        unsafe {
+            let watchdog = WatchDog::new();
+            watchdog.start(WatchdogTimeout::Ms16, WatchdogMode::Reset);
+            Interrupt::new().disable();
+        }
+    }
+
+    loop {}
+}
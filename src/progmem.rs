@@ -0,0 +1,181 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Storage of constant data in the flash memory instead of the SRAM.
+//! Values declared with the `progmem!` macro are placed in the `.progmem.data`
+//! section and are not copied to the SRAM at start up, so they have to be read
+//! with the `LPM` instruction through the functions given here.
+//! The linker places this section right after the interrupt vectors, so the data
+//! is in the lower 64 KB of flash on the ATMEGA2560P as well and `LPM` can reach it.
+
+// Crates to be used for the implementation.
+use core::mem::{size_of, MaybeUninit};
+
+/// A value of type `T` stored in the flash memory.
+/// Only made through the `progmem!` macro, the value can not be read directly
+/// as a normal reference would read the SRAM at the same address.
+#[repr(transparent)]
+pub struct ProgMem<T> {
+    value: T,
+}
+
+// The value is never written after compilation so it can be shared.
+unsafe impl<T> Sync for ProgMem<T> {}
+
+impl<T> ProgMem<T> {
+    /// Wraps a value which is to be placed in flash.
+    /// Use the `progmem!` macro instead, which also places the value in the right section.
+    /// # Arguments
+    /// * `value` - a T, the value to be stored.
+    /// # Returns
+    /// * `a ProgMem object` - which will be used for further implementations.
+    #[doc(hidden)]
+    pub const unsafe fn new(value: T) -> ProgMem<T> {
+        ProgMem { value }
+    }
+
+    /// Gives the address of the value in the flash memory.
+    /// # Returns
+    /// * `a pointer to u8` - the flash address of the first byte of the value.
+    pub fn as_ptr(&self) -> *const u8 {
+        &self.value as *const T as *const u8
+    }
+
+    /// Copies the value from flash to the SRAM.
+    /// # Returns
+    /// * `a T` - the stored value.
+    pub fn load(&self) -> T
+    where
+        T: Copy,
+    {
+        let mut value = MaybeUninit::<T>::uninit();
+        let src = self.as_ptr();
+        let dst = value.as_mut_ptr() as *mut u8;
+        for i in 0..size_of::<T>() {
+            unsafe { *dst.add(i) = read_byte(src.add(i)) };
+        }
+        unsafe { value.assume_init() }
+    }
+}
+
+impl<T: Copy, const N: usize> ProgMem<[T; N]> {
+    /// Gives the number of elements of the stored array.
+    /// # Returns
+    /// * `a usize` - the length of the array.
+    pub fn len(&self) -> usize {
+        N
+    }
+
+    /// Checks if the stored array has no elements.
+    /// # Returns
+    /// * `a boolean` - true if the length of the array is zero.
+    pub fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Copies one element of the stored array from flash, so that
+    /// large lookup tables do not have to be loaded as a whole.
+    /// # Arguments
+    /// * `index` - a usize, the position of the element in the array.
+    /// # Returns
+    /// * `a T` - the element at the position.
+    pub fn load_at(&self, index: usize) -> T {
+        if index >= N {
+            unreachable!()
+        }
+        let mut value = MaybeUninit::<T>::uninit();
+        let src = unsafe { self.as_ptr().add(index * size_of::<T>()) };
+        let dst = value.as_mut_ptr() as *mut u8;
+        for i in 0..size_of::<T>() {
+            unsafe { *dst.add(i) = read_byte(src.add(i)) };
+        }
+        unsafe { value.assume_init() }
+    }
+}
+
+impl<const N: usize> ProgMem<[u8; N]> {
+    /// Copies the stored bytes, such as a string, into a buffer in SRAM.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, where the bytes are copied.
+    /// # Returns
+    /// * `a usize` - the number of bytes copied, limited by the length of the buffer.
+    pub fn copy_to(&self, buffer: &mut [u8]) -> usize {
+        let count = if buffer.len() < N { buffer.len() } else { N };
+        for (i, byte) in buffer.iter_mut().enumerate().take(count) {
+            *byte = unsafe { read_byte(self.as_ptr().add(i)) };
+        }
+        count
+    }
+}
+
+/// Reads one byte from the flash memory using the `LPM` instruction.
+/// # Arguments
+/// * `address` - a pointer to u8, the flash address to be read.
+/// # Returns
+/// * `a u8` - the byte at the address.
+#[cfg(target_arch = "avr")]
+pub unsafe fn read_byte(address: *const u8) -> u8 {
+    let byte: u8;
+    llvm_asm!("lpm $0, Z"
+             : "=r" (byte)
+             : "z" (address as u16)
+             :
+             : "volatile");
+    byte
+}
+
+/// Reads one byte of a value, flash and SRAM are the same memory on other targets.
+/// # Arguments
+/// * `address` - a pointer to u8, the address to be read.
+/// # Returns
+/// * `a u8` - the byte at the address.
+#[cfg(not(target_arch = "avr"))]
+pub unsafe fn read_byte(address: *const u8) -> u8 {
+    core::ptr::read_volatile(address)
+}
+
+/// Reads a little endian word from the flash memory.
+/// # Arguments
+/// * `address` - a pointer to u8, the flash address of the lower byte.
+/// # Returns
+/// * `a u16` - the word at the address.
+pub unsafe fn read_word(address: *const u8) -> u16 {
+    (read_byte(address) as u16) | ((read_byte(address.add(1)) as u16) << 8)
+}
+
+/// Declares statics which are stored in the flash memory.
+/// Each static becomes a `ProgMem` object which is read with `load()`,
+/// `load_at()` or `copy_to()`.
+///
+/// ```ignore
+/// progmem! {
+///     static SINE: [u8; 4] = [0, 90, 127, 90];
+///     pub static GREETING: [u8; 5] = *b"hello";
+/// }
+///
+/// let value = SINE.load_at(1);
+/// ```
+#[macro_export]
+macro_rules! progmem {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident : $ty:ty = $value:expr ;)*) => {
+        $(
+            $(#[$attr])*
+            #[cfg_attr(target_arch = "avr", link_section = ".progmem.data")]
+            $vis static $name: $crate::progmem::ProgMem<$ty> =
+                unsafe { $crate::progmem::ProgMem::new($value) };
+        )*
+    };
+}
@@ -19,8 +19,10 @@
 //! humidity and stored in a sliced vector which could be given
 //! as an output.
 
+use super::traits::Thermometer;
 use crate::com::i2c;
 use crate::delay::delay_ms;
+use core::convert::Infallible;
 use fixed_slice_vec::FixedSliceVec;
 
 /// Used to control the AHT10 Arduino sensor
@@ -167,3 +169,11 @@ impl<'a> AHT10<'a> {
         }
     }
 }
+
+impl<'a> Thermometer for AHT10<'a> {
+    type Error = Infallible;
+
+    fn read_temperature(&mut self) -> Result<f32, Infallible> {
+        Ok(self.temperature() as f32)
+    }
+}
@@ -0,0 +1,183 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Percentages from analog soil moisture and water level sensors, calibrated with the
+//! readings at both ends of their range: dry in the air and wet when fully in water.
+//! Resistive probes read higher when wet and capacitive ones lower, both work as the
+//! percentage follows the two endpoints. The endpoints can be kept in the EEPROM so the
+//! sensor only has to be calibrated once.
+
+use crate::hal::analog::analog_read;
+use crate::hal::eeprom::Eeprom;
+
+/// Number of bytes a calibration takes in the EEPROM.
+pub const LEVEL_CALIBRATION_SIZE: usize = 5;
+/// Mixed into the check byte, so a blank EEPROM is not taken as a calibration.
+const CHECK_SEED: u8 = 0xA5;
+
+/// Errors of a calibration loaded from the EEPROM.
+/// * `NotCalibrated` - The EEPROM does not hold a calibration at this address.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LevelError {
+    NotCalibrated,
+}
+
+/// Readings of the sensor at both ends of its range.
+/// # Elements
+/// * `dry` - a u16, the reading at 0 %, in the air or in dry soil.
+/// * `wet` - a u16, the reading at 100 %, in water up to the mark of the sensor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LevelCalibration {
+    pub dry: u16,
+    pub wet: u16,
+}
+
+impl LevelCalibration {
+    /// Converts a reading to a percentage, outside the endpoints it is limited to 0 or 100.
+    /// # Arguments
+    /// * `reading` - a u16, the value given by `analog_read()`.
+    /// # Returns
+    /// * `a u8` - the percentage, from 0 to 100.
+    pub fn percent(&self, reading: u16) -> u8 {
+        if self.dry == self.wet {
+            return 0;
+        }
+        let span = self.wet as i32 - self.dry as i32;
+        let percent = (reading as i32 - self.dry as i32) * 100 / span;
+        percent.clamp(0, 100) as u8
+    }
+
+    /// Converts the calibration to the bytes kept in the EEPROM, the endpoints and a check byte.
+    /// # Returns
+    /// * `a u8 array` - the bytes of the calibration.
+    pub fn to_bytes(&self) -> [u8; LEVEL_CALIBRATION_SIZE] {
+        let dry = self.dry.to_le_bytes();
+        let wet = self.wet.to_le_bytes();
+        let check = dry[0] ^ dry[1] ^ wet[0] ^ wet[1] ^ CHECK_SEED;
+        [dry[0], dry[1], wet[0], wet[1], check]
+    }
+
+    /// Gets a calibration back from the bytes kept in the EEPROM.
+    /// # Arguments
+    /// * `bytes` - a u8 array, the bytes of the calibration.
+    /// # Returns
+    /// * `a Result` - the calibration, or `NotCalibrated` if the check byte does not match.
+    pub fn from_bytes(
+        bytes: &[u8; LEVEL_CALIBRATION_SIZE],
+    ) -> Result<LevelCalibration, LevelError> {
+        let check = bytes[..4]
+            .iter()
+            .fold(CHECK_SEED, |check, byte| check ^ byte);
+        if check != bytes[4] {
+            return Err(LevelError::NotCalibrated);
+        }
+        Ok(LevelCalibration {
+            dry: u16::from_le_bytes([bytes[0], bytes[1]]),
+            wet: u16::from_le_bytes([bytes[2], bytes[3]]),
+        })
+    }
+}
+
+/// A soil moisture or water level sensor on a analog pin.
+/// # Elements
+/// * `pin` - a usize, the number of the analog pin.
+/// * `calibration` - a `LevelCalibration` object, the readings at both ends of the range.
+pub struct AnalogLevel {
+    pin: usize,
+    calibration: LevelCalibration,
+}
+
+/// A soil moisture probe, resistive or capacitive.
+pub type SoilMoisture = AnalogLevel;
+
+/// A water level sensor, such as the strips with parallel traces.
+pub type WaterLevel = AnalogLevel;
+
+impl AnalogLevel {
+    /// Creates the struct to read a sensor with known endpoints.
+    /// # Arguments
+    /// * `pin` - a usize, the number of the analog pin.
+    /// * `calibration` - a `LevelCalibration` object, the readings at both ends of the range.
+    /// # Returns
+    /// * `a AnalogLevel object` - which will be used to read the sensor.
+    pub fn new(pin: usize, calibration: LevelCalibration) -> AnalogLevel {
+        AnalogLevel { pin, calibration }
+    }
+
+    /// Creates the struct to read a sensor with the endpoints kept in the EEPROM.
+    /// # Arguments
+    /// * `pin` - a usize, the number of the analog pin.
+    /// * `address` - a u16, the EEPROM address of the calibration given to `save()`.
+    /// # Returns
+    /// * `a Result` - the struct, or `NotCalibrated` if no calibration was saved there.
+    pub fn load(pin: usize, address: u16) -> Result<AnalogLevel, LevelError> {
+        let mut bytes = [0; LEVEL_CALIBRATION_SIZE];
+        unsafe { Eeprom::new() }.read(address, &mut bytes);
+        Ok(AnalogLevel::new(pin, LevelCalibration::from_bytes(&bytes)?))
+    }
+
+    /// Keeps the endpoints in the EEPROM, only the bytes which change are written.
+    /// # Arguments
+    /// * `address` - a u16, the EEPROM address, `LEVEL_CALIBRATION_SIZE` bytes are used from there.
+    pub fn save(&self, address: u16) {
+        unsafe { Eeprom::new() }.update(address, &self.calibration.to_bytes());
+    }
+
+    /// Reads the sensor several times and gives the mean.
+    /// # Arguments
+    /// * `samples` - a u8, the number of readings, at least 1.
+    /// # Returns
+    /// * `a u16` - the mean reading.
+    pub fn read_raw(&self, samples: u8) -> u16 {
+        if samples == 0 {
+            unreachable!();
+        }
+        let sum: u32 = (0..samples).map(|_| analog_read(self.pin)).sum();
+        (sum / samples as u32) as u16
+    }
+
+    /// Takes the present reading as the dry endpoint, the sensor has to be in the air
+    /// or in dry soil.
+    /// # Arguments
+    /// * `samples` - a u8, the number of readings averaged, at least 1.
+    pub fn calibrate_dry(&mut self, samples: u8) {
+        self.calibration.dry = self.read_raw(samples);
+    }
+
+    /// Takes the present reading as the wet endpoint, the sensor has to be in water
+    /// up to its mark.
+    /// # Arguments
+    /// * `samples` - a u8, the number of readings averaged, at least 1.
+    pub fn calibrate_wet(&mut self, samples: u8) {
+        self.calibration.wet = self.read_raw(samples);
+    }
+
+    /// Gives the endpoints.
+    /// # Returns
+    /// * `a LevelCalibration object` - the readings at both ends of the range.
+    pub fn calibration(&self) -> LevelCalibration {
+        self.calibration
+    }
+
+    /// Reads the moisture or water level.
+    /// # Arguments
+    /// * `samples` - a u8, the number of readings averaged, at least 1.
+    /// # Returns
+    /// * `a u8` - the percentage, from 0 to 100.
+    pub fn read_percent(&self, samples: u8) -> u8 {
+        self.calibration.percent(self.read_raw(samples))
+    }
+}
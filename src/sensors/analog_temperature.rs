@@ -0,0 +1,301 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Conversions from the counts of `analog_read()` to temperatures for the analog
+//! sensors LM35 and TMP36, and for NTC thermistors in a voltage divider.
+//! The thermistor follows the Steinhart–Hart equation 1/T = A + B ln(R) + C ln(R)^3,
+//! worked out with integers only so no floating point code is needed, and also with
+//! `f32` when the `math` feature is enabled.
+//! `<https://www.ti.com/lit/ds/symlink/lm35.pdf>`
+//! `<https://www.analog.com/media/en/technical-documentation/data-sheets/TMP35_36_37.pdf>`
+
+use super::traits::Thermometer;
+use crate::hal::analog::analog_read;
+#[cfg(feature = "math")]
+use micromath::F32Ext;
+
+/// Largest count of the 10 bit ADC.
+const ADC_MAX: u32 = 1023;
+/// 0 °C in hundredths of kelvins.
+const ZERO_CELSIUS: i64 = 27315;
+/// 25 °C in hundredths of kelvins, the temperature at which thermistors are rated.
+const ROOM_TEMPERATURE: i64 = 29815;
+/// Scale of the fixed point Steinhart–Hart coefficients.
+const SCALE: i64 = 1_000_000_000_000;
+/// ln(2) in Q32.
+const LN2_Q32: u64 = 2_977_044_472;
+
+/// Converts a reading to the voltage on the pin.
+/// # Arguments
+/// * `counts` - a u32, the value given by `analog_read()`.
+/// * `reference_mv` - a u32, the voltage of the ADC reference in mV, 5000 for the default reference or 1100 for the internal one.
+/// # Returns
+/// * `a u32` - the voltage in µV.
+pub fn counts_to_microvolts(counts: u32, reference_mv: u32) -> u32 {
+    counts * reference_mv * 1000 / (ADC_MAX + 1)
+}
+
+/// Converts a reading of a LM35, which gives 10 mV per °C from 0 V at 0 °C. The
+/// internal 1.1 V reference gives 0.1 °C steps up to 110 °C.
+/// # Arguments
+/// * `counts` - a u32, the value given by `analog_read()`.
+/// * `reference_mv` - a u32, the voltage of the ADC reference in mV.
+/// # Returns
+/// * `a i32` - the temperature in hundredths of °C.
+pub fn lm35_temperature(counts: u32, reference_mv: u32) -> i32 {
+    (counts_to_microvolts(counts, reference_mv) / 100) as i32
+}
+
+/// Converts a reading of a TMP36, which gives 10 mV per °C from 500 mV at 0 °C.
+/// # Arguments
+/// * `counts` - a u32, the value given by `analog_read()`.
+/// * `reference_mv` - a u32, the voltage of the ADC reference in mV.
+/// # Returns
+/// * `a i32` - the temperature in hundredths of °C, from -40 °C.
+pub fn tmp36_temperature(counts: u32, reference_mv: u32) -> i32 {
+    (counts_to_microvolts(counts, reference_mv) as i32 - 500_000) / 100
+}
+
+/// Reads a LM35 on a analog pin.
+/// # Arguments
+/// * `pin` - a usize, the number of the analog pin.
+/// * `reference_mv` - a u32, the voltage of the ADC reference in mV.
+/// # Returns
+/// * `a i32` - the temperature in hundredths of °C.
+pub fn read_lm35(pin: usize, reference_mv: u32) -> i32 {
+    lm35_temperature(analog_read(pin), reference_mv)
+}
+
+/// Reads a TMP36 on a analog pin.
+/// # Arguments
+/// * `pin` - a usize, the number of the analog pin.
+/// * `reference_mv` - a u32, the voltage of the ADC reference in mV.
+/// # Returns
+/// * `a i32` - the temperature in hundredths of °C.
+pub fn read_tmp36(pin: usize, reference_mv: u32) -> i32 {
+    tmp36_temperature(analog_read(pin), reference_mv)
+}
+
+/// Works out the natural logarithm with integers, from the binary logarithm whose
+/// fraction is found one bit at a time by squaring the mantissa.
+/// # Arguments
+/// * `x` - a u32, at least 1.
+/// # Returns
+/// * `a i64` - ln(x) in Q16.
+pub(crate) fn ln_q16(x: u32) -> i64 {
+    if x == 0 {
+        unreachable!();
+    }
+    let int = 31 - x.leading_zeros();
+    // The mantissa from 1 to 2 in Q30.
+    let mut mantissa = (x as u64) << (30 - int.min(30)) >> (int.max(30) - 30);
+    let mut log2 = (int as u64) << 16;
+    for bit in (0..16).rev() {
+        mantissa = (mantissa * mantissa) >> 30;
+        if mantissa >= 2 << 30 {
+            mantissa >>= 1;
+            log2 |= 1 << bit;
+        }
+    }
+    ((log2 * LN2_Q32) >> 32) as i64
+}
+
+/// Coefficients of the Steinhart–Hart equation of a thermistor, kept in fixed point
+/// as the coefficients multiplied by 10^12.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SteinhartHart {
+    a: i64,
+    b: i64,
+    c: i64,
+}
+
+impl SteinhartHart {
+    /// Creates the coefficients from the values given in fixed point, without any floating point code.
+    /// # Arguments
+    /// * `a` - a i64, the coefficient A multiplied by 10^12.
+    /// * `b` - a i64, the coefficient B multiplied by 10^12.
+    /// * `c` - a i64, the coefficient C multiplied by 10^12.
+    /// # Returns
+    /// * `a SteinhartHart object` - the coefficients.
+    pub const fn from_scaled(a: i64, b: i64, c: i64) -> SteinhartHart {
+        SteinhartHart { a, b, c }
+    }
+
+    /// Creates the coefficients from the values found in the datasheet or by calibration.
+    /// # Arguments
+    /// * `a` - a f32, the coefficient A, about 1e-3.
+    /// * `b` - a f32, the coefficient B, about 2e-4.
+    /// * `c` - a f32, the coefficient C, about 1e-7.
+    /// # Returns
+    /// * `a SteinhartHart object` - the coefficients.
+    pub fn new(a: f32, b: f32, c: f32) -> SteinhartHart {
+        SteinhartHart {
+            a: (a as f64 * SCALE as f64) as i64,
+            b: (b as f64 * SCALE as f64) as i64,
+            c: (c as f64 * SCALE as f64) as i64,
+        }
+    }
+
+    /// Creates the coefficients of the simpler beta model, given by most thermistor
+    /// sellers, where 1/T = 1/T0 + ln(R / R0) / beta with T0 at 25 °C.
+    /// # Arguments
+    /// * `beta` - a u32, the beta value in kelvins, such as 3950.
+    /// * `r0` - a u32, the resistance at 25 °C in Ω, such as 10000.
+    /// # Returns
+    /// * `a SteinhartHart object` - the coefficients.
+    pub fn from_beta(beta: u32, r0: u32) -> SteinhartHart {
+        if beta == 0 {
+            unreachable!();
+        }
+        let beta = beta as i64;
+        SteinhartHart {
+            a: SCALE * 100 / ROOM_TEMPERATURE - ((ln_q16(r0) * SCALE / beta) >> 16),
+            b: SCALE / beta,
+            c: 0,
+        }
+    }
+
+    /// Works out the temperature of the thermistor with integers only.
+    /// # Arguments
+    /// * `resistance` - a u32, the resistance of the thermistor in Ω, at least 1.
+    /// # Returns
+    /// * `a i32` - the temperature in hundredths of °C.
+    pub fn temperature(&self, resistance: u32) -> i32 {
+        let ln = ln_q16(resistance);
+        let ln3 = (((ln * ln) >> 16) * ln) >> 16;
+        let inverse = self.a + ((self.b * ln) >> 16) + ((self.c * ln3) >> 16);
+        if inverse <= 0 {
+            unreachable!();
+        }
+        (SCALE * 100 / inverse - ZERO_CELSIUS) as i32
+    }
+
+    /// Works out the temperature of the thermistor with floating point.
+    /// # Arguments
+    /// * `resistance` - a u32, the resistance of the thermistor in Ω, at least 1.
+    /// # Returns
+    /// * `a f32` - the temperature in °C.
+    #[cfg(feature = "math")]
+    pub fn temperature_f32(&self, resistance: u32) -> f32 {
+        let ln = (resistance as f32).ln();
+        let inverse =
+            (self.a as f32 + self.b as f32 * ln + self.c as f32 * ln * ln * ln) / SCALE as f32;
+        1.0 / inverse - ZERO_CELSIUS as f32 / 100.0
+    }
+}
+
+/// Errors of a thermistor reading.
+/// * `Open` - The reading is at the end of the range where the thermistor is disconnected.
+/// * `Short` - The reading is at the end of the range where the thermistor is shorted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThermistorError {
+    Open,
+    Short,
+}
+
+/// Side of the voltage divider on which the thermistor is.
+/// * `ToGround` - the thermistor is between the pin and ground, the series resistor to Vcc.
+/// * `ToVcc` - the thermistor is between Vcc and the pin, the series resistor to ground.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThermistorWiring {
+    ToGround,
+    ToVcc,
+}
+
+/// A NTC thermistor in a voltage divider on a analog pin, with the divider fed by the
+/// ADC reference so the reading does not depend on the supply voltage.
+/// # Elements
+/// * `pin` - a usize, the number of the analog pin.
+/// * `series_resistance` - a u32, the resistance of the series resistor in Ω.
+/// * `wiring` - a `ThermistorWiring` object, the side of the divider of the thermistor.
+/// * `coefficients` - a `SteinhartHart` object, the coefficients of the thermistor.
+pub struct Thermistor {
+    pin: usize,
+    series_resistance: u32,
+    wiring: ThermistorWiring,
+    coefficients: SteinhartHart,
+}
+
+impl Thermistor {
+    /// Creates the struct to read a thermistor.
+    /// # Arguments
+    /// * `pin` - a usize, the number of the analog pin.
+    /// * `series_resistance` - a u32, the resistance of the series resistor in Ω, usually the one of the thermistor at 25 °C.
+    /// * `wiring` - a `ThermistorWiring` object, the side of the divider of the thermistor.
+    /// * `coefficients` - a `SteinhartHart` object, the coefficients of the thermistor.
+    /// # Returns
+    /// * `a Thermistor object` - which will be used to read the thermistor.
+    pub fn new(
+        pin: usize,
+        series_resistance: u32,
+        wiring: ThermistorWiring,
+        coefficients: SteinhartHart,
+    ) -> Thermistor {
+        Thermistor {
+            pin,
+            series_resistance,
+            wiring,
+            coefficients,
+        }
+    }
+
+    /// Converts a reading to the resistance of the thermistor.
+    /// # Arguments
+    /// * `counts` - a u32, the value given by `analog_read()`.
+    /// # Returns
+    /// * `a Result` - the resistance in Ω, or a error at the ends of the range.
+    pub fn resistance(&self, counts: u32) -> Result<u32, ThermistorError> {
+        let (low, high) = match self.wiring {
+            ThermistorWiring::ToGround => (ThermistorError::Short, ThermistorError::Open),
+            ThermistorWiring::ToVcc => (ThermistorError::Open, ThermistorError::Short),
+        };
+        if counts == 0 {
+            return Err(low);
+        }
+        if counts >= ADC_MAX {
+            return Err(high);
+        }
+        let series = self.series_resistance as u64;
+        let counts = counts as u64;
+        let resistance = match self.wiring {
+            ThermistorWiring::ToGround => series * counts / (ADC_MAX as u64 - counts),
+            ThermistorWiring::ToVcc => series * (ADC_MAX as u64 - counts) / counts,
+        };
+        Ok(resistance.max(1) as u32)
+    }
+
+    /// Reads the resistance of the thermistor.
+    /// # Returns
+    /// * `a Result` - the resistance in Ω, or a error at the ends of the range.
+    pub fn read_resistance(&self) -> Result<u32, ThermistorError> {
+        self.resistance(analog_read(self.pin))
+    }
+
+    /// Reads the temperature, worked out with integers only.
+    /// # Returns
+    /// * `a Result` - the temperature in hundredths of °C, or a error at the ends of the range.
+    pub fn read(&self) -> Result<i32, ThermistorError> {
+        Ok(self.coefficients.temperature(self.read_resistance()?))
+    }
+}
+
+impl Thermometer for Thermistor {
+    type Error = ThermistorError;
+
+    fn read_temperature(&mut self) -> Result<f32, ThermistorError> {
+        Ok(self.read()? as f32 / 100.0)
+    }
+}
@@ -0,0 +1,345 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the APDS-9960 proximity, ambient light, colour and gesture sensor
+//! connected through I2C. In the gesture mode the sensor stores the light reflected
+//! towards four photodiodes in a FIFO, and pulls its INT pin low when the FIFO fills
+//! up, so a swipe over the sensor can be read when the interrupt occurs.
+//! `<https://docs.broadcom.com/doc/AV02-4191EN>`
+
+use crate::com::i2c::Twi;
+use crate::hal::ext_interrupt::{attach_interrupt, IntLine, Trigger};
+
+/// Address of the APDS-9960, which can not be changed.
+pub const APDS9960_ADDRESS: u8 = 0x39;
+
+// Registers.
+const REG_ENABLE: u8 = 0x80;
+const REG_ATIME: u8 = 0x81;
+const REG_WTIME: u8 = 0x83;
+const REG_PERS: u8 = 0x8C;
+const REG_CONFIG1: u8 = 0x8D;
+const REG_PPULSE: u8 = 0x8E;
+const REG_CONTROL: u8 = 0x8F;
+const REG_CONFIG2: u8 = 0x90;
+const REG_ID: u8 = 0x92;
+const REG_STATUS: u8 = 0x93;
+const REG_CDATAL: u8 = 0x94;
+const REG_PDATA: u8 = 0x9C;
+const REG_CONFIG3: u8 = 0x9F;
+const REG_GPENTH: u8 = 0xA0;
+const REG_GEXTH: u8 = 0xA1;
+const REG_GCONF1: u8 = 0xA2;
+const REG_GCONF2: u8 = 0xA3;
+const REG_GPULSE: u8 = 0xA6;
+const REG_GCONF3: u8 = 0xAA;
+const REG_GCONF4: u8 = 0xAB;
+const REG_GFLVL: u8 = 0xAE;
+const REG_GFIFO_U: u8 = 0xFC;
+
+// Bits of the enable register.
+const PON: u8 = 0x01;
+const AEN: u8 = 0x02;
+const PEN: u8 = 0x04;
+const WEN: u8 = 0x08;
+const GEN: u8 = 0x40;
+// Bits of the status and gesture configuration 4 registers.
+const AVALID: u8 = 0x01;
+const GMODE: u8 = 0x01;
+const GIEN: u8 = 0x02;
+
+// Identification of the APDS-9960, some modules answer with the second value.
+const IDS: [u8; 2] = [0xAB, 0xA8];
+// Datasets read from the FIFO at once, of four bytes each.
+const FIFO_CHUNK: usize = 8;
+// Smallest value of all four photodiodes for a dataset to be used.
+const GESTURE_THRESHOLD: u8 = 10;
+// Smallest change of the ratios, in percent, to be a swipe.
+const GESTURE_SENSITIVITY: i32 = 50;
+
+/// Errors of the APDS-9960.
+/// * `I2c` - The sensor did not answer, check the wiring.
+/// * `WrongDevice` - The identification register holds the value given, which is not of a APDS-9960.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Apds9960Error {
+    I2c,
+    WrongDevice(u8),
+}
+
+/// Direction of a swipe over the sensor, with the pins of the module at the bottom.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Apds9960Gesture {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Light measured by the colour photodiodes.
+/// # Elements
+/// * `clear` - a u16, the light without filter, which gives the ambient light.
+/// * `red` - a u16, the light through the red filter.
+/// * `green` - a u16, the light through the green filter.
+/// * `blue` - a u16, the light through the blue filter.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Apds9960Light {
+    pub clear: u16,
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+}
+
+/// Controls a APDS-9960 sensor.
+/// # Elements
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `enable` - a u8, the value last written to the enable register.
+/// * `first` - a optional u8 array, the up, down, left and right values at the start of the gesture.
+/// * `last` - a u8 array, the up, down, left and right values of the latest dataset of the gesture.
+pub struct Apds9960 {
+    i2c: &'static mut Twi,
+    enable: u8,
+    first: Option<[u8; 4]>,
+    last: [u8; 4],
+}
+
+impl Default for Apds9960 {
+    fn default() -> Self {
+        Apds9960::new()
+    }
+}
+
+impl Apds9960 {
+    /// Creates the struct to control the sensor on the I2C bus.
+    /// # Returns
+    /// * `a Apds9960 object` - To control the sensor, after `begin()`.
+    pub fn new() -> Apds9960 {
+        Apds9960 {
+            i2c: unsafe { Twi::new() },
+            enable: 0,
+            first: None,
+            last: [0; 4],
+        }
+    }
+
+    /// Checks the identification of the sensor, sets the gains, LED pulses and gesture
+    /// thresholds, and powers it on with every engine disabled.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor is ready, otherwise the error found.
+    pub fn begin(&mut self) -> Result<(), Apds9960Error> {
+        let id = self.read_register(REG_ID)?;
+        if !IDS.contains(&id) {
+            return Err(Apds9960Error::WrongDevice(id));
+        }
+        self.set_enable(0)?;
+        // Ambient light integrated for 103 ms, wait of 27 ms between cycles.
+        self.write_register(REG_ATIME, 0xDB)?;
+        self.write_register(REG_WTIME, 0xF6)?;
+        // Proximity with 8 pulses of 16 us, LED at 100 mA, gains of 4.
+        self.write_register(REG_PPULSE, 0x87)?;
+        self.write_register(REG_CONFIG1, 0x60)?;
+        self.write_register(REG_CONTROL, 0x09)?;
+        self.write_register(REG_CONFIG2, 0x01)?;
+        self.write_register(REG_CONFIG3, 0x00)?;
+        self.write_register(REG_PERS, 0x11)?;
+        // The gesture engine starts above a proximity of 40 and stops below 30, the
+        // FIFO interrupt comes after 4 datasets, with a gain of 4, LED at 100 mA,
+        // 10 pulses of 32 us and 2.8 ms between datasets.
+        self.write_register(REG_GPENTH, 40)?;
+        self.write_register(REG_GEXTH, 30)?;
+        self.write_register(REG_GCONF1, 0x40)?;
+        self.write_register(REG_GCONF2, 0x41)?;
+        self.write_register(REG_GPULSE, 0xC9)?;
+        self.write_register(REG_GCONF3, 0x00)?;
+        self.write_register(REG_GCONF4, 0x00)?;
+        self.set_enable(PON)
+    }
+
+    /// Starts or stops the proximity engine.
+    /// # Arguments
+    /// * `state` - a boolean, true to measure the proximity.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn enable_proximity(&mut self, state: bool) -> Result<(), Apds9960Error> {
+        self.set_enable_bits(PEN, state)
+    }
+
+    /// Starts or stops the ambient light and colour engine.
+    /// # Arguments
+    /// * `state` - a boolean, true to measure the light.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn enable_light(&mut self, state: bool) -> Result<(), Apds9960Error> {
+        self.set_enable_bits(AEN, state)
+    }
+
+    /// Starts or stops the gesture engine, which also needs the proximity engine
+    /// to know when a hand comes over the sensor.
+    /// # Arguments
+    /// * `state` - a boolean, true to detect gestures.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn enable_gesture(&mut self, state: bool) -> Result<(), Apds9960Error> {
+        self.first = None;
+        if state {
+            self.set_enable_bits(WEN | PEN | GEN, true)
+        } else {
+            self.write_register(REG_GCONF4, 0x00)?;
+            self.set_enable_bits(GEN, false)
+        }
+    }
+
+    /// Reads the latest proximity.
+    /// # Returns
+    /// * `a Result` - the proximity from 0 to 255, larger when closer, or the error found.
+    pub fn read_proximity(&mut self) -> Result<u8, Apds9960Error> {
+        self.read_register(REG_PDATA)
+    }
+
+    /// Checks if a ambient light and colour measurement is complete.
+    /// # Returns
+    /// * `a Result` - true if the light can be read, or the error found.
+    pub fn is_light_ready(&mut self) -> Result<bool, Apds9960Error> {
+        Ok(self.read_register(REG_STATUS)? & AVALID != 0)
+    }
+
+    /// Reads the latest ambient light and colour measurement.
+    /// # Returns
+    /// * `a Result` - the light of the four photodiodes, or the error found.
+    pub fn read_light(&mut self) -> Result<Apds9960Light, Apds9960Error> {
+        let mut data = [0; 8];
+        self.read_registers(REG_CDATAL, &mut data)?;
+        let value = |i: usize| u16::from_le_bytes([data[2 * i], data[2 * i + 1]]);
+        Ok(Apds9960Light {
+            clear: value(0),
+            red: value(1),
+            green: value(2),
+            blue: value(3),
+        })
+    }
+
+    /// Makes the sensor pull its INT pin low when the gesture FIFO holds 4 datasets,
+    /// and attaches a handler to the external interrupt line connected to it.
+    /// The handler runs in the interrupt, so it should only note the event
+    /// and leave `read_gesture()` to the main program, which releases the pin.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line connected to INT.
+    /// * `handler` - a function, which is called when INT falls.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn attach_gesture_interrupt(
+        &mut self,
+        line: IntLine,
+        handler: fn(),
+    ) -> Result<(), Apds9960Error> {
+        self.write_register(REG_GCONF4, GIEN)?;
+        attach_interrupt(line, Trigger::Falling, handler);
+        Ok(())
+    }
+
+    /// Empties the gesture FIFO and, once the hand has left the sensor, works out
+    /// the direction of the swipe from the first and last datasets. It can be called
+    /// after the interrupt, or polled while the gesture engine is enabled.
+    /// # Returns
+    /// * `a Result` - the direction of the swipe, None if the gesture is not over or
+    /// was not a swipe, or the error found.
+    pub fn read_gesture(&mut self) -> Result<Option<Apds9960Gesture>, Apds9960Error> {
+        loop {
+            let level = self.read_register(REG_GFLVL)? as usize;
+            if level == 0 {
+                break;
+            }
+            let mut data = [0; 4 * FIFO_CHUNK];
+            let count = level.min(FIFO_CHUNK);
+            self.read_registers(REG_GFIFO_U, &mut data[..4 * count])?;
+            for set in data[..4 * count].chunks_exact(4) {
+                if set.iter().all(|&value| value > GESTURE_THRESHOLD) {
+                    let set = [set[0], set[1], set[2], set[3]];
+                    self.first.get_or_insert(set);
+                    self.last = set;
+                }
+            }
+        }
+        if self.read_register(REG_GCONF4)? & GMODE != 0 {
+            return Ok(None);
+        }
+        Ok(match self.first.take() {
+            Some(first) => decode_gesture(first, self.last),
+            None => None,
+        })
+    }
+
+    fn set_enable_bits(&mut self, bits: u8, state: bool) -> Result<(), Apds9960Error> {
+        let value = if state {
+            self.enable | bits
+        } else {
+            self.enable & !bits
+        };
+        self.set_enable(value)
+    }
+
+    fn set_enable(&mut self, value: u8) -> Result<(), Apds9960Error> {
+        self.write_register(REG_ENABLE, value)?;
+        self.enable = value;
+        Ok(())
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u8, Apds9960Error> {
+        let mut value = [0];
+        self.read_registers(reg, &mut value)?;
+        Ok(value[0])
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Apds9960Error> {
+        self.i2c
+            .write_read_bytes(APDS9960_ADDRESS, &[reg], buffer)
+            .map_err(|_| Apds9960Error::I2c)
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Apds9960Error> {
+        self.i2c
+            .write_bytes(APDS9960_ADDRESS, &[reg, value])
+            .map_err(|_| Apds9960Error::I2c)
+    }
+}
+
+/// Works out the direction of a swipe from the change of the balance between the
+/// opposite photodiodes. The hand shades the photodiode it comes from first, so the
+/// balance moves towards the other side.
+/// # Arguments
+/// * `first` - a u8 array, the up, down, left and right values at the start of the gesture.
+/// * `last` - a u8 array, the up, down, left and right values at the end of the gesture.
+/// # Returns
+/// * `a Option` - the direction of the swipe, None if the balance changed too little.
+fn decode_gesture(first: [u8; 4], last: [u8; 4]) -> Option<Apds9960Gesture> {
+    let ratio = |a: u8, b: u8| (a as i32 - b as i32) * 100 / (a as i32 + b as i32);
+    let up_down = ratio(last[0], last[1]) - ratio(first[0], first[1]);
+    let left_right = ratio(last[2], last[3]) - ratio(first[2], first[3]);
+    if up_down.abs() < GESTURE_SENSITIVITY && left_right.abs() < GESTURE_SENSITIVITY {
+        None
+    } else if up_down.abs() > left_right.abs() {
+        Some(if up_down > 0 {
+            Apds9960Gesture::Down
+        } else {
+            Apds9960Gesture::Up
+        })
+    } else {
+        Some(if left_right > 0 {
+            Apds9960Gesture::Right
+        } else {
+            Apds9960Gesture::Left
+        })
+    }
+}
@@ -0,0 +1,284 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the BME280 humidity, pressure and temperature sensor connected through I2C.
+//! It is a BMP280 with a humidity sensor, so the temperature and pressure are compensated
+//! the same way, and the oversampling, filter and mode types of the BMP280 driver are used.
+//! `<https://www.bosch-sensortec.com/products/environmental-sensors/humidity-sensors-bme280/>`
+
+use super::bmp280::{
+    pressure_altitude, raw_20_bits, wait_measurement, Bmp280Calibration, BmpError, BmpFilter,
+    BmpMode, BmpOversampling,
+};
+use super::traits::Thermometer;
+use crate::com::i2c::Twi;
+use crate::delay::delay_ms;
+
+/// Address of the BME280 with the SDO pin to ground.
+pub const BME280_ADDRESS: u8 = 0x76;
+/// Address of the BME280 with the SDO pin to Vcc.
+pub const BME280_ADDRESS_SDO_HIGH: u8 = 0x77;
+
+// Registers.
+const REG_CALIBRATION: u8 = 0x88;
+const REG_CALIBRATION_H1: u8 = 0xA1;
+const REG_CHIP_ID: u8 = 0xD0;
+const REG_RESET: u8 = 0xE0;
+const REG_CALIBRATION_H2: u8 = 0xE1;
+const REG_CTRL_HUM: u8 = 0xF2;
+const REG_STATUS: u8 = 0xF3;
+const REG_CTRL_MEAS: u8 = 0xF4;
+const REG_CONFIG: u8 = 0xF5;
+const REG_PRESS_MSB: u8 = 0xF7;
+
+const CHIP_ID: u8 = 0x60;
+const RESET_VALUE: u8 = 0xB6;
+
+/// A compensated measurement.
+/// * `temperature` - the temperature in hundredths of degrees Celsius.
+/// * `pressure` - the pressure in Pa.
+/// * `humidity` - the relative humidity in hundredths of percent.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bme280Reading {
+    pub temperature: i32,
+    pub pressure: u32,
+    pub humidity: u32,
+}
+
+/// Calibration coefficients of the humidity, as named in the datasheet.
+#[derive(Clone, Copy, Default)]
+struct HumidityCalibration {
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+impl HumidityCalibration {
+    /// Reads the coefficients from H1 at 0xA1 and the 7 bytes starting at 0xE1,
+    /// where H4 and H5 are 12 bits sharing the byte at 0xE5.
+    fn from_bytes(h1: u8, data: &[u8; 7]) -> HumidityCalibration {
+        HumidityCalibration {
+            h1,
+            h2: i16::from_le_bytes([data[0], data[1]]),
+            h3: data[2],
+            h4: ((data[3] as i8 as i16) << 4) | (data[4] & 0x0F) as i16,
+            h5: ((data[5] as i8 as i16) << 4) | (data[4] >> 4) as i16,
+            h6: data[6] as i8,
+        }
+    }
+
+    /// Compensates the raw humidity.
+    /// # Returns
+    /// * `a u32` - the relative humidity in percent, with 10 bits of fraction.
+    fn humidity(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let x = t_fine as i64 - 76800;
+        let adc_h = adc_h as i64;
+        let mut v =
+            (((adc_h << 14) - ((self.h4 as i64) << 20) - (self.h5 as i64 * x)) + 16384) >> 15;
+        v *= ((((((x * self.h6 as i64) >> 10) * (((x * self.h3 as i64) >> 11) + 32768)) >> 10)
+            + 2097152)
+            * self.h2 as i64
+            + 8192)
+            >> 14;
+        v -= ((((v >> 15) * (v >> 15)) >> 7) * self.h1 as i64) >> 4;
+        (v.clamp(0, 419430400) >> 12) as u32
+    }
+}
+
+/// Controls a BME280 sensor.
+/// # Elements
+/// * `address` - a u8, the I2C slave address of the sensor, set by its SDO pin.
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `calibration` - the calibration coefficients of the temperature and pressure read by `begin()`.
+/// * `humidity_calibration` - the calibration coefficients of the humidity read by `begin()`.
+/// * `ctrl_meas` - a u8, the oversampling and mode last written to the sensor.
+pub struct Bme280 {
+    address: u8,
+    i2c: &'static mut Twi,
+    calibration: Bmp280Calibration,
+    humidity_calibration: HumidityCalibration,
+    ctrl_meas: u8,
+}
+
+impl Default for Bme280 {
+    /// Creates the struct for a sensor with the SDO pin to ground.
+    fn default() -> Self {
+        Bme280::new(BME280_ADDRESS)
+    }
+}
+
+impl Bme280 {
+    /// Creates the struct to control a sensor on the I2C bus.
+    /// # Arguments
+    /// * `address` - a u8, the slave address, `BME280_ADDRESS` (0x76) or `BME280_ADDRESS_SDO_HIGH` (0x77).
+    /// # Returns
+    /// * `a Bme280 object` - To control the sensor, after `begin()`.
+    pub fn new(address: u8) -> Bme280 {
+        match address {
+            BME280_ADDRESS | BME280_ADDRESS_SDO_HIGH => Bme280 {
+                address,
+                i2c: unsafe { Twi::new() },
+                calibration: Bmp280Calibration::default(),
+                humidity_calibration: HumidityCalibration::default(),
+                ctrl_meas: 0,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Checks the chip id, resets the sensor and reads its calibration, then starts
+    /// measuring in normal mode with 2 times oversampling of the temperature, 16 times
+    /// oversampling of the pressure, 1 sample of the humidity and the filter at 16.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor is ready, otherwise the error found.
+    pub fn begin(&mut self) -> Result<(), BmpError> {
+        let mut id = [0];
+        self.read_registers(REG_CHIP_ID, &mut id)?;
+        if id[0] != CHIP_ID {
+            return Err(BmpError::WrongDevice(id[0]));
+        }
+
+        self.write_register(REG_RESET, RESET_VALUE)?;
+        delay_ms(2);
+        // Bit 0 of the status is set while the calibration is copied from the NVM.
+        let mut status = [1];
+        while status[0] & 0x01 != 0 {
+            self.read_registers(REG_STATUS, &mut status)?;
+        }
+
+        let mut data = [0; 24];
+        self.read_registers(REG_CALIBRATION, &mut data)?;
+        self.calibration = Bmp280Calibration::from_bytes(&data);
+        let mut h1 = [0];
+        self.read_registers(REG_CALIBRATION_H1, &mut h1)?;
+        let mut data = [0; 7];
+        self.read_registers(REG_CALIBRATION_H2, &mut data)?;
+        self.humidity_calibration = HumidityCalibration::from_bytes(h1[0], &data);
+
+        self.set_filter(BmpFilter::X16)?;
+        self.set_oversampling(
+            BmpOversampling::X2,
+            BmpOversampling::X16,
+            BmpOversampling::X1,
+        )?;
+        self.set_mode(BmpMode::Normal)
+    }
+
+    /// Sets the oversampling of the measurements.
+    /// # Arguments
+    /// * `temperature` - a `BmpOversampling` object, for the temperature.
+    /// * `pressure` - a `BmpOversampling` object, for the pressure.
+    /// * `humidity` - a `BmpOversampling` object, for the humidity.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_oversampling(
+        &mut self,
+        temperature: BmpOversampling,
+        pressure: BmpOversampling,
+        humidity: BmpOversampling,
+    ) -> Result<(), BmpError> {
+        // The humidity setting is only used after the next write of ctrl_meas.
+        self.write_register(REG_CTRL_HUM, humidity.bits())?;
+        let value = (temperature.bits() << 5) | (pressure.bits() << 2) | (self.ctrl_meas & 0b11);
+        self.write_register(REG_CTRL_MEAS, value)?;
+        self.ctrl_meas = value;
+        Ok(())
+    }
+
+    /// Sets the power mode.
+    /// # Arguments
+    /// * `mode` - a `BmpMode` object, the mode.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_mode(&mut self, mode: BmpMode) -> Result<(), BmpError> {
+        let value = (self.ctrl_meas & !0b11) | mode.bits();
+        self.write_register(REG_CTRL_MEAS, value)?;
+        self.ctrl_meas = value;
+        Ok(())
+    }
+
+    /// Sets the IIR filter of the pressure and temperature.
+    /// # Arguments
+    /// * `filter` - a `BmpFilter` object, the filter coefficient.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_filter(&mut self, filter: BmpFilter) -> Result<(), BmpError> {
+        let mut config = [0];
+        self.read_registers(REG_CONFIG, &mut config)?;
+        self.write_register(
+            REG_CONFIG,
+            (config[0] & !0b0001_1100) | (filter.bits() << 2),
+        )
+    }
+
+    /// Reads the temperature, pressure and humidity. In forced mode a measurement is started and waited for.
+    /// # Returns
+    /// * `a Result` - the compensated measurement, or the error found.
+    pub fn read(&mut self) -> Result<Bme280Reading, BmpError> {
+        if self.ctrl_meas & 0b11 == BmpMode::Forced.bits() {
+            // The sensor goes back to sleep after each forced measurement.
+            self.write_register(REG_CTRL_MEAS, self.ctrl_meas)?;
+            wait_measurement(self.address, self.i2c)?;
+        }
+
+        let mut data = [0; 8];
+        self.read_registers(REG_PRESS_MSB, &mut data)?;
+        let adc_p = raw_20_bits(&data[0..3]);
+        let adc_t = raw_20_bits(&data[3..6]);
+        let adc_h = u16::from_be_bytes([data[6], data[7]]) as i32;
+
+        let (t_fine, temperature) = self.calibration.temperature(adc_t);
+        let humidity = self.humidity_calibration.humidity(adc_h, t_fine);
+        Ok(Bme280Reading {
+            temperature,
+            pressure: self.calibration.pressure(adc_p, t_fine) >> 8,
+            humidity: (humidity * 100 + 512) >> 10,
+        })
+    }
+
+    /// Reads the pressure and estimates the altitude with `pressure_altitude()`.
+    /// # Arguments
+    /// * `sea_level` - a u32, the pressure at sea level in Pa.
+    /// # Returns
+    /// * `a Result` - the altitude in metres, or the error found.
+    pub fn read_altitude(&mut self, sea_level: u32) -> Result<f32, BmpError> {
+        let reading = self.read()?;
+        Ok(pressure_altitude(reading.pressure, sea_level))
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), BmpError> {
+        self.i2c
+            .write_read_bytes(self.address, &[reg], buffer)
+            .map_err(|_| BmpError::I2c)
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), BmpError> {
+        self.i2c
+            .write_bytes(self.address, &[reg, value])
+            .map_err(|_| BmpError::I2c)
+    }
+}
+
+impl Thermometer for Bme280 {
+    type Error = BmpError;
+
+    fn read_temperature(&mut self) -> Result<f32, BmpError> {
+        Ok(self.read()?.temperature as f32 / 100.0)
+    }
+}
@@ -0,0 +1,260 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the BMP180 barometric pressure and temperature sensor connected through I2C,
+//! the older sensor replaced by the BMP280. Each reading starts a temperature and then a
+//! pressure conversion, which are compensated with the integer formulas of the datasheet.
+//! `<https://cdn-shop.adafruit.com/datasheets/BST-BMP180-DS000-09.pdf>`
+
+use super::bmp280::{pressure_altitude, BmpError, BmpReading};
+use super::traits::Thermometer;
+use crate::com::i2c::Twi;
+use crate::delay::{delay_ms, delay_us};
+
+/// Address of the BMP180, which can not be changed.
+pub const BMP180_ADDRESS: u8 = 0x77;
+
+// Registers.
+const REG_CALIBRATION: u8 = 0xAA;
+const REG_CHIP_ID: u8 = 0xD0;
+const REG_CONTROL: u8 = 0xF4;
+const REG_OUT_MSB: u8 = 0xF6;
+
+const CHIP_ID: u8 = 0x55;
+const READ_TEMPERATURE: u8 = 0x2E;
+const READ_PRESSURE: u8 = 0x34;
+
+/// Oversampling settings of the pressure, with the conversion time.
+/// * `UltraLowPower` - 1 sample, 4.5 ms.
+/// * `Standard` - 2 samples, 7.5 ms.
+/// * `HighResolution` - 4 samples, 13.5 ms.
+/// * `UltraHighResolution` - 8 samples, 25.5 ms.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bmp180Mode {
+    UltraLowPower,
+    Standard,
+    HighResolution,
+    UltraHighResolution,
+}
+
+/// Calibration coefficients, as named in the datasheet.
+#[derive(Clone, Copy, Default)]
+struct Calibration {
+    ac1: i16,
+    ac2: i16,
+    ac3: i16,
+    ac4: u16,
+    ac5: u16,
+    ac6: u16,
+    b1: i16,
+    b2: i16,
+    mc: i16,
+    md: i16,
+}
+
+impl Calibration {
+    /// Reads the coefficients from the 22 bytes starting at 0xAA, which are big endian.
+    fn from_bytes(data: &[u8; 22]) -> Calibration {
+        let unsigned = |i: usize| u16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+        let signed = |i: usize| unsigned(i) as i16;
+        Calibration {
+            ac1: signed(0),
+            ac2: signed(1),
+            ac3: signed(2),
+            ac4: unsigned(3),
+            ac5: unsigned(4),
+            ac6: unsigned(5),
+            b1: signed(6),
+            b2: signed(7),
+            // MB at index 8 is not used by the formulas.
+            mc: signed(9),
+            md: signed(10),
+        }
+    }
+
+    /// Compensates the raw temperature.
+    /// # Returns
+    /// * `a tuple of two i32` - B5, which is needed by the pressure, and the temperature in tenths of degrees Celsius.
+    fn temperature(&self, ut: i32) -> (i32, i32) {
+        let x1 = ((ut - self.ac6 as i32) * self.ac5 as i32) >> 15;
+        let x2 = ((self.mc as i32) << 11) / (x1 + self.md as i32);
+        let b5 = x1 + x2;
+        (b5, (b5 + 8) >> 4)
+    }
+
+    /// Compensates the raw pressure.
+    /// # Returns
+    /// * `a i32` - the pressure in Pa.
+    fn pressure(&self, up: i32, b5: i32, oss: u8) -> i32 {
+        let b6 = b5 - 4000;
+        let mut x1 = (self.b2 as i32 * ((b6 * b6) >> 12)) >> 11;
+        let mut x2 = (self.ac2 as i32 * b6) >> 11;
+        let mut x3 = x1 + x2;
+        let b3 = ((((self.ac1 as i32) * 4 + x3) << oss) + 2) / 4;
+        x1 = (self.ac3 as i32 * b6) >> 13;
+        x2 = (self.b1 as i32 * ((b6 * b6) >> 12)) >> 16;
+        x3 = ((x1 + x2) + 2) >> 2;
+        let b4 = (self.ac4 as u32 * (x3 + 32768) as u32) >> 15;
+        if b4 == 0 {
+            // Avoids a division by zero with a sensor which is not calibrated.
+            return 0;
+        }
+        let b7 = (up as u32).wrapping_sub(b3 as u32) * (50000 >> oss);
+        let mut p = if b7 < 0x8000_0000 {
+            (b7 * 2) / b4
+        } else {
+            (b7 / b4) * 2
+        } as i32;
+        x1 = (p >> 8) * (p >> 8);
+        x1 = (x1 * 3038) >> 16;
+        x2 = (-7357 * p) >> 16;
+        p += (x1 + x2 + 3791) >> 4;
+        p
+    }
+}
+
+impl Bmp180Mode {
+    /// Gives the oversampling setting, the log2 of the number of samples.
+    fn oss(self) -> u8 {
+        match self {
+            Bmp180Mode::UltraLowPower => 0,
+            Bmp180Mode::Standard => 1,
+            Bmp180Mode::HighResolution => 2,
+            Bmp180Mode::UltraHighResolution => 3,
+        }
+    }
+
+    /// Gives the conversion time of the pressure in microseconds.
+    fn conversion_time(self) -> u32 {
+        match self {
+            Bmp180Mode::UltraLowPower => 4500,
+            Bmp180Mode::Standard => 7500,
+            Bmp180Mode::HighResolution => 13500,
+            Bmp180Mode::UltraHighResolution => 25500,
+        }
+    }
+}
+
+/// Controls a BMP180 sensor.
+/// # Elements
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `calibration` - the calibration coefficients read by `begin()`.
+/// * `mode` - a `Bmp180Mode` object, the oversampling of the pressure.
+pub struct Bmp180 {
+    i2c: &'static mut Twi,
+    calibration: Calibration,
+    mode: Bmp180Mode,
+}
+
+impl Default for Bmp180 {
+    /// Creates the struct for a sensor read in the standard mode.
+    fn default() -> Self {
+        Bmp180::new(Bmp180Mode::Standard)
+    }
+}
+
+impl Bmp180 {
+    /// Creates the struct to control the sensor on the I2C bus.
+    /// # Arguments
+    /// * `mode` - a `Bmp180Mode` object, the oversampling of the pressure.
+    /// # Returns
+    /// * `a Bmp180 object` - To control the sensor, after `begin()`.
+    pub fn new(mode: Bmp180Mode) -> Bmp180 {
+        Bmp180 {
+            i2c: unsafe { Twi::new() },
+            calibration: Calibration::default(),
+            mode,
+        }
+    }
+
+    /// Checks the chip id and reads the calibration of the sensor.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor is ready, otherwise the error found.
+    pub fn begin(&mut self) -> Result<(), BmpError> {
+        let mut id = [0];
+        self.read_registers(REG_CHIP_ID, &mut id)?;
+        if id[0] != CHIP_ID {
+            return Err(BmpError::WrongDevice(id[0]));
+        }
+
+        let mut data = [0; 22];
+        self.read_registers(REG_CALIBRATION, &mut data)?;
+        self.calibration = Calibration::from_bytes(&data);
+        Ok(())
+    }
+
+    /// Sets the oversampling of the pressure.
+    /// # Arguments
+    /// * `mode` - a `Bmp180Mode` object, the oversampling.
+    pub fn set_mode(&mut self, mode: Bmp180Mode) {
+        self.mode = mode;
+    }
+
+    /// Measures the temperature and pressure, which takes from 9 to 30 ms depending on the mode.
+    /// # Returns
+    /// * `a Result` - the compensated measurement, or the error found.
+    pub fn read(&mut self) -> Result<BmpReading, BmpError> {
+        let mut data = [0; 3];
+
+        self.write_register(REG_CONTROL, READ_TEMPERATURE)?;
+        delay_us(4500);
+        self.read_registers(REG_OUT_MSB, &mut data[..2])?;
+        let ut = u16::from_be_bytes([data[0], data[1]]) as i32;
+
+        let oss = self.mode.oss();
+        self.write_register(REG_CONTROL, READ_PRESSURE | (oss << 6))?;
+        delay_ms(self.mode.conversion_time() / 1000);
+        delay_us(self.mode.conversion_time() % 1000);
+        self.read_registers(REG_OUT_MSB, &mut data)?;
+        let up = (((data[0] as i32) << 16) | ((data[1] as i32) << 8) | data[2] as i32) >> (8 - oss);
+
+        let (b5, temperature) = self.calibration.temperature(ut);
+        Ok(BmpReading {
+            temperature: temperature * 10,
+            pressure: self.calibration.pressure(up, b5, oss).max(0) as u32,
+        })
+    }
+
+    /// Reads the pressure and estimates the altitude with `pressure_altitude()`.
+    /// # Arguments
+    /// * `sea_level` - a u32, the pressure at sea level in Pa.
+    /// # Returns
+    /// * `a Result` - the altitude in metres, or the error found.
+    pub fn read_altitude(&mut self, sea_level: u32) -> Result<f32, BmpError> {
+        let reading = self.read()?;
+        Ok(pressure_altitude(reading.pressure, sea_level))
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), BmpError> {
+        self.i2c
+            .write_read_bytes(BMP180_ADDRESS, &[reg], buffer)
+            .map_err(|_| BmpError::I2c)
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), BmpError> {
+        self.i2c
+            .write_bytes(BMP180_ADDRESS, &[reg, value])
+            .map_err(|_| BmpError::I2c)
+    }
+}
+
+impl Thermometer for Bmp180 {
+    type Error = BmpError;
+
+    fn read_temperature(&mut self) -> Result<f32, BmpError> {
+        Ok(self.read()?.temperature as f32 / 100.0)
+    }
+}
@@ -0,0 +1,423 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the BMP280 barometric pressure and temperature sensor connected through I2C.
+//! The readings are compensated with the calibration coefficients stored in each sensor,
+//! using the integer formulas of the datasheet, and the altitude is estimated from the pressure.
+//! The oversampling, filter and mode types are shared with the BME280.
+//! `<https://www.bosch-sensortec.com/products/environmental-sensors/pressure-sensors/bmp280/>`
+
+use super::traits::Thermometer;
+use crate::com::i2c::Twi;
+use crate::delay::delay_ms;
+
+/// Address of the BMP280 with the SDO pin to ground.
+pub const BMP280_ADDRESS: u8 = 0x76;
+/// Address of the BMP280 with the SDO pin to Vcc.
+pub const BMP280_ADDRESS_SDO_HIGH: u8 = 0x77;
+
+/// Standard pressure at sea level in Pa, to be replaced with the local value for a precise altitude.
+pub const SEA_LEVEL_PRESSURE: u32 = 101325;
+
+// Registers.
+const REG_CALIBRATION: u8 = 0x88;
+const REG_CHIP_ID: u8 = 0xD0;
+const REG_RESET: u8 = 0xE0;
+const REG_STATUS: u8 = 0xF3;
+const REG_CTRL_MEAS: u8 = 0xF4;
+const REG_CONFIG: u8 = 0xF5;
+const REG_PRESS_MSB: u8 = 0xF7;
+
+const CHIP_ID: u8 = 0x58;
+const RESET_VALUE: u8 = 0xB6;
+
+/// Errors of the BMP180, BMP280 and BME280.
+/// * `I2c` - The sensor did not answer, check the wiring and the address.
+/// * `WrongDevice` - The chip id does not belong to the sensor, with the value read.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BmpError {
+    I2c,
+    WrongDevice(u8),
+}
+
+/// Oversampling of a measurement, more samples give less noise but take longer.
+/// `Skip` turns the measurement off.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BmpOversampling {
+    Skip,
+    X1,
+    X2,
+    X4,
+    X8,
+    X16,
+}
+
+/// Coefficient of the IIR filter, which smooths out short changes of pressure such as wind or doors.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BmpFilter {
+    Off,
+    X2,
+    X4,
+    X8,
+    X16,
+}
+
+/// Power modes of the sensor.
+/// * `Sleep` - no measurements, the lowest power.
+/// * `Forced` - one measurement at every read, then back to sleep.
+/// * `Normal` - measurements are repeated after the standby time and a read gives the latest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BmpMode {
+    Sleep,
+    Forced,
+    Normal,
+}
+
+/// A compensated measurement.
+/// * `temperature` - the temperature in hundredths of degrees Celsius.
+/// * `pressure` - the pressure in Pa.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BmpReading {
+    pub temperature: i32,
+    pub pressure: u32,
+}
+
+/// Calibration coefficients of the temperature and pressure, as named in the datasheet.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Bmp280Calibration {
+    pub(crate) t1: u16,
+    pub(crate) t2: i16,
+    pub(crate) t3: i16,
+    pub(crate) p1: u16,
+    pub(crate) p2: i16,
+    pub(crate) p3: i16,
+    pub(crate) p4: i16,
+    pub(crate) p5: i16,
+    pub(crate) p6: i16,
+    pub(crate) p7: i16,
+    pub(crate) p8: i16,
+    pub(crate) p9: i16,
+}
+
+impl Bmp280Calibration {
+    /// Reads the coefficients from the 24 bytes starting at 0x88, which are little endian.
+    pub(crate) fn from_bytes(data: &[u8; 24]) -> Bmp280Calibration {
+        let unsigned = |i: usize| u16::from_le_bytes([data[2 * i], data[2 * i + 1]]);
+        let signed = |i: usize| unsigned(i) as i16;
+        Bmp280Calibration {
+            t1: unsigned(0),
+            t2: signed(1),
+            t3: signed(2),
+            p1: unsigned(3),
+            p2: signed(4),
+            p3: signed(5),
+            p4: signed(6),
+            p5: signed(7),
+            p6: signed(8),
+            p7: signed(9),
+            p8: signed(10),
+            p9: signed(11),
+        }
+    }
+
+    /// Compensates the raw temperature.
+    /// # Returns
+    /// * `a tuple of two i32` - the fine temperature needed by the other compensations, and the temperature in hundredths of degrees Celsius.
+    pub(crate) fn temperature(&self, adc_t: i32) -> (i32, i32) {
+        let t1 = self.t1 as i32;
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * self.t2 as i32) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * self.t3 as i32) >> 14;
+        let t_fine = var1 + var2;
+        (t_fine, (t_fine * 5 + 128) >> 8)
+    }
+
+    /// Compensates the raw pressure.
+    /// # Returns
+    /// * `a u32` - the pressure in Pa, with 8 bits of fraction.
+    pub(crate) fn pressure(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let mut var1 = t_fine as i64 - 128000;
+        let mut var2 = var1 * var1 * self.p6 as i64;
+        var2 += (var1 * self.p5 as i64) << 17;
+        var2 += (self.p4 as i64) << 35;
+        var1 = ((var1 * var1 * self.p3 as i64) >> 8) + ((var1 * self.p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * self.p1 as i64) >> 33;
+        if var1 == 0 {
+            // Avoids a division by zero with a sensor which is not calibrated.
+            return 0;
+        }
+        let mut p = 1048576 - adc_p as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        var1 = (self.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        var2 = (self.p8 as i64 * p) >> 19;
+        (((p + var1 + var2) >> 8) + ((self.p7 as i64) << 4)) as u32
+    }
+}
+
+impl BmpOversampling {
+    /// Gives the value of the oversampling bits in the control registers.
+    pub(crate) fn bits(self) -> u8 {
+        match self {
+            BmpOversampling::Skip => 0,
+            BmpOversampling::X1 => 1,
+            BmpOversampling::X2 => 2,
+            BmpOversampling::X4 => 3,
+            BmpOversampling::X8 => 4,
+            BmpOversampling::X16 => 5,
+        }
+    }
+}
+
+impl BmpFilter {
+    /// Gives the value of the filter bits in the config register.
+    pub(crate) fn bits(self) -> u8 {
+        match self {
+            BmpFilter::Off => 0,
+            BmpFilter::X2 => 1,
+            BmpFilter::X4 => 2,
+            BmpFilter::X8 => 3,
+            BmpFilter::X16 => 4,
+        }
+    }
+}
+
+impl BmpMode {
+    /// Gives the value of the mode bits in the measurement control register.
+    pub(crate) fn bits(self) -> u8 {
+        match self {
+            BmpMode::Sleep => 0b00,
+            BmpMode::Forced => 0b01,
+            BmpMode::Normal => 0b11,
+        }
+    }
+}
+
+/// Estimates the altitude from the pressure with the international barometric formula,
+/// `44330 * (1 - (p / p0) ^ (1 / 5.255))`. The power is worked out with series which are
+/// precise to a few centimetres from sea level up to about 9 km.
+/// # Arguments
+/// * `pressure` - a u32, the measured pressure in Pa.
+/// * `sea_level` - a u32, the pressure at sea level in Pa, `SEA_LEVEL_PRESSURE` if it is not known.
+/// # Returns
+/// * `a f32` - the altitude in metres above the sea level.
+pub fn pressure_altitude(pressure: u32, sea_level: u32) -> f32 {
+    if pressure == 0 || sea_level == 0 {
+        return 0.0;
+    }
+    let ratio = pressure as f32 / sea_level as f32;
+
+    // ln(x) = 2 * (u + u^3 / 3 + u^5 / 5 + ...), with u = (x - 1) / (x + 1).
+    let u = (ratio - 1.0) / (ratio + 1.0);
+    let u2 = u * u;
+    let mut term = u;
+    let mut ln = 0.0;
+    for n in 0..8 {
+        ln += term / (2 * n + 1) as f32;
+        term *= u2;
+    }
+    let exponent = 2.0 * ln * 0.190_295;
+
+    // exp(y) = 1 + y + y^2 / 2! + ..., y is small.
+    let mut term = 1.0;
+    let mut power = 1.0;
+    for n in 1..10 {
+        term *= exponent / n as f32;
+        power += term;
+    }
+    44330.0 * (1.0 - power)
+}
+
+/// Controls a BMP280 sensor.
+/// # Elements
+/// * `address` - a u8, the I2C slave address of the sensor, set by its SDO pin.
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `calibration` - the calibration coefficients read by `begin()`.
+/// * `ctrl_meas` - a u8, the oversampling and mode last written to the sensor.
+pub struct Bmp280 {
+    address: u8,
+    i2c: &'static mut Twi,
+    calibration: Bmp280Calibration,
+    ctrl_meas: u8,
+}
+
+impl Default for Bmp280 {
+    /// Creates the struct for a sensor with the SDO pin to ground.
+    fn default() -> Self {
+        Bmp280::new(BMP280_ADDRESS)
+    }
+}
+
+impl Bmp280 {
+    /// Creates the struct to control a sensor on the I2C bus.
+    /// # Arguments
+    /// * `address` - a u8, the slave address, `BMP280_ADDRESS` (0x76) or `BMP280_ADDRESS_SDO_HIGH` (0x77).
+    /// # Returns
+    /// * `a Bmp280 object` - To control the sensor, after `begin()`.
+    pub fn new(address: u8) -> Bmp280 {
+        match address {
+            BMP280_ADDRESS | BMP280_ADDRESS_SDO_HIGH => Bmp280 {
+                address,
+                i2c: unsafe { Twi::new() },
+                calibration: Bmp280Calibration::default(),
+                ctrl_meas: 0,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Checks the chip id, resets the sensor and reads its calibration, then starts
+    /// measuring in normal mode with 2 times oversampling of the temperature, 16 times
+    /// oversampling of the pressure and the filter at 16.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor is ready, otherwise the error found.
+    pub fn begin(&mut self) -> Result<(), BmpError> {
+        let mut id = [0];
+        self.read_registers(REG_CHIP_ID, &mut id)?;
+        if id[0] != CHIP_ID {
+            return Err(BmpError::WrongDevice(id[0]));
+        }
+
+        self.write_register(REG_RESET, RESET_VALUE)?;
+        delay_ms(2);
+        // Bit 0 of the status is set while the calibration is copied from the NVM.
+        let mut status = [1];
+        while status[0] & 0x01 != 0 {
+            self.read_registers(REG_STATUS, &mut status)?;
+        }
+
+        let mut data = [0; 24];
+        self.read_registers(REG_CALIBRATION, &mut data)?;
+        self.calibration = Bmp280Calibration::from_bytes(&data);
+
+        self.set_filter(BmpFilter::X16)?;
+        self.set_oversampling(BmpOversampling::X2, BmpOversampling::X16)?;
+        self.set_mode(BmpMode::Normal)
+    }
+
+    /// Sets the oversampling of the measurements.
+    /// # Arguments
+    /// * `temperature` - a `BmpOversampling` object, for the temperature.
+    /// * `pressure` - a `BmpOversampling` object, for the pressure.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_oversampling(
+        &mut self,
+        temperature: BmpOversampling,
+        pressure: BmpOversampling,
+    ) -> Result<(), BmpError> {
+        let value = (temperature.bits() << 5) | (pressure.bits() << 2) | (self.ctrl_meas & 0b11);
+        self.write_register(REG_CTRL_MEAS, value)?;
+        self.ctrl_meas = value;
+        Ok(())
+    }
+
+    /// Sets the power mode.
+    /// # Arguments
+    /// * `mode` - a `BmpMode` object, the mode.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_mode(&mut self, mode: BmpMode) -> Result<(), BmpError> {
+        let value = (self.ctrl_meas & !0b11) | mode.bits();
+        self.write_register(REG_CTRL_MEAS, value)?;
+        self.ctrl_meas = value;
+        Ok(())
+    }
+
+    /// Sets the IIR filter of the pressure.
+    /// # Arguments
+    /// * `filter` - a `BmpFilter` object, the filter coefficient.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_filter(&mut self, filter: BmpFilter) -> Result<(), BmpError> {
+        let mut config = [0];
+        self.read_registers(REG_CONFIG, &mut config)?;
+        self.write_register(
+            REG_CONFIG,
+            (config[0] & !0b0001_1100) | (filter.bits() << 2),
+        )
+    }
+
+    /// Reads the temperature and pressure. In forced mode a measurement is started and waited for.
+    /// # Returns
+    /// * `a Result` - the compensated measurement, or the error found.
+    pub fn read(&mut self) -> Result<BmpReading, BmpError> {
+        if self.ctrl_meas & 0b11 == BmpMode::Forced.bits() {
+            // The sensor goes back to sleep after each forced measurement.
+            self.write_register(REG_CTRL_MEAS, self.ctrl_meas)?;
+            wait_measurement(self.address, self.i2c)?;
+        }
+
+        let mut data = [0; 6];
+        self.read_registers(REG_PRESS_MSB, &mut data)?;
+        let adc_p = raw_20_bits(&data[0..3]);
+        let adc_t = raw_20_bits(&data[3..6]);
+
+        let (t_fine, temperature) = self.calibration.temperature(adc_t);
+        Ok(BmpReading {
+            temperature,
+            pressure: self.calibration.pressure(adc_p, t_fine) >> 8,
+        })
+    }
+
+    /// Reads the pressure and estimates the altitude with `pressure_altitude()`.
+    /// # Arguments
+    /// * `sea_level` - a u32, the pressure at sea level in Pa.
+    /// # Returns
+    /// * `a Result` - the altitude in metres, or the error found.
+    pub fn read_altitude(&mut self, sea_level: u32) -> Result<f32, BmpError> {
+        let reading = self.read()?;
+        Ok(pressure_altitude(reading.pressure, sea_level))
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), BmpError> {
+        self.i2c
+            .write_read_bytes(self.address, &[reg], buffer)
+            .map_err(|_| BmpError::I2c)
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), BmpError> {
+        self.i2c
+            .write_bytes(self.address, &[reg, value])
+            .map_err(|_| BmpError::I2c)
+    }
+}
+
+/// Joins the most, least and extra least significant bytes of a 20 bit measurement.
+pub(crate) fn raw_20_bits(data: &[u8]) -> i32 {
+    ((data[0] as i32) << 12) | ((data[1] as i32) << 4) | ((data[2] as i32) >> 4)
+}
+
+/// Waits while bit 3 of the status is set, which is while a measurement is running.
+/// The longest measurement, with 16 times oversampling of everything, takes about 115 ms.
+pub(crate) fn wait_measurement(address: u8, i2c: &mut Twi) -> Result<(), BmpError> {
+    let mut status = [0];
+    for _ in 0..150 {
+        delay_ms(1);
+        i2c.write_read_bytes(address, &[REG_STATUS], &mut status)
+            .map_err(|_| BmpError::I2c)?;
+        if status[0] & 0x08 == 0 {
+            break;
+        }
+    }
+    Ok(())
+}
+
+impl Thermometer for Bmp280 {
+    type Error = BmpError;
+
+    fn read_temperature(&mut self) -> Result<f32, BmpError> {
+        Ok(self.read()?.temperature as f32 / 100.0)
+    }
+}
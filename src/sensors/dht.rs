@@ -0,0 +1,187 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the DHT11 and DHT22 (AM2302) humidity and temperature sensors, which use
+//! their own single wire protocol. The data line needs a pull-up resistor of about 5 kΩ,
+//! which most modules already have. The sensor sends 40 bits, humidity, temperature and
+//! a checksum, where each bit is a 50 µs low pulse followed by a high pulse of 26 to 28 µs
+//! for a zero or 70 µs for a one.
+//! The DHT11 can be read once a second and the DHT22 once every two seconds.
+//! `<https://cdn-shop.adafruit.com/datasheets/Digital+humidity+and+temperature+sensor+AM2302.pdf>`
+
+use super::traits::Thermometer;
+use crate::delay::{delay_ms, delay_us};
+use crate::hal::gpio::{OpenDrain, Pin};
+use crate::hal::interrupts;
+
+/// Number of 1 µs polls after which a level which does not change is a timeout.
+/// The longest level in the protocol lasts 80 µs.
+const TIMEOUT_POLLS: u16 = 200;
+
+/// Models of the sensor, which differ in the start signal and the data format.
+/// * `Dht11` - whole percents and degrees, from 0 to 50 °C.
+/// * `Dht22` - tenths of percents and degrees, from -40 to 80 °C, also sold as AM2302.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DhtModel {
+    Dht11,
+    Dht22,
+}
+
+/// Errors found while reading the sensor.
+/// * `Timeout` - The sensor did not answer or a bit did not end, check the wiring and the pull-up.
+/// * `Checksum` - The received checksum does not match the data, the reading should be repeated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DhtError {
+    Timeout,
+    Checksum,
+}
+
+/// A measurement of the sensor.
+/// * `humidity` - the relative humidity in percent.
+/// * `temperature` - the temperature in degrees Celsius.
+#[derive(Clone, Copy, Debug)]
+pub struct DhtReading {
+    pub humidity: f32,
+    pub temperature: f32,
+}
+
+/// Controls a DHT sensor on one pin.
+/// # Elements
+/// * `pin` - a `Pin<OpenDrain>` object, the pin of the data line.
+/// * `model` - a `DhtModel` object, the model of the sensor.
+pub struct Dht {
+    pin: Pin<OpenDrain>,
+    model: DhtModel,
+}
+
+impl Dht {
+    /// Creates the driver, the line is released.
+    /// # Arguments
+    /// * `pin` - a `Pin<OpenDrain>` object, the pin of the data line.
+    /// * `model` - a `DhtModel` object, the model of the sensor.
+    /// # Returns
+    /// * `a Dht object` - which will be used to read the sensor.
+    pub fn new(mut pin: Pin<OpenDrain>, model: DhtModel) -> Dht {
+        pin.high();
+        Dht { pin, model }
+    }
+
+    /// Reads the humidity and temperature.
+    /// The 40 bits are received with interrupts held off, which takes about 5 ms.
+    /// # Returns
+    /// * `a Result` - the measurement, or the error found.
+    pub fn read(&mut self) -> Result<DhtReading, DhtError> {
+        let mut data = [0; 5];
+        self.receive(&mut data)?;
+
+        let sum = data[..4]
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        if sum != data[4] {
+            return Err(DhtError::Checksum);
+        }
+        Ok(self.decode(&data))
+    }
+
+    /// Gives back the pin of the data line.
+    /// # Returns
+    /// * `a Pin<OpenDrain> object` - the pin, released.
+    pub fn release(self) -> Pin<OpenDrain> {
+        self.pin
+    }
+
+    /// Sends the start signal and receives the five bytes of data.
+    fn receive(&mut self, data: &mut [u8; 5]) -> Result<(), DhtError> {
+        // The start signal, at least 18 ms for the DHT11 and 1 ms for the DHT22.
+        self.pin.low();
+        match self.model {
+            DhtModel::Dht11 => delay_ms(20),
+            DhtModel::Dht22 => delay_us(1100),
+        }
+
+        interrupts::free(|_| {
+            self.pin.high();
+            delay_us(40);
+
+            // The answer of the sensor, 80 µs low and 80 µs high.
+            self.wait_while(false)?;
+            self.wait_while(true)?;
+
+            for i in 0..40 {
+                // A bit is a one when its high pulse is longer than the 50 µs low pulse before it.
+                let low = self.wait_while(false)?;
+                let high = self.wait_while(true)?;
+                data[i / 8] <<= 1;
+                if high > low {
+                    data[i / 8] |= 1;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Waits for the line to leave a level.
+    /// # Returns
+    /// * `a Result` - the number of polls the level lasted, or a timeout.
+    fn wait_while(&self, level: bool) -> Result<u16, DhtError> {
+        let mut polls: u16 = 0;
+        while self.pin.is_high() == level {
+            if polls == TIMEOUT_POLLS {
+                return Err(DhtError::Timeout);
+            }
+            polls += 1;
+            delay_us(1);
+        }
+        Ok(polls)
+    }
+
+    /// Converts the data bytes to a measurement according to the model.
+    fn decode(&self, data: &[u8; 5]) -> DhtReading {
+        match self.model {
+            DhtModel::Dht11 => {
+                // Newer DHT11 give tenths in the second byte of each value and the sign in bit 7.
+                let mut temperature = data[2] as f32 + (data[3] & 0x7F) as f32 / 10.0;
+                if data[3] & 0x80 != 0 {
+                    temperature = -temperature;
+                }
+                DhtReading {
+                    humidity: data[0] as f32 + data[1] as f32 / 10.0,
+                    temperature,
+                }
+            }
+            DhtModel::Dht22 => {
+                let humidity = u16::from_be_bytes([data[0], data[1]]);
+                let magnitude = u16::from_be_bytes([data[2] & 0x7F, data[3]]);
+                let mut temperature = magnitude as f32 / 10.0;
+                if data[2] & 0x80 != 0 {
+                    temperature = -temperature;
+                }
+                DhtReading {
+                    humidity: humidity as f32 / 10.0,
+                    temperature,
+                }
+            }
+        }
+    }
+}
+
+impl Thermometer for Dht {
+    type Error = DhtError;
+
+    fn read_temperature(&mut self) -> Result<f32, DhtError> {
+        Ok(self.read()?.temperature)
+    }
+}
@@ -34,7 +34,7 @@ pub fn setup(
     common_anode: bool,
     value: u8,
 ) {
-    let pins = Pins::new();
+    let pins = unsafe { Pins::new() };
     let mut data = pins.digital[datapin];
     let mut clock = pins.digital[clockpin];
     let mut latch = pins.digital[latchpin];
@@ -62,7 +62,7 @@ pub fn myfn_update_display(
     common_anode: bool,
     mut value: u8,
 ) {
-    let pins = Pins::new();
+    let pins = unsafe { Pins::new() };
     let mut latch = pins.digital[latchpin];
 
     if common_anode {
@@ -0,0 +1,254 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the DS18B20 temperature sensors on a 1-Wire bus. Several sensors can share
+//! the bus, they are found with the ROM search and addressed by their ROM code, or all at
+//! once to start their conversions together. The sensors have to be powered from Vdd,
+//! as the end of a conversion is found by polling the bus, which parasite power does not allow.
+//! `<https://www.analog.com/en/products/ds18b20.html>`
+
+use super::traits::Thermometer;
+use crate::com::one_wire::{crc8, OneWire};
+use crate::delay::delay_ms;
+
+/// Family code of the DS18B20, the first byte of its ROM code.
+pub const DS18B20_FAMILY: u8 = 0x28;
+
+// Function commands.
+const CONVERT_T: u8 = 0x44;
+const WRITE_SCRATCHPAD: u8 = 0x4E;
+const READ_SCRATCHPAD: u8 = 0xBE;
+const COPY_SCRATCHPAD: u8 = 0x48;
+
+/// Resolutions of the measurement, with the step and the conversion time.
+/// * `Bits9` - 0.5 °C, 94 ms.
+/// * `Bits10` - 0.25 °C, 188 ms.
+/// * `Bits11` - 0.125 °C, 375 ms.
+/// * `Bits12` - 0.0625 °C, 750 ms, the setting at power on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ds18b20Resolution {
+    Bits9,
+    Bits10,
+    Bits11,
+    Bits12,
+}
+
+/// Errors found while talking to the sensors.
+/// * `NoDevice` - No device answered the reset pulse, check the wiring and the pull-up.
+/// * `Crc` - The scratchpad was received with a wrong CRC, the reading should be repeated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ds18b20Error {
+    NoDevice,
+    Crc,
+}
+
+impl Ds18b20Resolution {
+    /// Gives the value of the configuration register.
+    fn config(self) -> u8 {
+        match self {
+            Ds18b20Resolution::Bits9 => 0x1F,
+            Ds18b20Resolution::Bits10 => 0x3F,
+            Ds18b20Resolution::Bits11 => 0x5F,
+            Ds18b20Resolution::Bits12 => 0x7F,
+        }
+    }
+
+    /// Gives the longest conversion time in milliseconds.
+    fn conversion_time(self) -> u32 {
+        match self {
+            Ds18b20Resolution::Bits9 => 94,
+            Ds18b20Resolution::Bits10 => 188,
+            Ds18b20Resolution::Bits11 => 375,
+            Ds18b20Resolution::Bits12 => 750,
+        }
+    }
+}
+
+/// Controls the DS18B20 sensors on a 1-Wire bus.
+/// In the functions taking a ROM code, `None` addresses every sensor on the bus,
+/// which for a read only works when there is a single sensor.
+/// # Elements
+/// * `bus` - a `OneWire` object, the bus of the sensors.
+/// * `resolution` - a `Ds18b20Resolution` object, the resolution last set, which gives the time to wait for a conversion.
+pub struct Ds18b20 {
+    bus: OneWire,
+    resolution: Ds18b20Resolution,
+}
+
+impl Ds18b20 {
+    /// Creates the driver on a bus, the sensors are assumed to be at their power on resolution.
+    /// # Arguments
+    /// * `bus` - a `OneWire` object, the bus of the sensors.
+    /// # Returns
+    /// * `a Ds18b20 object` - which will be used to read the sensors.
+    pub fn new(bus: OneWire) -> Ds18b20 {
+        Ds18b20 {
+            bus,
+            resolution: Ds18b20Resolution::Bits12,
+        }
+    }
+
+    /// Finds the DS18B20 sensors on the bus with the ROM search, other devices are left out.
+    /// # Arguments
+    /// * `roms` - a mutable slice of u8 arrays, where the ROM codes are stored.
+    /// # Returns
+    /// * `a usize` - the number of sensors found, at most the length of `roms`.
+    pub fn find(&mut self, roms: &mut [[u8; 8]]) -> usize {
+        let mut count = 0;
+        self.bus.reset_search();
+        while count < roms.len() {
+            match self.bus.search() {
+                Some(rom) if rom[0] == DS18B20_FAMILY => {
+                    roms[count] = rom;
+                    count += 1;
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        self.bus.reset_search();
+        count
+    }
+
+    /// Sets the resolution, keeping the alarm thresholds, and stores it in the EEPROM of the sensor
+    /// so it stays after a power cycle.
+    /// # Arguments
+    /// * `rom` - an optional u8 array, the ROM code of the sensor.
+    /// * `resolution` - a `Ds18b20Resolution` object, the resolution.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor answered, otherwise the error found.
+    pub fn set_resolution(
+        &mut self,
+        rom: Option<&[u8; 8]>,
+        resolution: Ds18b20Resolution,
+    ) -> Result<(), Ds18b20Error> {
+        let scratchpad = self.read_scratchpad(rom)?;
+
+        // TH, TL and the configuration are always written together.
+        self.address(rom)?;
+        self.bus.write(&[
+            WRITE_SCRATCHPAD,
+            scratchpad[2],
+            scratchpad[3],
+            resolution.config(),
+        ]);
+        self.address(rom)?;
+        self.bus.write_byte(COPY_SCRATCHPAD);
+        // Writing the EEPROM takes up to 10 ms.
+        delay_ms(10);
+
+        self.resolution = resolution;
+        Ok(())
+    }
+
+    /// Starts a temperature conversion, on every sensor at once with `None`.
+    /// # Arguments
+    /// * `rom` - an optional u8 array, the ROM code of the sensor.
+    /// # Returns
+    /// * `a Result` - Ok if a sensor answered.
+    pub fn start_conversion(&mut self, rom: Option<&[u8; 8]>) -> Result<(), Ds18b20Error> {
+        self.address(rom)?;
+        self.bus.write_byte(CONVERT_T);
+        Ok(())
+    }
+
+    /// Checks if the conversions have ended, the sensors send zeros while they convert.
+    /// # Returns
+    /// * `a boolean` - true when every sensor has finished.
+    pub fn is_conversion_done(&mut self) -> bool {
+        self.bus.read_bit()
+    }
+
+    /// Waits until the conversions have ended, at most the conversion time of the resolution.
+    pub fn wait_conversion(&mut self) {
+        for _ in 0..self.resolution.conversion_time() {
+            if self.is_conversion_done() {
+                return;
+            }
+            delay_ms(1);
+        }
+    }
+
+    /// Reads the temperature of the last conversion.
+    /// # Arguments
+    /// * `rom` - an optional u8 array, the ROM code of the sensor.
+    /// # Returns
+    /// * `a Result` - the temperature in hundredths of degrees Celsius, or the error found.
+    pub fn read_temperature(&mut self, rom: Option<&[u8; 8]>) -> Result<i32, Ds18b20Error> {
+        let scratchpad = self.read_scratchpad(rom)?;
+        // Sixteenths of degrees, the unused low bits are undefined at lower resolutions.
+        let mask = match scratchpad[4] >> 5 {
+            0 => !0b111,
+            1 => !0b11,
+            2 => !0b1,
+            _ => !0,
+        };
+        let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]) & mask;
+        Ok(raw as i32 * 25 / 4)
+    }
+
+    /// Starts a conversion, waits for it and reads the temperature.
+    /// # Arguments
+    /// * `rom` - an optional u8 array, the ROM code of the sensor.
+    /// # Returns
+    /// * `a Result` - the temperature in hundredths of degrees Celsius, or the error found.
+    pub fn measure(&mut self, rom: Option<&[u8; 8]>) -> Result<i32, Ds18b20Error> {
+        self.start_conversion(rom)?;
+        self.wait_conversion();
+        self.read_temperature(rom)
+    }
+
+    /// Gives back the bus.
+    /// # Returns
+    /// * `a OneWire object` - the bus of the sensors.
+    pub fn release(self) -> OneWire {
+        self.bus
+    }
+
+    /// Resets the bus and selects one sensor, or every sensor.
+    fn address(&mut self, rom: Option<&[u8; 8]>) -> Result<(), Ds18b20Error> {
+        if !self.bus.reset() {
+            return Err(Ds18b20Error::NoDevice);
+        }
+        match rom {
+            Some(rom) => self.bus.select(rom),
+            None => self.bus.skip(),
+        }
+        Ok(())
+    }
+
+    /// Reads the 9 bytes of the scratchpad and checks their CRC.
+    fn read_scratchpad(&mut self, rom: Option<&[u8; 8]>) -> Result<[u8; 9], Ds18b20Error> {
+        self.address(rom)?;
+        self.bus.write_byte(READ_SCRATCHPAD);
+        let mut scratchpad = [0; 9];
+        self.bus.read(&mut scratchpad);
+        // A bus held low gives only zeros, whose CRC is also zero.
+        if crc8(&scratchpad) != 0 || scratchpad.iter().all(|byte| *byte == 0) {
+            return Err(Ds18b20Error::Crc);
+        }
+        Ok(scratchpad)
+    }
+}
+
+impl Thermometer for Ds18b20 {
+    type Error = Ds18b20Error;
+
+    fn read_temperature(&mut self) -> Result<f32, Ds18b20Error> {
+        // Only for a single sensor on the bus, which is addressed without its ROM code.
+        Ok(self.measure(None)? as f32 / 100.0)
+    }
+}
@@ -0,0 +1,434 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Read only FAT16 and FAT32 file systems on an SD card.
+//! The card may be formatted as a whole or with a partition table, in which case the
+//! first partition is used. Files are opened by their path of 8.3 short names, such as
+//! "/CONFIG/WIFI.TXT", and read from start to end. Long file names are not read,
+//! the short names given to them by the computer have to be used.
+//! Only one block of 512 bytes is kept in memory for the whole volume.
+//! `<https://www.win.tue.nl/~aeb/linux/fs/fat/fat-1.html>`
+
+use crate::sensors::{SdCard, SdError, SD_BLOCK_SIZE};
+
+// Attributes of a directory entry.
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0F;
+
+/// Size in bytes of one directory entry.
+const DIR_ENTRY_SIZE: usize = 32;
+
+/// Errors given by the FAT file system.
+/// * `Sd` - The card could not be read.
+/// * `NoFileSystem` - No FAT16 or FAT32 file system was found.
+/// * `NotFound` - A name of the path was not found, or is not a valid 8.3 name.
+/// * `IsDirectory` - The path names a directory, which cannot be read as a file.
+/// * `BadCluster` - The cluster chain of a file ends before the file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FatError {
+    Sd(SdError),
+    NoFileSystem,
+    NotFound,
+    IsDirectory,
+    BadCluster,
+}
+
+impl From<SdError> for FatError {
+    fn from(error: SdError) -> FatError {
+        FatError::Sd(error)
+    }
+}
+
+/// Kinds of FAT file systems.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FatType {
+    Fat16,
+    Fat32,
+}
+
+/// A file opened with `FatVolume::open()`, it keeps the position of reading.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FatFile {
+    first_cluster: u32,
+    cluster: u32,
+    cluster_index: u32,
+    size: u32,
+    position: u32,
+}
+
+impl FatFile {
+    /// Gives the size of the file.
+    /// # Returns
+    /// * `a u32` - the size in bytes.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Gives the position of the next byte to be read.
+    /// # Returns
+    /// * `a u32` - the position in bytes from the start of the file.
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// Checks if the whole file has been read.
+    /// # Returns
+    /// * `a boolean` - true at the end of the file.
+    pub fn is_end(&self) -> bool {
+        self.position >= self.size
+    }
+}
+
+/// A directory which is searched, the root directory of FAT16 has a fixed place
+/// while all other directories are cluster chains.
+#[derive(Clone, Copy)]
+enum Directory {
+    FixedRoot,
+    Cluster(u32),
+}
+
+/// A FAT file system on an SD card.
+pub struct FatVolume {
+    card: SdCard,
+    buffer: [u8; SD_BLOCK_SIZE],
+    cached: Option<u32>,
+    fat_type: FatType,
+    fat_start: u32,
+    root_start: u32,
+    root_blocks: u32,
+    root_cluster: u32,
+    data_start: u32,
+    blocks_per_cluster: u32,
+}
+
+/// Reads a little endian u16 from the bytes.
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+/// Reads a little endian u32 from the bytes.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// Makes the 11 byte directory form of a 8.3 name, such as "LOG.TXT" to "LOG     TXT".
+/// # Returns
+/// * `a Option<[u8; 11]>` - the name, None if it is not a valid short name.
+fn short_name(name: &str) -> Option<[u8; 11]> {
+    let bytes = name.as_bytes();
+    let (base, extension) = match bytes.iter().rposition(|&b| b == b'.') {
+        Some(dot) => (&bytes[..dot], &bytes[dot + 1..]),
+        None => (bytes, &bytes[..0]),
+    };
+    if base.is_empty() || base.len() > 8 || extension.len() > 3 {
+        return None;
+    }
+    let mut result = [b' '; 11];
+    for (place, &b) in result[..8].iter_mut().zip(base) {
+        *place = b.to_ascii_uppercase();
+    }
+    for (place, &b) in result[8..].iter_mut().zip(extension) {
+        *place = b.to_ascii_uppercase();
+    }
+    if result.iter().any(|&b| b < 0x20 || b == b'.' || b == b'/') {
+        return None;
+    }
+    Some(result)
+}
+
+impl FatVolume {
+    /// Finds the file system on a card set up with `SdCard::init()`.
+    /// # Arguments
+    /// * `card` - a `SdCard` object, the card.
+    /// # Returns
+    /// * `a Result<FatVolume, FatError>` - the volume, `FatError::NoFileSystem` if the card is not FAT16 or FAT32.
+    pub fn mount(card: SdCard) -> Result<FatVolume, FatError> {
+        let mut volume = FatVolume {
+            card,
+            buffer: [0; SD_BLOCK_SIZE],
+            cached: None,
+            fat_type: FatType::Fat16,
+            fat_start: 0,
+            root_start: 0,
+            root_blocks: 0,
+            root_cluster: 0,
+            data_start: 0,
+            blocks_per_cluster: 1,
+        };
+
+        volume.load(0)?;
+        if read_u16(&volume.buffer, 510) != 0xAA55 {
+            return Err(FatError::NoFileSystem);
+        }
+        // A boot sector starts with a jump, otherwise block 0 holds the partition table.
+        let start = match volume.buffer[0] {
+            0xEB | 0xE9 => 0,
+            _ => {
+                let partition_type = volume.buffer[450];
+                if partition_type == 0 {
+                    return Err(FatError::NoFileSystem);
+                }
+                let start = read_u32(&volume.buffer, 454);
+                volume.load(start)?;
+                if read_u16(&volume.buffer, 510) != 0xAA55 {
+                    return Err(FatError::NoFileSystem);
+                }
+                start
+            }
+        };
+
+        // BIOS parameter block.
+        let bpb = &volume.buffer;
+        let bytes_per_block = read_u16(bpb, 11) as usize;
+        let blocks_per_cluster = bpb[13] as u32;
+        let reserved = read_u16(bpb, 14) as u32;
+        let fats = bpb[16] as u32;
+        let root_entries = read_u16(bpb, 17) as u32;
+        let total = match read_u16(bpb, 19) {
+            0 => read_u32(bpb, 32),
+            total => total as u32,
+        };
+        let fat_size = match read_u16(bpb, 22) {
+            0 => read_u32(bpb, 36),
+            size => size as u32,
+        };
+        let root_cluster = read_u32(bpb, 44);
+        if bytes_per_block != SD_BLOCK_SIZE || blocks_per_cluster == 0 || fats == 0 {
+            return Err(FatError::NoFileSystem);
+        }
+
+        let root_blocks = (root_entries * DIR_ENTRY_SIZE as u32).div_ceil(SD_BLOCK_SIZE as u32);
+        let meta = reserved + fats * fat_size + root_blocks;
+        if total <= meta {
+            return Err(FatError::NoFileSystem);
+        }
+        // The kind of FAT is given only by the number of clusters.
+        let clusters = (total - meta) / blocks_per_cluster;
+        volume.fat_type = if clusters < 4085 {
+            return Err(FatError::NoFileSystem);
+        } else if clusters < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+        volume.fat_start = start + reserved;
+        volume.root_start = volume.fat_start + fats * fat_size;
+        volume.root_blocks = root_blocks;
+        volume.root_cluster = root_cluster;
+        volume.data_start = start + meta;
+        volume.blocks_per_cluster = blocks_per_cluster;
+        Ok(volume)
+    }
+
+    /// Gives the kind of the file system.
+    /// # Returns
+    /// * `a FatType` - FAT16 or FAT32.
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    /// Reads a block into the buffer, unless it is there already.
+    fn load(&mut self, block: u32) -> Result<(), FatError> {
+        if self.cached != Some(block) {
+            self.cached = None;
+            self.card.read_block(block, &mut self.buffer)?;
+            self.cached = Some(block);
+        }
+        Ok(())
+    }
+
+    /// Gives the first block of a cluster.
+    fn cluster_block(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - 2) * self.blocks_per_cluster
+    }
+
+    /// Gives the cluster after this one in the chain, None at the end of the chain.
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, FatError> {
+        let (offset, end) = match self.fat_type {
+            FatType::Fat16 => (cluster * 2, 0xFFF8),
+            FatType::Fat32 => (cluster * 4, 0x0FFF_FFF8),
+        };
+        self.load(self.fat_start + offset / SD_BLOCK_SIZE as u32)?;
+        let place = offset as usize % SD_BLOCK_SIZE;
+        let next = match self.fat_type {
+            FatType::Fat16 => read_u16(&self.buffer, place) as u32,
+            FatType::Fat32 => read_u32(&self.buffer, place) & 0x0FFF_FFFF,
+        };
+        if next < 2 || next >= end {
+            Ok(None)
+        } else {
+            Ok(Some(next))
+        }
+    }
+
+    /// Gives the root directory.
+    fn root(&self) -> Directory {
+        match self.fat_type {
+            FatType::Fat16 => Directory::FixedRoot,
+            FatType::Fat32 => Directory::Cluster(self.root_cluster),
+        }
+    }
+
+    /// Searches a directory for a short name.
+    /// # Returns
+    /// * `a Result<(u8, u32, u32), FatError>` - the attributes, first cluster and size of the entry.
+    fn find(&mut self, directory: Directory, name: &[u8; 11]) -> Result<(u8, u32, u32), FatError> {
+        let (mut block, mut left, mut cluster) = match directory {
+            Directory::FixedRoot => (self.root_start, self.root_blocks, 0),
+            Directory::Cluster(cluster) => (
+                self.cluster_block(cluster),
+                self.blocks_per_cluster,
+                cluster,
+            ),
+        };
+        loop {
+            if left == 0 {
+                // The fixed root ends after its blocks, a directory cluster is followed by the next.
+                let next = match directory {
+                    Directory::FixedRoot => None,
+                    Directory::Cluster(_) => self.next_cluster(cluster)?,
+                };
+                match next {
+                    Some(next) => {
+                        cluster = next;
+                        block = self.cluster_block(next);
+                        left = self.blocks_per_cluster;
+                    }
+                    None => return Err(FatError::NotFound),
+                }
+            }
+            self.load(block)?;
+            for entry in self.buffer.chunks(DIR_ENTRY_SIZE) {
+                match entry[0] {
+                    // No entries follow the first free one.
+                    0x00 => return Err(FatError::NotFound),
+                    // Deleted entry.
+                    0xE5 => continue,
+                    _ => (),
+                }
+                let attributes = entry[11];
+                if attributes & ATTR_LONG_NAME == ATTR_LONG_NAME || attributes & ATTR_VOLUME_ID != 0
+                {
+                    continue;
+                }
+                if &entry[..11] == name {
+                    let first_cluster =
+                        ((read_u16(entry, 20) as u32) << 16) | read_u16(entry, 26) as u32;
+                    return Ok((attributes, first_cluster, read_u32(entry, 28)));
+                }
+            }
+            block += 1;
+            left -= 1;
+        }
+    }
+
+    /// Opens a file for reading.
+    /// # Arguments
+    /// * `path` - a string object, the path of 8.3 names split by '/', such as "/DATA/LOG.TXT".
+    /// # Returns
+    /// * `a Result<FatFile, FatError>` - the file, `FatError::NotFound` if the path does not exist.
+    pub fn open(&mut self, path: &str) -> Result<FatFile, FatError> {
+        let mut directory = self.root();
+        let mut parts = path.split('/').filter(|part| !part.is_empty()).peekable();
+        while let Some(part) = parts.next() {
+            let name = short_name(part).ok_or(FatError::NotFound)?;
+            let (attributes, cluster, size) = self.find(directory, &name)?;
+            let is_directory = attributes & ATTR_DIRECTORY != 0;
+            if parts.peek().is_none() {
+                if is_directory {
+                    return Err(FatError::IsDirectory);
+                }
+                return Ok(FatFile {
+                    first_cluster: cluster,
+                    cluster,
+                    cluster_index: 0,
+                    size,
+                    position: 0,
+                });
+            }
+            if !is_directory {
+                return Err(FatError::NotFound);
+            }
+            // A cluster of 0 in ".." of a subdirectory stands for the root.
+            directory = match cluster {
+                0 => self.root(),
+                cluster => Directory::Cluster(cluster),
+            };
+        }
+        Err(FatError::IsDirectory)
+    }
+
+    /// Reads the file from its present position onwards.
+    /// # Arguments
+    /// * `file` - a `FatFile` object, the file opened with `open()`.
+    /// * `buffer` - a mutable u8 slice, where the bytes are stored.
+    /// # Returns
+    /// * `a Result<usize, FatError>` - the number of bytes stored, 0 at the end of the file.
+    pub fn read(&mut self, file: &mut FatFile, buffer: &mut [u8]) -> Result<usize, FatError> {
+        let cluster_size = self.blocks_per_cluster * SD_BLOCK_SIZE as u32;
+        let mut count: usize = 0;
+        while count < buffer.len() && file.position < file.size {
+            // Follows the chain up to the cluster which holds the position.
+            let index = file.position / cluster_size;
+            if index < file.cluster_index {
+                file.cluster = file.first_cluster;
+                file.cluster_index = 0;
+            }
+            while file.cluster_index < index {
+                file.cluster = self
+                    .next_cluster(file.cluster)?
+                    .ok_or(FatError::BadCluster)?;
+                file.cluster_index += 1;
+            }
+            if file.cluster < 2 {
+                return Err(FatError::BadCluster);
+            }
+
+            let in_cluster = file.position % cluster_size;
+            let block = self.cluster_block(file.cluster) + in_cluster / SD_BLOCK_SIZE as u32;
+            self.load(block)?;
+            let offset = in_cluster as usize % SD_BLOCK_SIZE;
+            let length = (SD_BLOCK_SIZE - offset)
+                .min(buffer.len() - count)
+                .min((file.size - file.position) as usize);
+            buffer[count..count + length].copy_from_slice(&self.buffer[offset..offset + length]);
+            count += length;
+            file.position += length as u32;
+        }
+        Ok(count)
+    }
+
+    /// Moves the position of reading of a file.
+    /// # Arguments
+    /// * `file` - a `FatFile` object, the file opened with `open()`.
+    /// * `position` - a u32, the new position in bytes, limited to the size of the file.
+    pub fn seek(&mut self, file: &mut FatFile, position: u32) {
+        file.position = position.min(file.size);
+    }
+
+    /// Gives back the card.
+    /// # Returns
+    /// * `a SdCard object` - the card.
+    pub fn unmount(self) -> SdCard {
+        self.card
+    }
+}
@@ -0,0 +1,252 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the HMC5883L three axis magnetometer connected through I2C, with the
+//! compass heading worked out from its readings, also when the sensor is tilted.
+//! The heading functions are shared with the QMC5883L, which replaced the HMC5883L on
+//! most GY-271 boards, and need the `math` feature.
+//! `<https://cdn-shop.adafruit.com/datasheets/HMC5883L_3-Axis_Digital_Compass_IC.pdf>`
+
+use super::traits::Magnetometer;
+use crate::com::i2c::Twi;
+#[cfg(feature = "math")]
+use crate::math::{atan2_deg, isqrt};
+
+/// Address of the HMC5883L, which can not be changed.
+pub const HMC5883L_ADDRESS: u8 = 0x1E;
+
+// Registers.
+const REG_CONFIG_A: u8 = 0x00;
+const REG_CONFIG_B: u8 = 0x01;
+const REG_MODE: u8 = 0x02;
+const REG_DATA_X_MSB: u8 = 0x03;
+const REG_ID_A: u8 = 0x0A;
+
+// The identification registers read "H43".
+const ID: [u8; 3] = *b"H43";
+// Value of an axis whose field is beyond the range of the gain.
+const OVERFLOW: i16 = -4096;
+
+/// Errors of the HMC5883L and QMC5883L.
+/// * `I2c` - The sensor did not answer, check the wiring.
+/// * `WrongDevice` - The identification registers do not belong to the sensor.
+/// * `Overflow` - The field is beyond the range, the gain should be lowered.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompassError {
+    I2c,
+    WrongDevice,
+    Overflow,
+}
+
+/// Gain of the HMC5883L, given by the largest field it can measure.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hmc5883lGain {
+    Gauss0_88,
+    Gauss1_3,
+    Gauss1_9,
+    Gauss2_5,
+    Gauss4_0,
+    Gauss4_7,
+    Gauss5_6,
+    Gauss8_1,
+}
+
+impl Hmc5883lGain {
+    /// Gives the value of the gain bits in configuration register B.
+    fn bits(self) -> u8 {
+        match self {
+            Hmc5883lGain::Gauss0_88 => 0,
+            Hmc5883lGain::Gauss1_3 => 1,
+            Hmc5883lGain::Gauss1_9 => 2,
+            Hmc5883lGain::Gauss2_5 => 3,
+            Hmc5883lGain::Gauss4_0 => 4,
+            Hmc5883lGain::Gauss4_7 => 5,
+            Hmc5883lGain::Gauss5_6 => 6,
+            Hmc5883lGain::Gauss8_1 => 7,
+        }
+    }
+
+    /// Gives the counts for one gauss.
+    fn counts_per_gauss(self) -> i32 {
+        match self {
+            Hmc5883lGain::Gauss0_88 => 1370,
+            Hmc5883lGain::Gauss1_3 => 1090,
+            Hmc5883lGain::Gauss1_9 => 820,
+            Hmc5883lGain::Gauss2_5 => 660,
+            Hmc5883lGain::Gauss4_0 => 440,
+            Hmc5883lGain::Gauss4_7 => 390,
+            Hmc5883lGain::Gauss5_6 => 330,
+            Hmc5883lGain::Gauss8_1 => 230,
+        }
+    }
+}
+
+/// Works out the heading of a level magnetometer, the angle from the magnetic north
+/// to the x axis turning clockwise seen from above, with the z axis up.
+/// # Arguments
+/// * `mag` - a i16 array, the field along the x, y and z axes in any unit.
+/// * `declination` - a i16, the local magnetic declination in tenths of degrees, east positive, to give the true north.
+/// # Returns
+/// * `a u16` - the heading in tenths of degrees, from 0 to 3599.
+#[cfg(feature = "math")]
+pub fn heading(mag: [i16; 3], declination: i16) -> u16 {
+    wrap_heading(atan2_deg(mag[1] as i32, mag[0] as i32) as i32 + declination as i32)
+}
+
+/// Works out the heading of a tilted magnetometer, using the gravity measured by an
+/// accelerometer on the same axes to find the horizontal plane. The east is the cross
+/// product of the field and the up direction, and the north the cross product of the
+/// up direction and the east, so no sine or cosine is needed.
+/// # Arguments
+/// * `mag` - a i16 array, the field along the x, y and z axes in any unit.
+/// * `accel` - a i16 array, the acceleration along the same axes in any unit, which points up at rest.
+/// * `declination` - a i16, the local magnetic declination in tenths of degrees, east positive, to give the true north.
+/// # Returns
+/// * `a u16` - the heading of the x axis in tenths of degrees, from 0 to 3599.
+#[cfg(feature = "math")]
+pub fn tilt_compensated_heading(mag: [i16; 3], accel: [i16; 3], declination: i16) -> u16 {
+    let [mx, my, mz] = [mag[0] as i64, mag[1] as i64, mag[2] as i64];
+    let [ux, uy, uz] = [accel[0] as i64, accel[1] as i64, accel[2] as i64];
+
+    let east = [my * uz - mz * uy, mz * ux - mx * uz, mx * uy - my * ux];
+    let north_x = uy * east[2] - uz * east[1];
+    // The north is longer than the east by the length of the up vector.
+    let up = isqrt((ux * ux + uy * uy + uz * uz) as u32) as i64;
+    let mut y = east[0] * up;
+    let mut x = north_x;
+    while y.abs() > i32::MAX as i64 || x.abs() > i32::MAX as i64 {
+        y >>= 1;
+        x >>= 1;
+    }
+    wrap_heading(atan2_deg(y as i32, x as i32) as i32 + declination as i32)
+}
+
+/// Brings an angle in tenths of degrees to 0 to 3599.
+#[cfg(feature = "math")]
+fn wrap_heading(angle: i32) -> u16 {
+    angle.rem_euclid(3600) as u16
+}
+
+/// Controls a HMC5883L sensor.
+/// # Elements
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `gain` - a `Hmc5883lGain` object, the gain last set.
+pub struct Hmc5883l {
+    i2c: &'static mut Twi,
+    gain: Hmc5883lGain,
+}
+
+impl Default for Hmc5883l {
+    fn default() -> Self {
+        Hmc5883l::new()
+    }
+}
+
+impl Hmc5883l {
+    /// Creates the struct to control the sensor on the I2C bus.
+    /// # Returns
+    /// * `a Hmc5883l object` - To control the sensor, after `begin()`.
+    pub fn new() -> Hmc5883l {
+        Hmc5883l {
+            i2c: unsafe { Twi::new() },
+            gain: Hmc5883lGain::Gauss1_3,
+        }
+    }
+
+    /// Checks the identification of the sensor and starts measuring continuously,
+    /// averaging 8 samples at 15 Hz, with the gain of 1.3 gauss.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor is ready, otherwise the error found.
+    pub fn begin(&mut self) -> Result<(), CompassError> {
+        let mut id = [0; 3];
+        self.read_registers(REG_ID_A, &mut id)?;
+        if id != ID {
+            return Err(CompassError::WrongDevice);
+        }
+        self.write_register(REG_CONFIG_A, 0x70)?;
+        self.set_gain(Hmc5883lGain::Gauss1_3)?;
+        self.set_continuous_mode(true)
+    }
+
+    /// Sets the gain, the next reading is still at the old gain.
+    /// # Arguments
+    /// * `gain` - a `Hmc5883lGain` object, the gain.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_gain(&mut self, gain: Hmc5883lGain) -> Result<(), CompassError> {
+        self.write_register(REG_CONFIG_B, gain.bits() << 5)?;
+        self.gain = gain;
+        Ok(())
+    }
+
+    /// Starts measuring continuously at the rate of configuration register A, or stops.
+    /// # Arguments
+    /// * `state` - a boolean, true for the continuous mode, false for the idle mode.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_continuous_mode(&mut self, state: bool) -> Result<(), CompassError> {
+        self.write_register(REG_MODE, if state { 0x00 } else { 0x03 })
+    }
+
+    /// Reads the latest measurement.
+    /// # Returns
+    /// * `a Result` - the raw field along the x, y and z axes, or the error found.
+    pub fn read(&mut self) -> Result<[i16; 3], CompassError> {
+        let mut data = [0; 6];
+        self.read_registers(REG_DATA_X_MSB, &mut data)?;
+        // The registers are in the order x, z, y.
+        let value = |i: usize| i16::from_be_bytes([data[2 * i], data[2 * i + 1]]);
+        let field = [value(0), value(2), value(1)];
+        if field.contains(&OVERFLOW) {
+            return Err(CompassError::Overflow);
+        }
+        Ok(field)
+    }
+
+    /// Reads the latest measurement, converted with the gain.
+    /// # Returns
+    /// * `a Result` - the field along the x, y and z axes in milligauss, or the error found.
+    pub fn read_milligauss(&mut self) -> Result<[i32; 3], CompassError> {
+        let field = self.read()?;
+        let counts = self.gain.counts_per_gauss();
+        Ok([
+            field[0] as i32 * 1000 / counts,
+            field[1] as i32 * 1000 / counts,
+            field[2] as i32 * 1000 / counts,
+        ])
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), CompassError> {
+        self.i2c
+            .write_read_bytes(HMC5883L_ADDRESS, &[reg], buffer)
+            .map_err(|_| CompassError::I2c)
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), CompassError> {
+        self.i2c
+            .write_bytes(HMC5883L_ADDRESS, &[reg, value])
+            .map_err(|_| CompassError::I2c)
+    }
+}
+
+impl Magnetometer for Hmc5883l {
+    type Error = CompassError;
+
+    fn read_mag(&mut self) -> Result<[f32; 3], CompassError> {
+        let field = self.read_milligauss()?;
+        Ok([field[0] as f32, field[1] as f32, field[2] as f32])
+    }
+}
@@ -0,0 +1,259 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the HX711 24 bit ADC for load cells, read through its own two wire
+//! protocol on any two pins. Each rising edge of SCK shifts out a bit of the conversion
+//! on DOUT, most significant first, and 1 to 3 extra pulses choose the channel and gain
+//! of the next conversion. SCK held high for more than 60 µs powers the chip down, so
+//! the bits are clocked with interrupts held off.
+//! `<https://cdn.sparkfun.com/datasheets/Sensors/ForceFlex/hx711_english.pdf>`
+
+use crate::delay::{delay_ms, delay_us};
+use crate::hal::gpio::{Input, Output, Pin};
+use crate::hal::interrupts;
+
+/// Milliseconds to wait for a conversion, which takes 100 ms at the 10 Hz rate.
+const TIMEOUT_MS: u16 = 500;
+
+/// Input channel and gain of the conversions.
+/// * `ChannelA128` - channel A with a gain of 128, full scale of ±20 mV at 5 V.
+/// * `ChannelA64` - channel A with a gain of 64, full scale of ±40 mV at 5 V.
+/// * `ChannelB32` - channel B with a gain of 32, full scale of ±80 mV at 5 V.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hx711Gain {
+    ChannelA128,
+    ChannelA64,
+    ChannelB32,
+}
+
+impl Hx711Gain {
+    /// Gives the number of pulses after the 24 data bits which selects the gain.
+    fn pulses(self) -> u8 {
+        match self {
+            Hx711Gain::ChannelA128 => 1,
+            Hx711Gain::ChannelB32 => 2,
+            Hx711Gain::ChannelA64 => 3,
+        }
+    }
+}
+
+/// Errors of the HX711.
+/// * `Timeout` - No conversion was ready in time, check the wiring and that the chip is powered up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hx711Error {
+    Timeout,
+}
+
+/// Controls a HX711 on two pins.
+/// # Elements
+/// * `dout` - a `Pin<Input>` object, the pin of the data output of the chip.
+/// * `sck` - a `Pin<Output>` object, the pin of the clock input of the chip.
+/// * `gain` - a `Hx711Gain` object, the channel and gain of the next conversion.
+/// * `offset` - a i32, the raw value with no load, set by `tare()`.
+/// * `scale` - a f32, the raw counts for one unit of load.
+pub struct Hx711 {
+    dout: Pin<Input>,
+    sck: Pin<Output>,
+    gain: Hx711Gain,
+    offset: i32,
+    scale: f32,
+}
+
+impl Hx711 {
+    /// Creates the driver, the chip is powered up with channel A and a gain of 128.
+    /// # Arguments
+    /// * `dout` - a `Pin<Input>` object, the pin of the data output of the chip.
+    /// * `sck` - a `Pin<Output>` object, the pin of the clock input of the chip.
+    /// # Returns
+    /// * `a Hx711 object` - which will be used to read the chip.
+    pub fn new(dout: Pin<Input>, mut sck: Pin<Output>) -> Hx711 {
+        sck.low();
+        Hx711 {
+            dout,
+            sck,
+            gain: Hx711Gain::ChannelA128,
+            offset: 0,
+            scale: 1.0,
+        }
+    }
+
+    /// Checks if a conversion can be read, DOUT is low when it is.
+    /// # Returns
+    /// * `a boolean` - true if a conversion is ready.
+    pub fn is_ready(&self) -> bool {
+        self.dout.is_low()
+    }
+
+    /// Chooses the channel and gain. A conversion is read to send the new setting,
+    /// which applies from the next conversion on.
+    /// # Arguments
+    /// * `gain` - a `Hx711Gain` object, the channel and gain.
+    /// # Returns
+    /// * `a Result` - Ok if the setting was sent, otherwise the error found.
+    pub fn set_gain(&mut self, gain: Hx711Gain) -> Result<(), Hx711Error> {
+        self.gain = gain;
+        self.read_raw().map(|_| ())
+    }
+
+    /// Waits for a conversion and reads it.
+    /// # Returns
+    /// * `a Result` - the signed 24 bit conversion, or the error found.
+    pub fn read_raw(&mut self) -> Result<i32, Hx711Error> {
+        let mut polls = 0;
+        while !self.is_ready() {
+            if polls == TIMEOUT_MS {
+                return Err(Hx711Error::Timeout);
+            }
+            polls += 1;
+            delay_ms(1);
+        }
+
+        let pulses = self.gain.pulses();
+        let value = interrupts::free(|_| {
+            let mut value: u32 = 0;
+            for _ in 0..24 {
+                self.pulse();
+                value <<= 1;
+                if self.dout.is_high() {
+                    value |= 1;
+                }
+            }
+            for _ in 0..pulses {
+                self.pulse();
+            }
+            value
+        });
+        // Sign extension of the two's complement value.
+        Ok(((value << 8) as i32) >> 8)
+    }
+
+    /// Reads several conversions and gives their mean.
+    /// # Arguments
+    /// * `times` - a u8, the number of conversions, at least 1.
+    /// # Returns
+    /// * `a Result` - the mean raw value, or the error found.
+    pub fn read_average(&mut self, times: u8) -> Result<i32, Hx711Error> {
+        if times == 0 {
+            unreachable!();
+        }
+        let mut sum: i64 = 0;
+        for _ in 0..times {
+            sum += self.read_raw()? as i64;
+        }
+        Ok((sum / times as i64) as i32)
+    }
+
+    /// Takes the mean of several conversions as the value with no load.
+    /// # Arguments
+    /// * `times` - a u8, the number of conversions, at least 1.
+    /// # Returns
+    /// * `a Result` - Ok if the offset was measured, otherwise the error found.
+    pub fn tare(&mut self, times: u8) -> Result<(), Hx711Error> {
+        self.offset = self.read_average(times)?;
+        Ok(())
+    }
+
+    /// Sets the value with no load, for instance one saved from an earlier `tare()`.
+    /// # Arguments
+    /// * `offset` - a i32, the raw value with no load.
+    pub fn set_offset(&mut self, offset: i32) {
+        self.offset = offset;
+    }
+
+    /// Gives the value with no load.
+    /// # Returns
+    /// * `a i32` - the raw value with no load.
+    pub fn offset(&self) -> i32 {
+        self.offset
+    }
+
+    /// Sets the calibration scale, for instance one saved from an earlier `calibrate()`.
+    /// # Arguments
+    /// * `scale` - a f32, the raw counts for one unit of load, not zero.
+    pub fn set_scale(&mut self, scale: f32) {
+        if scale == 0.0 {
+            unreachable!();
+        }
+        self.scale = scale;
+    }
+
+    /// Gives the calibration scale.
+    /// # Returns
+    /// * `a f32` - the raw counts for one unit of load.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Works out the calibration scale with a known load on the cell, after `tare()`
+    /// was done with no load. The unit of the load is the one of later readings.
+    /// # Arguments
+    /// * `load` - a f32, the known load, such as a weight in grams, not zero.
+    /// * `times` - a u8, the number of conversions, at least 1.
+    /// # Returns
+    /// * `a Result` - Ok if the scale was measured, otherwise the error found.
+    pub fn calibrate(&mut self, load: f32, times: u8) -> Result<(), Hx711Error> {
+        let value = self.read_value(times)?;
+        self.set_scale(value as f32 / load);
+        Ok(())
+    }
+
+    /// Reads the raw value with the offset taken out.
+    /// # Arguments
+    /// * `times` - a u8, the number of conversions averaged, at least 1.
+    /// # Returns
+    /// * `a Result` - the raw value above the offset, or the error found.
+    pub fn read_value(&mut self, times: u8) -> Result<i32, Hx711Error> {
+        Ok(self.read_average(times)? - self.offset)
+    }
+
+    /// Reads the load in the unit of the calibration.
+    /// # Arguments
+    /// * `times` - a u8, the number of conversions averaged, at least 1.
+    /// # Returns
+    /// * `a Result` - the load, or the error found.
+    pub fn read_units(&mut self, times: u8) -> Result<f32, Hx711Error> {
+        Ok(self.read_value(times)? as f32 / self.scale)
+    }
+
+    /// Powers the chip down by holding SCK high.
+    pub fn power_down(&mut self) {
+        self.sck.low();
+        self.sck.high();
+        delay_us(70);
+    }
+
+    /// Powers the chip up, it goes back to channel A with a gain of 128 and the
+    /// first conversion takes about 400 ms.
+    pub fn power_up(&mut self) {
+        self.sck.low();
+        self.gain = Hx711Gain::ChannelA128;
+    }
+
+    /// Gives back the pins.
+    /// # Returns
+    /// * `a tuple` - the `Pin<Input>` of DOUT and the `Pin<Output>` of SCK.
+    pub fn release(self) -> (Pin<Input>, Pin<Output>) {
+        (self.dout, self.sck)
+    }
+
+    /// Sends a clock pulse, DOUT changes on its rising edge.
+    fn pulse(&mut self) {
+        self.sck.high();
+        delay_us(1);
+        self.sck.low();
+        delay_us(1);
+    }
+}
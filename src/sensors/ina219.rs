@@ -0,0 +1,226 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the INA219 current and power monitor connected through I2C. It measures
+//! the voltage across a shunt resistor and the bus voltage, and once calibrated with the
+//! shunt resistance it also gives the current and power.
+//! `<https://www.ti.com/lit/ds/symlink/ina219.pdf>`
+
+use crate::com::i2c::Twi;
+
+/// Address of the INA219 with A0 and A1 to ground, up to 0x4F with the other wirings.
+pub const INA219_ADDRESS: u8 = 0x40;
+
+// Registers.
+const REG_CONFIG: u8 = 0x00;
+const REG_SHUNT_VOLTAGE: u8 = 0x01;
+const REG_BUS_VOLTAGE: u8 = 0x02;
+const REG_POWER: u8 = 0x03;
+const REG_CURRENT: u8 = 0x04;
+const REG_CALIBRATION: u8 = 0x05;
+
+// Bit of the configuration register which resets the chip.
+const RESET: u16 = 0x8000;
+// Both ADCs at 12 bits, measuring the shunt and bus voltages continuously.
+const ADC_CONTINUOUS: u16 = 0x019F;
+// Bit of the bus voltage register set when the current or power is beyond its range.
+const MATH_OVERFLOW: u16 = 0x0001;
+// The calibration is 0.04096 divided by the current LSB and the shunt resistance, with
+// the current LSB in µA and the resistance in mΩ.
+const CALIBRATION_FACTOR: u32 = 40_960_000;
+
+/// Errors of the INA219.
+/// * `I2c` - The chip did not answer, check the wiring.
+/// * `Overflow` - The current or power is beyond the range of the calibration.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ina219Error {
+    I2c,
+    Overflow,
+}
+
+/// Full scale of the bus voltage.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ina219BusRange {
+    V16,
+    V32,
+}
+
+/// Full scale of the shunt voltage, set by the gain of the programmable amplifier.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ina219ShuntRange {
+    Mv40,
+    Mv80,
+    Mv160,
+    Mv320,
+}
+
+/// Controls a INA219 chip.
+/// # Elements
+/// * `address` - a u8, the slave address of the chip.
+/// * `i2c` - a static mutable reference to the I2C controller the chip is on.
+/// * `current_lsb_ua` - a u32, the current of one count of the current register in µA, 0 before `calibrate()`.
+pub struct Ina219 {
+    pub address: u8,
+    i2c: &'static mut Twi,
+    current_lsb_ua: u32,
+}
+
+impl Default for Ina219 {
+    fn default() -> Self {
+        Ina219::new(INA219_ADDRESS)
+    }
+}
+
+impl Ina219 {
+    /// Creates the struct to control a chip on the I2C bus.
+    /// # Arguments
+    /// * `address` - a u8, the slave address, from `INA219_ADDRESS` (0x40) to 0x4F depending on A0 and A1.
+    /// # Returns
+    /// * `a Ina219 object` - To control the chip, after `begin()`.
+    pub fn new(address: u8) -> Ina219 {
+        match address {
+            0x40..=0x4F => Ina219 {
+                address,
+                i2c: unsafe { Twi::new() },
+                current_lsb_ua: 0,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// Resets the chip, which then measures continuously with the ranges of 32 V and 320 mV.
+    /// # Returns
+    /// * `a Result` - Ok if the chip is ready, otherwise the error found.
+    pub fn begin(&mut self) -> Result<(), Ina219Error> {
+        self.write_register(REG_CONFIG, RESET)?;
+        self.current_lsb_ua = 0;
+        Ok(())
+    }
+
+    /// Sets the full scale of the bus and shunt voltages, the smallest ones covering
+    /// the circuit give the best resolution.
+    /// # Arguments
+    /// * `bus` - a `Ina219BusRange` object, the full scale of the bus voltage.
+    /// * `shunt` - a `Ina219ShuntRange` object, the full scale of the shunt voltage.
+    /// # Returns
+    /// * `a Result` - Ok if the chip was written.
+    pub fn configure(
+        &mut self,
+        bus: Ina219BusRange,
+        shunt: Ina219ShuntRange,
+    ) -> Result<(), Ina219Error> {
+        let bus = match bus {
+            Ina219BusRange::V16 => 0,
+            Ina219BusRange::V32 => 1,
+        };
+        let gain = match shunt {
+            Ina219ShuntRange::Mv40 => 0,
+            Ina219ShuntRange::Mv80 => 1,
+            Ina219ShuntRange::Mv160 => 2,
+            Ina219ShuntRange::Mv320 => 3,
+        };
+        self.write_register(REG_CONFIG, bus << 13 | gain << 11 | ADC_CONTINUOUS)
+    }
+
+    /// Calibrates the current and power readings for the shunt resistor. The current
+    /// LSB is the largest expected current divided by 32768, rounded up to a whole µA.
+    /// # Arguments
+    /// * `shunt_milliohms` - a u32, the resistance of the shunt in mΩ, 100 on most modules.
+    /// * `max_current_ma` - a u32, the largest expected current in mA.
+    /// # Returns
+    /// * `a Result` - Ok if the chip was written.
+    pub fn calibrate(
+        &mut self,
+        shunt_milliohms: u32,
+        max_current_ma: u32,
+    ) -> Result<(), Ina219Error> {
+        if shunt_milliohms == 0 || max_current_ma == 0 {
+            unreachable!();
+        }
+        let current_lsb_ua = (max_current_ma * 1000 + 32767) / 32768;
+        // The lowest bit of the calibration register can not be set.
+        let calibration = (CALIBRATION_FACTOR / (current_lsb_ua * shunt_milliohms)).min(0xFFFE);
+        self.write_register(REG_CALIBRATION, calibration as u16 & !1)?;
+        self.current_lsb_ua = current_lsb_ua;
+        Ok(())
+    }
+
+    /// Reads the voltage across the shunt.
+    /// # Returns
+    /// * `a Result` - the shunt voltage in µV, or the error found.
+    pub fn read_shunt_voltage_uv(&mut self) -> Result<i32, Ina219Error> {
+        Ok(self.read_register(REG_SHUNT_VOLTAGE)? as i16 as i32 * 10)
+    }
+
+    /// Reads the voltage of the bus from its negative input to ground.
+    /// # Returns
+    /// * `a Result` - the bus voltage in mV, or the error found.
+    pub fn read_bus_voltage_mv(&mut self) -> Result<u16, Ina219Error> {
+        Ok((self.read_register(REG_BUS_VOLTAGE)? >> 3) * 4)
+    }
+
+    /// Reads the current through the shunt, zero before `calibrate()`.
+    /// # Returns
+    /// * `a Result` - the current in µA, or the error found.
+    pub fn read_current_ua(&mut self) -> Result<i32, Ina219Error> {
+        self.check_overflow()?;
+        let current = self.read_register(REG_CURRENT)? as i16 as i32;
+        Ok(current * self.current_lsb_ua as i32)
+    }
+
+    /// Reads the power given to the load, zero before `calibrate()`.
+    /// # Returns
+    /// * `a Result` - the power in µW, or the error found.
+    pub fn read_power_uw(&mut self) -> Result<u32, Ina219Error> {
+        self.check_overflow()?;
+        let power = self.read_register(REG_POWER)? as u32;
+        // The power LSB is 20 times the current LSB.
+        Ok(power * 20 * self.current_lsb_ua)
+    }
+
+    /// Stops the measurements to save power, or starts them again.
+    /// # Arguments
+    /// * `state` - a boolean, true to power down, false to measure continuously.
+    /// # Returns
+    /// * `a Result` - Ok if the chip was written.
+    pub fn set_power_down(&mut self, state: bool) -> Result<(), Ina219Error> {
+        let config = self.read_register(REG_CONFIG)? & !0x0007;
+        let mode = if state { 0 } else { ADC_CONTINUOUS & 0x0007 };
+        self.write_register(REG_CONFIG, config | mode)
+    }
+
+    fn check_overflow(&mut self) -> Result<(), Ina219Error> {
+        if self.read_register(REG_BUS_VOLTAGE)? & MATH_OVERFLOW != 0 {
+            return Err(Ina219Error::Overflow);
+        }
+        Ok(())
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u16, Ina219Error> {
+        let mut value = [0; 2];
+        self.i2c
+            .write_read_bytes(self.address, &[reg], &mut value)
+            .map_err(|_| Ina219Error::I2c)?;
+        Ok(u16::from_be_bytes(value))
+    }
+
+    fn write_register(&mut self, reg: u8, value: u16) -> Result<(), Ina219Error> {
+        let value = value.to_be_bytes();
+        self.i2c
+            .write_bytes(self.address, &[reg, value[0], value[1]])
+            .map_err(|_| Ina219Error::I2c)
+    }
+}
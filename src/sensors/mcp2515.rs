@@ -0,0 +1,522 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the MCP2515 CAN controller, connected through SPI.
+//! Standard and extended data and remote frames are sent through the three transmit
+//! buffers and received through the two receive buffers, where buffer 0 rolls over into
+//! buffer 1. The bit timing is calculated from the oscillator frequency of the module.
+//! `<https://ww1.microchip.com/downloads/en/DeviceDoc/MCP2515-Stand-Alone-CAN-Controller-with-SPI-20001801J.pdf>`
+
+use crate::com::spi::{Spi, SpiDevice};
+use crate::delay::delay_us;
+
+// SPI instructions.
+const INSTRUCTION_RESET: u8 = 0xC0;
+const INSTRUCTION_READ: u8 = 0x03;
+const INSTRUCTION_WRITE: u8 = 0x02;
+const INSTRUCTION_BIT_MODIFY: u8 = 0x05;
+const INSTRUCTION_LOAD_TX: u8 = 0x40;
+const INSTRUCTION_RTS: u8 = 0x80;
+const INSTRUCTION_READ_RX: u8 = 0x90;
+
+// Registers.
+const REG_CANSTAT: u8 = 0x0E;
+const REG_CANCTRL: u8 = 0x0F;
+const REG_CNF3: u8 = 0x28;
+const REG_CNF2: u8 = 0x29;
+const REG_CNF1: u8 = 0x2A;
+const REG_CANINTE: u8 = 0x2B;
+const REG_CANINTF: u8 = 0x2C;
+const REG_EFLG: u8 = 0x2D;
+const REG_RXB0CTRL: u8 = 0x60;
+const REG_RXB1CTRL: u8 = 0x70;
+
+// Bits of the registers.
+const CANINTF_RX0IF: u8 = 0x01;
+const CANINTF_RX1IF: u8 = 0x02;
+const TXBCTRL_TXREQ: u8 = 0x08;
+const RXBCTRL_BUKT: u8 = 0x04;
+const SIDL_EXIDE: u8 = 0x08;
+const SIDL_SRR: u8 = 0x10;
+const DLC_RTR: u8 = 0x40;
+const CNF2_BTLMODE: u8 = 0x80;
+
+/// Time in microseconds to wait for a change of the operation mode.
+const MODE_TIMEOUT_US: u32 = 10_000;
+
+/// Largest identifier of a standard frame.
+pub const CAN_MAX_STANDARD_ID: u32 = 0x7FF;
+
+/// Largest identifier of an extended frame.
+pub const CAN_MAX_EXTENDED_ID: u32 = 0x1FFF_FFFF;
+
+/// Errors given by the MCP2515 driver.
+/// * `BitRate` - The bit rate can not be made from the oscillator frequency.
+/// * `Timeout` - The controller did not change to the asked mode.
+/// * `TxBusy` - All three transmit buffers are still waiting to send.
+/// * `InvalidFrame` - The identifier or the length of the frame is out of range.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CanError {
+    BitRate,
+    Timeout,
+    TxBusy,
+    InvalidFrame,
+}
+
+/// Operation modes of the controller.
+/// * `Normal` - Takes part in the bus, frames are sent and acknowledged.
+/// * `Sleep` - Low power mode, wakes up on bus activity.
+/// * `Loopback` - Sent frames are received back without going to the bus.
+/// * `ListenOnly` - Receives every frame but never sends, not even acknowledgements.
+/// * `Configuration` - The bit timing, masks and filters can only be written in this mode.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CanMode {
+    Normal,
+    Sleep,
+    Loopback,
+    ListenOnly,
+    Configuration,
+}
+
+impl CanMode {
+    /// Gives the value of the REQOP and OPMOD bits of the mode.
+    fn bits(&self) -> u8 {
+        match self {
+            CanMode::Normal => 0x00,
+            CanMode::Sleep => 0x20,
+            CanMode::Loopback => 0x40,
+            CanMode::ListenOnly => 0x60,
+            CanMode::Configuration => 0x80,
+        }
+    }
+}
+
+/// Acceptance masks, mask 0 belongs to receive buffer 0 and filters 0 and 1,
+/// mask 1 to receive buffer 1 and filters 2 to 5.
+#[derive(Clone, Copy)]
+pub enum CanMask {
+    Mask0,
+    Mask1,
+}
+
+/// Acceptance filters.
+#[derive(Clone, Copy)]
+pub enum CanFilter {
+    Filter0,
+    Filter1,
+    Filter2,
+    Filter3,
+    Filter4,
+    Filter5,
+}
+
+/// One CAN frame.
+/// * `id` - the identifier, 11 bits for a standard frame and 29 bits for an extended one.
+/// * `extended` - true for an extended frame.
+/// * `remote` - true for a remote frame, which asks for data and carries none.
+/// * `dlc` - the number of data bytes, from 0 to 8.
+/// * `data` - the data bytes, only the first `dlc` are used.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CanFrame {
+    pub id: u32,
+    pub extended: bool,
+    pub remote: bool,
+    pub dlc: u8,
+    pub data: [u8; 8],
+}
+
+impl CanFrame {
+    /// Creates a data frame.
+    /// # Arguments
+    /// * `id` - a u32, the identifier of the frame.
+    /// * `extended` - a boolean, true for a 29 bit identifier.
+    /// * `data` - a u8 slice, up to 8 data bytes.
+    /// # Returns
+    /// * `a Result<CanFrame, CanError>` - the frame, `CanError::InvalidFrame` if it does not fit.
+    pub fn new(id: u32, extended: bool, data: &[u8]) -> Result<CanFrame, CanError> {
+        if data.len() > 8 || !id_fits(id, extended) {
+            return Err(CanError::InvalidFrame);
+        }
+        let mut frame = CanFrame {
+            id,
+            extended,
+            remote: false,
+            dlc: data.len() as u8,
+            data: [0; 8],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+        Ok(frame)
+    }
+
+    /// Creates a remote frame.
+    /// # Arguments
+    /// * `id` - a u32, the identifier of the frame.
+    /// * `extended` - a boolean, true for a 29 bit identifier.
+    /// * `dlc` - a u8, the number of data bytes asked for.
+    /// # Returns
+    /// * `a Result<CanFrame, CanError>` - the frame, `CanError::InvalidFrame` if it does not fit.
+    pub fn new_remote(id: u32, extended: bool, dlc: u8) -> Result<CanFrame, CanError> {
+        if dlc > 8 || !id_fits(id, extended) {
+            return Err(CanError::InvalidFrame);
+        }
+        Ok(CanFrame {
+            id,
+            extended,
+            remote: true,
+            dlc,
+            data: [0; 8],
+        })
+    }
+
+    /// Gives the data bytes of the frame.
+    /// # Returns
+    /// * `a u8 slice` - the first `dlc` data bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data[..self.dlc as usize]
+    }
+}
+
+/// Checks that the identifier fits into the frame format.
+fn id_fits(id: u32, extended: bool) -> bool {
+    if extended {
+        id <= CAN_MAX_EXTENDED_ID
+    } else {
+        id <= CAN_MAX_STANDARD_ID
+    }
+}
+
+/// Puts an identifier into the four SIDH, SIDL, EID8 and EID0 registers.
+fn encode_id(id: u32, extended: bool) -> [u8; 4] {
+    if extended {
+        let sid = id >> 18;
+        let eid = id & 0x3FFFF;
+        [
+            (sid >> 3) as u8,
+            ((sid & 0x07) << 5) as u8 | SIDL_EXIDE | ((eid >> 16) & 0x03) as u8,
+            (eid >> 8) as u8,
+            eid as u8,
+        ]
+    } else {
+        [(id >> 3) as u8, ((id & 0x07) << 5) as u8, 0, 0]
+    }
+}
+
+/// Controls a MCP2515 on its own chip select.
+pub struct Mcp2515 {
+    spi: SpiDevice,
+    oscillator_hz: u32,
+}
+
+impl Mcp2515 {
+    /// Creates the driver, nothing is sent to the controller yet.
+    /// The controller takes SPI mode 0 or 3 up to 10 MHz.
+    /// # Arguments
+    /// * `spi` - a `SpiDevice` object, the SPI bus with the chip select of the controller.
+    /// * `oscillator_hz` - a u32, the frequency of the crystal of the module, usually 8 or 16 MHz.
+    /// # Returns
+    /// * `a Mcp2515 object` - which will be used to control the controller.
+    pub fn new(spi: SpiDevice, oscillator_hz: u32) -> Mcp2515 {
+        Mcp2515 { spi, oscillator_hz }
+    }
+
+    /// Resets the controller, sets the bit rate, makes the receive buffers accept every
+    /// frame with rollover from buffer 0 to buffer 1 and goes to normal mode.
+    /// # Arguments
+    /// * `bitrate` - a u32, the bit rate of the bus in bits per second.
+    /// # Returns
+    /// * `a Result<(), CanError>` - an error if the bit rate or the mode could not be set.
+    pub fn init(&mut self, bitrate: u32) -> Result<(), CanError> {
+        self.reset();
+        self.set_mode(CanMode::Configuration)?;
+        self.set_bitrate(bitrate)?;
+
+        self.set_mask(CanMask::Mask0, 0, false)?;
+        self.set_mask(CanMask::Mask1, 0, false)?;
+        self.write_register(REG_RXB0CTRL, RXBCTRL_BUKT);
+        self.write_register(REG_RXB1CTRL, 0x00);
+        self.write_register(REG_CANINTE, 0x00);
+        self.write_register(REG_CANINTF, 0x00);
+
+        self.set_mode(CanMode::Normal)
+    }
+
+    /// Sends the reset instruction, the controller goes to configuration mode.
+    pub fn reset(&mut self) {
+        self.spi.write(&[INSTRUCTION_RESET]);
+        // The oscillator needs 128 cycles to start again.
+        delay_us(100);
+    }
+
+    /// Reads one register.
+    /// # Arguments
+    /// * `address` - a u8, the address of the register.
+    /// # Returns
+    /// * `a u8` - the value of the register.
+    pub fn read_register(&mut self, address: u8) -> u8 {
+        let mut value = [0u8; 1];
+        self.spi
+            .write_read(&[INSTRUCTION_READ, address], &mut value);
+        value[0]
+    }
+
+    /// Writes one register.
+    /// # Arguments
+    /// * `address` - a u8, the address of the register.
+    /// * `value` - a u8, the value to be written.
+    pub fn write_register(&mut self, address: u8, value: u8) {
+        self.write_registers(address, &[value]);
+    }
+
+    /// Writes registers one after the other starting at the address.
+    fn write_registers(&mut self, address: u8, values: &[u8]) {
+        self.spi.transaction(|spi: &mut Spi| {
+            spi.write(&[INSTRUCTION_WRITE, address]);
+            spi.write(values);
+        });
+    }
+
+    /// Changes only the bits of a register which are set in the mask.
+    /// # Arguments
+    /// * `address` - a u8, the address of the register.
+    /// * `mask` - a u8, the bits to be changed.
+    /// * `value` - a u8, the new value of the bits.
+    pub fn modify_register(&mut self, address: u8, mask: u8, value: u8) {
+        self.spi
+            .write(&[INSTRUCTION_BIT_MODIFY, address, mask, value]);
+    }
+
+    /// Changes the operation mode and waits for the controller to take it.
+    /// # Arguments
+    /// * `mode` - a `CanMode` object, the new mode.
+    /// # Returns
+    /// * `a Result<(), CanError>` - `CanError::Timeout` if the mode did not change.
+    pub fn set_mode(&mut self, mode: CanMode) -> Result<(), CanError> {
+        self.modify_register(REG_CANCTRL, 0xE0, mode.bits());
+        // The mode changes only after the frame on the bus is over.
+        let mut polls = MODE_TIMEOUT_US / 100;
+        while self.read_register(REG_CANSTAT) & 0xE0 != mode.bits() {
+            if polls == 0 {
+                return Err(CanError::Timeout);
+            }
+            polls -= 1;
+            delay_us(100);
+        }
+        Ok(())
+    }
+
+    /// Calculates and writes the bit timing, the controller has to be in configuration mode.
+    /// The bit is split into 8 to 25 time quanta with the sample point near 75%.
+    /// # Arguments
+    /// * `bitrate` - a u32, the bit rate of the bus in bits per second.
+    /// # Returns
+    /// * `a Result<(), CanError>` - `CanError::BitRate` if the oscillator can not make the bit rate.
+    pub fn set_bitrate(&mut self, bitrate: u32) -> Result<(), CanError> {
+        if bitrate == 0 {
+            return Err(CanError::BitRate);
+        }
+        // The time quantum is 2 * (BRP + 1) oscillator periods.
+        for brp in 0..64u32 {
+            let quantum = 2 * (brp + 1) * bitrate;
+            if self.oscillator_hz % quantum != 0 {
+                continue;
+            }
+            let quanta = self.oscillator_hz / quantum;
+            if quanta > 25 {
+                continue;
+            }
+            if quanta < 8 {
+                break;
+            }
+            // One quantum of synchronisation, then propagation and phase 1 up to the
+            // sample point, then phase 2.
+            let sample = (quanta * 3 + 2) / 4;
+            let phase2 = quanta - sample;
+            let phase1 = if (sample - 1) / 2 > 8 {
+                8
+            } else {
+                (sample - 1) / 2
+            };
+            let propagation = sample - 1 - phase1;
+            if propagation > 8 || phase2 > 8 {
+                continue;
+            }
+            // Synchronisation jump width of one quantum.
+            self.write_register(REG_CNF1, brp as u8);
+            self.write_register(
+                REG_CNF2,
+                CNF2_BTLMODE | (((phase1 - 1) as u8) << 3) | (propagation - 1) as u8,
+            );
+            self.write_register(REG_CNF3, (phase2 - 1) as u8);
+            return Ok(());
+        }
+        Err(CanError::BitRate)
+    }
+
+    /// Sets an acceptance mask, the controller has to be in configuration mode.
+    /// Only the identifier bits which are one in the mask are compared with the filters,
+    /// so a mask of zero accepts every frame.
+    /// # Arguments
+    /// * `mask` - a `CanMask` object, the mask to be set.
+    /// * `id` - a u32, the mask bits in the place of the identifier bits.
+    /// * `extended` - a boolean, true if the mask is for 29 bit identifiers.
+    /// # Returns
+    /// * `a Result<(), CanError>` - `CanError::InvalidFrame` if the mask does not fit.
+    pub fn set_mask(&mut self, mask: CanMask, id: u32, extended: bool) -> Result<(), CanError> {
+        if !id_fits(id, extended) {
+            return Err(CanError::InvalidFrame);
+        }
+        let address = match mask {
+            CanMask::Mask0 => 0x20,
+            CanMask::Mask1 => 0x24,
+        };
+        let mut registers = encode_id(id, extended);
+        // Masks do not have the EXIDE bit.
+        registers[1] &= !SIDL_EXIDE;
+        self.write_registers(address, &registers);
+        Ok(())
+    }
+
+    /// Sets an acceptance filter, the controller has to be in configuration mode.
+    /// # Arguments
+    /// * `filter` - a `CanFilter` object, the filter to be set.
+    /// * `id` - a u32, the identifier to be accepted.
+    /// * `extended` - a boolean, true if the filter accepts only extended frames.
+    /// # Returns
+    /// * `a Result<(), CanError>` - `CanError::InvalidFrame` if the identifier does not fit.
+    pub fn set_filter(
+        &mut self,
+        filter: CanFilter,
+        id: u32,
+        extended: bool,
+    ) -> Result<(), CanError> {
+        if !id_fits(id, extended) {
+            return Err(CanError::InvalidFrame);
+        }
+        let address = match filter {
+            CanFilter::Filter0 => 0x00,
+            CanFilter::Filter1 => 0x04,
+            CanFilter::Filter2 => 0x08,
+            CanFilter::Filter3 => 0x10,
+            CanFilter::Filter4 => 0x14,
+            CanFilter::Filter5 => 0x18,
+        };
+        self.write_registers(address, &encode_id(id, extended));
+        Ok(())
+    }
+
+    /// Loads a frame into a free transmit buffer and asks for it to be sent.
+    /// # Arguments
+    /// * `frame` - a `CanFrame` object, the frame to be sent.
+    /// # Returns
+    /// * `a Result<(), CanError>` - `CanError::TxBusy` if no transmit buffer is free.
+    pub fn send(&mut self, frame: &CanFrame) -> Result<(), CanError> {
+        if frame.dlc > 8 || !id_fits(frame.id, frame.extended) {
+            return Err(CanError::InvalidFrame);
+        }
+        for buffer in 0..3u8 {
+            let control = 0x30 + 0x10 * buffer;
+            if self.read_register(control) & TXBCTRL_TXREQ != 0 {
+                continue;
+            }
+
+            let id = encode_id(frame.id, frame.extended);
+            let dlc = if frame.remote {
+                frame.dlc | DLC_RTR
+            } else {
+                frame.dlc
+            };
+            let length = if frame.remote { 0 } else { frame.dlc as usize };
+            // LOAD TX BUFFER starts at TXBnSIDH of buffer 0, 1 or 2.
+            self.spi.transaction(|spi: &mut Spi| {
+                spi.transfer_byte(INSTRUCTION_LOAD_TX | (buffer << 1));
+                spi.write(&id);
+                spi.transfer_byte(dlc);
+                spi.write(&frame.data[..length]);
+            });
+            self.spi.write(&[INSTRUCTION_RTS | (1 << buffer)]);
+            return Ok(());
+        }
+        Err(CanError::TxBusy)
+    }
+
+    /// Takes a received frame out of the receive buffers, buffer 0 first.
+    /// # Returns
+    /// * `a Option<CanFrame>` - the frame, None if no frame was received.
+    pub fn receive(&mut self) -> Option<CanFrame> {
+        let flags = self.read_register(REG_CANINTF);
+        let buffer: u8 = if flags & CANINTF_RX0IF != 0 {
+            0
+        } else if flags & CANINTF_RX1IF != 0 {
+            1
+        } else {
+            return None;
+        };
+
+        // READ RX BUFFER starts at RXBnSIDH and clears the flag of the buffer when done.
+        let mut registers = [0u8; 13];
+        self.spi
+            .write_read(&[INSTRUCTION_READ_RX | (buffer << 2)], &mut registers);
+
+        let sidh = registers[0] as u32;
+        let sidl = registers[1];
+        let extended = sidl & SIDL_EXIDE != 0;
+        let (id, remote) = if extended {
+            let id = (sidh << 21)
+                | (((sidl >> 5) as u32) << 18)
+                | (((sidl & 0x03) as u32) << 16)
+                | ((registers[2] as u32) << 8)
+                | registers[3] as u32;
+            (id, registers[4] & DLC_RTR != 0)
+        } else {
+            ((sidh << 3) | (sidl >> 5) as u32, sidl & SIDL_SRR != 0)
+        };
+        let mut dlc = registers[4] & 0x0F;
+        if dlc > 8 {
+            dlc = 8;
+        }
+        let mut frame = CanFrame {
+            id,
+            extended,
+            remote,
+            dlc,
+            data: [0; 8],
+        };
+        frame.data.copy_from_slice(&registers[5..13]);
+        Some(frame)
+    }
+
+    /// Checks if a received frame is waiting in a receive buffer.
+    /// # Returns
+    /// * `a boolean` - true if `receive()` will give a frame.
+    pub fn available(&mut self) -> bool {
+        self.read_register(REG_CANINTF) & (CANINTF_RX0IF | CANINTF_RX1IF) != 0
+    }
+
+    /// Reads the error flags, which tell about error counters past their warning
+    /// limits, the bus off state and receive buffer overflows.
+    /// # Returns
+    /// * `a u8` - the value of the EFLG register.
+    pub fn error_flags(&mut self) -> u8 {
+        self.read_register(REG_EFLG)
+    }
+
+    /// Gives back the SPI device of the controller.
+    /// # Returns
+    /// * `a SpiDevice object` - the SPI device.
+    pub fn release(self) -> SpiDevice {
+        self.spi
+    }
+}
@@ -15,11 +15,53 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
 mod aht10;
+mod analog_level;
+mod analog_temperature;
+mod apds9960;
+mod bme280;
+mod bmp180;
+mod bmp280;
+mod dht;
 mod display;
+mod ds18b20;
+mod fat;
+mod hmc5883l;
+mod hx711;
+mod ina219;
+mod mcp2515;
 mod mpu6050;
+mod mpu9250;
+mod mq;
+mod qmc5883l;
+mod sd_card;
 mod servo;
+mod sx127x;
+mod traits;
+mod vl53l0x;
+mod w5500;
 
 pub use aht10::*;
+pub use analog_level::*;
+pub use analog_temperature::*;
+pub use apds9960::*;
+pub use bme280::*;
+pub use bmp180::*;
+pub use bmp280::*;
+pub use dht::*;
 pub use display::*;
+pub use ds18b20::*;
+pub use fat::*;
+pub use hmc5883l::*;
+pub use hx711::*;
+pub use ina219::*;
+pub use mcp2515::*;
 pub use mpu6050::*;
+pub use mpu9250::*;
+pub use mq::*;
+pub use qmc5883l::*;
+pub use sd_card::*;
 pub use servo::*;
+pub use sx127x::*;
+pub use traits::*;
+pub use vl53l0x::*;
+pub use w5500::*;
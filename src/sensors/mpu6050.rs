@@ -18,11 +18,17 @@
 //! which might be attached or in-built to the current
 //! AVR Micro-controller.
 
+use super::traits::{Accelerometer, Gyroscope, Thermometer};
+use crate::hal::ext_interrupt::{attach_interrupt, detach_interrupt, IntLine, Trigger};
 use crate::{com::i2c, delay::delay_ms};
 use bit_field::BitField;
+use core::mem::MaybeUninit;
 use fixed_slice_vec::FixedSliceVec;
 
-const MPU6050_ADDRESS: u8 = 0x68; // 0x69 when AD0 pin to Vcc
+/// Address of the MPU6050 with the AD0 pin to ground.
+pub const MPU6050_ADDRESS: u8 = 0x68;
+/// Address of the MPU6050 with the AD0 pin to Vcc.
+pub const MPU6050_ADDRESS_AD0_HIGH: u8 = 0x69;
 const _MPU6050_REG_ACCEL_XOFFS_H: u8 = 0x06; //defining registers for accelerometer X,Y & Z axis for high(H) and low(L).
 const _MPU6050_REG_ACCEL_XOFFS_L: u8 = 0x07;
 const _MPU6050_REG_ACCEL_YOFFS_H: u8 = 0x08;
@@ -57,35 +63,35 @@ const MPU6050_REG_ZMOT_THRESHOLD: u8 = 0x21;
 const MPU6050_REG_ZMOT_DURATION: u8 = 0x22;
 
 // This register determines which sensor measurements are loaded into the FIFO buffer.
-const _MPU6050_REG_FIFO_EN: u8 = 0x23;
+const MPU6050_REG_FIFO_EN: u8 = 0x23;
 
 // This register configures the auxiliary I2C bus for single-master or multi-master control.
-const _MPU6050_REG_I2C_MST_CTRL: u8 = 0x24;
+const MPU6050_REG_I2C_MST_CTRL: u8 = 0x24;
 // Used to specify the I2C slave address of Slave 0
-const _MPU6050_REG_I2C_SLV0_ADDR: u8 = 0x25;
-const _MPU6050_REG_I2C_SLV0_REG: u8 = 0x26;
-const _MPU6050_REG_I2C_SLV0_CTRL: u8 = 0x27;
+const MPU6050_REG_I2C_SLV0_ADDR: u8 = 0x25;
+const MPU6050_REG_I2C_SLV0_REG: u8 = 0x26;
+const MPU6050_REG_I2C_SLV0_CTRL: u8 = 0x27;
 // Used to specify the I2C slave address of Slave 1.
-const _MPU6050_REG_I2C_SLV1_ADDR: u8 = 0x28;
-const _MPU6050_REG_I2C_SLV1_REG: u8 = 0x29;
-const _MPU6050_REG_I2C_SLV1_CTRL: u8 = 0x2A;
+const MPU6050_REG_I2C_SLV1_ADDR: u8 = 0x28;
+const MPU6050_REG_I2C_SLV1_REG: u8 = 0x29;
+const MPU6050_REG_I2C_SLV1_CTRL: u8 = 0x2A;
 // Used to specify the I2C slave address of Slave 2.
 const _MPU6050_REG_I2C_SLV2_ADDR: u8 = 0x2B;
 const _MPU6050_REG_I2C_SLV2_REG: u8 = 0x2C;
-const _MPU6050_REG_I2C_SLV2_CTRL: u8 = 0x2D;
+const MPU6050_REG_I2C_SLV2_CTRL: u8 = 0x2D;
 // Used to specify the I2C slave address of Slave 3.
 const _MPU6050_REG_I2C_SLV3_ADDR: u8 = 0x2E;
 // Slave3 configuration registers
 const _MPU6050_REG_I2C_SLV3_REG: u8 = 0x2F;
 const _MPU6050_REG_I2C_SLV3_CTRL: u8 = 0x30;
 // Used to specify the I2C slave address of Slave 4.
-const _MPU6050_REG_I2C_SLV4_ADDR: u8 = 0x31;
+const MPU6050_REG_I2C_SLV4_ADDR: u8 = 0x31;
 // Slave4 configuration registers
-const _MPU6050_REG_I2C_SLV4_REG: u8 = 0x32;
-const _MPU6050_REG_I2C_SLV4_DO: u8 = 0x33;
-const _MPU6050_REG_I2C_SLV4_CTRL: u8 = 0x34;
+const MPU6050_REG_I2C_SLV4_REG: u8 = 0x32;
+const MPU6050_REG_I2C_SLV4_DO: u8 = 0x33;
+const MPU6050_REG_I2C_SLV4_CTRL: u8 = 0x34;
 const _MPU6050_REG_I2C_SLV4_DI: u8 = 0x35;
-const _MPU6050_REG_I2C_MST_STATUS: u8 = 0x36; //Indicates master control status
+const MPU6050_REG_I2C_MST_STATUS: u8 = 0x36; //Indicates master control status
 
 // This register configures the behavior of the interrupt signals at the INT pins. This register is also used to enable the FSYNC Pin to be used as an interrupt to the host application processor, as well as to enable Bypass Mode on the I2C Master. This bit also enables the clock output
 // Used in functions :`set_i2c_byepass_enabled()`, `get_i2c_bypass_enabled()`
@@ -106,8 +112,9 @@ const _MPU6050_REG_ACCEL_YOUT_H: u8 = 0x3D; // Accel YOUT High
 const _MPU6050_REG_ACCEL_YOUT_L: u8 = 0x3E; // Accel YOUT Low
 const _MPU6050_REG_ACCEL_ZOUT_H: u8 = 0x3F; // Accel ZOUT High
 const _MPU6050_REG_ACCEL_ZOUT_L: u8 = 0x40; // Accel ZOUT Low
-const _MPU6050_REG_TEMP_OUT_H: u8 = 0x41;
-const _MPU6050_REG_TEMP_OUT_L: u8 = 0x42;
+                                            // These registers store the most recent temperature measurement.
+const MPU6050_REG_TEMP_OUT_H: u8 = 0x41;
+const MPU6050_REG_TEMP_OUT_L: u8 = 0x42;
 // These registers store the most recent gyroscope measurements.
 const MPU6050_REG_GYRO_XOUT_H: u8 = 0x43; //Registers for output of X,Y & Z axis.
 const _MPU6050_REG_GYRO_XOUT_L: u8 = 0x44;
@@ -115,7 +122,7 @@ const _MPU6050_REG_GYRO_YOUT_H: u8 = 0x45;
 const _MPU6050_REG_GYRO_YOUT_L: u8 = 0x46;
 const _MPU6050_REG_GYRO_ZOUT_H: u8 = 0x47;
 const _MPU6050_REG_GYRO_ZOUT_L: u8 = 0x48;
-const _MPU6050_REG_EXT_SENS_DATA_00: u8 = 0x49; //These registers store data read from external sensors by the Slave 0, 1, 2, and 3 on the auxiliary I2C interface.
+const MPU6050_REG_EXT_SENS_DATA_00: u8 = 0x49; //These registers store data read from external sensors by the Slave 0, 1, 2, and 3 on the auxiliary I2C interface.
 const _MPU6050_REG_EXT_SENS_DATA_01: u8 = 0x4A;
 const _MPU6050_REG_EXT_SENS_DATA_02: u8 = 0x4B;
 const _MPU6050_REG_EXT_SENS_DATA_03: u8 = 0x4C;
@@ -141,19 +148,56 @@ const _MPU6050_REG_EXT_SENS_DATA_22: u8 = 0x5F;
 const _MPU6050_REG_EXT_SENS_DATA_23: u8 = 0x60;
 const _MPU6050_REG_MOT_DETECT_STATUS: u8 = 0x61;
 const _MPU6050_REG_I2C_SLV0_DO: u8 = 0x63;
-const _MPU6050_REG_I2C_SLV1_DO: u8 = 0x64;
+const MPU6050_REG_I2C_SLV1_DO: u8 = 0x64;
 const _MPU6050_REG_I2C_SLV2_DO: u8 = 0x65;
 const _MPU6050_REG_I2C_SLV3_DO: u8 = 0x66;
-const _MPU6050_REG_I2C_MST_DELAY_CTRL: u8 = 0x67;
+const MPU6050_REG_I2C_MST_DELAY_CTRL: u8 = 0x67;
 const _MPU6050_REG_SIGNAL_PATH_RESET: u8 = 0x68;
 const MPU6050_REG_MOT_DETECT_CTRL: u8 = 0x69;
 const MPU6050_REG_USER_CTRL: u8 = 0x6A; // User Control
 const MPU6050_REG_PWR_MGMT_1: u8 = 0x6B; // Power Management 1
 const _MPU6050_REG_PWR_MGMT_2: u8 = 0x6C;
-const _MPU6050_REG_FIFO_COUNTH: u8 = 0x72;
-const _MPU6050_REG_FIFO_COUNTL: u8 = 0x73;
-const _MPU6050_REG_FIFO_R_W: u8 = 0x74;
-const _MPU6050_REG_WHO_AM_I: u8 = 0x75; // Who Am I
+const MPU6050_REG_FIFO_COUNTH: u8 = 0x72;
+const MPU6050_REG_FIFO_COUNTL: u8 = 0x73;
+const MPU6050_REG_FIFO_R_W: u8 = 0x74;
+const MPU6050_REG_WHO_AM_I: u8 = 0x75; // Who Am I
+
+// Value of WHO_AM_I, the upper six bits of the address without AD0.
+const MPU6050_WHO_AM_I: u8 = 0x68;
+
+// Bytes of one sample in the FIFO, the accelerometer and gyroscope axes.
+const MPU6050_FIFO_SAMPLE_SIZE: usize = 12;
+
+/// Errors of the MPU6050 and MPU9250.
+/// * `I2c` - The sensor did not acknowledge its address, check the wiring and the AD0 pin.
+/// * `WrongDevice` - WHO_AM_I did not give the value of the sensor, with the value read.
+/// * `MagnetometerOverflow` - The field on the magnetometer of the MPU9250 is beyond its range.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mpu6050Error {
+    I2c,
+    WrongDevice(u8),
+    MagnetometerOverflow,
+}
+
+/// All measurements of the sensor taken at the same sample, given by `read_all()`.
+/// * `accel` - the acceleration along the x, y and z axes in g.
+/// * `temperature` - the temperature of the die in degrees Celsius.
+/// * `gyro` - the rotation rate around the x, y and z axes in degrees per second.
+#[derive(Clone, Copy)]
+pub struct MPUMeasurements {
+    pub accel: [f32; 3],
+    pub temperature: f32,
+    pub gyro: [f32; 3],
+}
+
+/// Magnetometers which can be read through the auxiliary I2C bus of the MPU6050.
+/// * `Hmc5883l` - the HMC5883L compass at address 0x1E, in continuous mode at 75 Hz.
+/// * `Ak8975` - the AK8975 compass at address 0x0C, started for a single measurement at every read.
+#[derive(Clone, Copy)]
+pub enum MPUMagnetometer {
+    Hmc5883l,
+    Ak8975,
+}
 
 /// Selection of Source of the clock.
 #[derive(Clone, Copy)]
@@ -219,38 +263,75 @@ pub enum MPUdlpfT {
 
 /// Controls the MPU6050 Gyroscopic Sensor.
 /// # Elements
-/// * `address` - a u8, used to store the address to control the functioning AHT10 sensor.
-/// * `accel_output` - a vector with u8 objects, It would be used to store the two byte accelerometer data read through the sensors.
-/// * `gyro_output` - a vector with u8 objects, It would be used to store the two byte gyroscopic data read through the sensors.
-#[repr(C, packed)]
-pub struct MPU6050<'a> {
+/// * `address` - a u8, the I2C slave address of the sensor, set by its AD0 pin.
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `g_per_digit` - a f32, the acceleration in g of one count of the accelerometer, set by `set_range()`.
+/// * `dps_per_digit` - a f32, the rotation in degrees per second of one count of the gyroscope, set by `set_scale()`.
+pub struct MPU6050 {
     pub address: u8,
-    pub accel_output: FixedSliceVec<'a, f32>,
-    pub gyro_output: FixedSliceVec<'a, f32>,
+    i2c: &'static mut i2c::Twi,
+    g_per_digit: f32,
+    dps_per_digit: f32,
 }
 
-impl<'a> MPU6050<'a> {
-    /// Creates a mutable refernce to the struct to be used in the implementations.
+impl Default for MPU6050 {
+    /// Creates the struct for a sensor with the AD0 pin to ground, the usual breakout board wiring.
+    fn default() -> Self {
+        MPU6050::new(MPU6050_ADDRESS)
+    }
+}
+
+impl MPU6050 {
+    /// Creates the struct to control a sensor on the I2C bus.
+    /// # Arguments
+    /// * `address` - a u8, the slave address, `MPU6050_ADDRESS` (0x68) when AD0 is to ground and `MPU6050_ADDRESS_AD0_HIGH` (0x69) when AD0 is to Vcc.
     /// # Returns
     /// * `a MPU6050 object` - To control the sensor through I2C data protocol.
-    pub fn new() -> &'static mut Self {
-        unsafe { &mut *(0x00 as *mut Self) }
+    pub fn new(address: u8) -> Self {
+        match address {
+            MPU6050_ADDRESS | MPU6050_ADDRESS_AD0_HIGH => MPU6050 {
+                address,
+                i2c: unsafe { i2c::Twi::new() },
+                // The ranges the sensor starts with, 2 g and 250 degrees per second.
+                g_per_digit: 1.0 / 16384.0,
+                dps_per_digit: 1.0 / 131.0,
+            },
+            _ => unreachable!(),
+        }
     }
 
-    fn readregister(&mut self, reg: u8) -> u8 {
-        let mut vec1: FixedSliceVec<u8> = FixedSliceVec::new(&mut []);
+    /// Reads consecutive registers, by writing the first register address and then reading.
+    /// Gives false if the sensor did not acknowledge the register address.
+    fn readregisters(&mut self, reg: u8, buffer: &mut [u8]) -> bool {
+        let mut pointer = [MaybeUninit::<u8>::uninit(); 1];
+        let mut vec1: FixedSliceVec<u8> = FixedSliceVec::new(&mut pointer);
         vec1.push(reg);
-        let i2c = i2c::Twi::new();
-        i2c.read_from_slave(MPU6050_ADDRESS, 1, &mut vec1);
-        return vec1[1];
+        if !self.i2c.write_to_slave(self.address, &vec1) {
+            return false;
+        }
+
+        let mut storage = [MaybeUninit::<u8>::uninit(); 14];
+        let length = buffer.len().min(storage.len());
+        let mut vec2: FixedSliceVec<u8> = FixedSliceVec::new(&mut storage[..length]);
+        self.i2c.read_from_slave(self.address, length, &mut vec2);
+        for (byte, value) in buffer.iter_mut().zip(vec2.iter()) {
+            *byte = *value;
+        }
+        true
+    }
+
+    fn readregister(&mut self, reg: u8) -> u8 {
+        let mut value = [0];
+        self.readregisters(reg, &mut value);
+        value[0]
     }
 
     fn writeregister(&mut self, reg: u8, value: u8) {
-        let mut vec2: FixedSliceVec<u8> = FixedSliceVec::new(&mut []);
+        let mut storage = [MaybeUninit::<u8>::uninit(); 2];
+        let mut vec2: FixedSliceVec<u8> = FixedSliceVec::new(&mut storage);
         vec2.push(reg);
         vec2.push(value);
-        let i2c = i2c::Twi::new();
-        i2c.write_to_slave(MPU6050_ADDRESS, &vec2);
+        self.i2c.write_to_slave(self.address, &vec2);
     }
 
     fn writeregister_bit(&mut self, reg: u8, pos: u8, state: bool) {
@@ -309,6 +390,12 @@ impl<'a> MPU6050<'a> {
             MPUdpsT::MPU6050Scale250DPS => 0,
         } << 3);
         self.writeregister(MPU6050_REG_GYRO_CONFIG, value);
+        self.dps_per_digit = match scale {
+            MPUdpsT::MPU6050Scale2000DPS => 1.0 / 16.4,
+            MPUdpsT::MPU6050Scale1000DPS => 1.0 / 32.8,
+            MPUdpsT::MPU6050Scale500DPS => 1.0 / 65.5,
+            MPUdpsT::MPU6050Scale250DPS => 1.0 / 131.0,
+        };
     }
 
     /// Get the scale in DPS on which MPU6050 is currently set.
@@ -340,6 +427,12 @@ impl<'a> MPU6050<'a> {
             MPURangeT::MPU6050Range16G => 3,
         } << 3);
         self.writeregister(MPU6050_REG_ACCEL_CONFIG, value);
+        self.g_per_digit = match range {
+            MPURangeT::MPU6050Range2G => 1.0 / 16384.0,
+            MPURangeT::MPU6050Range4G => 1.0 / 8192.0,
+            MPURangeT::MPU6050Range8G => 1.0 / 4096.0,
+            MPURangeT::MPU6050Range16G => 1.0 / 2048.0,
+        };
     }
 
     /// Get the bandwidth range of MPU6050 currently set.
@@ -512,6 +605,63 @@ impl<'a> MPU6050<'a> {
         self.writeregister_bit(MPU6050_REG_INT_ENABLE, 6, state);
     }
 
+    /// Enables or disables the data ready interrupt, raised when a new set of measurements is written.
+    /// # Arguments
+    /// * `state` - a boolean, true to enable the interrupt.
+    pub fn set_int_data_ready_enabled(&mut self, state: bool) {
+        self.writeregister_bit(MPU6050_REG_INT_ENABLE, 0, state);
+    }
+
+    /// Checks if the data ready interrupt is enabled.
+    /// # Returns
+    /// * `a boolean` - true if the interrupt is enabled.
+    pub fn get_int_data_ready_enabled(&mut self) -> bool {
+        let value = self.readregister(MPU6050_REG_INT_ENABLE);
+        value.get_bit(0)
+    }
+
+    /// Calls the function whenever a new set of measurements is ready, so the sensor can be
+    /// read when it has data instead of after a fixed delay.
+    /// The INT pin of the sensor has to be wired to the pin of the external interrupt line.
+    /// The function runs in the interrupt handler, so it should only note that data is ready
+    /// for the main loop, which then reads it.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line wired to the INT pin.
+    /// * `callback` - a function, which is called at every new measurement.
+    pub fn on_data_ready(&mut self, line: IntLine, callback: fn()) {
+        self.configure_int_pin();
+        self.set_int_data_ready_enabled(true);
+        attach_interrupt(line, Trigger::Rising, callback);
+    }
+
+    /// Calls the function whenever motion is detected, as set with
+    /// `set_motion_detection_threshold()` and `set_motion_detection_duration()`.
+    /// Only one function is called for each line, so a function given before with
+    /// `on_data_ready()` is replaced, although both interrupts stay enabled.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line wired to the INT pin.
+    /// * `callback` - a function, which is called when motion is detected.
+    pub fn on_motion(&mut self, line: IntLine, callback: fn()) {
+        self.configure_int_pin();
+        self.set_int_motion_enabled(true);
+        attach_interrupt(line, Trigger::Rising, callback);
+    }
+
+    /// Disables all the interrupts of the sensor and detaches the function from the line.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line wired to the INT pin.
+    pub fn detach_interrupts(&mut self, line: IntLine) {
+        self.writeregister(MPU6050_REG_INT_ENABLE, 0);
+        detach_interrupt(line);
+    }
+
+    /// Sets the INT pin to give an active high, push-pull pulse of 50 us for every interrupt.
+    fn configure_int_pin(&mut self) {
+        self.writeregister_bit(MPU6050_REG_INT_PIN_CFG, 7, false);
+        self.writeregister_bit(MPU6050_REG_INT_PIN_CFG, 6, false);
+        self.writeregister_bit(MPU6050_REG_INT_PIN_CFG, 5, false);
+    }
+
     pub fn set_i2c_master_mode_enabled(&mut self, state: bool) {
         self.writeregister_bit(MPU6050_REG_USER_CTRL, 5, state);
     }
@@ -535,44 +685,286 @@ impl<'a> MPU6050<'a> {
     }
 
     /// Reads the three, two-byte accelerometer values from the sensor.
-    /// Returns the two-byte raw accelerometer values as a 32-bit float.
-    /// The vec accel_output stores the raw values of the accelerometer where `accel_output[0]` is the x-axis, `accel_output[1]` is the y-axis and `accel_output[2]` is the z-axis output respectively. These raw values are then converted to g's per second according to the scale given as input in `begin()` function.
-    pub fn read_accel(&mut self) {
-        let mut v: FixedSliceVec<u8> = FixedSliceVec::new(&mut []);
-        v.push(MPU6050_REG_ACCEL_XOUT_H);
-        let i2c = i2c::Twi::new();
-        i2c.read_from_slave(MPU6050_ADDRESS, 6, &mut v); //input from slave
-        self.accel_output
-            .push((((v[1] as u16) << 8) | (v[2] as u16)) as f32); //input of X axis
-        self.accel_output
-            .push((((v[3] as u16) << 8) | (v[4] as u16)) as f32); //input of Y axis
-        self.accel_output
-            .push((((v[5] as u16) << 8) | (v[6] as u16)) as f32); //input of Z axis
+    /// # Returns
+    /// * `a i16 array` - the raw counts of the x, y and z axes.
+    pub fn read_accel_raw(&mut self) -> [i16; 3] {
+        let mut v = [0; 6];
+        self.readregisters(MPU6050_REG_ACCEL_XOUT_H, &mut v); //input from slave
+        let mut output = [0; 3];
+        for (i, axis) in output.iter_mut().enumerate() {
+            *axis = i16::from_be_bytes([v[2 * i], v[2 * i + 1]]); //input of X, Y and Z axis
+        }
+        output
+    }
+
+    /// Reads the acceleration, converted to g according to the range given to `set_range()` or `begin()`.
+    /// # Returns
+    /// * `a f32 array` - the acceleration along the x, y and z axes in g.
+    pub fn read_accel(&mut self) -> [f32; 3] {
+        let raw = self.read_accel_raw();
+        let mut output = [0.0; 3];
+        for (axis, value) in output.iter_mut().zip(raw.iter()) {
+            *axis = *value as f32 * self.g_per_digit;
+        }
+        output
     }
 
     /// Reads the three, two-byte gyroscope values from the sensor.
-    /// Returns the two-byte raw gyroscope values as a 32-bit float.
-    /// The vec gyro_output stores the raw values of the gyroscope where `gyro_output[0]` is the x-axis, `gyro_output[1]` is the y-axis and `gyro_output[2]` is the z-axis output respectively. These raw values are then converted to degrees per second according to the scale given as input in `begin()` function.
-    pub fn read_gyro(&mut self) {
-        let mut v: FixedSliceVec<u8> = FixedSliceVec::new(&mut []);
-        v.push(MPU6050_REG_GYRO_XOUT_H);
-        let i2c = i2c::Twi::new();
-
-        i2c.read_from_slave(MPU6050_ADDRESS, 6, &mut v); //input from slave
-        self.gyro_output
-            .push((((v[1] as u16) << 8) | (v[2] as u16)) as f32); //input of X axis
-        self.gyro_output
-            .push((((v[3] as u16) << 8) | (v[4] as u16)) as f32); //input of Y axis
-        self.gyro_output
-            .push((((v[5] as u16) << 8) | (v[6] as u16)) as f32); //input of Z axis
-    }
-
-    /// Starts the sensor by setting the device to active mode ,setting the accelerometer range and gyroscope scale.
     /// # Returns
-    /// * `a boolean value` - true if started successfully otherwise false
-    pub fn begin(&mut self, scale: MPUdpsT, range: MPURangeT) -> bool {
+    /// * `a i16 array` - the raw counts of the x, y and z axes.
+    pub fn read_gyro_raw(&mut self) -> [i16; 3] {
+        let mut v = [0; 6];
+        self.readregisters(MPU6050_REG_GYRO_XOUT_H, &mut v); //input from slave
+        let mut output = [0; 3];
+        for (i, axis) in output.iter_mut().enumerate() {
+            *axis = i16::from_be_bytes([v[2 * i], v[2 * i + 1]]); //input of X, Y and Z axis
+        }
+        output
+    }
+
+    /// Reads the rotation rate, converted to degrees per second according to the scale given to `set_scale()` or `begin()`.
+    /// # Returns
+    /// * `a f32 array` - the rotation rate around the x, y and z axes in degrees per second.
+    pub fn read_gyro(&mut self) -> [f32; 3] {
+        let raw = self.read_gyro_raw();
+        let mut output = [0.0; 3];
+        for (axis, value) in output.iter_mut().zip(raw.iter()) {
+            *axis = *value as f32 * self.dps_per_digit;
+        }
+        output
+    }
+
+    /// Reads the temperature measured on the die of the sensor, using the formula of the register map.
+    /// # Returns
+    /// * `a f32` - the temperature in degrees Celsius.
+    pub fn read_temperature(&mut self) -> f32 {
+        self.read_temperature_raw() as f32 / 340.0 + 36.53
+    }
+
+    /// Reads the two-byte temperature value from the sensor.
+    /// # Returns
+    /// * `a i16` - the raw count of the temperature.
+    pub fn read_temperature_raw(&mut self) -> i16 {
+        let high = self.readregister(MPU6050_REG_TEMP_OUT_H);
+        let low = self.readregister(MPU6050_REG_TEMP_OUT_L);
+        i16::from_be_bytes([high, low])
+    }
+
+    /// Reads the accelerometer, temperature and gyroscope in one transfer of the 14 registers from
+    /// ACCEL_XOUT_H to GYRO_ZOUT_L. This takes half the bus time of separate reads, and all values
+    /// come from the same sample, as the sensor does not update the registers during a burst read.
+    /// # Returns
+    /// * `a MPUMeasurements object` - the acceleration in g, the temperature in degrees Celsius and the rotation rate in degrees per second.
+    pub fn read_all(&mut self) -> MPUMeasurements {
+        let mut v = [0; 14];
+        self.readregisters(MPU6050_REG_ACCEL_XOUT_H, &mut v); //input from slave
+        let value = |i: usize| i16::from_be_bytes([v[2 * i], v[2 * i + 1]]) as f32;
+
+        let mut measurements = MPUMeasurements {
+            accel: [0.0; 3],
+            temperature: value(3) / 340.0 + 36.53,
+            gyro: [0.0; 3],
+        };
+        for i in 0..3 {
+            measurements.accel[i] = value(i) * self.g_per_digit;
+            measurements.gyro[i] = value(i + 4) * self.dps_per_digit;
+        }
+        measurements
+    }
+
+    /// Enables or disables loading the accelerometer and gyroscope measurements into the FIFO.
+    /// Each sample takes 12 bytes, the accelerometer x, y and z followed by the gyroscope
+    /// x, y and z, so the 1024 byte FIFO holds 85 samples.
+    /// # Arguments
+    /// * `state` - a boolean, true to fill the FIFO at the sample rate.
+    pub fn set_fifo_enabled(&mut self, state: bool) {
+        // XG, YG, ZG and ACCEL FIFO enable bits.
+        let sources = if state { 0b0111_1000 } else { 0 };
+        self.writeregister(MPU6050_REG_FIFO_EN, sources);
+        self.writeregister_bit(MPU6050_REG_USER_CTRL, 6, state);
+    }
+
+    /// Checks if the FIFO is enabled.
+    /// # Returns
+    /// * `a boolean` - true if the FIFO is enabled.
+    pub fn get_fifo_enabled(&mut self) -> bool {
+        let value = self.readregister(MPU6050_REG_USER_CTRL);
+        value.get_bit(6)
+    }
+
+    /// Empties the FIFO. Needed after an overflow, as the samples are no longer aligned.
+    pub fn reset_fifo(&mut self) {
+        self.writeregister_bit(MPU6050_REG_USER_CTRL, 2, true);
+    }
+
+    /// Gives the number of bytes waiting in the FIFO.
+    /// # Returns
+    /// * `a u16` - the number of bytes, up to 1024.
+    pub fn get_fifo_count(&mut self) -> u16 {
+        let high = self.readregister(MPU6050_REG_FIFO_COUNTH);
+        let low = self.readregister(MPU6050_REG_FIFO_COUNTL);
+        u16::from_be_bytes([high, low])
+    }
+
+    /// Checks if the FIFO has overflowed, which clears the flag.
+    /// # Returns
+    /// * `a boolean` - true if samples were lost, the FIFO should then be reset.
+    pub fn get_fifo_overflow(&mut self) -> bool {
+        let value = self.readregister(MPU6050_REG_INT_STATUS);
+        value.get_bit(4)
+    }
+
+    /// Reads bytes from the FIFO.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, where the bytes are stored.
+    /// # Returns
+    /// * `a usize` - the number of bytes read, limited by the bytes waiting in the FIFO.
+    pub fn read_fifo(&mut self, buffer: &mut [u8]) -> usize {
+        let count = (self.get_fifo_count() as usize).min(buffer.len());
+        for byte in buffer[..count].iter_mut() {
+            *byte = self.readregister(MPU6050_REG_FIFO_R_W);
+        }
+        count
+    }
+
+    /// Takes the complete samples out of the FIFO, for reading the gyroscope at a high rate
+    /// from a slow main loop. The FIFO has to be enabled with `set_fifo_enabled()`.
+    /// # Arguments
+    /// * `samples` - a mutable slice of i16 arrays, where the raw accelerometer x, y, z and gyroscope x, y, z of each sample are stored.
+    /// # Returns
+    /// * `a usize` - the number of samples stored.
+    pub fn drain_fifo(&mut self, samples: &mut [[i16; 6]]) -> usize {
+        let waiting = self.get_fifo_count() as usize / MPU6050_FIFO_SAMPLE_SIZE;
+        let count = waiting.min(samples.len());
+        for sample in samples[..count].iter_mut() {
+            let mut bytes = [0; MPU6050_FIFO_SAMPLE_SIZE];
+            for byte in bytes.iter_mut() {
+                *byte = self.readregister(MPU6050_REG_FIFO_R_W);
+            }
+            for (i, value) in sample.iter_mut().enumerate() {
+                *value = i16::from_be_bytes([bytes[2 * i], bytes[2 * i + 1]]);
+            }
+        }
+        count
+    }
+
+    /// Starts reading a magnetometer attached to the auxiliary I2C bus (XDA and XCL pins),
+    /// for a 9 axis sensor. The MPU6050 becomes the master of that bus and copies the
+    /// readings of the magnetometer into its own registers at about 100 Hz, so they are
+    /// read with `read_mag()` without the magnetometer being on the main bus.
+    /// # Arguments
+    /// * `magnetometer` - a `MPUMagnetometer` object, the chip attached to the auxiliary bus.
+    /// # Returns
+    /// * `a boolean` - true if the magnetometer answered while it was set up.
+    pub fn begin_mag(&mut self, magnetometer: MPUMagnetometer) -> bool {
+        self.set_i2c_byepass_enabled(false);
+        // 400 kHz on the auxiliary bus.
+        self.writeregister(MPU6050_REG_I2C_MST_CTRL, 0x0D);
+        self.set_i2c_master_mode_enabled(true);
+
+        // Slaves 0 to 2 place the x, y and z axes in EXT_SENS_DATA_00 to 05, high byte first.
+        match magnetometer {
+            MPUMagnetometer::Hmc5883l => {
+                const ADDRESS: u8 = 0x1E;
+                // 75 Hz output rate, then continuous measurement mode.
+                if !self.aux_write(ADDRESS, 0x00, 0x18) || !self.aux_write(ADDRESS, 0x02, 0x00) {
+                    return false;
+                }
+                // The HMC5883L gives the axes in the order x, z, y.
+                for (slave, reg) in [(0, 0x03), (1, 0x07), (2, 0x05)].iter() {
+                    let base = MPU6050_REG_I2C_SLV0_ADDR + 3 * slave;
+                    self.writeregister(base, 0x80 | ADDRESS);
+                    self.writeregister(base + 1, *reg);
+                    self.writeregister(base + 2, 0x80 | 2);
+                }
+            }
+            MPUMagnetometer::Ak8975 => {
+                const ADDRESS: u8 = 0x0C;
+                if !self.aux_write(ADDRESS, 0x0A, 0x00) {
+                    return false;
+                }
+                // Read the six data bytes, swapping the bytes of each axis as they come low byte first.
+                self.writeregister(MPU6050_REG_I2C_SLV0_ADDR, 0x80 | ADDRESS);
+                self.writeregister(MPU6050_REG_I2C_SLV0_REG, 0x03);
+                self.writeregister(MPU6050_REG_I2C_SLV0_CTRL, 0x80 | 0x40 | 0x10 | 6);
+                // Start the next single measurement after each read.
+                self.writeregister(MPU6050_REG_I2C_SLV1_ADDR, ADDRESS);
+                self.writeregister(MPU6050_REG_I2C_SLV1_REG, 0x0A);
+                self.writeregister(MPU6050_REG_I2C_SLV1_DO, 0x01);
+                self.writeregister(MPU6050_REG_I2C_SLV1_CTRL, 0x80 | 1);
+                self.writeregister(MPU6050_REG_I2C_SLV2_CTRL, 0);
+            }
+        }
+
+        // Access the slaves on every tenth sample only.
+        self.writeregister(MPU6050_REG_I2C_MST_DELAY_CTRL, 0b0000_0111);
+        self.writeregister(MPU6050_REG_I2C_SLV4_CTRL, 9);
+        true
+    }
+
+    /// Reads the magnetometer started with `begin_mag()`.
+    /// # Returns
+    /// * `a i16 array` - the raw field along the x, y and z axes of the magnetometer, which may not line up with the axes of the MPU6050.
+    pub fn read_mag(&mut self) -> [i16; 3] {
+        let mut axes = [0; 3];
+        for (i, axis) in axes.iter_mut().enumerate() {
+            let high = self.readregister(MPU6050_REG_EXT_SENS_DATA_00 + 2 * i as u8);
+            let low = self.readregister(MPU6050_REG_EXT_SENS_DATA_00 + 2 * i as u8 + 1);
+            *axis = i16::from_be_bytes([high, low]);
+        }
+        axes
+    }
+
+    /// Writes a register of a device on the auxiliary I2C bus through slave 4.
+    /// # Returns
+    /// * `a boolean` - true if the device acknowledged the write.
+    fn aux_write(&mut self, address: u8, reg: u8, value: u8) -> bool {
+        self.writeregister(MPU6050_REG_I2C_SLV4_ADDR, address);
+        self.writeregister(MPU6050_REG_I2C_SLV4_REG, reg);
+        self.writeregister(MPU6050_REG_I2C_SLV4_DO, value);
+        self.writeregister(MPU6050_REG_I2C_SLV4_CTRL, 0x80);
+        for _ in 0..100 {
+            let status = self.readregister(MPU6050_REG_I2C_MST_STATUS);
+            // SLV4_DONE, and SLV4_NACK if the device did not answer.
+            if status.get_bit(6) {
+                return !status.get_bit(4);
+            }
+            delay_ms(1);
+        }
+        false
+    }
+
+    /// Reads the WHO_AM_I register, which identifies the sensor.
+    /// # Returns
+    /// * `a Result` - the value of WHO_AM_I, 0x68 for a MPU6050 whatever the AD0 pin and 0x71 for a MPU9250, or `Mpu6050Error::I2c` if the sensor did not answer.
+    pub fn who_am_i(&mut self) -> Result<u8, Mpu6050Error> {
+        let mut value = [0];
+        if !self.readregisters(MPU6050_REG_WHO_AM_I, &mut value) {
+            return Err(Mpu6050Error::I2c);
+        }
+        Ok(value[0])
+    }
+
+    /// Starts the sensor by checking that it answers as a MPU6050, then setting the device to active mode,
+    /// setting the accelerometer range and gyroscope scale.
+    /// # Returns
+    /// * `a Result` - Ok if started successfully, otherwise the error found, so that a wiring problem is not read as data.
+    pub fn begin(&mut self, scale: MPUdpsT, range: MPURangeT) -> Result<(), Mpu6050Error> {
         delay_ms(5);
 
+        // Bits 0 and 7 of WHO_AM_I are reserved.
+        let identity = self.who_am_i()?;
+        if identity & 0x7E != MPU6050_WHO_AM_I {
+            return Err(Mpu6050Error::WrongDevice(identity));
+        }
+
+        self.configure(scale, range);
+        Ok(())
+    }
+
+    /// Sets the clock source, range and scale and wakes the sensor up.
+    /// Shared with the MPU9250, which has the same registers.
+    pub(crate) fn configure(&mut self, scale: MPUdpsT, range: MPURangeT) {
         //Set clock source.
         self.set_clock_source(MPUClockSourceT::MPU6050ClockPllGyrox);
 
@@ -582,7 +974,29 @@ impl<'a> MPU6050<'a> {
 
         //disable sleep mode.
         self.set_sleep_enabled(false);
+    }
+}
+
+impl Accelerometer for MPU6050 {
+    type Error = Mpu6050Error;
+
+    fn read_accel(&mut self) -> Result<[f32; 3], Mpu6050Error> {
+        Ok(MPU6050::read_accel(self))
+    }
+}
+
+impl Gyroscope for MPU6050 {
+    type Error = Mpu6050Error;
+
+    fn read_gyro(&mut self) -> Result<[f32; 3], Mpu6050Error> {
+        Ok(MPU6050::read_gyro(self))
+    }
+}
+
+impl Thermometer for MPU6050 {
+    type Error = Mpu6050Error;
 
-        return true;
+    fn read_temperature(&mut self) -> Result<f32, Mpu6050Error> {
+        Ok(MPU6050::read_temperature(self))
     }
 }
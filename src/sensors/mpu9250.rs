@@ -0,0 +1,213 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the MPU9250 nine axis motion sensor connected through I2C. The accelerometer
+//! and gyroscope have the registers of the MPU6050, whose driver is used for them, and the
+//! AK8963 magnetometer inside is reached directly on the bus through the I2C bypass.
+//! `<https://invensense.tdk.com/products/motion-tracking/9-axis/mpu-9250/>`
+
+use super::mpu6050::{MPURangeT, MPUdpsT, Mpu6050Error, MPU6050, MPU6050_ADDRESS};
+use super::traits::{Accelerometer, Gyroscope, Magnetometer, Thermometer};
+use crate::com::i2c::Twi;
+use crate::delay::delay_ms;
+
+/// Address of the AK8963 magnetometer inside the MPU9250.
+pub const AK8963_ADDRESS: u8 = 0x0C;
+
+// Values of WHO_AM_I of the MPU9250 and of the MPU9255.
+const MPU9250_WHO_AM_I: u8 = 0x71;
+const MPU9255_WHO_AM_I: u8 = 0x73;
+
+// Registers of the AK8963.
+const AK8963_REG_WIA: u8 = 0x00;
+const AK8963_REG_HXL: u8 = 0x03;
+const AK8963_REG_CNTL1: u8 = 0x0A;
+const AK8963_REG_ASAX: u8 = 0x10;
+
+const AK8963_WIA: u8 = 0x48;
+// Modes of CNTL1, with bit 4 for 16 bit output.
+const AK8963_POWER_DOWN: u8 = 0x00;
+const AK8963_FUSE_ROM: u8 = 0x0F;
+const AK8963_CONTINUOUS_100HZ: u8 = 0x16;
+// Milligauss of one count in 16 bit output.
+const AK8963_MILLIGAUSS_PER_COUNT: f32 = 1.5;
+
+/// Controls a MPU9250 sensor.
+/// # Elements
+/// * `mpu` - a `MPU6050` object, which controls the accelerometer and gyroscope.
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `mag_adjust` - an array of f32, the sensitivity adjustment of each magnetometer axis read by `begin()`.
+pub struct Mpu9250 {
+    mpu: MPU6050,
+    i2c: &'static mut Twi,
+    mag_adjust: [f32; 3],
+}
+
+impl Default for Mpu9250 {
+    /// Creates the struct for a sensor with the AD0 pin to ground.
+    fn default() -> Self {
+        Mpu9250::new(MPU6050_ADDRESS)
+    }
+}
+
+impl Mpu9250 {
+    /// Creates the struct to control a sensor on the I2C bus.
+    /// # Arguments
+    /// * `address` - a u8, the slave address, 0x68 when AD0 is to ground and 0x69 when AD0 is to Vcc.
+    /// # Returns
+    /// * `a Mpu9250 object` - To control the sensor, after `begin()`.
+    pub fn new(address: u8) -> Mpu9250 {
+        Mpu9250 {
+            mpu: MPU6050::new(address),
+            i2c: unsafe { Twi::new() },
+            mag_adjust: [1.0; 3],
+        }
+    }
+
+    /// Gives the driver of the accelerometer and gyroscope, for the settings shared with the
+    /// MPU6050 such as the filters, interrupts and FIFO.
+    /// # Returns
+    /// * `a mutable reference to a MPU6050 object`.
+    pub fn mpu(&mut self) -> &mut MPU6050 {
+        &mut self.mpu
+    }
+
+    /// Starts the sensor by checking that it answers as a MPU9250, then setting the accelerometer
+    /// range and gyroscope scale, and starts the magnetometer at 100 Hz.
+    /// # Returns
+    /// * `a Result` - Ok if started successfully, otherwise the error found. `WrongDevice` is also given
+    /// with the value read when the magnetometer does not answer as an AK8963.
+    pub fn begin(&mut self, scale: MPUdpsT, range: MPURangeT) -> Result<(), Mpu6050Error> {
+        delay_ms(5);
+
+        let identity = self.mpu.who_am_i()?;
+        if identity != MPU9250_WHO_AM_I && identity != MPU9255_WHO_AM_I {
+            return Err(Mpu6050Error::WrongDevice(identity));
+        }
+        self.mpu.configure(scale, range);
+
+        // The magnetometer appears on the bus when the I2C master is off and the bypass on.
+        self.mpu.set_i2c_master_mode_enabled(false);
+        self.mpu.set_i2c_byepass_enabled(true);
+
+        let mut wia = [0];
+        self.read_mag_registers(AK8963_REG_WIA, &mut wia)?;
+        if wia[0] != AK8963_WIA {
+            return Err(Mpu6050Error::WrongDevice(wia[0]));
+        }
+
+        // The sensitivity adjustment values are only readable in the fuse ROM mode.
+        self.write_mag_register(AK8963_REG_CNTL1, AK8963_POWER_DOWN)?;
+        delay_ms(10);
+        self.write_mag_register(AK8963_REG_CNTL1, AK8963_FUSE_ROM)?;
+        delay_ms(10);
+        let mut asa = [0; 3];
+        self.read_mag_registers(AK8963_REG_ASAX, &mut asa)?;
+        for (adjust, value) in self.mag_adjust.iter_mut().zip(asa.iter()) {
+            *adjust = (*value as f32 - 128.0) / 256.0 + 1.0;
+        }
+        self.write_mag_register(AK8963_REG_CNTL1, AK8963_POWER_DOWN)?;
+        delay_ms(10);
+        self.write_mag_register(AK8963_REG_CNTL1, AK8963_CONTINUOUS_100HZ)?;
+        delay_ms(10);
+        Ok(())
+    }
+
+    /// Reads the acceleration, converted to g according to the range given to `begin()`.
+    /// # Returns
+    /// * `a f32 array` - the acceleration along the x, y and z axes in g.
+    pub fn read_accel(&mut self) -> [f32; 3] {
+        self.mpu.read_accel()
+    }
+
+    /// Reads the rotation rate, converted to degrees per second according to the scale given to `begin()`.
+    /// # Returns
+    /// * `a f32 array` - the rotation rate around the x, y and z axes in degrees per second.
+    pub fn read_gyro(&mut self) -> [f32; 3] {
+        self.mpu.read_gyro()
+    }
+
+    /// Reads the temperature measured on the die of the sensor, using the formula of the MPU9250 register map.
+    /// # Returns
+    /// * `a f32` - the temperature in degrees Celsius.
+    pub fn read_temperature(&mut self) -> f32 {
+        self.mpu.read_temperature_raw() as f32 / 333.87 + 21.0
+    }
+
+    /// Reads the latest measurement of the magnetometer, with the sensitivity adjustment.
+    /// The axes of the AK8963 differ from the accelerometer, its x and y are swapped and its z is
+    /// reversed, they are turned to the axes of the accelerometer as the fusion filters need.
+    /// # Returns
+    /// * `a Result` - the field along the x, y and z axes of the accelerometer in milligauss, or the error found.
+    pub fn read_mag(&mut self) -> Result<[f32; 3], Mpu6050Error> {
+        // Reading ST2 after the data ends the read, it also has the overflow bit.
+        let mut data = [0; 7];
+        self.read_mag_registers(AK8963_REG_HXL, &mut data)?;
+        if data[6] & 0x08 != 0 {
+            return Err(Mpu6050Error::MagnetometerOverflow);
+        }
+        let mut field = [0.0; 3];
+        for (i, axis) in field.iter_mut().enumerate() {
+            let raw = i16::from_le_bytes([data[2 * i], data[2 * i + 1]]);
+            *axis = raw as f32 * self.mag_adjust[i] * AK8963_MILLIGAUSS_PER_COUNT;
+        }
+        Ok([field[1], field[0], -field[2]])
+    }
+
+    fn read_mag_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Mpu6050Error> {
+        self.i2c
+            .write_read_bytes(AK8963_ADDRESS, &[reg], buffer)
+            .map_err(|_| Mpu6050Error::I2c)
+    }
+
+    fn write_mag_register(&mut self, reg: u8, value: u8) -> Result<(), Mpu6050Error> {
+        self.i2c
+            .write_bytes(AK8963_ADDRESS, &[reg, value])
+            .map_err(|_| Mpu6050Error::I2c)
+    }
+}
+
+impl Accelerometer for Mpu9250 {
+    type Error = Mpu6050Error;
+
+    fn read_accel(&mut self) -> Result<[f32; 3], Mpu6050Error> {
+        Ok(Mpu9250::read_accel(self))
+    }
+}
+
+impl Gyroscope for Mpu9250 {
+    type Error = Mpu6050Error;
+
+    fn read_gyro(&mut self) -> Result<[f32; 3], Mpu6050Error> {
+        Ok(Mpu9250::read_gyro(self))
+    }
+}
+
+impl Magnetometer for Mpu9250 {
+    type Error = Mpu6050Error;
+
+    fn read_mag(&mut self) -> Result<[f32; 3], Mpu6050Error> {
+        Mpu9250::read_mag(self)
+    }
+}
+
+impl Thermometer for Mpu9250 {
+    type Error = Mpu6050Error;
+
+    fn read_temperature(&mut self) -> Result<f32, Mpu6050Error> {
+        Ok(Mpu9250::read_temperature(self))
+    }
+}
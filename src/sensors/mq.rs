@@ -0,0 +1,272 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Helpers for the MQ series of analog gas sensors, such as the MQ-2 and MQ-135.
+//! The resistance Rs of the sensing layer is found from the voltage across the load
+//! resistor RL of the module, and compared to its resistance R0 in a known atmosphere.
+//! The datasheets give the concentration of each gas against Rs/R0 as straight lines
+//! on log-log graphs, so the concentration is interpolated between points of these
+//! lines, with integers only. The heater needs about a day of burn in for a new sensor
+//! and a few minutes of warm up before each use.
+//! `<https://www.pololu.com/file/0J309/MQ2.pdf>`
+
+use super::analog_temperature::ln_q16;
+use crate::hal::analog::analog_read;
+
+/// Largest count of the 10 bit ADC.
+const ADC_MAX: u32 = 1023;
+/// 1 / ln(2) in Q32.
+const INV_LN2_Q32: i64 = 6_196_328_019;
+/// 2^(2^-(i + 1)) in Q30, the factor of each bit of the fraction of a binary exponent.
+const EXP2_FRACTION: [u64; 16] = [
+    1518500250, 1276901417, 1170923762, 1121280436, 1097253708, 1085434106, 1079572136, 1076653033,
+    1075196443, 1074468888, 1074105294, 1073923544, 1073832680, 1073787251, 1073764537, 1073753181,
+];
+
+/// Rs/R0 of the MQ-2 in clean air, in thousandths.
+pub const MQ2_CLEAN_AIR_RATIO: u32 = 9830;
+/// Rs/R0 of the MQ-135 in clean air, in thousandths.
+pub const MQ135_CLEAN_AIR_RATIO: u32 = 3600;
+
+/// A point of the sensitivity graph of a gas.
+/// # Elements
+/// * `ppm` - a u32, the concentration of the gas in ppm.
+/// * `ratio` - a u32, Rs/R0 at this concentration, in thousandths.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MqPoint {
+    pub ppm: u32,
+    pub ratio: u32,
+}
+
+/// LPG on the MQ-2, from 200 to 10000 ppm.
+pub const MQ2_LPG: [MqPoint; 2] = [
+    MqPoint {
+        ppm: 200,
+        ratio: 1622,
+    },
+    MqPoint {
+        ppm: 10000,
+        ratio: 258,
+    },
+];
+/// Smoke on the MQ-2, from 200 to 10000 ppm.
+pub const MQ2_SMOKE: [MqPoint; 2] = [
+    MqPoint {
+        ppm: 200,
+        ratio: 3388,
+    },
+    MqPoint {
+        ppm: 10000,
+        ratio: 605,
+    },
+];
+/// Carbon monoxide on the MQ-2, from 200 to 10000 ppm.
+pub const MQ2_CO: [MqPoint; 2] = [
+    MqPoint {
+        ppm: 200,
+        ratio: 5248,
+    },
+    MqPoint {
+        ppm: 10000,
+        ratio: 1387,
+    },
+];
+/// Carbon dioxide on the MQ-135, from 10 to 200 ppm above the level of R0.
+pub const MQ135_CO2: [MqPoint; 2] = [
+    MqPoint {
+        ppm: 10,
+        ratio: 2428,
+    },
+    MqPoint {
+        ppm: 200,
+        ratio: 823,
+    },
+];
+
+/// Errors of a MQ sensor reading.
+/// * `NoSignal` - The output is at 0 V, the sensor is not connected or its heater is off.
+/// * `NotCalibrated` - R0 is not known yet, `calibrate()` or `set_r0()` must be called first.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MqError {
+    NoSignal,
+    NotCalibrated,
+}
+
+/// Works out the concentration of a gas from Rs/R0, interpolating between the points
+/// of its curve on a log-log scale. Beyond the ends of the curve the first or last
+/// segment is extended.
+/// # Arguments
+/// * `ratio` - a u32, Rs/R0 in thousandths, at least 1.
+/// * `curve` - a `MqPoint` slice, at least two points of the curve ordered by concentration.
+/// # Returns
+/// * `a u32` - the concentration in ppm.
+pub fn mq_ppm(ratio: u32, curve: &[MqPoint]) -> u32 {
+    if curve.len() < 2 {
+        unreachable!();
+    }
+    // The segment holding the ratio, or the end segment nearest to it.
+    let last = curve.len() - 2;
+    let segment = (0..=last)
+        .find(|&i| {
+            let (a, b) = (curve[i].ratio, curve[i + 1].ratio);
+            (a.min(b)..=a.max(b)).contains(&ratio)
+        })
+        .unwrap_or_else(|| {
+            let falling = curve[0].ratio > curve[last + 1].ratio;
+            if (ratio > curve[0].ratio) == falling {
+                0
+            } else {
+                last
+            }
+        });
+    let (start, end) = (curve[segment], curve[segment + 1]);
+    let ratio_start = ln_q16(start.ratio);
+    let ratio_span = ln_q16(end.ratio) - ratio_start;
+    if ratio_span == 0 {
+        unreachable!();
+    }
+    let ppm_start = ln_q16(start.ppm);
+    let ppm_span = ln_q16(end.ppm) - ppm_start;
+    exp_q16(ppm_start + (ln_q16(ratio) - ratio_start) * ppm_span / ratio_span)
+}
+
+/// Works out e^x with integers, as a power of two whose fraction is made of one
+/// factor for each of its bits.
+/// # Arguments
+/// * `x` - a i64, the exponent in Q16.
+/// # Returns
+/// * `a u32` - e^x rounded, saturated at `u32::MAX`.
+fn exp_q16(x: i64) -> u32 {
+    let exponent = (x * INV_LN2_Q32) >> 32;
+    let int = exponent >> 16;
+    if int >= 32 {
+        return u32::MAX;
+    }
+    if int < 0 {
+        return 0;
+    }
+    let mut mantissa: u64 = 1 << 30;
+    for (bit, factor) in EXP2_FRACTION.iter().enumerate() {
+        if exponent & (0x8000 >> bit) != 0 {
+            mantissa = (mantissa * factor) >> 30;
+        }
+    }
+    (((mantissa << int) + (1 << 29)) >> 30).min(u32::MAX as u64) as u32
+}
+
+/// A MQ sensor module on a analog pin, with its load resistor between the output and
+/// ground and the module fed by the ADC reference.
+/// # Elements
+/// * `pin` - a usize, the number of the analog pin.
+/// * `load_resistance` - a u32, the resistance of the load resistor in Ω.
+/// * `r0` - a u32, the resistance of the sensor in the reference atmosphere in Ω, 0 before calibration.
+pub struct MqSensor {
+    pin: usize,
+    load_resistance: u32,
+    r0: u32,
+}
+
+impl MqSensor {
+    /// Creates the struct to read a sensor.
+    /// # Arguments
+    /// * `pin` - a usize, the number of the analog pin.
+    /// * `load_resistance` - a u32, the resistance of the load resistor in Ω, 1000 on most MQ-2 modules and 20000 on the MQ-135 ones.
+    /// # Returns
+    /// * `a MqSensor object` - which will be used to read the sensor.
+    pub fn new(pin: usize, load_resistance: u32) -> MqSensor {
+        MqSensor {
+            pin,
+            load_resistance,
+            r0: 0,
+        }
+    }
+
+    /// Converts a reading to the resistance of the sensor, Rs = RL (Vc - Vout) / Vout.
+    /// # Arguments
+    /// * `counts` - a u32, the value given by `analog_read()`.
+    /// # Returns
+    /// * `a Result` - the resistance in Ω, or the error found.
+    pub fn resistance(&self, counts: u32) -> Result<u32, MqError> {
+        if counts == 0 {
+            return Err(MqError::NoSignal);
+        }
+        let counts = counts.min(ADC_MAX) as u64;
+        let resistance = self.load_resistance as u64 * (ADC_MAX as u64 - counts) / counts;
+        Ok(resistance.max(1) as u32)
+    }
+
+    /// Reads the resistance of the sensor.
+    /// # Returns
+    /// * `a Result` - the resistance in Ω, or the error found.
+    pub fn read_resistance(&self) -> Result<u32, MqError> {
+        self.resistance(analog_read(self.pin))
+    }
+
+    /// Finds R0 with the sensor in the reference atmosphere, usually clean air, where
+    /// Rs/R0 is known from the datasheet. The value can be saved and given to `set_r0()`
+    /// later.
+    /// # Arguments
+    /// * `reference_ratio` - a u32, Rs/R0 in the reference atmosphere in thousandths, such as `MQ2_CLEAN_AIR_RATIO`.
+    /// * `samples` - a u8, the number of readings averaged, at least 1.
+    /// # Returns
+    /// * `a Result` - R0 in Ω, or the error found.
+    pub fn calibrate(&mut self, reference_ratio: u32, samples: u8) -> Result<u32, MqError> {
+        if samples == 0 || reference_ratio == 0 {
+            unreachable!();
+        }
+        let mut sum: u64 = 0;
+        for _ in 0..samples {
+            sum += self.read_resistance()? as u64;
+        }
+        let r0 = (sum * 1000 / (samples as u64 * reference_ratio as u64)).max(1) as u32;
+        self.r0 = r0;
+        Ok(r0)
+    }
+
+    /// Sets R0, for instance one saved from an earlier `calibrate()`.
+    /// # Arguments
+    /// * `r0` - a u32, the resistance of the sensor in the reference atmosphere in Ω.
+    pub fn set_r0(&mut self, r0: u32) {
+        self.r0 = r0;
+    }
+
+    /// Gives R0.
+    /// # Returns
+    /// * `a u32` - the resistance of the sensor in the reference atmosphere in Ω, 0 before calibration.
+    pub fn r0(&self) -> u32 {
+        self.r0
+    }
+
+    /// Reads Rs/R0.
+    /// # Returns
+    /// * `a Result` - Rs/R0 in thousandths, or the error found.
+    pub fn read_ratio(&self) -> Result<u32, MqError> {
+        if self.r0 == 0 {
+            return Err(MqError::NotCalibrated);
+        }
+        let ratio = self.read_resistance()? as u64 * 1000 / self.r0 as u64;
+        Ok(ratio.clamp(1, u32::MAX as u64) as u32)
+    }
+
+    /// Reads the concentration of a gas.
+    /// # Arguments
+    /// * `curve` - a `MqPoint` slice, the curve of the gas, such as `MQ2_LPG`.
+    /// # Returns
+    /// * `a Result` - the concentration in ppm, or the error found.
+    pub fn read_ppm(&self, curve: &[MqPoint]) -> Result<u32, MqError> {
+        Ok(mq_ppm(self.read_ratio()?, curve))
+    }
+}
@@ -0,0 +1,210 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the QMC5883L three axis magnetometer connected through I2C, found on most
+//! GY-271 boards sold as HMC5883L. The heading is worked out with the functions of the
+//! HMC5883L driver, `heading()` and `tilt_compensated_heading()`.
+
+use super::hmc5883l::CompassError;
+use super::traits::Magnetometer;
+use crate::com::i2c::Twi;
+use crate::delay::delay_ms;
+
+/// Address of the QMC5883L, which can not be changed.
+pub const QMC5883L_ADDRESS: u8 = 0x0D;
+
+// Registers.
+const REG_DATA_X_LSB: u8 = 0x00;
+const REG_STATUS: u8 = 0x06;
+const REG_CONTROL_1: u8 = 0x09;
+const REG_CONTROL_2: u8 = 0x0A;
+const REG_SET_RESET: u8 = 0x0B;
+const REG_CHIP_ID: u8 = 0x0D;
+
+const CHIP_ID: u8 = 0xFF;
+
+/// Full scale range of the QMC5883L.
+/// * `Gauss2` - ±2 gauss, 12000 counts per gauss.
+/// * `Gauss8` - ±8 gauss, 3000 counts per gauss.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Qmc5883lRange {
+    Gauss2,
+    Gauss8,
+}
+
+/// Output data rate of the continuous mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Qmc5883lRate {
+    Hz10,
+    Hz50,
+    Hz100,
+    Hz200,
+}
+
+/// Controls a QMC5883L sensor.
+/// # Elements
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `control` - a u8, the value of control register 1, with the oversampling, range, rate and mode.
+pub struct Qmc5883l {
+    i2c: &'static mut Twi,
+    control: u8,
+}
+
+impl Default for Qmc5883l {
+    fn default() -> Self {
+        Qmc5883l::new()
+    }
+}
+
+impl Qmc5883l {
+    /// Creates the struct to control the sensor on the I2C bus.
+    /// # Returns
+    /// * `a Qmc5883l object` - To control the sensor, after `begin()`.
+    pub fn new() -> Qmc5883l {
+        Qmc5883l {
+            i2c: unsafe { Twi::new() },
+            control: 0,
+        }
+    }
+
+    /// Resets the sensor, checks its chip id and starts measuring continuously at 50 Hz,
+    /// with 512 times oversampling and the range of 8 gauss.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor is ready, otherwise the error found.
+    pub fn begin(&mut self) -> Result<(), CompassError> {
+        self.write_register(REG_CONTROL_2, 0x80)?;
+        delay_ms(1);
+        let mut id = [0];
+        self.read_registers(REG_CHIP_ID, &mut id)?;
+        if id[0] != CHIP_ID {
+            return Err(CompassError::WrongDevice);
+        }
+        // Period of the SET/RESET pulses, as recommended by the datasheet.
+        self.write_register(REG_SET_RESET, 0x01)?;
+
+        // Oversampling of 512 is 0b00 in bits 7 and 6.
+        self.control = 0;
+        self.set_range(Qmc5883lRange::Gauss8)?;
+        self.set_rate(Qmc5883lRate::Hz50)?;
+        self.set_continuous_mode(true)
+    }
+
+    /// Sets the full scale range.
+    /// # Arguments
+    /// * `range` - a `Qmc5883lRange` object, the range.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_range(&mut self, range: Qmc5883lRange) -> Result<(), CompassError> {
+        let bits = match range {
+            Qmc5883lRange::Gauss2 => 0b00,
+            Qmc5883lRange::Gauss8 => 0b01,
+        };
+        self.write_control((self.control & !0b0011_0000) | (bits << 4))
+    }
+
+    /// Sets the rate of the continuous mode.
+    /// # Arguments
+    /// * `rate` - a `Qmc5883lRate` object, the rate.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_rate(&mut self, rate: Qmc5883lRate) -> Result<(), CompassError> {
+        let bits = match rate {
+            Qmc5883lRate::Hz10 => 0b00,
+            Qmc5883lRate::Hz50 => 0b01,
+            Qmc5883lRate::Hz100 => 0b10,
+            Qmc5883lRate::Hz200 => 0b11,
+        };
+        self.write_control((self.control & !0b0000_1100) | (bits << 2))
+    }
+
+    /// Starts measuring continuously, or stops.
+    /// # Arguments
+    /// * `state` - a boolean, true for the continuous mode, false for the standby mode.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_continuous_mode(&mut self, state: bool) -> Result<(), CompassError> {
+        self.write_control((self.control & !0b11) | state as u8)
+    }
+
+    /// Checks if a new measurement is ready.
+    /// # Returns
+    /// * `a Result` - true if a measurement was made since the last read.
+    pub fn is_data_ready(&mut self) -> Result<bool, CompassError> {
+        let mut status = [0];
+        self.read_registers(REG_STATUS, &mut status)?;
+        Ok(status[0] & 0x01 != 0)
+    }
+
+    /// Reads the latest measurement.
+    /// # Returns
+    /// * `a Result` - the raw field along the x, y and z axes, or the error found.
+    pub fn read(&mut self) -> Result<[i16; 3], CompassError> {
+        let mut status = [0];
+        self.read_registers(REG_STATUS, &mut status)?;
+        // OVL, an axis is beyond the range.
+        if status[0] & 0x02 != 0 {
+            return Err(CompassError::Overflow);
+        }
+        let mut data = [0; 6];
+        self.read_registers(REG_DATA_X_LSB, &mut data)?;
+        let value = |i: usize| i16::from_le_bytes([data[2 * i], data[2 * i + 1]]);
+        Ok([value(0), value(1), value(2)])
+    }
+
+    /// Reads the latest measurement, converted with the range.
+    /// # Returns
+    /// * `a Result` - the field along the x, y and z axes in milligauss, or the error found.
+    pub fn read_milligauss(&mut self) -> Result<[i32; 3], CompassError> {
+        let field = self.read()?;
+        let counts = if self.control & 0b0011_0000 == 0 {
+            12000
+        } else {
+            3000
+        };
+        Ok([
+            field[0] as i32 * 1000 / counts,
+            field[1] as i32 * 1000 / counts,
+            field[2] as i32 * 1000 / counts,
+        ])
+    }
+
+    fn write_control(&mut self, value: u8) -> Result<(), CompassError> {
+        self.write_register(REG_CONTROL_1, value)?;
+        self.control = value;
+        Ok(())
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), CompassError> {
+        self.i2c
+            .write_read_bytes(QMC5883L_ADDRESS, &[reg], buffer)
+            .map_err(|_| CompassError::I2c)
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), CompassError> {
+        self.i2c
+            .write_bytes(QMC5883L_ADDRESS, &[reg, value])
+            .map_err(|_| CompassError::I2c)
+    }
+}
+
+impl Magnetometer for Qmc5883l {
+    type Error = CompassError;
+
+    fn read_mag(&mut self) -> Result<[f32; 3], CompassError> {
+        let field = self.read_milligauss()?;
+        Ok([field[0] as f32, field[1] as f32, field[2] as f32])
+    }
+}
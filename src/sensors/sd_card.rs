@@ -0,0 +1,286 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for SD and SDHC cards connected through SPI, such as on card reader modules and shields.
+//! The card is set up at a low clock as the specification asks, and is then read in
+//! blocks of 512 bytes at the clock of the `SpiDevice`. The `FatVolume` reads files
+//! from the blocks.
+//! `<https://www.sdcard.org/downloads/pls/>`
+
+use crate::com::spi::{Spi, SpiDevice, SpiSettings};
+use crate::delay::delay_ms;
+
+/// Size in bytes of one block of the card.
+pub const SD_BLOCK_SIZE: usize = 512;
+
+/// Clock used while the card is set up, the specification allows at most 400 kHz.
+const SD_INIT_CLOCK_HZ: u32 = 250_000;
+
+// Commands.
+const CMD_GO_IDLE_STATE: u8 = 0;
+const CMD_SEND_IF_COND: u8 = 8;
+const CMD_SET_BLOCKLEN: u8 = 16;
+const CMD_READ_SINGLE_BLOCK: u8 = 17;
+const CMD_APP_CMD: u8 = 55;
+const CMD_READ_OCR: u8 = 58;
+const ACMD_SD_SEND_OP_COND: u8 = 41;
+
+// Bits of the R1 answer.
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+
+/// Token which starts the data of a block.
+const DATA_START_TOKEN: u8 = 0xFE;
+
+/// Number of bytes clocked while waiting for the card to be ready or to send data.
+const WAIT_BYTES: u16 = 0xFFFF;
+
+/// Errors given by the SD card driver.
+/// * `NotFound` - No card answered, check the wiring and that a card is inserted.
+/// * `Timeout` - The card did not finish its set up or did not send data in time.
+/// * `Command` - The card rejected a command, with the R1 answer.
+/// * `Token` - The card sent an error token instead of data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SdError {
+    NotFound,
+    Timeout,
+    Command(u8),
+    Token(u8),
+}
+
+/// Kinds of cards.
+/// * `Sd1` - SD version 1, addressed in bytes.
+/// * `Sd2` - SD version 2 up to 2 GB, addressed in bytes.
+/// * `Sdhc` - SDHC and SDXC, addressed in blocks.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SdCardType {
+    Sd1,
+    Sd2,
+    Sdhc,
+}
+
+/// Waits until the card stops holding MISO low.
+fn wait_ready(spi: &mut Spi) -> bool {
+    for _ in 0..WAIT_BYTES {
+        if spi.transfer_byte(0xFF) == 0xFF {
+            return true;
+        }
+    }
+    false
+}
+
+/// Sends a command and gives the R1 answer, 0xFF if the card did not answer.
+fn send_command(spi: &mut Spi, command: u8, argument: u32) -> u8 {
+    wait_ready(spi);
+    // Only the first commands are checked for their CRC while in SPI mode.
+    let crc = match command {
+        CMD_GO_IDLE_STATE => 0x95,
+        CMD_SEND_IF_COND => 0x87,
+        _ => 0x01,
+    };
+    let arg = argument.to_be_bytes();
+    spi.write(&[0x40 | command, arg[0], arg[1], arg[2], arg[3], crc]);
+    for _ in 0..10 {
+        let r1 = spi.transfer_byte(0xFF);
+        if r1 & 0x80 == 0 {
+            return r1;
+        }
+    }
+    0xFF
+}
+
+/// Controls an SD card on its own chip select.
+pub struct SdCard {
+    spi: SpiDevice,
+    card_type: SdCardType,
+}
+
+impl SdCard {
+    /// Creates the driver, nothing is sent to the card yet.
+    /// The card takes SPI mode 0, the clock of the settings is used once the card is set up,
+    /// up to 25 MHz, so the highest clock of the microcontroller can be used.
+    /// # Arguments
+    /// * `spi` - a `SpiDevice` object, the SPI bus with the chip select of the card.
+    /// # Returns
+    /// * `a SdCard object` - which will be used to read the card.
+    pub fn new(spi: SpiDevice) -> SdCard {
+        SdCard {
+            spi,
+            card_type: SdCardType::Sd1,
+        }
+    }
+
+    /// Sets up the card in SPI mode, to be called once the card is inserted.
+    /// # Returns
+    /// * `a Result<SdCardType, SdError>` - the kind of the card, `SdError::NotFound` if no card answered.
+    pub fn init(&mut self) -> Result<SdCardType, SdError> {
+        let fast = self.spi.settings();
+        let slow = SpiSettings::new(SD_INIT_CLOCK_HZ, fast.mode, fast.bit_order);
+        self.spi.set_settings(slow);
+        // At least 74 clocks with CS high put the card in its native mode.
+        let spi = unsafe { Spi::new() };
+        spi.configure(&slow);
+        for _ in 0..10 {
+            spi.transfer_byte(0xFF);
+        }
+        let result = self.init_card();
+        self.spi.set_settings(fast);
+        result
+    }
+
+    /// Goes through the set up commands.
+    fn init_card(&mut self) -> Result<SdCardType, SdError> {
+        // CMD0 with CS low switches the card to SPI mode.
+        let mut tries = 10;
+        while self.command(CMD_GO_IDLE_STATE, 0, &mut []) != R1_IDLE {
+            if tries == 0 {
+                return Err(SdError::NotFound);
+            }
+            tries -= 1;
+            delay_ms(10);
+        }
+
+        // Only cards of version 2 know CMD8, which echoes the check pattern.
+        let mut answer = [0u8; 4];
+        let r1 = self.command(CMD_SEND_IF_COND, 0x1AA, &mut answer);
+        let version2 = if r1 & R1_ILLEGAL_COMMAND != 0 {
+            false
+        } else if answer[3] == 0xAA {
+            true
+        } else {
+            return Err(SdError::Command(r1));
+        };
+
+        // ACMD41 starts the set up, with HCS set if high capacity cards are accepted.
+        let argument = if version2 { 0x4000_0000 } else { 0 };
+        let mut polls = 1000;
+        loop {
+            self.command(CMD_APP_CMD, 0, &mut []);
+            let r1 = self.command(ACMD_SD_SEND_OP_COND, argument, &mut []);
+            if r1 == 0 {
+                break;
+            }
+            if r1 & !R1_IDLE != 0 {
+                return Err(SdError::Command(r1));
+            }
+            if polls == 0 {
+                return Err(SdError::Timeout);
+            }
+            polls -= 1;
+            delay_ms(1);
+        }
+
+        self.card_type = if version2 {
+            // CCS in the OCR tells if the card is addressed in blocks.
+            let r1 = self.command(CMD_READ_OCR, 0, &mut answer);
+            if r1 != 0 {
+                return Err(SdError::Command(r1));
+            }
+            if answer[0] & 0x40 != 0 {
+                SdCardType::Sdhc
+            } else {
+                SdCardType::Sd2
+            }
+        } else {
+            SdCardType::Sd1
+        };
+        if self.card_type != SdCardType::Sdhc {
+            let r1 = self.command(CMD_SET_BLOCKLEN, SD_BLOCK_SIZE as u32, &mut []);
+            if r1 != 0 {
+                return Err(SdError::Command(r1));
+            }
+        }
+        Ok(self.card_type)
+    }
+
+    /// Sends a command, reads the bytes which follow the R1 answer into the buffer and gives R1.
+    fn command(&mut self, command: u8, argument: u32, answer: &mut [u8]) -> u8 {
+        let r1 = self.spi.transaction(|spi| {
+            let r1 = send_command(spi, command, argument);
+            for byte in answer.iter_mut() {
+                *byte = spi.transfer_byte(0xFF);
+            }
+            r1
+        });
+        self.release_bus();
+        r1
+    }
+
+    /// Clocks one byte with CS high, after which the card lets go of MISO.
+    fn release_bus(&mut self) {
+        unsafe { Spi::new() }.transfer_byte(0xFF);
+    }
+
+    /// Gives the kind of the card found by `init()`.
+    /// # Returns
+    /// * `a SdCardType` - the kind of the card.
+    pub fn card_type(&self) -> SdCardType {
+        self.card_type
+    }
+
+    /// Reads one block.
+    /// # Arguments
+    /// * `block` - a u32, the number of the block.
+    /// * `buffer` - a mutable u8 array, where the 512 bytes of the block are stored.
+    /// # Returns
+    /// * `a Result<(), SdError>` - the error if the block could not be read.
+    pub fn read_block(
+        &mut self,
+        block: u32,
+        buffer: &mut [u8; SD_BLOCK_SIZE],
+    ) -> Result<(), SdError> {
+        let address = match self.card_type {
+            SdCardType::Sdhc => block,
+            _ => block * SD_BLOCK_SIZE as u32,
+        };
+        let result = self.spi.transaction(|spi| {
+            let r1 = send_command(spi, CMD_READ_SINGLE_BLOCK, address);
+            if r1 != 0 {
+                return Err(SdError::Command(r1));
+            }
+            let mut waited: u16 = 0;
+            loop {
+                let token = spi.transfer_byte(0xFF);
+                if token == DATA_START_TOKEN {
+                    break;
+                }
+                if token != 0xFF {
+                    return Err(SdError::Token(token));
+                }
+                if waited == WAIT_BYTES {
+                    return Err(SdError::Timeout);
+                }
+                waited += 1;
+            }
+            for byte in buffer.iter_mut() {
+                *byte = spi.transfer_byte(0xFF);
+            }
+            // The CRC is not checked in SPI mode.
+            spi.transfer_byte(0xFF);
+            spi.transfer_byte(0xFF);
+            Ok(())
+        });
+        self.release_bus();
+        result
+    }
+
+    /// Gives back the SPI device.
+    /// # Returns
+    /// * `a SpiDevice object` - the SPI bus with the chip select of the card.
+    pub fn release(self) -> SpiDevice {
+        self.spi
+    }
+}
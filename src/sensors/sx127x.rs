@@ -0,0 +1,512 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the SX1276 to SX1279 LoRa radios, such as the RFM95 modules, connected through SPI.
+//! Packets of up to 255 bytes are sent and received in the explicit header mode with
+//! the frequency, spreading factor, bandwidth and coding rate chosen by the user.
+//! The DIO0 pin of the radio rises when a packet is sent or received, and can be
+//! attached to an external interrupt so that the program does not have to poll.
+//! `<https://www.semtech.com/products/wireless-rf/lora-connect/sx1276>`
+
+use crate::com::spi::{Spi, SpiDevice};
+use crate::delay::{delay_ms, delay_us};
+use crate::hal::ext_interrupt::{attach_interrupt, IntLine, Trigger};
+
+// Registers.
+const REG_FIFO: u8 = 0x00;
+const REG_OP_MODE: u8 = 0x01;
+const REG_FRF_MSB: u8 = 0x06;
+const REG_PA_CONFIG: u8 = 0x09;
+const REG_OCP: u8 = 0x0B;
+const REG_LNA: u8 = 0x0C;
+const REG_FIFO_ADDR_PTR: u8 = 0x0D;
+const REG_FIFO_TX_BASE_ADDR: u8 = 0x0E;
+const REG_FIFO_RX_BASE_ADDR: u8 = 0x0F;
+const REG_FIFO_RX_CURRENT_ADDR: u8 = 0x10;
+const REG_IRQ_FLAGS: u8 = 0x12;
+const REG_RX_NB_BYTES: u8 = 0x13;
+const REG_PKT_SNR_VALUE: u8 = 0x19;
+const REG_PKT_RSSI_VALUE: u8 = 0x1A;
+const REG_MODEM_CONFIG_1: u8 = 0x1D;
+const REG_MODEM_CONFIG_2: u8 = 0x1E;
+const REG_PREAMBLE_MSB: u8 = 0x20;
+const REG_PAYLOAD_LENGTH: u8 = 0x22;
+const REG_MODEM_CONFIG_3: u8 = 0x26;
+const REG_DETECTION_OPTIMIZE: u8 = 0x31;
+const REG_DETECTION_THRESHOLD: u8 = 0x37;
+const REG_SYNC_WORD: u8 = 0x39;
+const REG_DIO_MAPPING_1: u8 = 0x40;
+const REG_VERSION: u8 = 0x42;
+const REG_PA_DAC: u8 = 0x4D;
+
+// Operation modes, with the LoRa bit set.
+const MODE_LONG_RANGE: u8 = 0x80;
+const MODE_SLEEP: u8 = 0x00;
+const MODE_STANDBY: u8 = 0x01;
+const MODE_TX: u8 = 0x03;
+const MODE_RX_CONTINUOUS: u8 = 0x05;
+
+// Bits of the IRQ flags register.
+const IRQ_RX_DONE: u8 = 0x40;
+const IRQ_PAYLOAD_CRC_ERROR: u8 = 0x20;
+const IRQ_TX_DONE: u8 = 0x08;
+
+// Values of DIO0 in the DIO mapping register.
+const DIO0_RX_DONE: u8 = 0x00;
+const DIO0_TX_DONE: u8 = 0x40;
+
+/// Value of the version register of the SX1276 to SX1279.
+const SX127X_VERSION: u8 = 0x12;
+
+/// Largest payload of one packet.
+pub const LORA_MAX_PAYLOAD: usize = 255;
+
+/// Time in milliseconds after which a packet being sent is given up.
+const TX_TIMEOUT_MS: u32 = 5000;
+
+/// Errors given by the SX127x driver.
+/// * `NotFound` - The version register did not give the SX127x version, check the wiring.
+/// * `Timeout` - The packet was not sent or received in time.
+/// * `Crc` - A packet was received with a wrong CRC.
+/// * `PayloadTooLong` - The packet is longer than 255 bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoRaError {
+    NotFound,
+    Timeout,
+    Crc,
+    PayloadTooLong,
+}
+
+/// Events which raise DIO0.
+/// * `TxDone` - The packet has been sent.
+/// * `RxDone` - A packet with a right CRC has been received.
+/// * `CrcError` - A packet has been received with a wrong CRC.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoRaEvent {
+    TxDone,
+    RxDone,
+    CrcError,
+}
+
+/// Signal bandwidths of the LoRa modem.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoRaBandwidth {
+    Bw7_8kHz,
+    Bw10_4kHz,
+    Bw15_6kHz,
+    Bw20_8kHz,
+    Bw31_25kHz,
+    Bw41_7kHz,
+    Bw62_5kHz,
+    Bw125kHz,
+    Bw250kHz,
+    Bw500kHz,
+}
+
+impl LoRaBandwidth {
+    /// Gives the value of the bandwidth bits.
+    fn bits(&self) -> u8 {
+        match self {
+            LoRaBandwidth::Bw7_8kHz => 0,
+            LoRaBandwidth::Bw10_4kHz => 1,
+            LoRaBandwidth::Bw15_6kHz => 2,
+            LoRaBandwidth::Bw20_8kHz => 3,
+            LoRaBandwidth::Bw31_25kHz => 4,
+            LoRaBandwidth::Bw41_7kHz => 5,
+            LoRaBandwidth::Bw62_5kHz => 6,
+            LoRaBandwidth::Bw125kHz => 7,
+            LoRaBandwidth::Bw250kHz => 8,
+            LoRaBandwidth::Bw500kHz => 9,
+        }
+    }
+
+    /// Gives the bandwidth in hertz.
+    /// # Returns
+    /// * `a u32` - the bandwidth.
+    pub fn hz(&self) -> u32 {
+        match self {
+            LoRaBandwidth::Bw7_8kHz => 7_800,
+            LoRaBandwidth::Bw10_4kHz => 10_400,
+            LoRaBandwidth::Bw15_6kHz => 15_600,
+            LoRaBandwidth::Bw20_8kHz => 20_800,
+            LoRaBandwidth::Bw31_25kHz => 31_250,
+            LoRaBandwidth::Bw41_7kHz => 41_700,
+            LoRaBandwidth::Bw62_5kHz => 62_500,
+            LoRaBandwidth::Bw125kHz => 125_000,
+            LoRaBandwidth::Bw250kHz => 250_000,
+            LoRaBandwidth::Bw500kHz => 500_000,
+        }
+    }
+}
+
+/// Controls a SX127x radio on its own chip select.
+pub struct Sx127x {
+    spi: SpiDevice,
+    frequency: u32,
+}
+
+impl Sx127x {
+    /// Creates the driver, nothing is sent to the radio yet.
+    /// The radio takes SPI mode 0 up to 10 MHz.
+    /// # Arguments
+    /// * `spi` - a `SpiDevice` object, the SPI bus with the chip select of the radio.
+    /// # Returns
+    /// * `a Sx127x object` - which will be used to control the radio.
+    pub fn new(spi: SpiDevice) -> Sx127x {
+        Sx127x { spi, frequency: 0 }
+    }
+
+    /// Checks the radio and sets it up for LoRa at the frequency, with spreading factor 7,
+    /// 125 kHz bandwidth, coding rate 4/5, CRC on and 17 dBm on the PA_BOOST pin.
+    /// The radio is left in standby mode.
+    /// # Arguments
+    /// * `frequency` - a u32, the carrier frequency in hertz, such as 433, 868 or 915 MHz.
+    /// # Returns
+    /// * `a Result<(), LoRaError>` - `LoRaError::NotFound` if no SX127x answered.
+    pub fn init(&mut self, frequency: u32) -> Result<(), LoRaError> {
+        if self.read_register(REG_VERSION) != SX127X_VERSION {
+            return Err(LoRaError::NotFound);
+        }
+        // The LoRa mode can only be chosen in sleep mode.
+        self.write_register(REG_OP_MODE, MODE_LONG_RANGE | MODE_SLEEP);
+        delay_ms(10);
+
+        self.set_frequency(frequency);
+        self.write_register(REG_FIFO_TX_BASE_ADDR, 0);
+        self.write_register(REG_FIFO_RX_BASE_ADDR, 0);
+        // Highest LNA gain with the boost for the high frequency port, and automatic gain control.
+        self.write_register(REG_LNA, 0x23);
+        self.write_register(REG_MODEM_CONFIG_3, 0x04);
+        self.write_register(
+            REG_MODEM_CONFIG_1,
+            (LoRaBandwidth::Bw125kHz.bits() << 4) | (1 << 1),
+        );
+        self.set_spreading_factor(7);
+        self.set_crc(true);
+        self.set_preamble_length(8);
+        self.set_tx_power(17);
+        self.standby();
+        Ok(())
+    }
+
+    /// Reads one register.
+    /// # Arguments
+    /// * `address` - a u8, the address of the register.
+    /// # Returns
+    /// * `a u8` - the value of the register.
+    pub fn read_register(&mut self, address: u8) -> u8 {
+        let mut value = [0u8; 1];
+        self.spi.write_read(&[address & 0x7F], &mut value);
+        value[0]
+    }
+
+    /// Writes one register.
+    /// # Arguments
+    /// * `address` - a u8, the address of the register.
+    /// * `value` - a u8, the value to be written.
+    pub fn write_register(&mut self, address: u8, value: u8) {
+        self.spi.write(&[address | 0x80, value]);
+    }
+
+    /// Changes only the bits of a register which are set in the mask.
+    fn modify_register(&mut self, address: u8, mask: u8, value: u8) {
+        let old = self.read_register(address);
+        self.write_register(address, (old & !mask) | (value & mask));
+    }
+
+    /// Puts the radio to sleep, where only the registers are kept.
+    pub fn sleep(&mut self) {
+        self.write_register(REG_OP_MODE, MODE_LONG_RANGE | MODE_SLEEP);
+    }
+
+    /// Puts the radio in standby, where the oscillator runs and the FIFO can be used.
+    pub fn standby(&mut self) {
+        self.write_register(REG_OP_MODE, MODE_LONG_RANGE | MODE_STANDBY);
+    }
+
+    /// Sets the carrier frequency, which is a multiple of 32 MHz / 2^19, about 61 Hz.
+    /// # Arguments
+    /// * `frequency` - a u32, the carrier frequency in hertz.
+    pub fn set_frequency(&mut self, frequency: u32) {
+        self.frequency = frequency;
+        // frf = frequency * 2^19 / 32 MHz = frequency * 2^8 / 15625, split to stay in 32 bits.
+        let frf = ((frequency / 15625) << 8) + (((frequency % 15625) << 8) / 15625);
+        self.write_register(REG_FRF_MSB, (frf >> 16) as u8);
+        self.write_register(REG_FRF_MSB + 1, (frf >> 8) as u8);
+        self.write_register(REG_FRF_MSB + 2, frf as u8);
+    }
+
+    /// Sets the spreading factor, a higher one reaches farther but sends slower.
+    /// # Arguments
+    /// * `sf` - a u8, the spreading factor from 6 to 12. 6 works only with the implicit header mode.
+    pub fn set_spreading_factor(&mut self, sf: u8) {
+        if !(6..=12).contains(&sf) {
+            unreachable!()
+        }
+        if sf == 6 {
+            self.write_register(REG_DETECTION_OPTIMIZE, 0xC5);
+            self.write_register(REG_DETECTION_THRESHOLD, 0x0C);
+        } else {
+            self.write_register(REG_DETECTION_OPTIMIZE, 0xC3);
+            self.write_register(REG_DETECTION_THRESHOLD, 0x0A);
+        }
+        self.modify_register(REG_MODEM_CONFIG_2, 0xF0, sf << 4);
+        self.update_low_data_rate();
+    }
+
+    /// Gives the spreading factor in use.
+    /// # Returns
+    /// * `a u8` - the spreading factor.
+    pub fn spreading_factor(&mut self) -> u8 {
+        self.read_register(REG_MODEM_CONFIG_2) >> 4
+    }
+
+    /// Sets the signal bandwidth, a narrower one reaches farther but sends slower.
+    /// # Arguments
+    /// * `bandwidth` - a `LoRaBandwidth` object, the bandwidth.
+    pub fn set_bandwidth(&mut self, bandwidth: LoRaBandwidth) {
+        self.modify_register(REG_MODEM_CONFIG_1, 0xF0, bandwidth.bits() << 4);
+        self.update_low_data_rate();
+    }
+
+    /// Gives the signal bandwidth in use.
+    /// # Returns
+    /// * `a LoRaBandwidth object` - the bandwidth.
+    pub fn bandwidth(&mut self) -> LoRaBandwidth {
+        match self.read_register(REG_MODEM_CONFIG_1) >> 4 {
+            0 => LoRaBandwidth::Bw7_8kHz,
+            1 => LoRaBandwidth::Bw10_4kHz,
+            2 => LoRaBandwidth::Bw15_6kHz,
+            3 => LoRaBandwidth::Bw20_8kHz,
+            4 => LoRaBandwidth::Bw31_25kHz,
+            5 => LoRaBandwidth::Bw41_7kHz,
+            6 => LoRaBandwidth::Bw62_5kHz,
+            7 => LoRaBandwidth::Bw125kHz,
+            8 => LoRaBandwidth::Bw250kHz,
+            _ => LoRaBandwidth::Bw500kHz,
+        }
+    }
+
+    /// Turns on the low data rate optimisation when a symbol is longer than 16 milliseconds.
+    fn update_low_data_rate(&mut self) {
+        let symbol_us = (1_000_000 / self.bandwidth().hz()) << self.spreading_factor();
+        let optimize = if symbol_us > 16_000 { 0x08 } else { 0x00 };
+        self.modify_register(REG_MODEM_CONFIG_3, 0x08, optimize);
+    }
+
+    /// Sets the coding rate, more redundant bits correct more errors.
+    /// # Arguments
+    /// * `denominator` - a u8, from 5 to 8 for the coding rates 4/5 to 4/8.
+    pub fn set_coding_rate(&mut self, denominator: u8) {
+        if !(5..=8).contains(&denominator) {
+            unreachable!()
+        }
+        self.modify_register(REG_MODEM_CONFIG_1, 0x0E, (denominator - 4) << 1);
+    }
+
+    /// Sets the number of preamble symbols, both ends have to use the same.
+    /// # Arguments
+    /// * `length` - a u16, the preamble length, 4.25 symbols are added by the radio.
+    pub fn set_preamble_length(&mut self, length: u16) {
+        self.write_register(REG_PREAMBLE_MSB, (length >> 8) as u8);
+        self.write_register(REG_PREAMBLE_MSB + 1, length as u8);
+    }
+
+    /// Sets the sync word, radios only receive packets with their own sync word.
+    /// # Arguments
+    /// * `word` - a u8, 0x12 for private networks and 0x34 for LoRaWAN.
+    pub fn set_sync_word(&mut self, word: u8) {
+        self.write_register(REG_SYNC_WORD, word);
+    }
+
+    /// Turns the CRC of the payload on or off.
+    /// # Arguments
+    /// * `enable` - a boolean, true to send and check the CRC.
+    pub fn set_crc(&mut self, enable: bool) {
+        let value = if enable { 0x04 } else { 0x00 };
+        self.modify_register(REG_MODEM_CONFIG_2, 0x04, value);
+    }
+
+    /// Sets the output power on the PA_BOOST pin, which is used by the RFM95 modules.
+    /// # Arguments
+    /// * `dbm` - a u8, the power from 2 to 17 dBm, or 20 dBm with the high power DAC.
+    pub fn set_tx_power(&mut self, dbm: u8) {
+        match dbm {
+            2..=17 => {
+                self.write_register(REG_PA_DAC, 0x84);
+                // Over current protection at 100 mA.
+                self.write_register(REG_OCP, 0x20 | 0x0B);
+                self.write_register(REG_PA_CONFIG, 0x80 | (dbm - 2));
+            }
+            20 => {
+                self.write_register(REG_PA_DAC, 0x87);
+                // Over current protection at 140 mA.
+                self.write_register(REG_OCP, 0x20 | 0x11);
+                self.write_register(REG_PA_CONFIG, 0x80 | 0x0F);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Writes the packet to the FIFO and starts sending it, without waiting.
+    /// DIO0 rises when the packet has been sent.
+    /// # Arguments
+    /// * `data` - a u8 slice, the payload of up to 255 bytes.
+    /// # Returns
+    /// * `a Result<(), LoRaError>` - `LoRaError::PayloadTooLong` if the payload does not fit.
+    pub fn start_send(&mut self, data: &[u8]) -> Result<(), LoRaError> {
+        if data.len() > LORA_MAX_PAYLOAD {
+            return Err(LoRaError::PayloadTooLong);
+        }
+        self.standby();
+        self.write_register(REG_FIFO_ADDR_PTR, 0);
+        self.spi.transaction(|spi: &mut Spi| {
+            spi.write(&[REG_FIFO | 0x80]);
+            spi.write(data);
+        });
+        self.write_register(REG_PAYLOAD_LENGTH, data.len() as u8);
+        self.write_register(REG_DIO_MAPPING_1, DIO0_TX_DONE);
+        self.write_register(REG_IRQ_FLAGS, 0xFF);
+        self.write_register(REG_OP_MODE, MODE_LONG_RANGE | MODE_TX);
+        Ok(())
+    }
+
+    /// Sends one packet and waits until it is out.
+    /// The radio goes back to standby by itself afterwards.
+    /// # Arguments
+    /// * `data` - a u8 slice, the payload of up to 255 bytes.
+    /// # Returns
+    /// * `a Result<(), LoRaError>` - an error if the payload is too long or the radio did not finish.
+    pub fn send(&mut self, data: &[u8]) -> Result<(), LoRaError> {
+        self.start_send(data)?;
+        let mut polls = TX_TIMEOUT_MS;
+        while self.read_register(REG_IRQ_FLAGS) & IRQ_TX_DONE == 0 {
+            if polls == 0 {
+                self.standby();
+                return Err(LoRaError::Timeout);
+            }
+            polls -= 1;
+            delay_ms(1);
+        }
+        self.write_register(REG_IRQ_FLAGS, IRQ_TX_DONE);
+        Ok(())
+    }
+
+    /// Starts continuous reception, without waiting.
+    /// DIO0 rises when a packet has been received.
+    pub fn start_receive(&mut self) {
+        self.write_register(REG_DIO_MAPPING_1, DIO0_RX_DONE);
+        self.write_register(REG_IRQ_FLAGS, 0xFF);
+        self.write_register(REG_OP_MODE, MODE_LONG_RANGE | MODE_RX_CONTINUOUS);
+    }
+
+    /// Waits for one packet, the radio is left receiving.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, where the payload is stored.
+    /// * `timeout_ms` - a u32, the time in milliseconds after which the wait is given up.
+    /// # Returns
+    /// * `a Result<usize, LoRaError>` - the length of the payload, or the error.
+    pub fn receive(&mut self, buffer: &mut [u8], timeout_ms: u32) -> Result<usize, LoRaError> {
+        self.start_receive();
+        let mut polls = timeout_ms * 10;
+        loop {
+            match self.poll_event() {
+                Some(LoRaEvent::RxDone) => return Ok(self.read_packet(buffer)),
+                Some(LoRaEvent::CrcError) => return Err(LoRaError::Crc),
+                _ => (),
+            }
+            if polls == 0 {
+                return Err(LoRaError::Timeout);
+            }
+            polls -= 1;
+            delay_us(100);
+        }
+    }
+
+    /// Reads and clears the event which raised DIO0, to be called after the DIO0
+    /// interrupt or in a polling loop.
+    /// # Returns
+    /// * `a Option<LoRaEvent>` - the event, None if nothing happened.
+    pub fn poll_event(&mut self) -> Option<LoRaEvent> {
+        let flags = self.read_register(REG_IRQ_FLAGS);
+        if flags & IRQ_RX_DONE != 0 {
+            self.write_register(REG_IRQ_FLAGS, IRQ_RX_DONE | IRQ_PAYLOAD_CRC_ERROR);
+            if flags & IRQ_PAYLOAD_CRC_ERROR != 0 {
+                Some(LoRaEvent::CrcError)
+            } else {
+                Some(LoRaEvent::RxDone)
+            }
+        } else if flags & IRQ_TX_DONE != 0 {
+            self.write_register(REG_IRQ_FLAGS, IRQ_TX_DONE);
+            Some(LoRaEvent::TxDone)
+        } else {
+            None
+        }
+    }
+
+    /// Copies the last received packet from the FIFO.
+    /// # Arguments
+    /// * `buffer` - a mutable u8 slice, where the payload is stored.
+    /// # Returns
+    /// * `a usize` - the number of bytes copied, limited by the length of the buffer.
+    pub fn read_packet(&mut self, buffer: &mut [u8]) -> usize {
+        let length = self.read_register(REG_RX_NB_BYTES) as usize;
+        let length = length.min(buffer.len());
+        let start = self.read_register(REG_FIFO_RX_CURRENT_ADDR);
+        self.write_register(REG_FIFO_ADDR_PTR, start);
+        self.spi
+            .write_read(&[REG_FIFO & 0x7F], &mut buffer[..length]);
+        length
+    }
+
+    /// Gives the signal strength of the last received packet.
+    /// # Returns
+    /// * `a i16` - the RSSI in dBm.
+    pub fn packet_rssi(&mut self) -> i16 {
+        let value = self.read_register(REG_PKT_RSSI_VALUE) as i16;
+        // The low frequency port below 525 MHz has a different offset.
+        if self.frequency < 525_000_000 {
+            value - 164
+        } else {
+            value - 157
+        }
+    }
+
+    /// Gives the signal to noise ratio of the last received packet.
+    /// # Returns
+    /// * `a i8` - the SNR in dB, negative values are below the noise floor.
+    pub fn packet_snr(&mut self) -> i8 {
+        (self.read_register(REG_PKT_SNR_VALUE) as i8) / 4
+    }
+
+    /// Attaches a handler to the external interrupt line connected to DIO0.
+    /// The handler runs in the interrupt, so it should only note the event
+    /// and leave `poll_event()` to the main program.
+    /// # Arguments
+    /// * `line` - a `IntLine` object, the external interrupt line connected to DIO0.
+    /// * `handler` - a function, which is called when DIO0 rises.
+    pub fn attach_dio0(&mut self, line: IntLine, handler: fn()) {
+        attach_interrupt(line, Trigger::Rising, handler);
+    }
+
+    /// Gives back the SPI device, the radio is put to sleep.
+    /// # Returns
+    /// * `a SpiDevice object` - the SPI bus with the chip select of the radio.
+    pub fn release(mut self) -> SpiDevice {
+        self.sleep();
+        self.spi
+    }
+}
@@ -0,0 +1,63 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Common interfaces of the sensors, so that application code and the fusion filters of
+//! the math module work the same with any of the drivers. Each driver gives its own error type.
+
+/// A sensor of acceleration.
+pub trait Accelerometer {
+    type Error;
+
+    /// Reads the acceleration.
+    /// # Returns
+    /// * `a Result` - the acceleration along the x, y and z axes in g, or the error of the driver.
+    fn read_accel(&mut self) -> Result<[f32; 3], Self::Error>;
+}
+
+/// A sensor of rotation rate.
+pub trait Gyroscope {
+    type Error;
+
+    /// Reads the rotation rate.
+    /// # Returns
+    /// * `a Result` - the rotation rate around the x, y and z axes in degrees per second, or the error of the driver.
+    fn read_gyro(&mut self) -> Result<[f32; 3], Self::Error>;
+}
+
+/// A sensor of magnetic field.
+pub trait Magnetometer {
+    type Error;
+
+    /// Reads the magnetic field.
+    /// # Returns
+    /// * `a Result` - the field along the x, y and z axes in milligauss, or the error of the driver.
+    fn read_mag(&mut self) -> Result<[f32; 3], Self::Error>;
+}
+
+/// A sensor of temperature.
+pub trait Thermometer {
+    type Error;
+
+    /// Reads the temperature.
+    /// # Returns
+    /// * `a Result` - the temperature in degrees Celsius, or the error of the driver.
+    fn read_temperature(&mut self) -> Result<f32, Self::Error>;
+}
+
+/// An inertial measurement unit, any sensor which is an accelerometer, a gyroscope and a thermometer.
+pub trait Imu: Accelerometer + Gyroscope + Thermometer {}
+
+impl<T: Accelerometer + Gyroscope + Thermometer> Imu for T {}
@@ -0,0 +1,620 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the VL53L0X time-of-flight distance sensor connected through I2C,
+//! which times infrared laser pulses to measure up to about 2 m. Most of the set up is
+//! not documented in the datasheet, the sequence here follows the ST API: reference
+//! SPADs, tuning settings, timing budget and reference calibration.
+//! `<https://www.st.com/resource/en/datasheet/vl53l0x.pdf>`
+
+use crate::com::i2c::Twi;
+use crate::delay::delay_ms;
+
+/// Address of the VL53L0X after it is powered up.
+pub const VL53L0X_ADDRESS: u8 = 0x29;
+
+// Registers.
+const REG_SYSRANGE_START: u8 = 0x00;
+const REG_SYSTEM_SEQUENCE_CONFIG: u8 = 0x01;
+const REG_SYSTEM_INTERMEASUREMENT_PERIOD: u8 = 0x04;
+const REG_SYSTEM_INTERRUPT_CONFIG_GPIO: u8 = 0x0A;
+const REG_SYSTEM_INTERRUPT_CLEAR: u8 = 0x0B;
+const REG_RESULT_INTERRUPT_STATUS: u8 = 0x13;
+const REG_RESULT_RANGE: u8 = 0x1E;
+const REG_FINAL_RANGE_MIN_COUNT_RATE_RTN_LIMIT: u8 = 0x44;
+const REG_MSRC_CONFIG_TIMEOUT_MACROP: u8 = 0x46;
+const REG_DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD: u8 = 0x4E;
+const REG_DYNAMIC_SPAD_REF_EN_START_OFFSET: u8 = 0x4F;
+const REG_PRE_RANGE_CONFIG_VCSEL_PERIOD: u8 = 0x50;
+const REG_PRE_RANGE_CONFIG_TIMEOUT_MACROP_HI: u8 = 0x51;
+const REG_MSRC_CONFIG_CONTROL: u8 = 0x60;
+const REG_FINAL_RANGE_CONFIG_VCSEL_PERIOD: u8 = 0x70;
+const REG_FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI: u8 = 0x71;
+const REG_GPIO_HV_MUX_ACTIVE_HIGH: u8 = 0x84;
+const REG_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV: u8 = 0x89;
+const REG_I2C_SLAVE_DEVICE_ADDRESS: u8 = 0x8A;
+const REG_GLOBAL_CONFIG_SPAD_ENABLES_REF_0: u8 = 0xB0;
+const REG_GLOBAL_CONFIG_REF_EN_START_SELECT: u8 = 0xB6;
+const REG_IDENTIFICATION_MODEL_ID: u8 = 0xC0;
+const REG_OSC_CALIBRATE_VAL: u8 = 0xF8;
+
+// Value of the model identification register.
+const MODEL_ID: u8 = 0xEE;
+// Milliseconds to wait for the sensor before giving up.
+const TIMEOUT_MS: u16 = 500;
+// Range given when no target is found.
+const OUT_OF_RANGE: u16 = 8190;
+// Shortest timing budget, in microseconds, the sensor can work with.
+const MIN_TIMING_BUDGET_US: u32 = 20_000;
+
+// Time taken by each step of a measurement besides its timeout, in microseconds.
+const START_OVERHEAD_US: u32 = 1320;
+const END_OVERHEAD_US: u32 = 960;
+const MSRC_OVERHEAD_US: u32 = 660;
+const TCC_OVERHEAD_US: u32 = 590;
+const DSS_OVERHEAD_US: u32 = 690;
+const PRE_RANGE_OVERHEAD_US: u32 = 660;
+const FINAL_RANGE_OVERHEAD_US: u32 = 550;
+
+// Tuning settings of the ST API, written in order. The register 0xFF selects the page.
+const TUNING: [(u8, u8); 80] = [
+    (0xFF, 0x01),
+    (0x00, 0x00),
+    (0xFF, 0x00),
+    (0x09, 0x00),
+    (0x10, 0x00),
+    (0x11, 0x00),
+    (0x24, 0x01),
+    (0x25, 0xFF),
+    (0x75, 0x00),
+    (0xFF, 0x01),
+    (0x4E, 0x2C),
+    (0x48, 0x00),
+    (0x30, 0x20),
+    (0xFF, 0x00),
+    (0x30, 0x09),
+    (0x54, 0x00),
+    (0x31, 0x04),
+    (0x32, 0x03),
+    (0x40, 0x83),
+    (0x46, 0x25),
+    (0x60, 0x00),
+    (0x27, 0x00),
+    (0x50, 0x06),
+    (0x51, 0x00),
+    (0x52, 0x96),
+    (0x56, 0x08),
+    (0x57, 0x30),
+    (0x61, 0x00),
+    (0x62, 0x00),
+    (0x64, 0x00),
+    (0x65, 0x00),
+    (0x66, 0xA0),
+    (0xFF, 0x01),
+    (0x22, 0x32),
+    (0x47, 0x14),
+    (0x49, 0xFF),
+    (0x4A, 0x00),
+    (0xFF, 0x00),
+    (0x7A, 0x0A),
+    (0x7B, 0x00),
+    (0x78, 0x21),
+    (0xFF, 0x01),
+    (0x23, 0x34),
+    (0x42, 0x00),
+    (0x44, 0xFF),
+    (0x45, 0x26),
+    (0x46, 0x05),
+    (0x40, 0x40),
+    (0x0E, 0x06),
+    (0x20, 0x1A),
+    (0x43, 0x40),
+    (0xFF, 0x00),
+    (0x34, 0x03),
+    (0x35, 0x44),
+    (0xFF, 0x01),
+    (0x31, 0x04),
+    (0x4B, 0x09),
+    (0x4C, 0x05),
+    (0x4D, 0x04),
+    (0xFF, 0x00),
+    (0x44, 0x00),
+    (0x45, 0x20),
+    (0x47, 0x08),
+    (0x48, 0x28),
+    (0x67, 0x00),
+    (0x70, 0x04),
+    (0x71, 0x01),
+    (0x72, 0xFE),
+    (0x76, 0x00),
+    (0x77, 0x00),
+    (0xFF, 0x01),
+    (0x0D, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x01),
+    (0x01, 0xF8),
+    (0xFF, 0x01),
+    (0x8E, 0x01),
+    (0x00, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x00),
+];
+
+/// Errors of the VL53L0X.
+/// * `I2c` - The sensor did not answer, check the wiring.
+/// * `WrongDevice` - The model identification register holds the value given, which is not of a VL53L0X.
+/// * `Timeout` - The sensor did not finish a measurement or its set up in time.
+/// * `OutOfRange` - No target was found in the range of the sensor.
+/// * `InvalidBudget` - The timing budget is too short for the enabled steps.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Vl53l0xError {
+    I2c,
+    WrongDevice(u8),
+    Timeout,
+    OutOfRange,
+    InvalidBudget,
+}
+
+/// Steps of a measurement enabled in the sequence configuration register.
+struct SequenceSteps {
+    tcc: bool,
+    dss: bool,
+    msrc: bool,
+    pre_range: bool,
+    final_range: bool,
+}
+
+/// Timeouts of the steps of a measurement.
+struct SequenceTimeouts {
+    final_range_vcsel_pclks: u16,
+    msrc_dss_tcc_us: u32,
+    pre_range_mclks: u16,
+    pre_range_us: u32,
+    final_range_us: u32,
+}
+
+/// Controls a VL53L0X sensor.
+/// # Elements
+/// * `address` - a u8, the slave address of the sensor.
+/// * `i2c` - a static mutable reference to the I2C controller the sensor is on.
+/// * `stop_variable` - a u8, read from the sensor in `begin()` and written back before each measurement.
+pub struct Vl53l0x {
+    pub address: u8,
+    i2c: &'static mut Twi,
+    stop_variable: u8,
+}
+
+impl Default for Vl53l0x {
+    fn default() -> Self {
+        Vl53l0x::new(VL53L0X_ADDRESS)
+    }
+}
+
+impl Vl53l0x {
+    /// Creates the struct to control a sensor on the I2C bus.
+    /// # Arguments
+    /// * `address` - a u8, the slave address, `VL53L0X_ADDRESS` (0x29) after power up or the one given to `set_address()`.
+    /// # Returns
+    /// * `a Vl53l0x object` - To control the sensor, after `begin()`.
+    pub fn new(address: u8) -> Vl53l0x {
+        if address > 0x7F {
+            unreachable!();
+        }
+        Vl53l0x {
+            address,
+            i2c: unsafe { Twi::new() },
+            stop_variable: 0,
+        }
+    }
+
+    /// Changes the slave address, so several sensors can share the bus. The sensor
+    /// forgets it when powered down, so the others are usually held in reset through
+    /// their XSHUT pin meanwhile.
+    /// # Arguments
+    /// * `address` - a u8, the new slave address, up to 0x7F.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn set_address(&mut self, address: u8) -> Result<(), Vl53l0xError> {
+        if address > 0x7F {
+            unreachable!();
+        }
+        self.write_register(REG_I2C_SLAVE_DEVICE_ADDRESS, address)?;
+        self.address = address;
+        Ok(())
+    }
+
+    /// Checks the identification of the sensor and sets it up for 2.8 V I/O: reference
+    /// SPADs, tuning settings, interrupt on new measurements, a timing budget of about
+    /// 33 ms and the reference calibration.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor is ready, otherwise the error found.
+    pub fn begin(&mut self) -> Result<(), Vl53l0xError> {
+        let id = self.read_register(REG_IDENTIFICATION_MODEL_ID)?;
+        if id != MODEL_ID {
+            return Err(Vl53l0xError::WrongDevice(id));
+        }
+
+        let pad = self.read_register(REG_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV)?;
+        self.write_register(REG_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV, pad | 0x01)?;
+        // Standard I2C mode.
+        self.write_register(0x88, 0x00)?;
+        self.write_registers(&[(0x80, 0x01), (0xFF, 0x01), (0x00, 0x00)])?;
+        self.stop_variable = self.read_register(0x91)?;
+        self.write_registers(&[(0x00, 0x01), (0xFF, 0x00), (0x80, 0x00)])?;
+
+        // Disables the signal rate checks of the MSRC and pre-range steps, and sets
+        // the final range signal rate limit to 0.25 MCPS, in 9.7 fixed point.
+        let control = self.read_register(REG_MSRC_CONFIG_CONTROL)?;
+        self.write_register(REG_MSRC_CONFIG_CONTROL, control | 0x12)?;
+        self.write_register16(REG_FINAL_RANGE_MIN_COUNT_RATE_RTN_LIMIT, 32)?;
+        self.write_register(REG_SYSTEM_SEQUENCE_CONFIG, 0xFF)?;
+
+        self.set_reference_spads()?;
+        self.write_registers(&TUNING)?;
+
+        // New measurements pull GPIO1 low.
+        self.write_register(REG_SYSTEM_INTERRUPT_CONFIG_GPIO, 0x04)?;
+        let mux = self.read_register(REG_GPIO_HV_MUX_ACTIVE_HIGH)?;
+        self.write_register(REG_GPIO_HV_MUX_ACTIVE_HIGH, mux & !0x10)?;
+        self.write_register(REG_SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+
+        // The MSRC and TCC steps are left out, the final range gets their time.
+        let budget = self.timing_budget()?;
+        self.write_register(REG_SYSTEM_SEQUENCE_CONFIG, 0xE8)?;
+        self.set_timing_budget(budget)?;
+
+        // VHV and phase calibrations.
+        self.write_register(REG_SYSTEM_SEQUENCE_CONFIG, 0x01)?;
+        self.single_reference_calibration(0x40)?;
+        self.write_register(REG_SYSTEM_SEQUENCE_CONFIG, 0x02)?;
+        self.single_reference_calibration(0x00)?;
+        self.write_register(REG_SYSTEM_SEQUENCE_CONFIG, 0xE8)
+    }
+
+    /// Sets the time given to a measurement, longer budgets give more accurate ranges.
+    /// # Arguments
+    /// * `budget_us` - a u32, the timing budget in microseconds, at least 20000.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written, `InvalidBudget` if the budget is too short.
+    pub fn set_timing_budget(&mut self, budget_us: u32) -> Result<(), Vl53l0xError> {
+        if budget_us < MIN_TIMING_BUDGET_US {
+            return Err(Vl53l0xError::InvalidBudget);
+        }
+        let steps = self.sequence_steps()?;
+        let timeouts = self.sequence_timeouts(&steps)?;
+        if !steps.final_range {
+            return Ok(());
+        }
+        let used_us =
+            START_OVERHEAD_US + steps_overhead(&steps, &timeouts) + FINAL_RANGE_OVERHEAD_US;
+        if used_us > budget_us {
+            return Err(Vl53l0xError::InvalidBudget);
+        }
+        let mut final_range_mclks =
+            microseconds_to_mclks(budget_us - used_us, timeouts.final_range_vcsel_pclks);
+        if steps.pre_range {
+            final_range_mclks += timeouts.pre_range_mclks as u32;
+        }
+        self.write_register16(
+            REG_FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI,
+            encode_timeout(final_range_mclks),
+        )?;
+        Ok(())
+    }
+
+    /// Gives the time given to a measurement, as read from the sensor.
+    /// # Returns
+    /// * `a Result` - the timing budget in microseconds, or the error found.
+    pub fn timing_budget(&mut self) -> Result<u32, Vl53l0xError> {
+        let steps = self.sequence_steps()?;
+        let timeouts = self.sequence_timeouts(&steps)?;
+        // Reading the budget counts a longer start than setting it, as the ST API does.
+        let mut budget_us = 1910 + steps_overhead(&steps, &timeouts);
+        if steps.final_range {
+            budget_us += timeouts.final_range_us + FINAL_RANGE_OVERHEAD_US;
+        }
+        Ok(budget_us)
+    }
+
+    /// Makes a single measurement and waits for it.
+    /// # Returns
+    /// * `a Result` - the range in millimetres, or the error found.
+    pub fn read_range_single(&mut self) -> Result<u16, Vl53l0xError> {
+        self.restore_stop_variable()?;
+        self.write_register(REG_SYSRANGE_START, 0x01)?;
+        // The start bit clears when the measurement begins.
+        self.wait_for(REG_SYSRANGE_START, 0x01, false)?;
+        self.read_range_continuous()
+    }
+
+    /// Starts measuring continuously, until `stop_continuous()`.
+    /// # Arguments
+    /// * `period_ms` - a u32, the time between measurements in milliseconds, 0 to measure back to back.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn start_continuous(&mut self, period_ms: u32) -> Result<(), Vl53l0xError> {
+        self.restore_stop_variable()?;
+        if period_ms == 0 {
+            return self.write_register(REG_SYSRANGE_START, 0x02);
+        }
+        // The period is counted by the internal oscillator.
+        let mut calibrate = [0; 2];
+        self.read_registers(REG_OSC_CALIBRATE_VAL, &mut calibrate)?;
+        let calibrate = u16::from_be_bytes(calibrate) as u32;
+        let period = if calibrate != 0 {
+            period_ms * calibrate
+        } else {
+            period_ms
+        };
+        let period = period.to_be_bytes();
+        self.i2c
+            .write_bytes(
+                self.address,
+                &[
+                    REG_SYSTEM_INTERMEASUREMENT_PERIOD,
+                    period[0],
+                    period[1],
+                    period[2],
+                    period[3],
+                ],
+            )
+            .map_err(|_| Vl53l0xError::I2c)?;
+        self.write_register(REG_SYSRANGE_START, 0x04)
+    }
+
+    /// Stops measuring continuously.
+    /// # Returns
+    /// * `a Result` - Ok if the sensor was written.
+    pub fn stop_continuous(&mut self) -> Result<(), Vl53l0xError> {
+        self.write_register(REG_SYSRANGE_START, 0x01)?;
+        self.write_registers(&[
+            (0xFF, 0x01),
+            (0x00, 0x00),
+            (0x91, 0x00),
+            (0x00, 0x01),
+            (0xFF, 0x00),
+        ])
+    }
+
+    /// Checks if a measurement is complete, for instance after GPIO1 falls.
+    /// # Returns
+    /// * `a Result` - true if a range can be read, or the error found.
+    pub fn is_range_ready(&mut self) -> Result<bool, Vl53l0xError> {
+        Ok(self.read_register(REG_RESULT_INTERRUPT_STATUS)? & 0x07 != 0)
+    }
+
+    /// Waits for the next measurement while measuring continuously.
+    /// # Returns
+    /// * `a Result` - the range in millimetres, or the error found.
+    pub fn read_range_continuous(&mut self) -> Result<u16, Vl53l0xError> {
+        self.wait_for(REG_RESULT_INTERRUPT_STATUS, 0x07, true)?;
+        let mut range = [0; 2];
+        self.read_registers(REG_RESULT_RANGE, &mut range)?;
+        self.write_register(REG_SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+        let range = u16::from_be_bytes(range);
+        if range >= OUT_OF_RANGE {
+            return Err(Vl53l0xError::OutOfRange);
+        }
+        Ok(range)
+    }
+
+    /// Enables the reference SPADs found at the factory, leaving out the first 12 if
+    /// they are of the aperture type.
+    fn set_reference_spads(&mut self) -> Result<(), Vl53l0xError> {
+        self.write_registers(&[(0x80, 0x01), (0xFF, 0x01), (0x00, 0x00), (0xFF, 0x06)])?;
+        let value = self.read_register(0x83)?;
+        self.write_register(0x83, value | 0x04)?;
+        self.write_registers(&[(0xFF, 0x07), (0x81, 0x01), (0x80, 0x01), (0x94, 0x6B)])?;
+        self.write_register(0x83, 0x00)?;
+        self.wait_for(0x83, 0xFF, true)?;
+        self.write_register(0x83, 0x01)?;
+        let info = self.read_register(0x92)?;
+        self.write_registers(&[(0x81, 0x00), (0xFF, 0x06)])?;
+        let value = self.read_register(0x83)?;
+        self.write_register(0x83, value & !0x04)?;
+        self.write_registers(&[(0xFF, 0x01), (0x00, 0x01), (0xFF, 0x00), (0x80, 0x00)])?;
+
+        let count = info & 0x7F;
+        let first = if info & 0x80 != 0 { 12 } else { 0 };
+        let mut map = [0; 6];
+        self.read_registers(REG_GLOBAL_CONFIG_SPAD_ENABLES_REF_0, &mut map)?;
+        self.write_registers(&[
+            (0xFF, 0x01),
+            (REG_DYNAMIC_SPAD_REF_EN_START_OFFSET, 0x00),
+            (REG_DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD, 0x2C),
+            (0xFF, 0x00),
+            (REG_GLOBAL_CONFIG_REF_EN_START_SELECT, 0xB4),
+        ])?;
+        let mut enabled = 0;
+        for i in 0..48 {
+            let bit = 1 << (i % 8);
+            if i < first || enabled == count {
+                map[i / 8] &= !bit;
+            } else if map[i / 8] & bit != 0 {
+                enabled += 1;
+            }
+        }
+        let mut data = [0; 7];
+        data[0] = REG_GLOBAL_CONFIG_SPAD_ENABLES_REF_0;
+        data[1..].copy_from_slice(&map);
+        self.i2c
+            .write_bytes(self.address, &data)
+            .map_err(|_| Vl53l0xError::I2c)
+    }
+
+    fn single_reference_calibration(&mut self, vhv_init: u8) -> Result<(), Vl53l0xError> {
+        self.write_register(REG_SYSRANGE_START, 0x01 | vhv_init)?;
+        self.wait_for(REG_RESULT_INTERRUPT_STATUS, 0x07, true)?;
+        self.write_register(REG_SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+        self.write_register(REG_SYSRANGE_START, 0x00)
+    }
+
+    fn restore_stop_variable(&mut self) -> Result<(), Vl53l0xError> {
+        let stop_variable = self.stop_variable;
+        self.write_registers(&[
+            (0x80, 0x01),
+            (0xFF, 0x01),
+            (0x00, 0x00),
+            (0x91, stop_variable),
+            (0x00, 0x01),
+            (0xFF, 0x00),
+            (0x80, 0x00),
+        ])
+    }
+
+    fn sequence_steps(&mut self) -> Result<SequenceSteps, Vl53l0xError> {
+        let config = self.read_register(REG_SYSTEM_SEQUENCE_CONFIG)?;
+        Ok(SequenceSteps {
+            tcc: config & 0x10 != 0,
+            dss: config & 0x08 != 0,
+            msrc: config & 0x04 != 0,
+            pre_range: config & 0x40 != 0,
+            final_range: config & 0x80 != 0,
+        })
+    }
+
+    fn sequence_timeouts(
+        &mut self,
+        steps: &SequenceSteps,
+    ) -> Result<SequenceTimeouts, Vl53l0xError> {
+        let pre_range_vcsel_pclks =
+            decode_vcsel_period(self.read_register(REG_PRE_RANGE_CONFIG_VCSEL_PERIOD)?);
+        let msrc_dss_tcc_mclks = self.read_register(REG_MSRC_CONFIG_TIMEOUT_MACROP)? as u32 + 1;
+        let pre_range_mclks =
+            decode_timeout(self.read_register16(REG_PRE_RANGE_CONFIG_TIMEOUT_MACROP_HI)?);
+        let final_range_vcsel_pclks =
+            decode_vcsel_period(self.read_register(REG_FINAL_RANGE_CONFIG_VCSEL_PERIOD)?);
+        let mut final_range_mclks =
+            decode_timeout(self.read_register16(REG_FINAL_RANGE_CONFIG_TIMEOUT_MACROP_HI)?);
+        if steps.pre_range {
+            final_range_mclks = final_range_mclks.wrapping_sub(pre_range_mclks);
+        }
+        Ok(SequenceTimeouts {
+            final_range_vcsel_pclks,
+            msrc_dss_tcc_us: mclks_to_microseconds(msrc_dss_tcc_mclks, pre_range_vcsel_pclks),
+            pre_range_mclks,
+            pre_range_us: mclks_to_microseconds(pre_range_mclks as u32, pre_range_vcsel_pclks),
+            final_range_us: mclks_to_microseconds(
+                final_range_mclks as u32,
+                final_range_vcsel_pclks,
+            ),
+        })
+    }
+
+    /// Waits until the bits of a register are set, or cleared.
+    fn wait_for(&mut self, reg: u8, mask: u8, set: bool) -> Result<(), Vl53l0xError> {
+        for _ in 0..TIMEOUT_MS {
+            if (self.read_register(reg)? & mask != 0) == set {
+                return Ok(());
+            }
+            delay_ms(1);
+        }
+        Err(Vl53l0xError::Timeout)
+    }
+
+    fn read_register(&mut self, reg: u8) -> Result<u8, Vl53l0xError> {
+        let mut value = [0];
+        self.read_registers(reg, &mut value)?;
+        Ok(value[0])
+    }
+
+    fn read_register16(&mut self, reg: u8) -> Result<u16, Vl53l0xError> {
+        let mut value = [0; 2];
+        self.read_registers(reg, &mut value)?;
+        Ok(u16::from_be_bytes(value))
+    }
+
+    fn read_registers(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Vl53l0xError> {
+        self.i2c
+            .write_read_bytes(self.address, &[reg], buffer)
+            .map_err(|_| Vl53l0xError::I2c)
+    }
+
+    fn write_register(&mut self, reg: u8, value: u8) -> Result<(), Vl53l0xError> {
+        self.i2c
+            .write_bytes(self.address, &[reg, value])
+            .map_err(|_| Vl53l0xError::I2c)
+    }
+
+    fn write_register16(&mut self, reg: u8, value: u16) -> Result<(), Vl53l0xError> {
+        let value = value.to_be_bytes();
+        self.i2c
+            .write_bytes(self.address, &[reg, value[0], value[1]])
+            .map_err(|_| Vl53l0xError::I2c)
+    }
+
+    fn write_registers(&mut self, values: &[(u8, u8)]) -> Result<(), Vl53l0xError> {
+        for &(reg, value) in values {
+            self.write_register(reg, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the time of the enabled steps before the final range.
+fn steps_overhead(steps: &SequenceSteps, timeouts: &SequenceTimeouts) -> u32 {
+    let mut time_us = END_OVERHEAD_US;
+    if steps.tcc {
+        time_us += timeouts.msrc_dss_tcc_us + TCC_OVERHEAD_US;
+    }
+    if steps.dss {
+        time_us += 2 * (timeouts.msrc_dss_tcc_us + DSS_OVERHEAD_US);
+    } else if steps.msrc {
+        time_us += timeouts.msrc_dss_tcc_us + MSRC_OVERHEAD_US;
+    }
+    if steps.pre_range {
+        time_us += timeouts.pre_range_us + PRE_RANGE_OVERHEAD_US;
+    }
+    time_us
+}
+
+/// Gives the VCSEL pulse period in PCLKs from its register value.
+fn decode_vcsel_period(value: u8) -> u16 {
+    (value as u16 + 1) << 1
+}
+
+/// Gives the macro period in nanoseconds for a VCSEL pulse period.
+fn macro_period_ns(vcsel_pclks: u16) -> u32 {
+    (2304 * vcsel_pclks as u32 * 1655 + 500) / 1000
+}
+
+fn mclks_to_microseconds(mclks: u32, vcsel_pclks: u16) -> u32 {
+    let period = macro_period_ns(vcsel_pclks);
+    (mclks * period + period / 2) / 1000
+}
+
+fn microseconds_to_mclks(us: u32, vcsel_pclks: u16) -> u32 {
+    let period = macro_period_ns(vcsel_pclks);
+    (us * 1000 + period / 2) / period
+}
+
+/// Gives the timeout in MCLKs from its register value, a mantissa shifted by the high byte.
+fn decode_timeout(value: u16) -> u16 {
+    ((value & 0xFF) << (value >> 8)) + 1
+}
+
+fn encode_timeout(mclks: u32) -> u16 {
+    if mclks == 0 {
+        return 0;
+    }
+    let mut mantissa = mclks - 1;
+    let mut exponent = 0;
+    while mantissa > 0xFF {
+        mantissa >>= 1;
+        exponent += 1;
+    }
+    (exponent << 8) | mantissa as u16
+}
@@ -0,0 +1,514 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Driver for the WIZnet W5500 Ethernet controller, as on the Ethernet Shield 2, connected through SPI.
+//! The W5500 runs TCP, UDP and IP itself, so the driver only sets the addresses and
+//! moves data through the eight hardware sockets, each with 2 KB of send and receive buffer.
+//! A socket is opened for UDP with `open()`, or for TCP with `connect()` as a client or
+//! `listen()` as a server, and is given back with `close()`.
+//! `<https://www.wiznet.io/product-item/w5500/>`
+
+use crate::com::spi::SpiDevice;
+use crate::delay::{delay_ms, delay_us};
+
+// Registers of the common block.
+const MR: u16 = 0x0000;
+const GAR: u16 = 0x0001;
+const SUBR: u16 = 0x0005;
+const SHAR: u16 = 0x0009;
+const SIPR: u16 = 0x000F;
+const PHYCFGR: u16 = 0x002E;
+const VERSIONR: u16 = 0x0039;
+
+// Registers of each socket block.
+const SN_MR: u16 = 0x0000;
+const SN_CR: u16 = 0x0001;
+const SN_IR: u16 = 0x0002;
+const SN_SR: u16 = 0x0003;
+const SN_PORT: u16 = 0x0004;
+const SN_DIPR: u16 = 0x000C;
+const SN_DPORT: u16 = 0x0010;
+const SN_TX_FSR: u16 = 0x0020;
+const SN_TX_WR: u16 = 0x0024;
+const SN_RX_RSR: u16 = 0x0026;
+const SN_RX_RD: u16 = 0x0028;
+
+// Socket commands.
+const CMD_OPEN: u8 = 0x01;
+const CMD_LISTEN: u8 = 0x02;
+const CMD_CONNECT: u8 = 0x04;
+const CMD_DISCON: u8 = 0x08;
+const CMD_CLOSE: u8 = 0x10;
+const CMD_SEND: u8 = 0x20;
+const CMD_RECV: u8 = 0x40;
+
+// Bits of the socket interrupt register.
+const IR_TIMEOUT: u8 = 0x08;
+const IR_SENDOK: u8 = 0x10;
+
+/// Value of the version register of the W5500.
+const W5500_VERSION: u8 = 0x04;
+
+/// Number of hardware sockets.
+pub const W5500_SOCKETS: u8 = 8;
+
+/// Size in bytes of the send and receive buffer of each socket.
+pub const W5500_BUFFER_SIZE: usize = 2048;
+
+/// Length of the header which the W5500 puts before each received UDP packet.
+const UDP_HEADER_LENGTH: usize = 8;
+
+/// Errors given by the W5500 driver.
+/// * `NotFound` - The version register did not give the W5500 version, check the wiring.
+/// * `NotConnected` - The TCP socket has no connection.
+/// * `Closed` - The socket was closed, by the remote side or because it could not be opened.
+/// * `Timeout` - The remote side did not answer after all retransmissions.
+/// * `PacketTooLong` - The UDP packet does not fit in the send buffer.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EthError {
+    NotFound,
+    NotConnected,
+    Closed,
+    Timeout,
+    PacketTooLong,
+}
+
+/// Protocols of a socket.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SocketProtocol {
+    Tcp,
+    Udp,
+}
+
+/// States of a socket, the states passed through quickly are given as `Other`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SocketStatus {
+    Closed,
+    Init,
+    Listen,
+    SynSent,
+    Established,
+    CloseWait,
+    Udp,
+    Other(u8),
+}
+
+impl SocketStatus {
+    /// Gives the state of the value of the socket status register.
+    fn from_register(value: u8) -> SocketStatus {
+        match value {
+            0x00 => SocketStatus::Closed,
+            0x13 => SocketStatus::Init,
+            0x14 => SocketStatus::Listen,
+            0x15 => SocketStatus::SynSent,
+            0x17 => SocketStatus::Established,
+            0x1C => SocketStatus::CloseWait,
+            0x22 => SocketStatus::Udp,
+            _ => SocketStatus::Other(value),
+        }
+    }
+}
+
+/// Controls a W5500 on its own chip select.
+pub struct W5500 {
+    spi: SpiDevice,
+}
+
+impl W5500 {
+    /// Creates the driver, nothing is sent to the controller yet.
+    /// The W5500 takes SPI mode 0 or 3 up to 80 MHz, so the highest clock of the microcontroller can be used.
+    /// # Arguments
+    /// * `spi` - a `SpiDevice` object, the SPI bus with the chip select of the controller.
+    /// # Returns
+    /// * `a W5500 object` - which will be used to control the controller.
+    pub fn new(spi: SpiDevice) -> W5500 {
+        W5500 { spi }
+    }
+
+    /// Resets the controller, checks it and sets the addresses of the network interface.
+    /// # Arguments
+    /// * `mac` - a u8 array, the hardware address, such as the one printed on the shield.
+    /// * `ip` - a u8 array, the IP address of the interface.
+    /// * `subnet` - a u8 array, the subnet mask, such as 255.255.255.0.
+    /// * `gateway` - a u8 array, the IP address of the router.
+    /// # Returns
+    /// * `a Result<(), EthError>` - `EthError::NotFound` if no W5500 answered.
+    pub fn init(
+        &mut self,
+        mac: [u8; 6],
+        ip: [u8; 4],
+        subnet: [u8; 4],
+        gateway: [u8; 4],
+    ) -> Result<(), EthError> {
+        self.write(MR, 0, &[0x80]);
+        let mut polls = 10;
+        while self.read_u8(MR, 0) & 0x80 != 0 {
+            if polls == 0 {
+                return Err(EthError::NotFound);
+            }
+            polls -= 1;
+            delay_ms(1);
+        }
+        if self.read_u8(VERSIONR, 0) != W5500_VERSION {
+            return Err(EthError::NotFound);
+        }
+        self.write(SHAR, 0, &mac);
+        self.write(SIPR, 0, &ip);
+        self.write(SUBR, 0, &subnet);
+        self.write(GAR, 0, &gateway);
+        Ok(())
+    }
+
+    /// Checks if the Ethernet cable is connected to a working link.
+    /// # Returns
+    /// * `a boolean` - true if the link is up.
+    pub fn link_up(&mut self) -> bool {
+        self.read_u8(PHYCFGR, 0) & 0x01 != 0
+    }
+
+    /// Reads registers or buffer memory.
+    /// # Arguments
+    /// * `address` - a u16, the offset in the block.
+    /// * `block` - a u8, the block, 0 for the common registers, `4n+1` for the registers, `4n+2` for the send buffer and `4n+3` for the receive buffer of socket n.
+    /// * `data` - a mutable u8 slice, where the read bytes are stored.
+    fn read(&mut self, address: u16, block: u8, data: &mut [u8]) {
+        let header = [(address >> 8) as u8, address as u8, block << 3];
+        self.spi.write_read(&header, data);
+    }
+
+    /// Writes registers or buffer memory, the block is chosen as in `read()`.
+    fn write(&mut self, address: u16, block: u8, data: &[u8]) {
+        let header = [(address >> 8) as u8, address as u8, (block << 3) | 0x04];
+        self.spi.transaction(|spi| {
+            spi.write(&header);
+            spi.write(data);
+        });
+    }
+
+    /// Reads a one byte register.
+    fn read_u8(&mut self, address: u16, block: u8) -> u8 {
+        let mut value = [0u8; 1];
+        self.read(address, block, &mut value);
+        value[0]
+    }
+
+    /// Reads a two byte register.
+    fn read_u16(&mut self, address: u16, block: u8) -> u16 {
+        let mut value = [0u8; 2];
+        self.read(address, block, &mut value);
+        u16::from_be_bytes(value)
+    }
+
+    /// Writes a two byte register.
+    fn write_u16(&mut self, address: u16, block: u8, value: u16) {
+        self.write(address, block, &value.to_be_bytes());
+    }
+
+    /// Reads a two byte counter which the controller may change during the read,
+    /// until two reads in a row agree.
+    fn read_counter(&mut self, address: u16, block: u8) -> u16 {
+        let mut value = self.read_u16(address, block);
+        loop {
+            let again = self.read_u16(address, block);
+            if again == value {
+                return value;
+            }
+            value = again;
+        }
+    }
+
+    /// Gives the register block of a socket.
+    fn socket_block(socket: u8) -> u8 {
+        if socket >= W5500_SOCKETS {
+            unreachable!()
+        }
+        socket * 4 + 1
+    }
+
+    /// Gives a command to a socket and waits until the controller has taken it.
+    fn command(&mut self, socket: u8, command: u8) {
+        let block = W5500::socket_block(socket);
+        self.write(SN_CR, block, &[command]);
+        while self.read_u8(SN_CR, block) != 0 {
+            delay_us(10);
+        }
+    }
+
+    /// Gives the state of a socket.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// # Returns
+    /// * `a SocketStatus` - the state.
+    pub fn status(&mut self, socket: u8) -> SocketStatus {
+        SocketStatus::from_register(self.read_u8(SN_SR, W5500::socket_block(socket)))
+    }
+
+    /// Opens a socket, a socket already open is closed first.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// * `protocol` - a `SocketProtocol` object, TCP or UDP.
+    /// * `local_port` - a u16, the local port of the socket.
+    /// # Returns
+    /// * `a Result<(), EthError>` - `EthError::Closed` if the socket did not open.
+    pub fn open(
+        &mut self,
+        socket: u8,
+        protocol: SocketProtocol,
+        local_port: u16,
+    ) -> Result<(), EthError> {
+        let block = W5500::socket_block(socket);
+        self.close(socket);
+        let (mode, expected) = match protocol {
+            SocketProtocol::Tcp => (0x01, SocketStatus::Init),
+            SocketProtocol::Udp => (0x02, SocketStatus::Udp),
+        };
+        self.write(SN_MR, block, &[mode]);
+        self.write_u16(SN_PORT, block, local_port);
+        self.command(socket, CMD_OPEN);
+        if self.status(socket) == expected {
+            Ok(())
+        } else {
+            self.close(socket);
+            Err(EthError::Closed)
+        }
+    }
+
+    /// Opens a TCP connection to a server and waits until it is made.
+    /// The controller gives up after its retransmissions, about 30 seconds by default.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// * `ip` - a u8 array, the IP address of the server.
+    /// * `port` - a u16, the port of the server.
+    /// * `local_port` - a u16, the local port, any free port above 1023.
+    /// # Returns
+    /// * `a Result<(), EthError>` - `EthError::Timeout` if the server did not answer.
+    pub fn connect(
+        &mut self,
+        socket: u8,
+        ip: [u8; 4],
+        port: u16,
+        local_port: u16,
+    ) -> Result<(), EthError> {
+        let block = W5500::socket_block(socket);
+        self.open(socket, SocketProtocol::Tcp, local_port)?;
+        self.write(SN_DIPR, block, &ip);
+        self.write_u16(SN_DPORT, block, port);
+        self.command(socket, CMD_CONNECT);
+        loop {
+            match self.status(socket) {
+                SocketStatus::Established => return Ok(()),
+                SocketStatus::Closed => {
+                    let timeout = self.read_u8(SN_IR, block) & IR_TIMEOUT != 0;
+                    self.close(socket);
+                    return Err(if timeout {
+                        EthError::Timeout
+                    } else {
+                        EthError::Closed
+                    });
+                }
+                _ => delay_us(100),
+            }
+        }
+    }
+
+    /// Opens a TCP socket which waits for a client to connect.
+    /// The connection is made once `is_connected()` gives true.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// * `port` - a u16, the local port to listen on, such as 80 for HTTP.
+    /// # Returns
+    /// * `a Result<(), EthError>` - `EthError::Closed` if the socket did not open.
+    pub fn listen(&mut self, socket: u8, port: u16) -> Result<(), EthError> {
+        self.open(socket, SocketProtocol::Tcp, port)?;
+        self.command(socket, CMD_LISTEN);
+        if self.status(socket) == SocketStatus::Listen {
+            Ok(())
+        } else {
+            self.close(socket);
+            Err(EthError::Closed)
+        }
+    }
+
+    /// Checks if a TCP socket has a connection.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// # Returns
+    /// * `a boolean` - true if connected.
+    pub fn is_connected(&mut self, socket: u8) -> bool {
+        self.status(socket) == SocketStatus::Established
+    }
+
+    /// Gives the number of received bytes waiting in a socket.
+    /// For UDP this includes the 8 byte header before each packet.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// # Returns
+    /// * `a usize` - the number of bytes.
+    pub fn available(&mut self, socket: u8) -> usize {
+        self.read_counter(SN_RX_RSR, W5500::socket_block(socket)) as usize
+    }
+
+    /// Copies data into the send buffer and sends it, waiting until the remote side has it.
+    fn send_buffer(&mut self, socket: u8, data: &[u8]) -> Result<(), EthError> {
+        let block = W5500::socket_block(socket);
+        // The buffer offsets wrap around the buffer size by themselves.
+        let pointer = self.read_u16(SN_TX_WR, block);
+        self.write(pointer, block + 1, data);
+        self.write_u16(SN_TX_WR, block, pointer.wrapping_add(data.len() as u16));
+        self.command(socket, CMD_SEND);
+        loop {
+            let ir = self.read_u8(SN_IR, block);
+            if ir & IR_SENDOK != 0 {
+                self.write(SN_IR, block, &[IR_SENDOK]);
+                return Ok(());
+            }
+            if ir & IR_TIMEOUT != 0 {
+                self.write(SN_IR, block, &[IR_TIMEOUT]);
+                return Err(EthError::Timeout);
+            }
+            if self.status(socket) == SocketStatus::Closed {
+                return Err(EthError::Closed);
+            }
+            delay_us(10);
+        }
+    }
+
+    /// Sends data over a TCP connection, longer data is sent in parts of the buffer size.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// * `data` - a u8 slice, the data to be sent.
+    /// # Returns
+    /// * `a Result<(), EthError>` - `EthError::NotConnected` if there is no connection.
+    pub fn send(&mut self, socket: u8, data: &[u8]) -> Result<(), EthError> {
+        let block = W5500::socket_block(socket);
+        for part in data.chunks(W5500_BUFFER_SIZE) {
+            loop {
+                match self.status(socket) {
+                    SocketStatus::Established | SocketStatus::CloseWait => (),
+                    _ => return Err(EthError::NotConnected),
+                }
+                if self.read_counter(SN_TX_FSR, block) as usize >= part.len() {
+                    break;
+                }
+                delay_us(100);
+            }
+            self.send_buffer(socket, part)?;
+        }
+        Ok(())
+    }
+
+    /// Reads received data of a TCP connection without waiting.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// * `buffer` - a mutable u8 slice, where the data is stored.
+    /// # Returns
+    /// * `a Result<usize, EthError>` - the number of bytes stored, `EthError::Closed` once the connection is closed and all data has been read.
+    pub fn recv(&mut self, socket: u8, buffer: &mut [u8]) -> Result<usize, EthError> {
+        let block = W5500::socket_block(socket);
+        let length = self.available(socket).min(buffer.len());
+        if length == 0 {
+            return match self.status(socket) {
+                SocketStatus::Established | SocketStatus::Listen | SocketStatus::SynSent => Ok(0),
+                _ => Err(EthError::Closed),
+            };
+        }
+        let pointer = self.read_u16(SN_RX_RD, block);
+        self.read(pointer, block + 2, &mut buffer[..length]);
+        self.write_u16(SN_RX_RD, block, pointer.wrapping_add(length as u16));
+        self.command(socket, CMD_RECV);
+        Ok(length)
+    }
+
+    /// Sends a UDP packet from a socket opened for UDP.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// * `ip` - a u8 array, the IP address of the receiver, 255.255.255.255 for broadcast.
+    /// * `port` - a u16, the port of the receiver.
+    /// * `data` - a u8 slice, the packet of at most 2048 bytes.
+    /// # Returns
+    /// * `a Result<(), EthError>` - `EthError::Timeout` if the address of the receiver was not found.
+    pub fn send_to(
+        &mut self,
+        socket: u8,
+        ip: [u8; 4],
+        port: u16,
+        data: &[u8],
+    ) -> Result<(), EthError> {
+        let block = W5500::socket_block(socket);
+        if data.len() > W5500_BUFFER_SIZE {
+            return Err(EthError::PacketTooLong);
+        }
+        if self.status(socket) != SocketStatus::Udp {
+            return Err(EthError::Closed);
+        }
+        while (self.read_counter(SN_TX_FSR, block) as usize) < data.len() {
+            delay_us(100);
+        }
+        self.write(SN_DIPR, block, &ip);
+        self.write_u16(SN_DPORT, block, port);
+        self.send_buffer(socket, data)
+    }
+
+    /// Reads one received UDP packet without waiting.
+    /// The part of a packet which does not fit in the buffer is dropped.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    /// * `buffer` - a mutable u8 slice, where the packet is stored.
+    /// # Returns
+    /// * `a Option<(usize, [u8; 4], u16)>` - the length stored, the IP address and the port of the sender, or None if no packet is waiting.
+    pub fn recv_from(&mut self, socket: u8, buffer: &mut [u8]) -> Option<(usize, [u8; 4], u16)> {
+        let block = W5500::socket_block(socket);
+        if self.available(socket) < UDP_HEADER_LENGTH {
+            return None;
+        }
+        let pointer = self.read_u16(SN_RX_RD, block);
+        // The header is the IP address, the port and the length of the packet.
+        let mut header = [0u8; UDP_HEADER_LENGTH];
+        self.read(pointer, block + 2, &mut header);
+        let ip = [header[0], header[1], header[2], header[3]];
+        let port = u16::from_be_bytes([header[4], header[5]]);
+        let length = u16::from_be_bytes([header[6], header[7]]) as usize;
+        let stored = length.min(buffer.len());
+        let data = pointer.wrapping_add(UDP_HEADER_LENGTH as u16);
+        self.read(data, block + 2, &mut buffer[..stored]);
+        self.write_u16(SN_RX_RD, block, data.wrapping_add(length as u16));
+        self.command(socket, CMD_RECV);
+        Some((stored, ip, port))
+    }
+
+    /// Ends a TCP connection, the remote side is told and the socket closes when it answers.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    pub fn disconnect(&mut self, socket: u8) {
+        self.command(socket, CMD_DISCON);
+    }
+
+    /// Closes a socket at once and clears its interrupt flags.
+    /// # Arguments
+    /// * `socket` - a u8, the socket from 0 to 7.
+    pub fn close(&mut self, socket: u8) {
+        let block = W5500::socket_block(socket);
+        self.command(socket, CMD_CLOSE);
+        self.write(SN_IR, block, &[0xFF]);
+    }
+
+    /// Gives back the SPI device.
+    /// # Returns
+    /// * `a SpiDevice object` - the SPI bus with the chip select of the controller.
+    pub fn release(self) -> SpiDevice {
+        self.spi
+    }
+}
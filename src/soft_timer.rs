@@ -0,0 +1,181 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Software timers calling a function once after a delay or repeatedly at an interval.
+//! The timers count on a millisecond clock which is advanced by `tick()`, called from
+//! a periodic interrupt such as a timer compare match registered with `interrupt!`.
+//! Expired timers are run by `poll()` from the main loop, so the callbacks do not
+//! run inside the interrupt handler and may take their time.
+//!
+//! ```ignore
+//! fn blink() { /* toggle a led */ }
+//!
+//! Interval::every(500, blink).unwrap();
+//! loop {
+//!     soft_timer::poll();
+//! }
+//! ```
+
+#[cfg(all(feature = "atmega2560p", not(feature = "atmega328p")))]
+use crate::atmega2560p::hal::interrupts;
+#[cfg(feature = "atmega328p")]
+use crate::atmega328p::hal::interrupts;
+use crate::sync::{AtomicCell, Mutex};
+use core::cell::RefCell;
+
+/// Number of timers which can be active at the same time.
+pub const MAX_TIMERS: usize = 8;
+
+/// Errors given when starting a timer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimerError {
+    /// All the `MAX_TIMERS` slots are taken.
+    Full,
+}
+
+/// One slot of the timer table.
+#[derive(Clone, Copy)]
+struct SoftTimer {
+    start: u32,
+    period: u32,
+    repeat: bool,
+    generation: u8,
+    callback: fn(),
+}
+
+static NOW: AtomicCell<u32> = AtomicCell::new(0);
+static GENERATION: AtomicCell<u8> = AtomicCell::new(0);
+static TIMERS: Mutex<RefCell<[Option<SoftTimer>; MAX_TIMERS]>> =
+    Mutex::new(RefCell::new([None; MAX_TIMERS]));
+
+/// Handle of a started timer, used to cancel it.
+/// A handle of a timer which has already expired is ignored, even if its slot is used again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimerHandle {
+    slot: u8,
+    generation: u8,
+}
+
+impl TimerHandle {
+    /// Stops the timer before its callback is called again.
+    pub fn cancel(self) {
+        interrupts::free(|cs| {
+            let mut timers = TIMERS.borrow_ref_mut(cs);
+            let slot = &mut timers[self.slot as usize];
+            if let Some(timer) = slot {
+                if timer.generation == self.generation {
+                    *slot = None;
+                }
+            }
+        });
+    }
+}
+
+/// A timer calling its function once.
+pub struct Timeout;
+
+impl Timeout {
+    /// Calls the function once after the given time has passed.
+    /// # Arguments
+    /// * `ms` - a u32, the delay in milliseconds.
+    /// * `callback` - a function, which is called by `poll()` after the delay.
+    /// # Returns
+    /// * `a Result<TimerHandle, TimerError>` - the handle of the timer, or `Full` if no slot is free.
+    pub fn after(ms: u32, callback: fn()) -> Result<TimerHandle, TimerError> {
+        start_timer(ms, false, callback)
+    }
+}
+
+/// A timer calling its function repeatedly.
+pub struct Interval;
+
+impl Interval {
+    /// Calls the function every time the interval passes, until the timer is cancelled.
+    /// # Arguments
+    /// * `ms` - a u32, the interval in milliseconds.
+    /// * `callback` - a function, which is called by `poll()` at every interval.
+    /// # Returns
+    /// * `a Result<TimerHandle, TimerError>` - the handle of the timer, or `Full` if no slot is free.
+    pub fn every(ms: u32, callback: fn()) -> Result<TimerHandle, TimerError> {
+        start_timer(ms, true, callback)
+    }
+}
+
+/// Places a timer in the first free slot of the table.
+fn start_timer(period: u32, repeat: bool, callback: fn()) -> Result<TimerHandle, TimerError> {
+    interrupts::free(|cs| {
+        let mut timers = TIMERS.borrow_ref_mut(cs);
+        let slot = timers
+            .iter()
+            .position(|t| t.is_none())
+            .ok_or(TimerError::Full)?;
+        let generation = GENERATION.update(|g| g.wrapping_add(1));
+        timers[slot] = Some(SoftTimer {
+            start: NOW.load(),
+            period,
+            repeat,
+            generation,
+            callback,
+        });
+        Ok(TimerHandle {
+            slot: slot as u8,
+            generation,
+        })
+    })
+}
+
+/// Advances the clock of the timers, called from a periodic interrupt.
+/// # Arguments
+/// * `elapsed_ms` - a u32, the milliseconds passed since the last call.
+pub fn tick(elapsed_ms: u32) {
+    NOW.update(|now| now.wrapping_add(elapsed_ms));
+}
+
+/// Gives the time counted by `tick()`.
+/// # Returns
+/// * `a u32` - the milliseconds counted since the start, which wraps around after about 49 days.
+pub fn now() -> u32 {
+    NOW.load()
+}
+
+/// Calls the functions of the expired timers, to be called often from the main loop.
+/// Repeating timers are started again from the time they were due, so they do not drift
+/// when `poll()` is late.
+pub fn poll() {
+    for slot in 0..MAX_TIMERS {
+        let due = interrupts::free(|cs| {
+            let mut timers = TIMERS.borrow_ref_mut(cs);
+            let now = NOW.load();
+            match &mut timers[slot] {
+                Some(timer) if now.wrapping_sub(timer.start) >= timer.period => {
+                    let callback = timer.callback;
+                    if timer.repeat {
+                        timer.start = timer.start.wrapping_add(timer.period);
+                    } else {
+                        timers[slot] = None;
+                    }
+                    Some(callback)
+                }
+                _ => None,
+            }
+        });
+
+        // The callback runs with the interrupts enabled and may start or cancel timers.
+        if let Some(callback) = due {
+            callback();
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//     RustDuino : A generic HAL implementation for Arduino Boards in Rust
+//     Copyright (C) 2021  Shivam Malhotra, Indian Institute of Technology Kanpur
+//
+//     This program is free software: you can redistribute it and/or modify
+//     it under the terms of the GNU Affero General Public License as published
+//     by the Free Software Foundation, either version 3 of the License, or
+//     (at your option) any later version.
+//
+//     This program is distributed in the hope that it will be useful,
+//     but WITHOUT ANY WARRANTY; without even the implied warranty of
+//     MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//     GNU Affero General Public License for more details.
+//
+//     You should have received a copy of the GNU Affero General Public License
+//     along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Cells for sharing data between the interrupt handlers and the main program without `static mut`.
+//! `Mutex` gives access to its data only inside a critical section, made with `interrupts::free`,
+//! and is used as `Mutex<RefCell<T>>` for data which is changed in place.
+//! `AtomicCell` holds small `Copy` values like counters and flags, and disables the interrupts
+//! by itself for every access.
+//!
+//! ```ignore
+//! static TICKS: AtomicCell<u16> = AtomicCell::new(0);
+//! static BUFFER: Mutex<RefCell<[u8; 8]>> = Mutex::new(RefCell::new([0; 8]));
+//!
+//! TICKS.update(|t| t + 1);
+//! interrupts::free(|cs| BUFFER.borrow_ref_mut(cs)[0] = 1);
+//! ```
+
+#[cfg(all(feature = "atmega2560p", not(feature = "atmega328p")))]
+use crate::atmega2560p::hal::interrupts;
+#[cfg(feature = "atmega328p")]
+use crate::atmega328p::hal::interrupts;
+use core::cell::{Ref, RefCell, RefMut, UnsafeCell};
+use core::marker::PhantomData;
+
+/// Token showing that the global interrupts are disabled, given to the closure of `interrupts::free`.
+/// It can not be made outside of this crate and lives only as long as the critical section.
+#[derive(Clone, Copy, Debug)]
+pub struct CriticalSection<'cs> {
+    _private: PhantomData<&'cs ()>,
+}
+
+impl<'cs> CriticalSection<'cs> {
+    /// Creates a new token.
+    /// # Safety
+    /// The global interrupts must stay disabled for the whole lifetime `'cs`.
+    pub(crate) unsafe fn new() -> CriticalSection<'cs> {
+        CriticalSection {
+            _private: PhantomData,
+        }
+    }
+}
+
+/// Data shared with the interrupt handlers, which is reached only with a `CriticalSection` token.
+pub struct Mutex<T> {
+    inner: UnsafeCell<T>,
+}
+
+// Safety: the data is reached only inside a critical section, where no interrupt handler can run.
+unsafe impl<T: Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Creates a new mutex, which can be placed in a `static`.
+    /// # Arguments
+    /// * `value` - the data to be guarded.
+    /// # Returns
+    /// * `a Mutex object` - holding the data.
+    pub const fn new(value: T) -> Mutex<T> {
+        Mutex {
+            inner: UnsafeCell::new(value),
+        }
+    }
+
+    /// Gives a reference to the data for the length of the critical section.
+    /// # Arguments
+    /// * `_cs` - the token of the running critical section.
+    /// # Returns
+    /// * `a reference` - to the guarded data.
+    pub fn borrow<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> &'cs T {
+        unsafe { &*self.inner.get() }
+    }
+
+    /// Gives a mutable reference to the data, which needs no critical section as the mutex is borrowed mutably.
+    /// # Returns
+    /// * `a mutable reference` - to the guarded data.
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.inner.get() }
+    }
+
+    /// Takes the data out of the mutex.
+    /// # Returns
+    /// * `the guarded data`.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T> Mutex<RefCell<T>> {
+    /// Borrows the data of the `RefCell` immutably for the length of the critical section.
+    /// # Arguments
+    /// * `cs` - the token of the running critical section.
+    /// # Returns
+    /// * `a Ref object` - for the guarded data.
+    pub fn borrow_ref<'cs>(&'cs self, cs: CriticalSection<'cs>) -> Ref<'cs, T> {
+        self.borrow(cs).borrow()
+    }
+
+    /// Borrows the data of the `RefCell` mutably for the length of the critical section.
+    /// # Arguments
+    /// * `cs` - the token of the running critical section.
+    /// # Returns
+    /// * `a RefMut object` - for the guarded data.
+    pub fn borrow_ref_mut<'cs>(&'cs self, cs: CriticalSection<'cs>) -> RefMut<'cs, T> {
+        self.borrow(cs).borrow_mut()
+    }
+}
+
+/// A value which can be read and written from both the interrupt handlers and the main program.
+/// Each access runs with the interrupts disabled, so multi-byte values are never seen half written.
+pub struct AtomicCell<T> {
+    value: UnsafeCell<T>,
+}
+
+// Safety: every access to the value is made inside a critical section.
+unsafe impl<T: Send> Sync for AtomicCell<T> {}
+
+impl<T: Copy> AtomicCell<T> {
+    /// Creates a new cell, which can be placed in a `static`.
+    /// # Arguments
+    /// * `value` - the starting value of the cell.
+    /// # Returns
+    /// * `a AtomicCell object` - holding the value.
+    pub const fn new(value: T) -> AtomicCell<T> {
+        AtomicCell {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Reads the value of the cell.
+    /// # Returns
+    /// * `the value` - stored in the cell.
+    pub fn load(&self) -> T {
+        interrupts::free(|_| unsafe { *self.value.get() })
+    }
+
+    /// Writes a new value to the cell.
+    /// # Arguments
+    /// * `value` - the value to be stored.
+    pub fn store(&self, value: T) {
+        interrupts::free(|_| unsafe { *self.value.get() = value })
+    }
+
+    /// Writes a new value to the cell and gives back the old one.
+    /// # Arguments
+    /// * `value` - the value to be stored.
+    /// # Returns
+    /// * `the value` - which was stored before.
+    pub fn swap(&self, value: T) -> T {
+        interrupts::free(|_| unsafe { core::mem::replace(&mut *self.value.get(), value) })
+    }
+
+    /// Changes the value with the closure, without an interrupt coming between the read and the write.
+    /// # Arguments
+    /// * `f` - a closure, which takes the old value and gives the new one.
+    /// # Returns
+    /// * `the value` - which is now stored.
+    pub fn update<F>(&self, f: F) -> T
+    where
+        F: FnOnce(T) -> T,
+    {
+        interrupts::free(|_| unsafe {
+            let value = f(*self.value.get());
+            *self.value.get() = value;
+            value
+        })
+    }
+}